@@ -1,12 +1,48 @@
+//! VU-Server API types.
+//!
+//! With the `requests` feature enabled, this module also has a submodule for
+//! each of the VU-Server's endpoints. Each builds plain [`http::Request`]s
+//! and parses plain [`http::Response`]s, so the API can be used with any HTTP
+//! client, not only the reqwest-backed client.
 use crate::dial;
 use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 use std::{fmt, str::FromStr};
 use thiserror::Error;
 
+#[cfg(feature = "requests")]
+pub(crate) mod endpoint;
+#[cfg(feature = "requests")]
+pub use self::endpoint::{Image, JsonError, RequestError, ResponseError};
+
+#[cfg(feature = "requests")]
+pub mod dial_status;
+#[cfg(feature = "requests")]
+pub mod easing;
+#[cfg(feature = "requests")]
+pub mod image;
+#[cfg(feature = "requests")]
+pub mod list_dials;
+#[cfg(feature = "requests")]
+pub mod reload;
+#[cfg(feature = "requests")]
+pub mod set_backlight;
+#[cfg(feature = "requests")]
+pub mod set_name;
+#[cfg(feature = "requests")]
+pub mod set_value;
+
 /// A [response] from the VU API server.
 ///
+/// The types in this module serialize the same way as the VU-Server's own
+/// responses, so they can also be used to implement a VU-Server-compatible
+/// endpoint or proxy. The VU-Server sometimes sends integers as strings, so
+/// they're deserialized from either, but they're serialized as numbers,
+/// except for a [dial status]'s `index`, which the VU-Server always sends as a
+/// string.
+///
 /// [response]: https://docs.vudials.com/api_messaging/
+/// [dial status]: dial::Status
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Response<T> {
     pub status: Status,
@@ -14,6 +50,54 @@ pub struct Response<T> {
     pub data: T,
 }
 
+impl<T> Response<T> {
+    /// Returns a successful response containing `data`, with an empty
+    /// message.
+    pub fn ok(data: T) -> Self {
+        Self {
+            status: Status::Ok,
+            message: String::new(),
+            data,
+        }
+    }
+
+    /// Returns this response with its message set to `message`.
+    pub fn with_message(self, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            ..self
+        }
+    }
+
+    /// Returns `true` if this response's status is [`Status::Ok`].
+    pub fn is_ok(&self) -> bool {
+        self.status == Status::Ok
+    }
+
+    /// Returns this response's data if it succeeded, or its message if it
+    /// failed.
+    pub fn into_result(self) -> Result<T, String> {
+        match self.status {
+            Status::Ok => Ok(self.data),
+            Status::Fail => Err(self.message),
+        }
+    }
+}
+
+impl Response<()> {
+    /// Returns a failed response with the error message `message`.
+    ///
+    /// Like the VU-Server's own failed responses, its `data` field is
+    /// serialized as `null`.
+    pub fn fail(message: impl Into<String>) -> Self {
+        Self {
+            status: Status::Fail,
+            message: message.into(),
+            data: (),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct DialInfo {
     pub uid: dial::Id,
@@ -23,6 +107,22 @@ pub struct DialInfo {
     pub image_file: String,
 }
 
+/// An API key, as listed by the VU-Server's admin API.
+#[cfg(feature = "admin")]
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct KeyInfo {
+    /// The key's human-readable name.
+    pub name: String,
+    /// The key itself.
+    pub key: String,
+    /// The key's priority level. The master key has the highest priority.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority_level: Option<u8>,
+    /// The UIDs of the dials that the key may access.
+    #[serde(default)]
+    pub dials: Vec<dial::Id>,
+}
+
 /// The [status] of a response from the VU API server.
 ///
 /// [status]: https://docs.vudials.com/api_messaging/#status
@@ -0,0 +1,17 @@
+//! `GET /api/v0/dial/{uid}/status`: returns a dial's status.
+use super::{endpoint, RequestError, ResponseError};
+use crate::dial;
+use http::Method;
+use url::Url;
+
+pub fn request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+) -> Result<http::Request<Vec<u8>>, RequestError> {
+    Ok(endpoint::dial_request(base_url, key, uid, Method::GET, "status")?.build()?)
+}
+
+pub fn parse_response(rsp: http::Response<Vec<u8>>) -> Result<dial::Status, ResponseError> {
+    endpoint::parse_json(rsp)
+}
@@ -0,0 +1,61 @@
+//! `GET /api/v0/dial/{uid}/easing/...`: reads and sets a dial's easing config.
+use super::{endpoint, RequestError, ResponseError};
+use crate::dial;
+use http::Method;
+use std::time::Duration;
+use url::Url;
+
+/// Returns a request that sets the easing of the dial's needle.
+pub fn set_dial_request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+    period: Duration,
+    step: dial::Percent,
+) -> Result<http::Request<Vec<u8>>, RequestError> {
+    set_request(base_url, key, uid, "easing/dial", period, step)
+}
+
+/// Returns a request that sets the easing of the dial's backlight.
+pub fn set_backlight_request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+    period: Duration,
+    step: dial::Percent,
+) -> Result<http::Request<Vec<u8>>, RequestError> {
+    set_request(base_url, key, uid, "easing/backlight", period, step)
+}
+
+pub fn parse_set_response(rsp: http::Response<Vec<u8>>) -> Result<(), ResponseError> {
+    endpoint::parse_json(rsp)
+}
+
+/// Returns a request for the dial's current easing config.
+pub fn get_request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+) -> Result<http::Request<Vec<u8>>, RequestError> {
+    Ok(endpoint::dial_request(base_url, key, uid, Method::GET, "easing/get")?.build()?)
+}
+
+pub fn parse_get_response(rsp: http::Response<Vec<u8>>) -> Result<dial::Easing, ResponseError> {
+    endpoint::parse_json(rsp)
+}
+
+fn set_request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+    path: &str,
+    period: Duration,
+    step: dial::Percent,
+) -> Result<http::Request<Vec<u8>>, RequestError> {
+    Ok(
+        endpoint::dial_request(base_url, key, uid, Method::GET, path)?
+            .query("period", period.as_millis().to_string())
+            .query("step", endpoint::percent(step))
+            .build()?,
+    )
+}
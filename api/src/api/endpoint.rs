@@ -0,0 +1,143 @@
+//! Plumbing shared by the low-level endpoint modules.
+use super::{Response, Status};
+use crate::{dial, requests::RequestBuilder};
+use http::{Method, StatusCode};
+use thiserror::Error;
+use url::Url;
+
+/// An error building a request for one of the VU-Server's endpoints.
+#[derive(Debug, Error, miette::Diagnostic)]
+pub enum RequestError {
+    #[error(transparent)]
+    #[diagnostic(code(vu_api::api::RequestError::Url))]
+    Url(#[from] url::ParseError),
+
+    #[error("failed to build request: {0}")]
+    #[diagnostic(code(vu_api::api::RequestError::Http))]
+    Http(#[from] http::Error),
+}
+
+/// An error returned by one of the VU-Server's endpoints.
+#[derive(Debug, Error, miette::Diagnostic)]
+pub enum ResponseError {
+    /// The server returned an HTTP error.
+    #[error("VU-Server returned {}: {}", .status, .message)]
+    #[diagnostic(code(vu_api::api::ResponseError::Http))]
+    Http {
+        /// The HTTP status returned by the server.
+        status: StatusCode,
+        /// The body of the server's HTTP response.
+        message: String,
+    },
+
+    /// The server returned invalid JSON.
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    DecodeJson(#[from] JsonError),
+
+    /// The server returned a JSON response with `"status:": "fail"`.
+    #[error("VU-Server API error: {}", .0)]
+    #[diagnostic(code(vu_api::api::ResponseError::Server))]
+    Server(String),
+}
+
+/// Error indicating that a JSON response could not be deserialized.
+#[derive(Debug, Error, miette::Diagnostic)]
+#[error("JSON deserialization error")]
+#[diagnostic(code(vu_api::client::JsonError))]
+pub struct JsonError {
+    // The Source that we're gonna be printing snippets out of.
+    // This can be a String if you don't have or care about file names.
+    #[source_code]
+    src: miette::NamedSource<String>,
+
+    // Snippets and highlights can be included in the diagnostic!
+    #[label("here")]
+    span: miette::SourceSpan,
+
+    #[source]
+    error: serde_json::Error,
+}
+
+/// A dial's background image, returned by [`image::parse_get_response`].
+///
+/// [`image::parse_get_response`]: super::image::parse_get_response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    /// The value of the `Content-Type` header in the VU-Server's response, if
+    /// it sent one.
+    ///
+    /// This is usually `image/png`.
+    pub content_type: Option<String>,
+    /// The contents of the image file.
+    pub bytes: Vec<u8>,
+}
+
+/// Returns a request to `endpoint`, relative to `base_url`.
+pub(crate) fn request(
+    base_url: &Url,
+    key: &str,
+    method: Method,
+    endpoint: &str,
+) -> Result<RequestBuilder, url::ParseError> {
+    let url = base_url.join(endpoint)?;
+    Ok(RequestBuilder::new(method, url).query("key", key))
+}
+
+/// Returns a request to one of `uid`'s per-dial endpoints.
+pub(crate) fn dial_request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+    method: Method,
+    endpoint: &str,
+) -> Result<RequestBuilder, url::ParseError> {
+    request(
+        base_url,
+        key,
+        method,
+        &format!("api/v0/dial/{uid}/{endpoint}"),
+    )
+}
+
+/// Formats a percentage as the bare number the VU-Server expects, without a
+/// `%` sign.
+pub(crate) fn percent(value: dial::Percent) -> String {
+    u8::from(value).to_string()
+}
+
+/// Returns an error if `rsp` has an HTTP error status.
+pub(crate) fn check_status(rsp: &http::Response<Vec<u8>>) -> Result<(), ResponseError> {
+    let status = rsp.status();
+    if status.is_client_error() || status.is_server_error() {
+        return Err(ResponseError::Http {
+            status,
+            message: String::from_utf8_lossy(rsp.body()).into_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// Returns the `data` field of a JSON response, or an error if the
+/// response's `status` is `fail`.
+pub(crate) fn parse_json<T: serde::de::DeserializeOwned>(
+    rsp: http::Response<Vec<u8>>,
+) -> Result<T, ResponseError> {
+    check_status(&rsp)?;
+    let body = rsp.into_body();
+    let json = match serde_json::from_slice::<Response<T>>(&body) {
+        Ok(json) => json,
+        Err(error) => {
+            let src = String::from_utf8_lossy(&body).to_string();
+            let start = miette::SourceOffset::from_location(&src, error.line(), error.column());
+            let span = miette::SourceSpan::new(start, 1);
+            let src = miette::NamedSource::new("response body", src).with_language("JSON");
+            return Err(JsonError { src, span, error }.into());
+        }
+    };
+    if json.status != Status::Ok {
+        return Err(ResponseError::Server(json.message));
+    }
+
+    Ok(json.data)
+}
@@ -0,0 +1,65 @@
+//! `/api/v0/dial/{uid}/image/...`: uploads and downloads a dial's background
+//! image.
+use super::{endpoint, Image, RequestError, ResponseError};
+use crate::dial;
+use http::{header, Method};
+use url::Url;
+
+/// Returns a request that uploads `image` as the dial's background image.
+///
+/// If `force` is `true`, the VU-Server sends the image to the dial even if
+/// it thinks the dial already displays it.
+pub fn set_request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+    filename: &str,
+    image: &[u8],
+    force: bool,
+) -> Result<http::Request<Vec<u8>>, RequestError> {
+    let mut req = endpoint::dial_request(base_url, key, uid, Method::POST, "image/set")?
+        .query("imgfile", filename);
+    if force {
+        req = req.query("force", "true");
+    }
+    Ok(req.image(filename, image).build()?)
+}
+
+pub fn parse_set_response(rsp: http::Response<Vec<u8>>) -> Result<(), ResponseError> {
+    endpoint::parse_json(rsp)
+}
+
+/// Returns a request that downloads the dial's current background image.
+pub fn get_request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+) -> Result<http::Request<Vec<u8>>, RequestError> {
+    Ok(endpoint::dial_request(base_url, key, uid, Method::GET, "image/get")?.build()?)
+}
+
+pub fn parse_get_response(rsp: http::Response<Vec<u8>>) -> Result<Image, ResponseError> {
+    endpoint::check_status(&rsp)?;
+
+    // If the server can't find the image, it returns a JSON error rather
+    // than an HTTP error status.
+    let content_type = rsp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map(ToOwned::to_owned);
+    let is_json = content_type
+        .as_deref()
+        .is_some_and(|content_type| content_type.starts_with("application/json"));
+    if is_json {
+        endpoint::parse_json::<serde::de::IgnoredAny>(rsp)?;
+        return Err(ResponseError::Server(
+            "expected an image, but got a JSON response".to_owned(),
+        ));
+    }
+
+    Ok(Image {
+        content_type,
+        bytes: rsp.into_body(),
+    })
+}
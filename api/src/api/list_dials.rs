@@ -0,0 +1,12 @@
+//! `GET /api/v0/dial/list`: lists the dials that the API key can access.
+use super::{endpoint, DialInfo, RequestError, ResponseError};
+use http::Method;
+use url::Url;
+
+pub fn request(base_url: &Url, key: &str) -> Result<http::Request<Vec<u8>>, RequestError> {
+    Ok(endpoint::request(base_url, key, Method::GET, "/api/v0/dial/list")?.build()?)
+}
+
+pub fn parse_response(rsp: http::Response<Vec<u8>>) -> Result<Vec<DialInfo>, ResponseError> {
+    endpoint::parse_json(rsp)
+}
@@ -0,0 +1,24 @@
+//! `GET /api/v0/dial/{uid}/backlight`: sets a dial's backlight color.
+use super::{endpoint, RequestError, ResponseError};
+use crate::dial;
+use http::Method;
+use url::Url;
+
+pub fn request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+    dial::Backlight { red, green, blue }: dial::Backlight,
+) -> Result<http::Request<Vec<u8>>, RequestError> {
+    Ok(
+        endpoint::dial_request(base_url, key, uid, Method::GET, "backlight")?
+            .query("red", endpoint::percent(red))
+            .query("green", endpoint::percent(green))
+            .query("blue", endpoint::percent(blue))
+            .build()?,
+    )
+}
+
+pub fn parse_response(rsp: http::Response<Vec<u8>>) -> Result<(), ResponseError> {
+    endpoint::parse_json(rsp)
+}
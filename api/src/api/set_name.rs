@@ -0,0 +1,22 @@
+//! `GET /api/v0/dial/{uid}/name`: sets a dial's name.
+use super::{endpoint, RequestError, ResponseError};
+use crate::dial;
+use http::Method;
+use url::Url;
+
+pub fn request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+    name: &str,
+) -> Result<http::Request<Vec<u8>>, RequestError> {
+    Ok(
+        endpoint::dial_request(base_url, key, uid, Method::GET, "name")?
+            .query("name", name)
+            .build()?,
+    )
+}
+
+pub fn parse_response(rsp: http::Response<Vec<u8>>) -> Result<(), ResponseError> {
+    endpoint::parse_json(rsp)
+}
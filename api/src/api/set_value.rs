@@ -0,0 +1,22 @@
+//! `GET /api/v0/dial/{uid}/set`: sets a dial's value.
+use super::{endpoint, RequestError, ResponseError};
+use crate::dial;
+use http::Method;
+use url::Url;
+
+pub fn request(
+    base_url: &Url,
+    key: &str,
+    uid: &dial::Id,
+    value: dial::Percent,
+) -> Result<http::Request<Vec<u8>>, RequestError> {
+    Ok(
+        endpoint::dial_request(base_url, key, uid, Method::GET, "set")?
+            .query("value", endpoint::percent(value))
+            .build()?,
+    )
+}
+
+pub fn parse_response(rsp: http::Response<Vec<u8>>) -> Result<(), ResponseError> {
+    endpoint::parse_json(rsp)
+}
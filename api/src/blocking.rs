@@ -0,0 +1,250 @@
+//! A blocking VU-Server client.
+//!
+//! This wraps the asynchronous [`Client`](crate::Client), driving it on a
+//! private, single-threaded Tokio runtime, for programs that don't want to run
+//! their own runtime. Like `reqwest`'s blocking client, the blocking client
+//! must not be used from within an async context, or it will panic.
+use crate::{
+    api,
+    client::{self, ClientBuilder, Error, Image, NewClientError, ServerInfo},
+    dial::{self, Id, Percent},
+};
+use core::fmt;
+use reqwest::{IntoUrl, Url};
+use std::{sync::Arc, time::Duration};
+use tokio::runtime::Runtime;
+
+/// A blocking client for the VU-Server API.
+///
+/// See [`crate::Client`] for details on each method.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Client {
+    inner: crate::Client,
+    runtime: Arc<Runtime>,
+}
+
+/// A blocking handle to an in-progress provisioning scan.
+///
+/// See [`crate::client::Provisioning`] for details on each method.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Provisioning {
+    inner: client::Provisioning,
+    runtime: Arc<Runtime>,
+}
+
+/// A blocking handle to a single dial.
+///
+/// See [`crate::Dial`] for details on each method.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Dial {
+    inner: crate::Dial,
+    runtime: Arc<Runtime>,
+}
+
+impl Client {
+    pub fn new(key: String, base_url: impl IntoUrl) -> Result<Self, NewClientError> {
+        Self::from_async(crate::Client::new(key, base_url)?)
+    }
+
+    /// Returns a new client that gives up on connecting to the VU-Server, and
+    /// on each request, after `timeout`.
+    pub fn with_timeout(
+        key: String,
+        base_url: impl IntoUrl,
+        timeout: Duration,
+    ) -> Result<Self, NewClientError> {
+        Self::from_async(crate::Client::with_timeout(key, base_url, timeout)?)
+    }
+
+    pub fn from_builder(
+        builder: ClientBuilder,
+        key: String,
+        base_url: impl IntoUrl,
+    ) -> Result<Self, NewClientError> {
+        Self::from_async(crate::Client::from_builder(builder, key, base_url)?)
+    }
+
+    /// Returns a blocking client that drives the asynchronous client `inner`.
+    pub fn from_async(inner: crate::Client) -> Result<Self, NewClientError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(NewClientError::BuildRuntime)?;
+        Ok(Self {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    pub fn dial(&self, uid: impl Into<Id>) -> Result<Dial, url::ParseError> {
+        Ok(Dial {
+            inner: self.inner.dial(uid)?,
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    pub fn list_dials(&self) -> Result<Vec<(Dial, api::DialInfo)>, Error> {
+        let dials = self.runtime.block_on(self.inner.list_dials())?;
+        Ok(wrap_dials(dials, &self.runtime))
+    }
+
+    pub fn set_values(
+        &self,
+        values: impl IntoIterator<Item = (Id, Percent)>,
+    ) -> Vec<(Id, Result<(), Error>)> {
+        self.runtime.block_on(self.inner.set_values(values))
+    }
+
+    pub fn set_backlights(
+        &self,
+        backlights: impl IntoIterator<Item = (Id, dial::Backlight)>,
+    ) -> Vec<(Id, Result<(), Error>)> {
+        self.runtime.block_on(self.inner.set_backlights(backlights))
+    }
+
+    /// Returns the VU-Server's base URL.
+    pub fn base_url(&self) -> &Url {
+        self.inner.base_url()
+    }
+
+    /// Changes the API key sent with every request.
+    pub fn set_key(&self, key: impl Into<String>) {
+        self.inner.set_key(key)
+    }
+
+    pub fn server_info(&self) -> Result<ServerInfo, Error> {
+        self.runtime.block_on(self.inner.server_info())
+    }
+
+    pub fn provision_dials(&self) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.provision_dials())
+    }
+
+    pub fn start_provisioning(&self) -> Result<Provisioning, Error> {
+        let inner = self.runtime.block_on(self.inner.start_provisioning())?;
+        Ok(Provisioning {
+            inner,
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    #[cfg(feature = "admin")]
+    pub fn list_api_keys(&self) -> Result<Vec<api::KeyInfo>, Error> {
+        self.runtime.block_on(self.inner.list_api_keys())
+    }
+
+    #[cfg(feature = "admin")]
+    pub fn create_api_key(&self, name: &str, dials: &[Id]) -> Result<String, Error> {
+        self.runtime
+            .block_on(self.inner.create_api_key(name, dials))
+    }
+
+    #[cfg(feature = "admin")]
+    pub fn update_api_key(&self, target: &str, name: &str, dials: &[Id]) -> Result<(), Error> {
+        self.runtime
+            .block_on(self.inner.update_api_key(target, name, dials))
+    }
+
+    #[cfg(feature = "admin")]
+    pub fn delete_api_key(&self, target: &str) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.delete_api_key(target))
+    }
+}
+
+impl Dial {
+    pub fn id(&self) -> &Id {
+        self.inner.id()
+    }
+
+    pub fn status(&self) -> Result<dial::Status, Error> {
+        self.runtime.block_on(self.inner.status())
+    }
+
+    pub fn set_name(&self, name: &str) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.set_name(name))
+    }
+
+    pub fn set(&self, value: Percent) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.set(value))
+    }
+
+    pub fn set_backlight(&self, backlight: dial::Backlight) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.set_backlight(backlight))
+    }
+
+    pub fn calibrate(&self, point: dial::CalibrationPoint, raw: u16) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.calibrate(point, raw))
+    }
+
+    pub fn set_dial_easing(&self, period: Duration, step: Percent) -> Result<(), Error> {
+        self.runtime
+            .block_on(self.inner.set_dial_easing(period, step))
+    }
+
+    pub fn set_backlight_easing(&self, period: Duration, step: Percent) -> Result<(), Error> {
+        self.runtime
+            .block_on(self.inner.set_backlight_easing(period, step))
+    }
+
+    pub fn get_easing(&self) -> Result<dial::Easing, Error> {
+        self.runtime.block_on(self.inner.get_easing())
+    }
+
+    pub fn set_easing(
+        &self,
+        dial: Option<dial::EasingConfig>,
+        backlight: Option<dial::EasingConfig>,
+    ) -> Result<(), Error> {
+        self.runtime
+            .block_on(self.inner.set_easing(dial, backlight))
+    }
+
+    pub fn set_image(&self, filename: &str, image: &[u8], force: bool) -> Result<(), Error> {
+        self.runtime
+            .block_on(self.inner.set_image(filename, image, force))
+    }
+
+    pub fn get_image(&self) -> Result<Image, Error> {
+        self.runtime.block_on(self.inner.get_image())
+    }
+
+    pub fn reload_hw_info(&self) -> Result<dial::Status, Error> {
+        self.runtime.block_on(self.inner.reload_hw_info())
+    }
+}
+
+impl Provisioning {
+    pub fn new_dials(&self) -> Result<Vec<(Dial, api::DialInfo)>, Error> {
+        let dials = self.runtime.block_on(self.inner.new_dials())?;
+        Ok(wrap_dials(dials, &self.runtime))
+    }
+
+    pub fn known_dials(&self) -> &[Id] {
+        self.inner.known_dials()
+    }
+}
+
+impl fmt::Display for Dial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+fn wrap_dials(
+    dials: Vec<(crate::Dial, api::DialInfo)>,
+    runtime: &Arc<Runtime>,
+) -> Vec<(Dial, api::DialInfo)> {
+    dials
+        .into_iter()
+        .map(|(inner, info)| {
+            let dial = Dial {
+                inner,
+                runtime: runtime.clone(),
+            };
+            (dial, info)
+        })
+        .collect()
+}
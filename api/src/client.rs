@@ -1,33 +1,147 @@
+#[cfg(all(feature = "retry", not(target_arch = "wasm32")))]
+use crate::retry::RetryPolicy;
 use crate::{
-    api,
+    api::{self, endpoint::percent},
     dial::{self, Id, Percent},
+    requests::RequestBuilder,
+    transport::{BoxError, Response, Transport},
 };
 use core::fmt;
+use http::{header, Method, StatusCode};
 pub use reqwest::ClientBuilder;
-use reqwest::{header::HeaderValue, IntoUrl, Method, Url};
-use std::{sync::Arc, time::Duration};
+use reqwest::{IntoUrl, Url};
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 use thiserror::Error;
 use tracing::Level;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod breaker;
+#[cfg(not(target_arch = "wasm32"))]
+mod cache;
+#[cfg(not(target_arch = "wasm32"))]
+mod observer;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::breaker::{CircuitBreaker, CircuitState};
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::observer::{Observer, RequestEvent};
+pub use crate::api::{Image, JsonError};
+
 #[derive(Debug, Clone)]
 #[must_use]
 pub struct Client {
     pub(crate) cfg: Arc<Config>,
-    pub(crate) client: reqwest::Client,
+    pub(crate) transport: Arc<dyn Transport>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 #[must_use]
 pub struct Dial {
     uid: Id,
     client: crate::Client,
-    base_url: Url,
+    urls: Arc<DialUrls>,
 }
 
+/// The most requests that [`Client::set_values`] and [`Client::set_backlights`]
+/// send at once.
+///
+/// The VU-Server talks to all of its dials over a single serial connection,
+/// so sending more requests at once than this mostly just queues them up on
+/// the server.
+pub const MAX_CONCURRENT_SETS: usize = 4;
+
+/// The URLs of each of a dial's API endpoints.
+///
+/// These are built once when the [`Dial`] is created, rather than on every
+/// request, since the daemon sets each dial's value every second. The API key
+/// isn't included, since it may change; it's added to each request as it's
+/// built.
 #[derive(Debug)]
+struct DialUrls {
+    status: Url,
+    name: Url,
+    set: Url,
+    backlight: Url,
+    calibrate_max: Url,
+    calibrate_half: Url,
+    easing_dial: Url,
+    easing_backlight: Url,
+    easing_get: Url,
+    image_set: Url,
+    image_get: Url,
+    reload: Url,
+}
+
+/// Configures a [`Client`] that sends requests using `reqwest`, returned by
+/// [`Client::builder`].
+///
+/// The builder returned by [`Client::builder`] takes the API key and server
+/// address from the `VU_DIALS_API_KEY` and `VU_DIALS_SERVER_ADDR` environment
+/// variables, the same ones that `dialctl` and `vupdated` use, unless they're
+/// overridden with [`Builder::key`] and [`Builder::server`].
+///
+/// This isn't available on `wasm32` targets, where timeouts are up to the
+/// browser.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct Builder {
+    key: Option<String>,
+    server: Option<String>,
+    user_agent: Option<String>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: usize,
+    tcp_keepalive: Option<Duration>,
+    http2_prior_knowledge: bool,
+}
+
+/// An in-progress provisioning scan, returned by [`Client::start_provisioning`].
+///
+/// This remembers which dials the VU-Server had already provisioned when the
+/// scan started, so that newly provisioned dials can be told apart from them.
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct Provisioning {
+    client: Client,
+    known: Vec<Id>,
+}
+
+/// Information about a VU-Server, returned by [`Client::server_info`].
+#[derive(Clone, Debug)]
+pub struct ServerInfo {
+    /// The value of the `Server` header in the VU-Server's responses, if it
+    /// sent one.
+    ///
+    /// The VU-Server doesn't report its own version, so this is usually the
+    /// version of the web server it's built on.
+    pub server: Option<String>,
+    /// The number of dials the VU-Server has provisioned.
+    pub dials: usize,
+    /// How long the VU-Server took to start responding to a request.
+    ///
+    /// This is `None` on `wasm32` targets, where there's no clock to measure
+    /// it with.
+    pub latency: Option<Duration>,
+}
+
+#[derive(Clone, Debug)]
 pub(crate) struct Config {
-    pub(crate) key: String,
+    /// The API key, which is shared by every clone of the client so that
+    /// [`Client::set_key`] changes it for all of them.
+    key: Arc<RwLock<String>>,
     pub(crate) base_url: Url,
+    #[cfg(all(feature = "retry", not(target_arch = "wasm32")))]
+    pub(crate) retry: Option<RetryPolicy>,
+    #[cfg(not(target_arch = "wasm32"))]
+    breaker: Option<Arc<breaker::Breaker>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    cache: Option<Arc<cache::StatusCache>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    observer: Option<observer::SharedObserver>,
 }
 
 #[derive(Debug, Error, miette::Diagnostic)]
@@ -35,27 +149,20 @@ pub enum NewClientError {
     #[error("invalid VU-Server base URL: {0}")]
     #[diagnostic(code(vu_api::client::NewClientError::InvalidBaseUrl))]
     InvalidBaseUrl(#[source] reqwest::Error),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("a VU-Server API key is required")]
+    #[diagnostic(
+        code(vu_api::client::NewClientError::MissingKey),
+        help("set the API key using the `VU_DIALS_API_KEY` environment variable")
+    )]
+    MissingKey,
     #[error("failed to build reqwest client: {0}")]
     #[diagnostic(code(vu_api::client::NewClientError::InvalidBaseUrl))]
     BuildClient(#[source] reqwest::Error),
-}
-
-/// Error indicating that a JSON response could not be deserialized.
-#[derive(Debug, Error, miette::Diagnostic)]
-#[error("JSON deserialization error")]
-#[diagnostic(code(vu_api::client::JsonError))]
-pub struct JsonError {
-    // The Source that we're gonna be printing snippets out of.
-    // This can be a String if you don't have or care about file names.
-    #[source_code]
-    src: miette::NamedSource<String>,
-
-    // Snippets and highlights can be included in the diagnostic!
-    #[label("here")]
-    span: miette::SourceSpan,
-
-    #[source]
-    error: serde_json::Error,
+    #[cfg(feature = "blocking")]
+    #[error("failed to build Tokio runtime for blocking client: {0}")]
+    #[diagnostic(code(vu_api::client::NewClientError::BuildRuntime))]
+    BuildRuntime(#[source] std::io::Error),
 }
 
 #[derive(Debug, Error, miette::Diagnostic)]
@@ -68,10 +175,10 @@ pub enum Error {
     #[diagnostic(code(vu_api::client::Error::BuildUrl))]
     BuildUrl(#[from] url::ParseError),
 
-    #[cfg(feature = "client")]
-    #[error(transparent)]
-    #[diagnostic(code(vu_api::client::Error::Request))]
-    Request(#[from] reqwest::Error),
+    /// The request could not be sent, or no response was received.
+    #[error("failed to send request to the VU-Server: {0}")]
+    #[diagnostic(code(vu_api::client::Error::Transport))]
+    Transport(#[source] BoxError),
 
     /// The server returned invalid JSON.
     #[error(transparent)]
@@ -83,9 +190,35 @@ pub enum Error {
     #[diagnostic(code(vu_Error::ServerHttp))]
     ServerHttp {
         /// The HTTP status returned by the server.
-        status: reqwest::StatusCode,
+        status: StatusCode,
         /// The message returned in the server's HTTP response.
         message: String,
+        /// How long the server asked the client to wait before retrying, if
+        /// it responded with `429 Too Many Requests` or `503 Service
+        /// Unavailable` and a `Retry-After` header.
+        retry_after: Option<Duration>,
+    },
+
+    /// The request wasn't sent, because the client's [`CircuitBreaker`] is
+    /// open after too many requests in a row failed.
+    #[error("VU-Server failed too many requests in a row, not sending requests for {remaining:?}")]
+    #[diagnostic(code(vu_api::client::Error::CircuitOpen))]
+    CircuitOpen {
+        /// How long until the breaker lets requests through again.
+        remaining: Duration,
+    },
+
+    /// An [`EasingConfig`](dial::EasingConfig) passed to
+    /// [`Dial::set_easing`] was invalid, so no requests were sent.
+    #[error("invalid {channel} easing")]
+    #[diagnostic(code(vu_api::client::Error::InvalidEasing))]
+    InvalidEasing {
+        /// Which easing channel the invalid config was for, either "dial" or
+        /// "backlight".
+        channel: &'static str,
+        #[source]
+        #[diagnostic_source]
+        source: dial::EasingError,
     },
 
     /// The server returned a JSON response with `"status:": "fail"`.
@@ -94,21 +227,171 @@ pub enum Error {
     Server(String),
 }
 
+impl Error {
+    /// Returns `true` if the request that failed with this error might succeed
+    /// if it's retried.
+    ///
+    /// Only failures to reach the VU-Server at all (see
+    /// [`Error::is_unreachable`]), `5xx` server errors, and `429 Too Many
+    /// Requests` are transient. API errors and other `4xx` responses mean the
+    /// VU-Server understood the request and refused it, so sending it again
+    /// won't help. Requests refused by an open [`CircuitBreaker`] aren't worth
+    /// retrying immediately either, since the breaker decides when requests
+    /// are sent again; a `RetryPolicy` waits for [`Error::retry_after`] before
+    /// retrying them.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::CircuitOpen { .. } => false,
+            Self::ServerHttp { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => self.is_unreachable(),
+        }
+    }
+
+    /// Returns `true` if the VU-Server could not be reached at all, because
+    /// the connection failed or timed out.
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            // In the browser, `fetch` doesn't distinguish connection errors
+            // from other failed requests, so any request that got no response
+            // counts as unreachable. The same goes for other transports, which
+            // we know nothing about.
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Transport(error) => match error.downcast_ref::<reqwest::Error>() {
+                Some(error) => error.is_connect() || error.is_timeout(),
+                None => true,
+            },
+            #[cfg(target_arch = "wasm32")]
+            Self::Transport(_) => true,
+            Self::CircuitOpen { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Returns how long to wait before retrying the request, if the VU-Server
+    /// (or the client's [`CircuitBreaker`]) said so.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match *self {
+            Self::ServerHttp { retry_after, .. } => retry_after,
+            Self::CircuitOpen { remaining } => Some(remaining),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the VU-Server rejected the request's API key.
+    ///
+    /// The VU-Server doesn't always use an HTTP status code for this, so API
+    /// errors whose message starts with `Unauthorized`, as the VU-Server's
+    /// rejections of invalid keys do, also count. Other errors that merely
+    /// mention a key don't.
+    pub fn is_unauthorized(&self) -> bool {
+        match self {
+            Self::ServerHttp { status, .. } => {
+                matches!(*status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+            }
+            Self::Server(message) => message.trim_start().starts_with("Unauthorized"),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the VU-Server doesn't know about the requested dial.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Self::ServerHttp { status, .. } => *status == StatusCode::NOT_FOUND,
+            Self::Server(message) => message.to_ascii_lowercase().contains("not found"),
+            _ => false,
+        }
+    }
+}
+
+impl From<api::RequestError> for Error {
+    fn from(error: api::RequestError) -> Self {
+        match error {
+            api::RequestError::Url(error) => Self::BuildUrl(error),
+            api::RequestError::Http(error) => Self::BuildRequest(error),
+        }
+    }
+}
+
+impl From<api::ResponseError> for Error {
+    fn from(error: api::ResponseError) -> Self {
+        match error {
+            api::ResponseError::Http { status, message } => Self::ServerHttp {
+                status,
+                message,
+                retry_after: None,
+            },
+            api::ResponseError::DecodeJson(error) => Self::DecodeJson(error),
+            api::ResponseError::Server(message) => Self::Server(message),
+        }
+    }
+}
+
 impl Client {
+    /// Returns a new client with the default timeouts.
+    ///
+    /// See [`Builder`] for the defaults.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(key: String, base_url: impl reqwest::IntoUrl) -> Result<Self, NewClientError> {
+        Builder::default()
+            .key(key)
+            .server(into_url(base_url)?)
+            .build()
+    }
+
+    #[cfg(target_arch = "wasm32")]
     pub fn new(key: String, base_url: impl reqwest::IntoUrl) -> Result<Self, NewClientError> {
-        static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+        Self::from_builder(Self::default_builder(), key, base_url)
+    }
+
+    /// Returns a [`Builder`] for configuring a new client, with the API key
+    /// and server address taken from the environment, if they're set.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn builder() -> Builder {
+        Builder::from_env()
+    }
+
+    /// Returns a new client that gives up on connecting to the VU-Server, and
+    /// on each request, after `timeout`.
+    ///
+    /// This isn't available on `wasm32` targets, where timeouts are up to the
+    /// browser.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_timeout(
+        key: String,
+        base_url: impl reqwest::IntoUrl,
+        timeout: Duration,
+    ) -> Result<Self, NewClientError> {
+        Builder::default()
+            .key(key)
+            .server(into_url(base_url)?)
+            .connect_timeout(timeout)
+            .timeout(timeout)
+            .build()
+    }
 
-        let builder = reqwest::Client::builder().user_agent(HeaderValue::from_static(USER_AGENT));
-        Self::from_builder(builder, key, base_url)
+    #[cfg(not(target_arch = "wasm32"))]
+    fn default_builder() -> ClientBuilder {
+        reqwest::Client::builder().user_agent(reqwest::header::HeaderValue::from_static(
+            Builder::DEFAULT_USER_AGENT,
+        ))
+    }
+
+    /// Browsers don't let scripts set the `User-Agent` header, so the browser's
+    /// own is used.
+    #[cfg(target_arch = "wasm32")]
+    fn default_builder() -> ClientBuilder {
+        reqwest::Client::builder()
     }
 
     pub fn dial(&self, uid: impl Into<Id>) -> Result<Dial, url::ParseError> {
         let uid = uid.into();
-        let base_url = self.cfg.base_url.join(&format!("api/v0/dial/{uid}/"))?;
+        let urls = DialUrls::new(&self.cfg, &uid)?;
         Ok(Dial {
             uid,
             client: self.clone(),
-            base_url,
+            urls: Arc::new(urls),
         })
     }
 
@@ -118,16 +401,12 @@ impl Client {
         err(Display, level = Level::DEBUG),
     )]
     pub async fn list_dials(&self) -> Result<Vec<(Dial, api::DialInfo)>, Error> {
-        let url = self.cfg.base_url.join("/api/v0/dial/list")?;
-        let response = self
-            .client
-            .get(url)
-            .query(&[("key", &*self.cfg.key)])
-            .send()
-            .await?
-            .error_for_status()?;
-
-        let mut dials = response_json::<Vec<api::DialInfo>>(response).await?;
+        let req = self.request(Method::GET, "/api/v0/dial/list")?;
+        let mut dials = self.send_json::<Vec<api::DialInfo>>(req).await?;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(ref cache) = self.cfg.cache {
+            cache.revalidate(&dials);
+        }
         dials
             .drain(..)
             .map(|dialinfo| {
@@ -137,33 +416,463 @@ impl Client {
             .collect()
     }
 
+    /// Sets the value of each dial in `values`, sending up to
+    /// [`MAX_CONCURRENT_SETS`] requests at once.
+    ///
+    /// Returns the result of setting each dial, in the same order as
+    /// `values`. A failure to set one dial doesn't stop the others from
+    /// being set.
+    pub async fn set_values(
+        &self,
+        values: impl IntoIterator<Item = (Id, Percent)>,
+    ) -> Vec<(Id, Result<(), Error>)> {
+        self.set_each(values, |dial, value| async move { dial.set(value).await })
+            .await
+    }
+
+    /// Sets the backlight of each dial in `backlights`, sending up to
+    /// [`MAX_CONCURRENT_SETS`] requests at once.
+    ///
+    /// Returns the result of setting each dial, in the same order as
+    /// `backlights`. A failure to set one dial doesn't stop the others from
+    /// being set.
+    pub async fn set_backlights(
+        &self,
+        backlights: impl IntoIterator<Item = (Id, dial::Backlight)>,
+    ) -> Vec<(Id, Result<(), Error>)> {
+        self.set_each(backlights, |dial, backlight| async move {
+            dial.set_backlight(backlight).await
+        })
+        .await
+    }
+
+    async fn set_each<T, F>(
+        &self,
+        items: impl IntoIterator<Item = (Id, T)>,
+        set: impl Fn(Dial, T) -> F,
+    ) -> Vec<(Id, Result<(), Error>)>
+    where
+        F: std::future::Future<Output = Result<(), Error>>,
+    {
+        use futures::stream::{self, StreamExt};
+
+        let set = &set;
+        stream::iter(items)
+            .map(|(uid, item)| {
+                let dial = self.dial(uid.clone());
+                async move {
+                    let result = match dial {
+                        Ok(dial) => set(dial, item).await,
+                        Err(error) => Err(error.into()),
+                    };
+                    (uid, result)
+                }
+            })
+            .buffered(MAX_CONCURRENT_SETS)
+            .collect()
+            .await
+    }
+
+    /// Returns the VU-Server's base URL.
+    pub fn base_url(&self) -> &Url {
+        &self.cfg.base_url
+    }
+
+    /// Checks that the VU-Server is reachable and accepts the client's API
+    /// key, returning information about it.
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        skip(self),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn server_info(&self) -> Result<ServerInfo, Error> {
+        let req = self.request(Method::GET, "/api/v0/dial/list")?;
+        #[cfg(not(target_arch = "wasm32"))]
+        let start = std::time::Instant::now();
+        let response = self.send(req).await?;
+        #[cfg(not(target_arch = "wasm32"))]
+        let latency = Some(start.elapsed());
+        #[cfg(target_arch = "wasm32")]
+        let latency = None;
+        let server = response
+            .headers()
+            .get(header::SERVER)
+            .and_then(|server| server.to_str().ok())
+            .map(ToOwned::to_owned);
+        let dials = response_json::<Vec<serde::de::IgnoredAny>>(response)?.len();
+        Ok(ServerInfo {
+            server,
+            dials,
+            latency,
+        })
+    }
+
+    /// Asks the VU-Server to scan for newly connected dials, and provision
+    /// them.
+    ///
+    /// Provisioning happens in the background, so newly connected dials may
+    /// not be returned by [`Client::list_dials`] immediately.
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        skip(self),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn provision_dials(&self) -> Result<(), Error> {
+        let req = self.request(Method::GET, "/api/v0/dial/provision")?;
+        self.send_json::<serde::de::IgnoredAny>(req).await?;
+        Ok(())
+    }
+
+    /// Asks the VU-Server to scan for newly connected dials, and provision
+    /// them, returning a [`Provisioning`] that can be polled for the new dials.
+    ///
+    /// Unlike [`Client::provision_dials`], this lists the dials the VU-Server
+    /// has already provisioned before starting the scan.
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        skip(self),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn start_provisioning(&self) -> Result<Provisioning, Error> {
+        let known = self
+            .list_dials()
+            .await?
+            .into_iter()
+            .map(|(_, info)| info.uid)
+            .collect::<Vec<_>>();
+        tracing::debug!(dials = known.len(), "Provisioning dials...");
+        self.provision_dials().await?;
+        Ok(Provisioning {
+            client: self.clone(),
+            known,
+        })
+    }
+
+    /// Lists all of the VU-Server's API keys.
+    ///
+    /// The admin API requires the client to use the VU-Server's master key.
+    #[cfg(feature = "admin")]
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        skip(self),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn list_api_keys(&self) -> Result<Vec<api::KeyInfo>, Error> {
+        self.send_json(self.admin_request("keys/list")?).await
+    }
+
+    /// Creates a new API key named `name`, which may access the dials in
+    /// `dials`, and returns the new key.
+    ///
+    /// The admin API requires the client to use the VU-Server's master key.
+    #[cfg(feature = "admin")]
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        skip(self),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn create_api_key(&self, name: &str, dials: &[Id]) -> Result<String, Error> {
+        let req = self
+            .admin_request("keys/create")?
+            .query("name", name)
+            .query("dials", dial_list(dials));
+        self.send_json(req).await
+    }
+
+    /// Changes the name of the API key `target` to `name`, and replaces the
+    /// dials it may access with `dials`.
+    ///
+    /// The admin API requires the client to use the VU-Server's master key.
+    #[cfg(feature = "admin")]
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        skip(self, target),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn update_api_key(
+        &self,
+        target: &str,
+        name: &str,
+        dials: &[Id],
+    ) -> Result<(), Error> {
+        let req = self
+            .admin_request("keys/update")?
+            .query("target_key", target)
+            .query("name", name)
+            .query("dials", dial_list(dials));
+        self.send_json::<serde::de::IgnoredAny>(req).await?;
+        Ok(())
+    }
+
+    /// Deletes the API key `target`.
+    ///
+    /// The admin API requires the client to use the VU-Server's master key.
+    #[cfg(feature = "admin")]
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        skip(self, target),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn delete_api_key(&self, target: &str) -> Result<(), Error> {
+        let req = self
+            .admin_request("keys/remove")?
+            .query("target_key", target);
+        self.send_json::<serde::de::IgnoredAny>(req).await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "admin")]
+    fn admin_request(&self, path: &str) -> Result<RequestBuilder, Error> {
+        let url = self.cfg.base_url.join("/api/v0/admin/")?.join(path)?;
+        Ok(self.authorized(Method::GET, url))
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder, Error> {
+        let url = self.cfg.base_url.join(path)?;
+        Ok(self.authorized(method, url))
+    }
+
+    fn authorized(&self, method: Method, url: Url) -> RequestBuilder {
+        RequestBuilder::new(method, url).query("key", self.cfg.key())
+    }
+
+    /// Sends `req`, and returns the `data` field of its JSON response,
+    /// retrying if the client has a retry policy.
+    async fn send_json<T: serde::de::DeserializeOwned>(
+        &self,
+        req: RequestBuilder,
+    ) -> Result<T, Error> {
+        let req = &req;
+        self.retrying(move || async move { response_json(self.send(req.clone()).await?) })
+            .await
+    }
+
+    /// Runs `f`, retrying transient errors if the client has a retry policy.
+    #[cfg(all(feature = "retry", not(target_arch = "wasm32")))]
+    async fn retrying<T, F>(&self, mut f: impl FnMut() -> F) -> Result<T, Error>
+    where
+        F: std::future::Future<Output = Result<T, Error>>,
+    {
+        match self.cfg.retry {
+            Some(ref policy) => policy.retry("send request", f).await,
+            None => f().await,
+        }
+    }
+
+    #[cfg(not(all(feature = "retry", not(target_arch = "wasm32"))))]
+    async fn retrying<T, F>(&self, f: impl FnOnce() -> F) -> Result<T, Error>
+    where
+        F: std::future::Future<Output = Result<T, Error>>,
+    {
+        f().await
+    }
+
+    async fn send(&self, req: RequestBuilder) -> Result<Response, Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(ref breaker) = self.cfg.breaker {
+            if let CircuitState::Open { remaining } = breaker.state() {
+                return Err(Error::CircuitOpen { remaining });
+            }
+            let result = self.send_unchecked(req).await;
+            match result {
+                Ok(_) => breaker.record_success(),
+                // Only failures that mean the VU-Server itself is in trouble
+                // count towards opening the breaker.
+                Err(Error::Transport(_)) => breaker.record_failure(),
+                Err(Error::ServerHttp { status, .. }) if status.is_server_error() => {
+                    breaker.record_failure()
+                }
+                Err(_) => {}
+            }
+            return result;
+        }
+
+        self.send_unchecked(req).await
+    }
+
+    async fn send_unchecked(&self, req: RequestBuilder) -> Result<Response, Error> {
+        let req = req.build()?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let rsp = match self.cfg.observer {
+            Some(ref observer) => {
+                let method = req.method().clone();
+                let path = req.uri().path().to_owned();
+                let start = std::time::Instant::now();
+                let rsp = self.transport.send(req).await;
+                let status = rsp.as_ref().ok().map(|rsp| rsp.status());
+                observer.observe(&method, &path, status, start.elapsed());
+                rsp
+            }
+            None => self.transport.send(req).await,
+        };
+        #[cfg(target_arch = "wasm32")]
+        let rsp = self.transport.send(req).await;
+
+        let rsp = rsp.map_err(Error::Transport)?;
+        tracing::debug!(rsp.http_status = %rsp.status(), "received response");
+
+        let status = rsp.status();
+        if status.is_client_error() || status.is_server_error() {
+            let message = String::from_utf8_lossy(rsp.body()).into_owned();
+            let retry_after = match status {
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                    retry_after(rsp.headers())
+                }
+                _ => None,
+            };
+            return Err(Error::ServerHttp {
+                status,
+                message,
+                retry_after,
+            });
+        }
+        Ok(rsp)
+    }
+
     pub fn from_builder(
         builder: ClientBuilder,
         key: String,
         base_url: impl IntoUrl,
     ) -> Result<Self, NewClientError> {
         let client = builder.build().map_err(NewClientError::BuildClient)?;
-        let base_url = base_url
-            .into_url()
-            .map_err(NewClientError::InvalidBaseUrl)?;
+        Self::with_transport(client, key, base_url)
+    }
+
+    /// Returns a new client that sends requests using `transport`, rather
+    /// than `reqwest`.
+    pub fn with_transport(
+        transport: impl Transport,
+        key: String,
+        base_url: impl IntoUrl,
+    ) -> Result<Self, NewClientError> {
+        let base_url = into_url(base_url)?;
         Ok(Self {
-            cfg: Arc::new(Config { key, base_url }),
-            client,
+            cfg: Arc::new(Config {
+                key: Arc::new(RwLock::new(key)),
+                base_url,
+                #[cfg(all(feature = "retry", not(target_arch = "wasm32")))]
+                retry: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                breaker: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                cache: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                observer: None,
+            }),
+            transport: Arc::new(transport),
         })
     }
+
+    /// Returns a client that retries requests that fail with transient errors
+    /// according to `policy`.
+    ///
+    /// By default, clients don't retry requests. [`Client::server_info`] is
+    /// never retried, since it's used to check whether the VU-Server is
+    /// reachable.
+    #[cfg(all(feature = "retry", not(target_arch = "wasm32")))]
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        Arc::make_mut(&mut self.cfg).retry = Some(policy);
+        self
+    }
+
+    /// Returns a client whose requests go through a circuit breaker
+    /// configured by `breaker`.
+    ///
+    /// The breaker's state is shared by all clones of the returned client,
+    /// and the [`Dial`]s it creates, so this should be called before creating
+    /// any dials.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        Arc::make_mut(&mut self.cfg).breaker = Some(Arc::new(breaker::Breaker::new(breaker)));
+        self
+    }
+
+    /// Returns the state of the client's circuit breaker, or `None` if it
+    /// doesn't have one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn circuit_state(&self) -> Option<CircuitState> {
+        self.cfg.breaker.as_ref().map(|breaker| breaker.state())
+    }
+
+    /// Returns a client that caches each dial's [status] for up to
+    /// `max_age`.
+    ///
+    /// A dial's cached status is forgotten as soon as the client changes
+    /// that dial, or [`Client::list_dials`] shows that it changed some other
+    /// way. Statuses with changes that the VU-Server hasn't sent to the dial
+    /// yet aren't cached. Otherwise, changes made by other clients may not be
+    /// seen until `max_age` has passed, so it should be kept short.
+    ///
+    /// The cache is shared by all clones of the returned client, and the
+    /// [`Dial`]s it creates, so this should be called before creating any
+    /// dials.
+    ///
+    /// [status]: Dial::status
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_status_cache(mut self, max_age: Duration) -> Self {
+        Arc::make_mut(&mut self.cfg).cache = Some(Arc::new(cache::StatusCache::new(max_age)));
+        self
+    }
+
+    /// Returns a client that calls `observer` with the method, endpoint,
+    /// status, and latency of every request it sends.
+    ///
+    /// The observer is shared by all clones of the returned client, and the
+    /// [`Dial`]s it creates, so this should be called before creating any
+    /// dials.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_observer(mut self, observer: impl Observer) -> Self {
+        Arc::make_mut(&mut self.cfg).observer = Some(observer::SharedObserver::new(observer));
+        self
+    }
+
+    /// Forgets every status cached by [`Client::with_status_cache`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn clear_status_cache(&self) {
+        if let Some(ref cache) = self.cfg.cache {
+            cache.clear();
+        }
+    }
+
+    /// Changes the API key sent with every request.
+    ///
+    /// The key is shared by all clones of this client, and the [`Dial`]s
+    /// they've created, so requests sent by any of them after this returns
+    /// use the new key. Requests that are already in flight (including any
+    /// retries of them) keep using the old key.
+    pub fn set_key(&self, key: impl Into<String>) {
+        *self
+            .cfg
+            .key
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = key.into();
+        tracing::debug!("API key changed");
+    }
 }
 
 impl Dial {
-    fn build_request(&self, method: Method, path: &str) -> Result<reqwest::RequestBuilder, Error> {
-        let Client {
-            ref cfg,
-            ref client,
-        } = self.client;
+    fn build_request(&self, method: Method, url: &Url) -> RequestBuilder {
+        self.client.authorized(method, url.clone())
+    }
+
+    // Every request sent through this method may change the dial. Requests
+    // that only read the dial's state go to the client directly.
+
+    async fn send_json<T: serde::de::DeserializeOwned>(
+        &self,
+        req: RequestBuilder,
+    ) -> Result<T, Error> {
+        let result = self.client.send_json(req).await;
+        self.invalidate_status();
+        result
+    }
 
-        // TODO(eliza): i hate that Reqwest takes owned, non-ref-counted URLs
-        // and we can't seem to cache these...maybe switch to raw Hyper?
-        let url = self.base_url.join(path)?;
-        Ok(client.request(method, url).query(&[("key", &*cfg.key)]))
+    fn invalidate_status(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(ref cache) = self.client.cfg.cache {
+            cache.invalidate(&self.uid);
+        }
     }
 
     pub fn id(&self) -> &Id {
@@ -178,8 +887,69 @@ impl Dial {
         err(Display, level = Level::DEBUG),
     )]
     pub async fn status(&self) -> Result<dial::Status, Error> {
-        let response = self.build_request(Method::GET, "status")?.send().await?;
-        response_json(response).await
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(ref cache) = self.client.cfg.cache {
+            if let Some(status) = cache.get(&self.uid) {
+                return Ok(status);
+            }
+        }
+
+        let status: dial::Status = self
+            .client
+            .send_json(self.build_request(Method::GET, &self.urls.status))
+            .await?;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(ref cache) = self.client.cfg.cache {
+            cache.insert(&status);
+        }
+        Ok(status)
+    }
+
+    /// Returns a stream that polls the dial's status every `interval`, and
+    /// yields it whenever something changed.
+    ///
+    /// The first status is always yielded. After that, a status is only
+    /// yielded if the dial's value, backlight, or image differ from the last
+    /// one, or if the VU-Server reports that one of them has changed but
+    /// hasn't been sent to the dial yet. Errors are yielded as they happen,
+    /// and polling continues afterwards, so that a restarting VU-Server
+    /// doesn't end the stream.
+    #[cfg(feature = "watch")]
+    pub fn watch_status(
+        &self,
+        interval: Duration,
+    ) -> impl futures::Stream<Item = Result<dial::Status, Error>> + Send + 'static {
+        struct Watch {
+            dial: Dial,
+            last: Option<dial::Status>,
+            polled: bool,
+        }
+
+        let watch = Watch {
+            dial: self.clone(),
+            last: None,
+            polled: false,
+        };
+        futures::stream::unfold(watch, move |mut watch| async move {
+            loop {
+                if watch.polled {
+                    tokio::time::sleep(interval).await;
+                }
+                watch.polled = true;
+
+                let status = match watch.dial.status().await {
+                    Ok(status) => status,
+                    Err(error) => return Some((Err(error), watch)),
+                };
+                if let Some(ref last) = watch.last {
+                    if !status_changed(last, &status) {
+                        continue;
+                    }
+                }
+                watch.last = Some(status.clone());
+                return Some((Ok(status), watch));
+            }
+        })
     }
 
     #[tracing::instrument(
@@ -190,12 +960,10 @@ impl Dial {
         err(Display, level = Level::DEBUG),
     )]
     pub async fn set_name(&self, name: &str) -> Result<(), Error> {
-        let rsp = self
-            .build_request(Method::GET, "name")?
-            .query(&[("name", name)])
-            .send()
-            .await?;
-        response_json(rsp).await
+        let req = self
+            .build_request(Method::GET, &self.urls.name)
+            .query("name", name);
+        self.send_json(req).await
     }
 
     #[tracing::instrument(
@@ -206,12 +974,10 @@ impl Dial {
         err(Display, level = Level::DEBUG),
     )]
     pub async fn set(&self, value: Percent) -> Result<(), Error> {
-        let rsp = self
-            .build_request(Method::GET, "set")?
-            .query(&[("value", &value)])
-            .send()
-            .await?;
-        response_json(rsp).await
+        let req = self
+            .build_request(Method::GET, &self.urls.set)
+            .query("value", percent(value));
+        self.send_json(req).await
     }
 
     #[tracing::instrument(
@@ -225,12 +991,35 @@ impl Dial {
         &self,
         dial::Backlight { red, green, blue }: dial::Backlight,
     ) -> Result<(), Error> {
-        let rsp = self
-            .build_request(Method::GET, "backlight")?
-            .query(&[("red", &red), ("green", &green), ("blue", &blue)])
-            .send()
-            .await?;
-        response_json(rsp).await
+        let req = self
+            .build_request(Method::GET, &self.urls.backlight)
+            .query("red", percent(red))
+            .query("green", percent(green))
+            .query("blue", percent(blue));
+        self.send_json(req).await
+    }
+
+    /// Moves the dial's needle to the raw position `raw`, and saves that
+    /// position as the calibrated position of `point`.
+    ///
+    /// Raw positions are the values written to the dial's motor driver, rather
+    /// than percentages, so this can be used to correct needle drift.
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        name = "Dial::calibrate",
+        skip(self),
+        fields(uid = %self.uid),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn calibrate(&self, point: dial::CalibrationPoint, raw: u16) -> Result<(), Error> {
+        let url = match point {
+            dial::CalibrationPoint::Max => &self.urls.calibrate_max,
+            dial::CalibrationPoint::Half => &self.urls.calibrate_half,
+        };
+        let req = self
+            .build_request(Method::GET, url)
+            .query("value", raw.to_string());
+        self.send_json(req).await
     }
 
     #[tracing::instrument(
@@ -245,8 +1034,8 @@ impl Dial {
         period: std::time::Duration,
         step: Percent,
     ) -> Result<(), Error> {
-        self.set_easing(
-            self.build_request(Method::GET, "easing/dial")?,
+        self.send_easing(
+            self.build_request(Method::GET, &self.urls.easing_dial),
             period,
             step,
         )
@@ -261,51 +1050,125 @@ impl Dial {
         err(Display, level = Level::DEBUG),
     )]
     pub async fn set_backlight_easing(&self, period: Duration, step: Percent) -> Result<(), Error> {
-        self.set_easing(
-            self.build_request(Method::GET, "easing/backlight")?,
+        self.send_easing(
+            self.build_request(Method::GET, &self.urls.easing_backlight),
             period,
             step,
         )
         .await
     }
 
-    async fn set_easing(
+    /// Returns the dial's current easing config for its needle and backlight.
+    ///
+    /// This is the same as the `easing` field of the dial's [status], without
+    /// fetching the rest of the status.
+    ///
+    /// [status]: Dial::status
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        name = "Dial::get_easing",
+        skip(self),
+        fields(uid = %self.uid),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn get_easing(&self) -> Result<dial::Easing, Error> {
+        self.client
+            .send_json(self.build_request(Method::GET, &self.urls.easing_get))
+            .await
+    }
+
+    /// Sets the easing of the dial's needle and backlight at once.
+    ///
+    /// Channels whose config is `None` are left as they are. Both configs are
+    /// [validated](dial::EasingConfig::validate) before any request is sent,
+    /// so an invalid backlight config won't leave the needle's easing
+    /// changed.
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        name = "Dial::set_easing",
+        skip(self),
+        fields(uid = %self.uid),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn set_easing(
+        &self,
+        dial: Option<dial::EasingConfig>,
+        backlight: Option<dial::EasingConfig>,
+    ) -> Result<(), Error> {
+        let validate = |channel, config: &Option<dial::EasingConfig>| {
+            config
+                .as_ref()
+                .map_or(Ok(()), dial::EasingConfig::validate)
+                .map_err(|source| Error::InvalidEasing { channel, source })
+        };
+        validate("dial", &dial)?;
+        validate("backlight", &backlight)?;
+
+        if let Some(dial::EasingConfig { period, step }) = dial {
+            self.set_dial_easing(period, step).await?;
+        }
+        if let Some(dial::EasingConfig { period, step }) = backlight {
+            self.set_backlight_easing(period, step).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_easing(
         &self,
-        req: reqwest::RequestBuilder,
+        req: RequestBuilder,
         period: Duration,
         step: Percent,
     ) -> Result<(), Error> {
-        let rsp = req
-            .query(&[("period", period.as_millis())])
-            .query(&[("step", step)])
-            .send()
-            .await?;
-        response_json(rsp).await
+        let req = req
+            .query("period", period.as_millis().to_string())
+            .query("step", percent(step));
+        self.send_json(req).await
     }
 
     #[tracing::instrument(
         level = Level::DEBUG,
         name = "Dial::set_image",
-        skip(self, part),
+        skip(self, image),
         fields(uid = %self.uid),
         err(Display, level = Level::DEBUG),
     )]
-    pub async fn set_image(
-        &self,
-        filename: &str,
-        part: reqwest::multipart::Part,
-        force: bool,
-    ) -> Result<(), Error> {
-        let part = part.file_name(filename.to_string());
-        let multipart = reqwest::multipart::Form::new().part("imgfile", part);
+    pub async fn set_image(&self, filename: &str, image: &[u8], force: bool) -> Result<(), Error> {
         let mut req = self
-            .build_request(Method::POST, "image/set")?
-            .query(&[("imgfile", filename)]);
+            .build_request(Method::POST, &self.urls.image_set)
+            .query("imgfile", filename);
         if force {
-            req = req.query(&[("force", "true")])
+            req = req.query("force", "true")
         }
-        let rsp = req.multipart(multipart).send().await?;
-        response_json(rsp).await
+        let req = req.image(filename, image);
+        self.send_json(req).await
+    }
+
+    /// Downloads the dial's current background image.
+    ///
+    /// Returns the image file's contents, which are usually a PNG, and its
+    /// content type.
+    ///
+    /// The VU-Server doesn't report a checksum of the dial's image, so checking
+    /// whether a dial already displays an image means downloading it and
+    /// comparing the bytes.
+    #[tracing::instrument(
+        level = Level::DEBUG,
+        name = "Dial::get_image",
+        skip(self),
+        fields(uid = %self.uid),
+        err(Display, level = Level::DEBUG),
+    )]
+    pub async fn get_image(&self) -> Result<Image, Error> {
+        self.client.retrying(|| self.get_image_once()).await
+    }
+
+    async fn get_image_once(&self) -> Result<Image, Error> {
+        let rsp = self
+            .client
+            .send(self.build_request(Method::GET, &self.urls.image_get))
+            .await?;
+
+        Ok(api::image::parse_get_response(rsp)?)
     }
 
     #[tracing::instrument(
@@ -316,8 +1179,214 @@ impl Dial {
         err(Display, level = Level::DEBUG),
     )]
     pub async fn reload_hw_info(&self) -> Result<dial::Status, Error> {
-        let rsp = self.build_request(Method::GET, "reload")?.send().await?;
-        response_json(rsp).await
+        self.send_json(self.build_request(Method::GET, &self.urls.reload))
+            .await
+    }
+}
+
+// === impl Builder ===
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Builder {
+    /// The environment variable that [`Client::builder`] reads the API key
+    /// from.
+    pub const API_KEY_ENV: &'static str = "VU_DIALS_API_KEY";
+
+    /// The environment variable that [`Client::builder`] reads the server
+    /// address from.
+    pub const SERVER_ADDR_ENV: &'static str = "VU_DIALS_SERVER_ADDR";
+
+    /// The address of the VU-Server, if none is configured.
+    pub const DEFAULT_SERVER_ADDR: &'static str = "http://localhost:5340";
+
+    /// The `User-Agent` header sent with requests, by default.
+    pub const DEFAULT_USER_AGENT: &'static str =
+        concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+    /// How long to wait for the VU-Server to accept a connection, by default.
+    pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// How long to wait for the VU-Server to respond to a request, by default.
+    ///
+    /// This is longer than the connect timeout, since uploading an image to a
+    /// dial can take several seconds.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// How long an idle connection to the VU-Server is kept open, by default.
+    pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+    /// Sets how long to wait for the VU-Server to accept a connection, or
+    /// `None` to wait for as long as the OS allows.
+    pub fn connect_timeout(self, timeout: impl Into<Option<Duration>>) -> Self {
+        Self {
+            connect_timeout: timeout.into(),
+            ..self
+        }
+    }
+
+    /// Sets how long to wait for each request to complete, from connecting
+    /// to reading the whole response, or `None` to wait forever.
+    pub fn timeout(self, timeout: impl Into<Option<Duration>>) -> Self {
+        Self {
+            timeout: timeout.into(),
+            ..self
+        }
+    }
+
+    /// Sets how long to keep idle connections to the VU-Server open, or `None`
+    /// to keep them open forever.
+    pub fn pool_idle_timeout(self, timeout: impl Into<Option<Duration>>) -> Self {
+        Self {
+            pool_idle_timeout: timeout.into(),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of idle connections to the VU-Server to keep
+    /// open.
+    ///
+    /// By default, there's no limit.
+    pub fn pool_max_idle_per_host(self, max: usize) -> Self {
+        Self {
+            pool_max_idle_per_host: max,
+            ..self
+        }
+    }
+
+    /// Sets the interval between TCP keepalive probes on connections to the
+    /// VU-Server, or `None` to disable TCP keepalive.
+    ///
+    /// Keepalive probes let the OS notice when a pooled connection has been
+    /// silently dropped (by a flaky Wi-Fi link, or a NAT timing it out), so
+    /// that it isn't used for the next request. By default, TCP keepalive is
+    /// disabled.
+    pub fn tcp_keepalive(self, interval: impl Into<Option<Duration>>) -> Self {
+        Self {
+            tcp_keepalive: interval.into(),
+            ..self
+        }
+    }
+
+    /// Sets whether to speak HTTP/2 to the VU-Server without negotiating it
+    /// first.
+    ///
+    /// HTTP/2 sends every request over one connection, rather than opening a
+    /// connection per concurrent request. The VU-Server itself only speaks
+    /// HTTP/1.1, though, so this is only useful when it's behind a reverse
+    /// proxy that speaks HTTP/2. By default, HTTP/1.1 is used.
+    pub fn http2_prior_knowledge(self, enabled: bool) -> Self {
+        Self {
+            http2_prior_knowledge: enabled,
+            ..self
+        }
+    }
+
+    /// Returns a builder with the API key and server address taken from the
+    /// [`API_KEY_ENV`] and [`SERVER_ADDR_ENV`] environment variables, if
+    /// they're set.
+    ///
+    /// [`API_KEY_ENV`]: Self::API_KEY_ENV
+    /// [`SERVER_ADDR_ENV`]: Self::SERVER_ADDR_ENV
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        Self {
+            key: var(Self::API_KEY_ENV),
+            server: var(Self::SERVER_ADDR_ENV),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the API key to send with requests.
+    pub fn key(self, key: impl Into<String>) -> Self {
+        Self {
+            key: Some(key.into()),
+            ..self
+        }
+    }
+
+    /// Sets the base URL of the VU-Server, such as `http://localhost:5340`.
+    ///
+    /// If this isn't set, [`DEFAULT_SERVER_ADDR`](Self::DEFAULT_SERVER_ADDR)
+    /// is used.
+    pub fn server(self, base_url: impl Into<String>) -> Self {
+        Self {
+            server: Some(base_url.into()),
+            ..self
+        }
+    }
+
+    /// Sets the `User-Agent` header sent with requests.
+    pub fn user_agent(self, user_agent: impl Into<String>) -> Self {
+        Self {
+            user_agent: Some(user_agent.into()),
+            ..self
+        }
+    }
+
+    /// Builds the client.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if no API key was configured, or if the server
+    /// address isn't a valid URL.
+    pub fn build(self) -> Result<Client, NewClientError> {
+        let key = self.key.ok_or(NewClientError::MissingKey)?;
+        let server = self.server.as_deref().unwrap_or(Self::DEFAULT_SERVER_ADDR);
+        let mut builder = Client::default_builder()
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .tcp_keepalive(self.tcp_keepalive);
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(user_agent) = self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        Client::from_builder(builder, key, server)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            key: None,
+            server: None,
+            user_agent: None,
+            connect_timeout: Some(Self::DEFAULT_CONNECT_TIMEOUT),
+            timeout: Some(Self::DEFAULT_TIMEOUT),
+            pool_idle_timeout: Some(Self::DEFAULT_POOL_IDLE_TIMEOUT),
+            pool_max_idle_per_host: usize::MAX,
+            tcp_keepalive: None,
+            http2_prior_knowledge: false,
+        }
+    }
+}
+
+// === impl Provisioning ===
+
+impl Provisioning {
+    /// Returns the dials that have been provisioned since the scan started.
+    ///
+    /// Provisioning happens in the background, so this may return no dials
+    /// until the VU-Server has finished, and should be polled until new dials
+    /// show up, or the caller gives up waiting.
+    pub async fn new_dials(&self) -> Result<Vec<(Dial, api::DialInfo)>, Error> {
+        let mut dials = self.client.list_dials().await?;
+        dials.retain(|(_, info)| !self.known.contains(&info.uid));
+        Ok(dials)
+    }
+
+    /// Returns the UIDs of the dials that were already provisioned when the
+    /// scan started.
+    pub fn known_dials(&self) -> &[Id] {
+        &self.known
     }
 }
 
@@ -327,23 +1396,83 @@ impl fmt::Display for Dial {
     }
 }
 
-async fn response_json<T: serde::de::DeserializeOwned>(rsp: reqwest::Response) -> Result<T, Error> {
-    tracing::debug!(rsp.http_status = %rsp.status(), "received response");
-    let rsp = rsp.error_for_status()?;
-    let body = rsp.bytes().await?;
-    let json = match serde_json::from_slice::<api::Response<T>>(&body) {
-        Ok(json) => json,
-        Err(error) => {
-            let src = String::from_utf8_lossy(&body).to_string();
-            let start = miette::SourceOffset::from_location(&src, error.line(), error.column());
-            let span = miette::SourceSpan::new(start, 1);
-            let src = miette::NamedSource::new("response body", src).with_language("JSON");
-            return Err(JsonError { src, span, error }.into());
-        }
-    };
-    if json.status != api::Status::Ok {
-        return Err(Error::Server(json.message));
-    }
-
-    Ok(json.data)
+// === impl Config ===
+
+impl Config {
+    fn key(&self) -> String {
+        self.key
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+// === impl DialUrls ===
+
+impl DialUrls {
+    fn new(cfg: &Config, uid: &Id) -> Result<Self, url::ParseError> {
+        let base = cfg.base_url.join(&format!("api/v0/dial/{uid}/"))?;
+        let endpoint = |path: &str| base.join(path);
+        Ok(Self {
+            status: endpoint("status")?,
+            name: endpoint("name")?,
+            set: endpoint("set")?,
+            backlight: endpoint("backlight")?,
+            calibrate_max: endpoint("calibrate/max")?,
+            calibrate_half: endpoint("calibrate/half")?,
+            easing_dial: endpoint("easing/dial")?,
+            easing_backlight: endpoint("easing/backlight")?,
+            easing_get: endpoint("easing/get")?,
+            image_set: endpoint("image/set")?,
+            image_get: endpoint("image/get")?,
+            reload: endpoint("reload")?,
+        })
+    }
+}
+
+fn into_url(url: impl IntoUrl) -> Result<Url, NewClientError> {
+    url.into_url().map_err(NewClientError::InvalidBaseUrl)
+}
+
+/// Formats a list of dials the way the admin API expects: a single
+/// semicolon-separated string.
+#[cfg(feature = "admin")]
+fn dial_list(dials: &[Id]) -> String {
+    dials
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Parses a `Retry-After` header.
+///
+/// Only the delay-seconds form is supported. The VU-Server doesn't send
+/// HTTP dates, and a date that can't be parsed is treated the same as a
+/// missing header, so the retry policy's own backoff is used instead.
+fn retry_after(headers: &http::HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Returns `true` if `status` should be yielded by [`Dial::watch_status`],
+/// given the last status it yielded.
+#[cfg(feature = "watch")]
+fn status_changed(last: &dial::Status, status: &dial::Status) -> bool {
+    status.value_changed
+        || status.backlight_changed
+        || status.image_changed
+        || status.value != last.value
+        || status.backlight != last.backlight
+        || status.image_file != last.image_file
+}
+
+fn response_json<T: serde::de::DeserializeOwned>(rsp: Response) -> Result<T, Error> {
+    Ok(api::endpoint::parse_json(rsp)?)
 }
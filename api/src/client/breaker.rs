@@ -0,0 +1,144 @@
+//! A circuit breaker that stops sending requests to a VU-Server that keeps
+//! failing.
+//!
+//! When the VU-Server goes down, every dial the daemon manages keeps retrying
+//! its own requests, so the server is hit with retries from all of them at
+//! once when it comes back. A circuit breaker shared by all of a client's
+//! requests fails them immediately instead, until the server has had time to
+//! recover.
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Configures a circuit breaker for a [`Client`](super::Client).
+///
+/// After enough requests in a row fail because the VU-Server couldn't be
+/// reached or returned a server error, the breaker opens, and requests fail
+/// immediately with [`Error::CircuitOpen`](super::Error::CircuitOpen) until
+/// the cool-down period ends. After that, the breaker is half-open: requests
+/// are sent again, and the breaker closes as soon as one succeeds, or opens
+/// again if one fails.
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct CircuitBreaker {
+    failure_threshold: usize,
+    cool_down: Duration,
+}
+
+/// The state of a client's circuit breaker, returned by
+/// [`Client::circuit_state`](super::Client::circuit_state).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent normally.
+    Closed,
+    /// Too many requests failed in a row, so requests fail immediately for
+    /// the `remaining` cool-down time.
+    Open { remaining: Duration },
+    /// The cool-down has ended, and the next request decides whether the
+    /// breaker closes, or opens again.
+    HalfOpen,
+}
+
+/// A circuit breaker's state, shared by all clones of a client.
+#[derive(Debug)]
+pub(super) struct Breaker {
+    config: CircuitBreaker,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    failures: usize,
+    open_until: Option<Instant>,
+}
+
+// === impl CircuitBreaker ===
+
+impl CircuitBreaker {
+    /// The default number of requests in a row that must fail to open the
+    /// breaker.
+    pub const DEFAULT_FAILURE_THRESHOLD: usize = 5;
+
+    /// The default time that the breaker stays open for.
+    pub const DEFAULT_COOL_DOWN: Duration = Duration::from_secs(10);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of requests in a row that must fail to open the
+    /// breaker. Thresholds less than 1 are treated as 1.
+    pub fn failure_threshold(self, failure_threshold: usize) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            ..self
+        }
+    }
+
+    /// Sets how long the breaker stays open before requests are sent again.
+    pub fn cool_down(self, cool_down: Duration) -> Self {
+        Self { cool_down, ..self }
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            failure_threshold: Self::DEFAULT_FAILURE_THRESHOLD,
+            cool_down: Self::DEFAULT_COOL_DOWN,
+        }
+    }
+}
+
+// === impl Breaker ===
+
+impl Breaker {
+    pub(super) fn new(config: CircuitBreaker) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub(super) fn state(&self) -> CircuitState {
+        let state = self.lock();
+        match state.open_until {
+            None => CircuitState::Closed,
+            Some(until) => match until.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => CircuitState::Open { remaining },
+                _ => CircuitState::HalfOpen,
+            },
+        }
+    }
+
+    pub(super) fn record_success(&self) {
+        let mut state = self.lock();
+        if state.open_until.is_some() {
+            tracing::info!("VU-Server is responding again, closing circuit breaker");
+        }
+        *state = State::default();
+    }
+
+    pub(super) fn record_failure(&self) {
+        let mut state = self.lock();
+        state.failures += 1;
+        if state.failures < self.config.failure_threshold {
+            return;
+        }
+        if state.open_until.is_none() {
+            tracing::warn!(
+                failures = state.failures,
+                cool_down = ?self.config.cool_down,
+                "VU-Server failed too many requests in a row, opening circuit breaker",
+            );
+        }
+        state.open_until = Some(Instant::now() + self.config.cool_down);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
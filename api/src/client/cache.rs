@@ -0,0 +1,107 @@
+//! Caching dials' statuses.
+//!
+//! A dial's status is only available one dial at a time, so anything that
+//! needs every dial's status (such as finding a dial by its index) sends a
+//! request per dial. A status cache remembers each dial's last status, so
+//! that asking again soon after doesn't resend those requests.
+//!
+//! The VU-Server doesn't support conditional requests, so a cached status
+//! can't be revalidated cheaply. Instead, statuses are cached for a short
+//! time, and are forgotten as soon as the client changes the dial, or as
+//! soon as the VU-Server's dial list shows that the dial has changed.
+use crate::{api::DialInfo, dial};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// A status cache's state, shared by all clones of a client.
+#[derive(Debug)]
+pub(super) struct StatusCache {
+    max_age: Duration,
+    statuses: Mutex<HashMap<dial::Id, Entry>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    status: dial::Status,
+    fetched_at: Instant,
+}
+
+// === impl StatusCache ===
+
+impl StatusCache {
+    pub(super) fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            statuses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached status for `uid`, if it was fetched within the
+    /// cache's max age.
+    pub(super) fn get(&self, uid: &dial::Id) -> Option<dial::Status> {
+        let mut statuses = self.lock();
+        match statuses.get(uid) {
+            Some(entry) if entry.fetched_at.elapsed() <= self.max_age => {
+                tracing::trace!(%uid, "status cache hit");
+                Some(entry.status.clone())
+            }
+            Some(_) => {
+                statuses.remove(uid);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Caches a status that was just fetched.
+    ///
+    /// Statuses with changes that the VU-Server hasn't sent to the dial yet
+    /// aren't cached, since they're about to be out of date.
+    pub(super) fn insert(&self, status: &dial::Status) {
+        if status.value_changed || status.backlight_changed || status.image_changed {
+            self.invalidate(&status.uid);
+            return;
+        }
+        self.lock().insert(
+            status.uid.clone(),
+            Entry {
+                status: status.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Forgets the cached status for `uid`.
+    pub(super) fn invalidate(&self, uid: &dial::Id) {
+        self.lock().remove(uid);
+    }
+
+    /// Forgets every cached status.
+    pub(super) fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Forgets the cached statuses of dials whose info in the VU-Server's
+    /// dial list no longer matches, or that are no longer listed.
+    pub(super) fn revalidate(&self, infos: &[DialInfo]) {
+        self.lock().retain(|uid, entry| {
+            infos.iter().any(|info| {
+                let status = &entry.status;
+                info.uid == *uid
+                    && info.dial_name == status.dial_name
+                    && info.value == status.value
+                    && info.backlight == status.backlight
+                    && info.image_file == status.image_file
+            })
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<dial::Id, Entry>> {
+        self.statuses
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
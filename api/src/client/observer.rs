@@ -0,0 +1,97 @@
+//! Observing the requests that a client sends.
+//!
+//! The client's tracing spans say what it's doing, but they aren't a good
+//! source of metrics. An [`Observer`] is told about every request as it
+//! completes, so that request counts and latencies can be recorded in
+//! whatever metrics system the application uses.
+use http::{Method, StatusCode};
+use std::{fmt, sync::Arc, time::Duration};
+
+/// Observes the requests that a [`Client`](super::Client) sends.
+///
+/// Observers are configured with
+/// [`Client::with_observer`](super::Client::with_observer). Any
+/// `Fn(&RequestEvent<'_>)` closure is an observer.
+pub trait Observer: Send + Sync + 'static {
+    /// Called when a request completes, whether or not it succeeded.
+    ///
+    /// This is called for each attempt, so a request that's retried is
+    /// observed once per retry. Requests that aren't sent at all, because
+    /// the client's circuit breaker is open, aren't observed.
+    fn on_request(&self, event: &RequestEvent<'_>);
+}
+
+/// A request that a client sent, passed to an [`Observer`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct RequestEvent<'a> {
+    /// The request's HTTP method.
+    pub method: &'a Method,
+    /// The API endpoint the request was sent to, such as
+    /// `/api/v0/dial/{uid}/set`.
+    ///
+    /// Dial UIDs are replaced with `{uid}`, so that requests to different
+    /// dials' endpoints can be aggregated. The API key isn't included.
+    pub endpoint: &'a str,
+    /// The HTTP status of the VU-Server's response, or `None` if no response
+    /// was received.
+    pub status: Option<StatusCode>,
+    /// How long it took to send the request and receive the response.
+    pub latency: Duration,
+}
+
+/// A client's observer, which is shared by all its clones.
+#[derive(Clone)]
+pub(super) struct SharedObserver(Arc<dyn Observer>);
+
+impl<F> Observer for F
+where
+    F: Fn(&RequestEvent<'_>) + Send + Sync + 'static,
+{
+    fn on_request(&self, event: &RequestEvent<'_>) {
+        (self)(event)
+    }
+}
+
+// === impl SharedObserver ===
+
+impl SharedObserver {
+    pub(super) fn new(observer: impl Observer) -> Self {
+        Self(Arc::new(observer))
+    }
+
+    pub(super) fn observe(
+        &self,
+        method: &Method,
+        path: &str,
+        status: Option<StatusCode>,
+        latency: Duration,
+    ) {
+        let endpoint = endpoint(path);
+        self.0.on_request(&RequestEvent {
+            method,
+            endpoint: &endpoint,
+            status,
+            latency,
+        });
+    }
+}
+
+impl fmt::Debug for SharedObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedObserver").finish_non_exhaustive()
+    }
+}
+
+/// Returns `path` with the dial UID in per-dial endpoints replaced by
+/// `{uid}`.
+fn endpoint(path: &str) -> String {
+    let Some((base, rest)) = path.split_once("/api/v0/dial/") else {
+        return path.to_owned();
+    };
+    match rest.split_once('/') {
+        Some((_uid, endpoint)) => format!("{base}/api/v0/dial/{{uid}}/{endpoint}"),
+        // `/api/v0/dial/list` isn't a per-dial endpoint.
+        None => path.to_owned(),
+    }
+}
@@ -1,11 +1,41 @@
 use serde::{de, Deserialize, Serialize};
-use serde_with::{serde_as, DeserializeFromStr, DisplayFromStr, SerializeDisplay};
-use std::{fmt, str::FromStr, sync::Arc, time::Duration};
+use serde_with::{serde_as, DisplayFromStr, SerializeDisplay};
+use std::{
+    fmt, ops,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 use thiserror::Error;
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash, DeserializeFromStr, SerializeDisplay)]
+mod color;
+
+pub use self::color::HexColorError;
+
+/// A dial's unique ID.
+///
+/// VU dials' UIDs are 24 hexadecimal digits, such as
+/// `290063000750524834313020`. Parsing an `Id` with [`FromStr`] checks that
+/// it looks like one, so that typos are caught before they're sent to the
+/// VU-Server. UIDs from the VU-Server itself, and from deserialized configs,
+/// are accepted as-is.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, SerializeDisplay)]
 pub struct Id(Arc<str>);
 
+#[derive(Debug, Error, miette::Diagnostic)]
+pub enum IdError {
+    #[error("dial UID {uid:?} must be {} digits long, not {len}", Id::LEN)]
+    #[diagnostic(code(vu_api::dial::IdError::Length), help("{}", ID_HELP))]
+    Length { uid: String, len: usize },
+
+    #[error("dial UID {uid:?} contains non-hexadecimal character {ch:?}")]
+    #[diagnostic(code(vu_api::dial::IdError::Character), help("{}", ID_HELP))]
+    Character { uid: String, ch: char },
+}
+
+const ID_HELP: &str = "dial UIDs are 24 hexadecimal digits; run `dialctl status` to list \
+    connected dials, or select a dial by its index or name instead";
+
 #[serde_as]
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Status {
@@ -22,7 +52,12 @@ pub struct Status {
     pub protocol_version: String,
     pub backlight: Backlight,
     pub image_file: String,
-    pub update_deadline: f64,
+    /// When the VU-Server will next send pending changes to the dial.
+    ///
+    /// The VU-Server sends this as a floating-point number of seconds since
+    /// the Unix epoch, and it's serialized the same way.
+    #[serde(with = "epoch_secs")]
+    pub update_deadline: SystemTime,
     pub value_changed: bool,
     pub backlight_changed: bool,
     pub image_changed: bool,
@@ -33,16 +68,45 @@ pub struct Status {
 pub struct Easing {
     pub backlight_step: Percent,
 
-    #[serde(alias = "backlight_period_ms", deserialize_with = "deserialize_millis")]
+    #[serde(
+        alias = "backlight_period_ms",
+        serialize_with = "serialize_millis",
+        deserialize_with = "deserialize_millis"
+    )]
     pub backlight_period: Duration,
 
     pub dial_step: Percent,
 
-    #[serde(alias = "dial_period_ms", deserialize_with = "deserialize_millis")]
+    #[serde(
+        alias = "dial_period_ms",
+        serialize_with = "serialize_millis",
+        deserialize_with = "deserialize_millis"
+    )]
     pub dial_period: Duration,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Serialize)]
+/// A point on a dial's scale whose needle position can be calibrated.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum CalibrationPoint {
+    /// The needle's position at 100%, its end stop.
+    Max,
+    /// The needle's position at 50%.
+    Half,
+}
+
+/// How a dial's needle or backlight eases towards a new value, as set by
+/// [`Dial::set_easing`](crate::Dial::set_easing).
+///
+/// Every `period`, the dial moves `step` percent closer to its target.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct EasingConfig {
+    /// How long to wait between steps.
+    pub period: Duration,
+    /// How far to move in each step.
+    pub step: Percent,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
 pub struct Percent(u8);
 
 #[derive(Debug, Error, miette::Diagnostic)]
@@ -51,6 +115,12 @@ pub struct Percent(u8);
 #[diagnostic(code(vu_api::errors::backlight_error))]
 pub struct PercentError(u8);
 
+#[derive(Debug, Error, miette::Diagnostic)]
+#[error("invalid percent {0}")]
+#[help = "percents must be finite numbers in the range 0-100"]
+#[diagnostic(code(vu_api::errors::percent_float_error))]
+pub struct PercentFloatError(f64);
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Backlight {
     pub red: Percent,
@@ -81,17 +151,89 @@ pub struct BacklightError {
     field: &'static str,
 }
 
+#[derive(Debug, Error, miette::Diagnostic)]
+pub enum EasingError {
+    #[error("easing period must not be zero")]
+    #[help = "a dial with a zero easing period would never reach its target"]
+    #[diagnostic(code(vu_api::dial::EasingError::ZeroPeriod))]
+    ZeroPeriod,
+
+    #[error("easing step must not be zero")]
+    #[help = "a dial with a zero easing step would never reach its target"]
+    #[diagnostic(code(vu_api::dial::EasingError::ZeroStep))]
+    ZeroStep,
+}
+
 impl fmt::Display for Id {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(&self.0)
     }
 }
 
+// === impl Status ===
+
+impl Status {
+    /// Returns how long until the VU-Server sends pending changes to the
+    /// dial, or `None` if the deadline has already passed.
+    pub fn time_until_deadline(&self) -> Option<Duration> {
+        self.update_deadline
+            .duration_since(SystemTime::now())
+            .ok()
+            .filter(|until| !until.is_zero())
+    }
+}
+
+// === impl Id ===
+
+impl Id {
+    /// The number of hex digits in a dial UID.
+    pub const LEN: usize = 24;
+
+    /// Returns a dial `Id` for `uid`, or an error if it isn't
+    /// [`Id::LEN`] hexadecimal digits.
+    ///
+    /// Leading and trailing whitespace is ignored.
+    pub fn new(uid: &str) -> Result<Self, IdError> {
+        let uid = uid.trim();
+        if let Some(ch) = uid.chars().find(|c| !c.is_ascii_hexdigit()) {
+            return Err(IdError::Character {
+                uid: uid.to_owned(),
+                ch,
+            });
+        }
+        if uid.len() != Self::LEN {
+            return Err(IdError::Length {
+                uid: uid.to_owned(),
+                len: uid.len(),
+            });
+        }
+        Ok(Self(uid.into()))
+    }
+
+    /// Returns a dial `Id` for `uid` without checking that it looks like a
+    /// dial UID.
+    ///
+    /// This is an escape hatch for dials whose UIDs don't match the usual
+    /// format.
+    pub fn new_unchecked(uid: impl Into<Arc<str>>) -> Self {
+        Self(uid.into())
+    }
+}
+
 impl FromStr for Id {
-    type Err = core::convert::Infallible;
+    type Err = IdError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(s.to_owned().into()))
+        Self::new(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(Self::new_unchecked)
     }
 }
 
@@ -106,9 +248,41 @@ impl Backlight {
     }
 }
 
+// === impl EasingConfig ===
+
+impl EasingConfig {
+    /// Returns a new `EasingConfig`, or an error if `period` or `step` is
+    /// zero.
+    pub fn new(period: Duration, step: Percent) -> Result<Self, EasingError> {
+        let config = Self { period, step };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Returns an error if this config's `period` or `step` is zero.
+    ///
+    /// Since the fields are public, an `EasingConfig` may not have been
+    /// constructed with [`EasingConfig::new`].
+    pub fn validate(&self) -> Result<(), EasingError> {
+        if self.period.is_zero() {
+            return Err(EasingError::ZeroPeriod);
+        }
+        if self.step.0 == 0 {
+            return Err(EasingError::ZeroStep);
+        }
+        Ok(())
+    }
+}
+
 // === impl Percent ===
 
 impl Percent {
+    /// 0%.
+    pub const MIN: Self = Self(0);
+
+    /// 100%.
+    pub const MAX: Self = Self(100);
+
     pub fn new(value: u8) -> Result<Self, PercentError> {
         if value > 100 {
             Err(PercentError(value))
@@ -116,6 +290,63 @@ impl Percent {
             Ok(Self(value))
         }
     }
+
+    /// Returns a `Percent` for `value`, clamping values over 100 to 100%.
+    pub const fn saturating_new(value: u8) -> Self {
+        if value > 100 {
+            Self::MAX
+        } else {
+            Self(value)
+        }
+    }
+
+    /// Returns a `Percent` for `value`, rounded to the nearest whole percent
+    /// and clamped to 0-100.
+    ///
+    /// `NaN` is treated as 0%.
+    pub fn from_f64_clamped(value: f64) -> Self {
+        if value.is_nan() {
+            return Self::MIN;
+        }
+        Self(value.round().clamp(0.0, 100.0) as u8)
+    }
+}
+
+impl ops::Add for Percent {
+    type Output = Self;
+
+    /// Adds two percents, saturating at 100%.
+    fn add(self, rhs: Self) -> Self {
+        Self::saturating_new(self.0 + rhs.0)
+    }
+}
+
+impl ops::AddAssign for Percent {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl ops::Sub for Percent {
+    type Output = Self;
+
+    /// Subtracts two percents, saturating at 0%.
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl ops::SubAssign for Percent {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis().try_into().unwrap_or(u64::MAX))
 }
 
 fn deserialize_millis<'de, D>(deserializer: D) -> Result<Duration, D::Error>
@@ -153,6 +384,20 @@ impl TryFrom<u8> for Percent {
     }
 }
 
+impl TryFrom<f64> for Percent {
+    type Error = PercentFloatError;
+
+    /// Rounds `value` to the nearest whole percent, returning an error if it
+    /// isn't finite or is outside of 0-100.
+    fn try_from(value: f64) -> Result<Percent, Self::Error> {
+        let rounded = value.round();
+        if !(0.0..=100.0).contains(&rounded) {
+            return Err(PercentFloatError(value));
+        }
+        Ok(Self(rounded as u8))
+    }
+}
+
 impl fmt::Display for Percent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Debug::fmt(self, f)
@@ -258,3 +503,78 @@ impl<'de> de::Visitor<'de> for IntsAreSometimesStrings {
             .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(s), &self))
     }
 }
+
+/// (De)serializes a [`SystemTime`] as a floating-point number of seconds
+/// since the Unix epoch, which is how the VU-Server sends timestamps.
+mod epoch_secs {
+    use serde::{de, Serializer};
+    use std::{
+        fmt,
+        time::{Duration, SystemTime},
+    };
+
+    pub(super) fn serialize<S: Serializer>(
+        time: &SystemTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let secs = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|since| since.as_secs_f64())
+            .unwrap_or(0.0);
+        serializer.serialize_f64(secs)
+    }
+
+    pub(super) fn deserialize<'de, D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<SystemTime, D::Error> {
+        deserializer.deserialize_any(EpochSecs)
+    }
+
+    struct EpochSecs;
+
+    impl<'de> de::Visitor<'de> for EpochSecs {
+        type Value = SystemTime;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a non-negative number of seconds since the Unix epoch")
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Duration::try_from_secs_f64(v)
+                .ok()
+                .and_then(|since| SystemTime::UNIX_EPOCH.checked_add(since))
+                .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Float(v), &self))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_secs(v))
+                .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Unsigned(v), &self))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            u64::try_from(v)
+                .map_err(|_| de::Error::invalid_value(de::Unexpected::Signed(v), &self))
+                .and_then(|v| self.visit_u64(v))
+        }
+
+        fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            s.trim()
+                .parse::<f64>()
+                .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(s), &self))
+                .and_then(|v| self.visit_f64(v))
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Color math for [`Backlight`]s.
+//!
+//! The VU-Server sets backlights using red, green, and blue percentages, but
+//! colors usually come from somewhere else: hex strings, HSV values, 8-bit
+//! RGB, or other color crates. These conversions, and the brightness and
+//! blending helpers, round to the nearest percent.
+use super::{Backlight, Percent};
+use thiserror::Error;
+
+#[derive(Debug, Error, miette::Diagnostic)]
+#[error("invalid hex color {0:?}")]
+#[help = "hex colors are written as `#RRGGBB` or `#RGB`"]
+#[diagnostic(code(vu_api::dial::HexColorError))]
+pub struct HexColorError(String);
+
+// === impl Backlight ===
+
+impl Backlight {
+    /// A backlight that's turned off.
+    pub const OFF: Self = Self::gray(0);
+
+    /// A backlight at full brightness on every channel.
+    pub const WHITE: Self = Self::gray(100);
+
+    const fn gray(percent: u8) -> Self {
+        Self {
+            red: Percent(percent),
+            green: Percent(percent),
+            blue: Percent(percent),
+        }
+    }
+
+    /// Returns a backlight color from 8-bit (0-255) red, green, and blue
+    /// values.
+    pub fn from_rgb8(red: u8, green: u8, blue: u8) -> Self {
+        let percent = |c: u8| Percent(((u16::from(c) * 100 + 127) / 255) as u8);
+        Self {
+            red: percent(red),
+            green: percent(green),
+            blue: percent(blue),
+        }
+    }
+
+    /// Returns this color's red, green, and blue values in the range 0-255.
+    pub fn to_rgb8(self) -> [u8; 3] {
+        let scale = |c: Percent| ((u16::from(c.0) * 255 + 50) / 100) as u8;
+        [scale(self.red), scale(self.green), scale(self.blue)]
+    }
+
+    /// Parses a hex color, such as `#ff8800` or `#f80`.
+    ///
+    /// The leading `#` is optional.
+    pub fn from_hex(hex: &str) -> Result<Self, HexColorError> {
+        let err = || HexColorError(hex.to_owned());
+        let digits = hex
+            .trim()
+            .strip_prefix('#')
+            .unwrap_or(hex.trim())
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(err)?;
+        let [red, green, blue] = match digits[..] {
+            [r, g, b] => [r * 0x11, g * 0x11, b * 0x11],
+            [r1, r2, g1, g2, b1, b2] => [(r1 << 4) | r2, (g1 << 4) | g2, (b1 << 4) | b2],
+            _ => return Err(err()),
+        };
+        Ok(Self::from_rgb8(red, green, blue))
+    }
+
+    /// Returns this color as a `#rrggbb` hex string.
+    pub fn to_hex(self) -> String {
+        let [red, green, blue] = self.to_rgb8();
+        format!("#{red:02x}{green:02x}{blue:02x}")
+    }
+
+    /// Returns a backlight color from a hue in degrees, and a saturation and
+    /// value in the range 0.0-1.0.
+    ///
+    /// Hues outside of 0-360 wrap around, and saturations and values outside
+    /// of 0.0-1.0 are clamped.
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let (s, v) = (saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+        let chroma = v * s;
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = v - chroma;
+        Self::from_fractions(r + m, g + m, b + m)
+    }
+
+    /// Returns this color's hue in degrees, and its saturation and value in
+    /// the range 0.0-1.0.
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let [r, g, b] = self.to_fractions();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+        (hue, saturation, max)
+    }
+
+    /// Returns this color's brightness, the value of its brightest channel.
+    pub fn brightness(self) -> Percent {
+        self.red.max(self.green).max(self.blue)
+    }
+
+    /// Returns this color with every channel multiplied by `factor`.
+    ///
+    /// Channels are clamped to 100%, so scaling by more than 1.0 may change
+    /// the color's hue.
+    pub fn scale(self, factor: f32) -> Self {
+        let [r, g, b] = self.to_fractions();
+        let factor = factor.max(0.0);
+        Self::from_fractions(r * factor, g * factor, b * factor)
+    }
+
+    /// Returns this color, scaled so that its [brightness] is `brightness`.
+    ///
+    /// If this color is off, it stays off.
+    ///
+    /// [brightness]: Self::brightness
+    pub fn with_brightness(self, brightness: Percent) -> Self {
+        match self.brightness().0 {
+            0 => self,
+            current => self.scale(f32::from(brightness.0) / f32::from(current)),
+        }
+    }
+
+    /// Linearly interpolates between this color and `other`.
+    ///
+    /// A `t` of 0.0 returns this color, and a `t` of 1.0 returns `other`.
+    /// Values of `t` outside of 0.0-1.0 are clamped.
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let [r1, g1, b1] = self.to_fractions();
+        let [r2, g2, b2] = other.to_fractions();
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Self::from_fractions(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+    }
+
+    fn from_fractions(red: f32, green: f32, blue: f32) -> Self {
+        let percent = |c: f32| Percent::from_f64_clamped(f64::from(c) * 100.0);
+        Self {
+            red: percent(red),
+            green: percent(green),
+            blue: percent(blue),
+        }
+    }
+
+    fn to_fractions(self) -> [f32; 3] {
+        [self.red, self.green, self.blue].map(|c| f32::from(c.0) / 100.0)
+    }
+}
+
+#[cfg(feature = "rgb")]
+impl From<rgb::RGB8> for Backlight {
+    fn from(rgb::RGB8 { r, g, b }: rgb::RGB8) -> Self {
+        Self::from_rgb8(r, g, b)
+    }
+}
+
+#[cfg(feature = "rgb")]
+impl From<Backlight> for rgb::RGB8 {
+    fn from(backlight: Backlight) -> Self {
+        let [r, g, b] = backlight.to_rgb8();
+        rgb::RGB8::new(r, g, b)
+    }
+}
+
+#[cfg(feature = "palette")]
+impl From<palette::Srgb<u8>> for Backlight {
+    fn from(color: palette::Srgb<u8>) -> Self {
+        Self::from_rgb8(color.red, color.green, color.blue)
+    }
+}
+
+#[cfg(feature = "palette")]
+impl From<Backlight> for palette::Srgb<u8> {
+    fn from(backlight: Backlight) -> Self {
+        let [red, green, blue] = backlight.to_rgb8();
+        palette::Srgb::new(red, green, blue)
+    }
+}
+
+#[cfg(feature = "palette")]
+impl From<palette::Srgb> for Backlight {
+    fn from(color: palette::Srgb) -> Self {
+        Self::from_fractions(color.red, color.green, color.blue)
+    }
+}
+
+#[cfg(feature = "palette")]
+impl From<Backlight> for palette::Srgb {
+    fn from(backlight: Backlight) -> Self {
+        let [red, green, blue] = backlight.to_fractions();
+        palette::Srgb::new(red, green, blue)
+    }
+}
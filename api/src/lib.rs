@@ -1,7 +1,20 @@
 pub mod api;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
 #[cfg(feature = "client")]
 pub mod client;
 pub mod dial;
+#[cfg(feature = "requests")]
+mod requests;
+#[cfg(all(feature = "retry", not(target_arch = "wasm32")))]
+pub mod retry;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "client")]
+pub mod transport;
 
 #[cfg(feature = "client")]
-pub use self::client::{Client, Dial};
+pub use self::{
+    client::{Client, Dial},
+    transport::Transport,
+};
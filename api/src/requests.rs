@@ -1 +1,83 @@
+//! Building VU-Server API requests.
+//!
+//! Requests are built as [`http::Request`]s, independently of the HTTP
+//! client that sends them.
+use http::{header, Method};
+use url::Url;
 
+/// The name of the multipart form field that the VU-Server expects image
+/// uploads in.
+const IMAGE_FIELD: &str = "imgfile";
+
+/// A request that's being built.
+///
+/// This is cloned to send the same request again when retrying.
+#[derive(Clone, Debug)]
+#[must_use]
+pub(crate) struct RequestBuilder {
+    method: Method,
+    url: Url,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+impl RequestBuilder {
+    pub(crate) fn new(method: Method, url: Url) -> Self {
+        Self {
+            method,
+            url,
+            content_type: None,
+            body: Vec::new(),
+        }
+    }
+
+    /// Adds a query parameter to the request's URL.
+    pub(crate) fn query(mut self, name: &str, value: impl AsRef<str>) -> Self {
+        self.url.query_pairs_mut().append_pair(name, value.as_ref());
+        self
+    }
+
+    /// Sets the request's body to a multipart form containing an image file.
+    pub(crate) fn image(mut self, filename: &str, image: &[u8]) -> Self {
+        let boundary = boundary(image);
+        // Quotes and line breaks would end the header early.
+        let filename = filename
+            .replace('"', "%22")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A");
+        let mut body = format!(
+            "--{boundary}\r\n\
+            Content-Disposition: form-data; name=\"{IMAGE_FIELD}\"; filename=\"{filename}\"\r\n\
+            \r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(image);
+        body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+
+        self.content_type = Some(format!("multipart/form-data; boundary={boundary}"));
+        self.body = body;
+        self
+    }
+
+    pub(crate) fn build(self) -> Result<http::Request<Vec<u8>>, http::Error> {
+        let mut builder = http::Request::builder()
+            .method(self.method)
+            .uri(self.url.as_str());
+        if let Some(content_type) = self.content_type {
+            builder = builder.header(header::CONTENT_TYPE, content_type);
+        }
+        builder.body(self.body)
+    }
+}
+
+/// Returns a multipart boundary that doesn't appear in `contents`.
+fn boundary(contents: &[u8]) -> String {
+    (0u32..)
+        .map(|n| format!("vu-api-boundary-{n:08x}"))
+        .find(|boundary| {
+            !contents
+                .windows(boundary.len())
+                .any(|window| window == boundary.as_bytes())
+        })
+        .expect("some boundary must not appear in the image")
+}
@@ -0,0 +1,159 @@
+//! Retrying requests that fail with transient errors.
+//!
+//! A [`RetryPolicy`] can be used to retry individual operations with
+//! [`RetryPolicy::retry`], or configured on a [`Client`] with
+//! [`Client::with_retry_policy`], so that every request the client sends is
+//! retried.
+//!
+//! Whether an error is worth retrying is decided by
+//! [`Error::is_transient`].
+//!
+//! [`Client`]: crate::Client
+//! [`Client::with_retry_policy`]: crate::Client::with_retry_policy
+use crate::client::Error;
+use std::{future::Future, time::Duration};
+
+/// How to retry operations that fail with transient errors.
+///
+/// Retries are spaced out with randomized exponential backoff. The defaults
+/// are the same as the [`backoff`] crate's.
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct RetryPolicy {
+    initial_interval: Duration,
+    randomization_factor: f64,
+    multiplier: f64,
+    max_interval: Duration,
+    max_elapsed_time: Option<Duration>,
+}
+
+impl RetryPolicy {
+    /// The default delay before the first retry.
+    pub const DEFAULT_INITIAL_INTERVAL: Duration =
+        Duration::from_millis(backoff::default::INITIAL_INTERVAL_MILLIS);
+
+    /// The default amount of jitter added to each delay, as a fraction of the
+    /// delay.
+    pub const DEFAULT_RANDOMIZATION_FACTOR: f64 = backoff::default::RANDOMIZATION_FACTOR;
+
+    /// The default factor that each delay is multiplied by.
+    pub const DEFAULT_MULTIPLIER: f64 = backoff::default::MULTIPLIER;
+
+    /// The default longest delay between retries.
+    pub const DEFAULT_MAX_INTERVAL: Duration =
+        Duration::from_millis(backoff::default::MAX_INTERVAL_MILLIS);
+
+    /// The default total time to spend retrying an operation.
+    pub const DEFAULT_MAX_ELAPSED_TIME: Duration =
+        Duration::from_millis(backoff::default::MAX_ELAPSED_TIME_MILLIS);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the delay before the first retry.
+    pub fn initial_interval(self, initial_interval: Duration) -> Self {
+        Self {
+            initial_interval,
+            ..self
+        }
+    }
+
+    /// Sets the amount of jitter added to each delay, as a fraction of the
+    /// delay.
+    pub fn randomization_factor(self, randomization_factor: f64) -> Self {
+        Self {
+            randomization_factor,
+            ..self
+        }
+    }
+
+    /// Sets the factor that each delay is multiplied by.
+    pub fn multiplier(self, multiplier: f64) -> Self {
+        Self { multiplier, ..self }
+    }
+
+    /// Sets the longest delay between retries.
+    pub fn max_interval(self, max_interval: Duration) -> Self {
+        Self {
+            max_interval,
+            ..self
+        }
+    }
+
+    /// Sets the total time to spend retrying an operation before giving up,
+    /// or `None` to retry forever.
+    pub fn max_elapsed_time(self, max_elapsed_time: impl Into<Option<Duration>>) -> Self {
+        Self {
+            max_elapsed_time: max_elapsed_time.into(),
+            ..self
+        }
+    }
+
+    /// Runs `f` until it succeeds, fails with an error that isn't
+    /// [transient], or the policy's time budget runs out.
+    ///
+    /// If the VU-Server says how long to wait with a `Retry-After` header,
+    /// that delay is used instead of the next exponential backoff interval.
+    ///
+    /// Each retry is logged at the `WARN` level, as "failed to {action},
+    /// retrying...".
+    ///
+    /// [transient]: Error::is_transient
+    pub async fn retry<T, F>(&self, action: &str, mut f: impl FnMut() -> F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        backoff::future::retry_notify(
+            self.backoff(),
+            || {
+                let attempt = f();
+                async move { attempt.await.map_err(backoff_error) }
+            },
+            |error, retry_after| {
+                tracing::warn!(%error, ?retry_after, "failed to {action}, retrying...");
+            },
+        )
+        .await
+    }
+
+    fn backoff(&self) -> backoff::ExponentialBackoff {
+        backoff::ExponentialBackoffBuilder::new()
+            .with_initial_interval(self.initial_interval)
+            .with_randomization_factor(self.randomization_factor)
+            .with_multiplier(self.multiplier)
+            .with_max_interval(self.max_interval)
+            .with_max_elapsed_time(self.max_elapsed_time)
+            .build()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_interval: Self::DEFAULT_INITIAL_INTERVAL,
+            randomization_factor: Self::DEFAULT_RANDOMIZATION_FACTOR,
+            multiplier: Self::DEFAULT_MULTIPLIER,
+            max_interval: Self::DEFAULT_MAX_INTERVAL,
+            max_elapsed_time: Some(Self::DEFAULT_MAX_ELAPSED_TIME),
+        }
+    }
+}
+
+/// Classifies `error` as transient or permanent, using the server's
+/// `Retry-After` hint as the next delay if it sent one.
+///
+/// Requests refused by an open [`CircuitBreaker`](crate::client::CircuitBreaker)
+/// aren't [transient](Error::is_transient) on their own, but when retrying,
+/// it's worth waiting for the breaker to let requests through again, rather
+/// than giving up while the VU-Server is still starting.
+fn backoff_error(error: Error) -> backoff::Error<Error> {
+    let circuit_open = matches!(error, Error::CircuitOpen { .. });
+    if !error.is_transient() && !circuit_open {
+        return backoff::Error::permanent(error);
+    }
+    match error.retry_after() {
+        Some(retry_after) => backoff::Error::retry_after(error, retry_after),
+        None => backoff::Error::transient(error),
+    }
+}
@@ -0,0 +1,518 @@
+//! An in-process mock VU-Server, for testing code that uses the client
+//! without any dials attached.
+//!
+//! [`MockServer`] implements [`Transport`], so a [`Client`] built with
+//! [`MockServer::client`] sends its requests straight to the mock, without
+//! touching the network. The mock keeps track of each dial's state, so
+//! setting a dial's value and then reading its status works the way it does
+//! with a real VU-Server.
+//!
+//! Like the real VU-Server, the mock sends some integers in its JSON
+//! responses as strings. By default, it only does so for dial indices, which
+//! the real server always sends as strings, but
+//! [`MockServer::ints_as_strings`] makes it send every integer as a string,
+//! to check that callers cope with the server's other inconsistencies.
+use crate::{
+    api,
+    dial::{Backlight, Easing, Id, Percent},
+    transport::{Request, Response, ResponseFuture, Transport},
+    Client,
+};
+use http::{header, Method, StatusCode};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+use url::Url;
+
+/// An in-process mock VU-Server.
+///
+/// Clones of a `MockServer` share the same state, so a test can keep one
+/// clone to inspect the dials while a [`Client`] sends requests to another.
+#[derive(Clone, Debug)]
+pub struct MockServer {
+    state: Arc<Mutex<State>>,
+}
+
+/// The state of a dial attached to a [`MockServer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockDial {
+    pub uid: Id,
+    pub name: String,
+    pub value: Percent,
+    pub backlight: Backlight,
+    pub easing: Easing,
+    /// The filename of the dial's background image.
+    pub image_file: String,
+    /// The contents of the dial's background image, if one has been
+    /// uploaded.
+    pub image: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+struct State {
+    key: String,
+    master_key: String,
+    ints_as_strings: bool,
+    dials: Vec<MockDial>,
+    /// Dials that have been connected, but not yet provisioned.
+    unprovisioned: Vec<MockDial>,
+    keys: Vec<api::KeyInfo>,
+    canned: HashMap<String, Value>,
+    requests: Vec<String>,
+}
+
+/// The mock's base URL. Nothing is ever sent to it.
+const BASE_URL: &str = "http://vu-server.mock:5340/";
+
+// === impl MockServer ===
+
+impl MockServer {
+    /// Returns a new mock VU-Server with no dials, which accepts `key` as an
+    /// API key.
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                key: key.into(),
+                master_key: "mock-master-key".to_owned(),
+                ints_as_strings: false,
+                dials: Vec::new(),
+                unprovisioned: Vec::new(),
+                keys: Vec::new(),
+                canned: HashMap::new(),
+                requests: Vec::new(),
+            })),
+        }
+    }
+
+    /// Returns a [`Client`] that sends its requests to this mock, using the
+    /// mock's API key.
+    pub fn client(&self) -> Client {
+        let key = self.lock().key.clone();
+        self.client_with_key(key)
+    }
+
+    /// Returns a [`Client`] that sends its requests to this mock, using the
+    /// mock's master key, for testing the admin API.
+    pub fn admin_client(&self) -> Client {
+        let key = self.lock().master_key.clone();
+        self.client_with_key(key)
+    }
+
+    fn client_with_key(&self, key: String) -> Client {
+        Client::with_transport(self.clone(), key, BASE_URL)
+            .expect("the mock's base URL must be valid")
+    }
+
+    /// Attaches a provisioned dial with the UID `uid` and the name `name`.
+    pub fn add_dial(&self, uid: &str, name: &str) -> &Self {
+        self.lock().dials.push(MockDial::new(uid, name));
+        self
+    }
+
+    /// Connects a dial that the mock won't list until it's provisioned.
+    pub fn connect_dial(&self, uid: &str, name: &str) -> &Self {
+        self.lock().unprovisioned.push(MockDial::new(uid, name));
+        self
+    }
+
+    /// Sets whether every integer in the mock's responses is sent as a
+    /// string, like the real VU-Server sometimes does.
+    pub fn ints_as_strings(&self, ints_as_strings: bool) -> &Self {
+        self.lock().ints_as_strings = ints_as_strings;
+        self
+    }
+
+    /// Makes the mock answer every request to `path` with `response`, rather
+    /// than handling it.
+    ///
+    /// `path` is the request's URL path, without the query, such as
+    /// `/api/v0/dial/list`.
+    pub fn canned<T: serde::Serialize>(&self, path: &str, response: &api::Response<T>) -> &Self {
+        let response = serde_json::to_value(response).expect("response must serialize to JSON");
+        self.lock().canned.insert(path.to_owned(), response);
+        self
+    }
+
+    /// Returns the state of the dial with the UID `uid`, if it's provisioned.
+    pub fn dial(&self, uid: &str) -> Option<MockDial> {
+        self.lock()
+            .dials
+            .iter()
+            .find(|dial| dial.uid.to_string() == uid)
+            .cloned()
+    }
+
+    /// Returns the path and query of each request the mock has received, in
+    /// order, with API keys removed.
+    pub fn requests(&self) -> Vec<String> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Transport for MockServer {
+    fn send(&self, request: Request) -> ResponseFuture<'_> {
+        let response = self.lock().handle(request);
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+// === impl MockDial ===
+
+impl MockDial {
+    fn new(uid: &str, name: &str) -> Self {
+        let zero = Percent::new(0).expect("0 must be a valid percent");
+        let step = Percent::new(5).expect("5 must be a valid percent");
+        Self {
+            uid: Id::new_unchecked(uid),
+            name: name.to_owned(),
+            value: zero,
+            backlight: Backlight {
+                red: zero,
+                green: zero,
+                blue: zero,
+            },
+            easing: Easing {
+                backlight_step: step,
+                backlight_period: Duration::from_millis(100),
+                dial_step: step,
+                dial_period: Duration::from_millis(50),
+            },
+            image_file: format!("img_{uid}"),
+            image: None,
+        }
+    }
+}
+
+// === impl State ===
+
+impl State {
+    fn handle(&mut self, request: Request) -> Response {
+        let url = match Url::parse(&request.uri().to_string()) {
+            Ok(url) => url,
+            Err(error) => return http_error(StatusCode::BAD_REQUEST, &error.to_string()),
+        };
+        let query = url
+            .query_pairs()
+            .into_owned()
+            .collect::<HashMap<String, String>>();
+        let path = url.path().to_owned();
+        let logged = url
+            .query_pairs()
+            .filter(|(name, _)| name != "key")
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>();
+        self.requests.push(if logged.is_empty() {
+            path.clone()
+        } else {
+            format!("{path}?{}", logged.join("&"))
+        });
+
+        if let Some(canned) = self.canned.get(&path) {
+            return json_response(canned);
+        }
+
+        let key = query.get("key").map(String::as_str);
+        if let Some(admin) = path.strip_prefix("/api/v0/admin/") {
+            if key != Some(&*self.master_key) {
+                return fail("Unauthorized: admin API requires the master key");
+            }
+            return self.handle_admin(admin, &query);
+        }
+        if key != Some(&*self.key) && key != Some(&*self.master_key) {
+            return fail("Unauthorized: invalid API key");
+        }
+
+        match path.as_str() {
+            "/api/v0/dial/list" => {
+                let dials = self
+                    .dials
+                    .iter()
+                    .map(|dial| self.dial_info(dial))
+                    .collect::<Vec<_>>();
+                ok(Value::Array(dials))
+            }
+            "/api/v0/dial/provision" => {
+                let new = std::mem::take(&mut self.unprovisioned);
+                self.dials.extend(new);
+                ok(Value::Null)
+            }
+            path => match path
+                .strip_prefix("/api/v0/dial/")
+                .and_then(|rest| rest.split_once('/'))
+            {
+                Some((uid, endpoint)) => {
+                    self.handle_dial(uid, endpoint, request.method(), &query, request.body())
+                }
+                None => http_error(StatusCode::NOT_FOUND, "Not Found"),
+            },
+        }
+    }
+
+    fn handle_dial(
+        &mut self,
+        uid: &str,
+        endpoint: &str,
+        method: &Method,
+        query: &HashMap<String, String>,
+        body: &[u8],
+    ) -> Response {
+        let Some(index) = self
+            .dials
+            .iter()
+            .position(|dial| dial.uid.to_string() == uid)
+        else {
+            return fail("Dial not found");
+        };
+
+        let dial = &mut self.dials[index];
+        let result = match (method, endpoint) {
+            (&Method::GET, "status" | "reload" | "easing/get") => Ok(()),
+            (&Method::GET, "set") => percent(query, "value").map(|value| dial.value = value),
+            (&Method::GET, "backlight") => (|| -> Result<(), String> {
+                dial.backlight = Backlight {
+                    red: percent(query, "red")?,
+                    green: percent(query, "green")?,
+                    blue: percent(query, "blue")?,
+                };
+                Ok(())
+            })(),
+            (&Method::GET, "name") => match query.get("name") {
+                Some(name) => {
+                    dial.name = name.clone();
+                    Ok(())
+                }
+                None => Err("missing 'name' parameter".to_owned()),
+            },
+            (&Method::GET, "easing/dial") => (|| -> Result<(), String> {
+                dial.easing.dial_period = millis(query, "period")?;
+                dial.easing.dial_step = percent(query, "step")?;
+                Ok(())
+            })(),
+            (&Method::GET, "easing/backlight") => (|| -> Result<(), String> {
+                dial.easing.backlight_period = millis(query, "period")?;
+                dial.easing.backlight_step = percent(query, "step")?;
+                Ok(())
+            })(),
+            (&Method::GET, "calibrate/max" | "calibrate/half") => query
+                .get("value")
+                .and_then(|value| value.parse::<u16>().ok())
+                .map(|_| ())
+                .ok_or_else(|| "missing or invalid 'value' parameter".to_owned()),
+            (&Method::POST, "image/set") => match multipart_file(body) {
+                Some(image) => {
+                    if let Some(file) = query.get("imgfile") {
+                        dial.image_file = file.clone();
+                    }
+                    dial.image = Some(image.to_vec());
+                    Ok(())
+                }
+                None => Err("missing 'imgfile' upload".to_owned()),
+            },
+            (&Method::GET, "image/get") => {
+                return match dial.image {
+                    Some(ref image) => http::Response::builder()
+                        .status(StatusCode::OK)
+                        .header(header::CONTENT_TYPE, "image/png")
+                        .body(image.clone())
+                        .expect("image response must be valid"),
+                    None => fail("Image not found"),
+                };
+            }
+            _ => return http_error(StatusCode::NOT_FOUND, "Not Found"),
+        };
+
+        match result {
+            Ok(()) if endpoint == "status" || endpoint == "reload" => ok(self.dial_status(index)),
+            Ok(()) if endpoint == "easing/get" => ok(self.easing(&self.dials[index].easing)),
+            Ok(()) => ok(Value::Null),
+            Err(message) => fail(&message),
+        }
+    }
+
+    fn handle_admin(&mut self, endpoint: &str, query: &HashMap<String, String>) -> Response {
+        let dials = |query: &HashMap<String, String>| {
+            query
+                .get("dials")
+                .map(|dials| {
+                    dials
+                        .split(';')
+                        .filter(|uid| !uid.is_empty())
+                        .map(Id::new_unchecked)
+                        .collect::<Vec<Id>>()
+                })
+                .unwrap_or_default()
+        };
+        match endpoint {
+            "keys/list" => ok(serde_json::to_value(&self.keys).expect("keys must serialize")),
+            "keys/create" => {
+                let Some(name) = query.get("name") else {
+                    return fail("missing 'name' parameter");
+                };
+                let key = format!("mock-key-{}", self.keys.len() + 1);
+                self.keys.push(api::KeyInfo {
+                    name: name.clone(),
+                    key: key.clone(),
+                    priority_level: Some(1),
+                    dials: dials(query),
+                });
+                ok(Value::String(key))
+            }
+            "keys/update" => {
+                let target = query.get("target_key");
+                let Some(info) = self.keys.iter_mut().find(|info| Some(&info.key) == target) else {
+                    return fail("API key not found");
+                };
+                if let Some(name) = query.get("name") {
+                    info.name = name.clone();
+                }
+                info.dials = dials(query);
+                ok(Value::Null)
+            }
+            "keys/remove" => {
+                let target = query.get("target_key");
+                let len = self.keys.len();
+                self.keys.retain(|info| Some(&info.key) != target);
+                if self.keys.len() == len {
+                    return fail("API key not found");
+                }
+                ok(Value::Null)
+            }
+            _ => http_error(StatusCode::NOT_FOUND, "Not Found"),
+        }
+    }
+
+    fn dial_info(&self, dial: &MockDial) -> Value {
+        json!({
+            "uid": dial.uid.to_string(),
+            "dial_name": dial.name,
+            "value": self.int(u8::from(dial.value)),
+            "backlight": self.backlight(&dial.backlight),
+            "image_file": dial.image_file,
+        })
+    }
+
+    fn dial_status(&self, index: usize) -> Value {
+        let dial = &self.dials[index];
+        let zero = Percent::new(0).expect("0 must be a valid percent");
+        let rgbw = [
+            dial.backlight.red,
+            dial.backlight.green,
+            dial.backlight.blue,
+            zero,
+        ]
+        .map(|channel| self.int(u8::from(channel)));
+        json!({
+            // The real VU-Server always sends the index as a string.
+            "index": index.to_string(),
+            "uid": dial.uid.to_string(),
+            "dial_name": dial.name,
+            "value": self.int(u8::from(dial.value)),
+            "rgbw": rgbw,
+            "easing": self.easing(&dial.easing),
+            "fw_hash": "mock",
+            "fw_version": "mock",
+            "hw_version": "mock",
+            "protocol_version": "V1",
+            "backlight": self.backlight(&dial.backlight),
+            "image_file": dial.image_file,
+            "update_deadline": 0.0,
+            "value_changed": false,
+            "backlight_changed": false,
+            "image_changed": false,
+        })
+    }
+
+    fn easing(&self, easing: &Easing) -> Value {
+        json!({
+            "backlight_step": self.int(u8::from(easing.backlight_step)),
+            "backlight_period": self.int(easing.backlight_period.as_millis() as u64),
+            "dial_step": self.int(u8::from(easing.dial_step)),
+            "dial_period": self.int(easing.dial_period.as_millis() as u64),
+        })
+    }
+
+    fn backlight(&self, backlight: &Backlight) -> Value {
+        json!({
+            "red": self.int(u8::from(backlight.red)),
+            "green": self.int(u8::from(backlight.green)),
+            "blue": self.int(u8::from(backlight.blue)),
+        })
+    }
+
+    fn int(&self, int: impl Into<u64>) -> Value {
+        let int = int.into();
+        if self.ints_as_strings {
+            Value::String(int.to_string())
+        } else {
+            Value::from(int)
+        }
+    }
+}
+
+fn percent(query: &HashMap<String, String>, name: &str) -> Result<Percent, String> {
+    let value = query
+        .get(name)
+        .ok_or_else(|| format!("missing '{name}' parameter"))?;
+    value
+        .parse::<u8>()
+        .ok()
+        .and_then(|value| Percent::new(value).ok())
+        .ok_or_else(|| format!("invalid '{name}' parameter: {value:?}"))
+}
+
+fn millis(query: &HashMap<String, String>, name: &str) -> Result<Duration, String> {
+    let value = query
+        .get(name)
+        .ok_or_else(|| format!("missing '{name}' parameter"))?;
+    value
+        .parse::<u64>()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("invalid '{name}' parameter: {value:?}"))
+}
+
+/// Returns the contents of the first file in a multipart form body.
+fn multipart_file(body: &[u8]) -> Option<&[u8]> {
+    let boundary_end = body.windows(2).position(|window| window == b"\r\n")?;
+    let boundary = &body[..boundary_end];
+    let headers_end = body.windows(4).position(|window| window == b"\r\n\r\n")? + 4;
+    let rest = &body[headers_end..];
+    let mut closing = b"\r\n".to_vec();
+    closing.extend_from_slice(boundary);
+    let end = rest
+        .windows(closing.len())
+        .position(|window| window == closing)?;
+    Some(&rest[..end])
+}
+
+fn ok(data: Value) -> Response {
+    json_response(&api::Response::ok(data))
+}
+
+fn fail(message: &str) -> Response {
+    json_response(&api::Response::fail(message))
+}
+
+fn json_response(json: &impl serde::Serialize) -> Response {
+    let body = serde_json::to_vec(json).expect("JSON response must serialize");
+    http::Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .expect("JSON response must be valid")
+}
+
+fn http_error(status: StatusCode, message: &str) -> Response {
+    http::Response::builder()
+        .status(status)
+        .body(message.as_bytes().to_vec())
+        .expect("error response must be valid")
+}
@@ -0,0 +1,63 @@
+//! HTTP transports for the VU-Server client.
+//!
+//! The [`Client`](crate::Client) builds each VU-Server API request as an
+//! [`http::Request`], and hands it to a [`Transport`] to send. By default,
+//! requests are sent using [`reqwest`], but other HTTP clients (or a test
+//! double that never touches the network) can be used by implementing
+//! [`Transport`], and passing it to [`Client::with_transport`].
+//!
+//! [`Client::with_transport`]: crate::Client::with_transport
+use core::{fmt, future::Future, pin::Pin};
+
+/// A request to the VU-Server.
+pub type Request = http::Request<Vec<u8>>;
+
+/// A response from the VU-Server.
+pub type Response = http::Response<Vec<u8>>;
+
+/// An error returned by a [`Transport`].
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The future returned by [`Transport::send`].
+#[cfg(not(target_arch = "wasm32"))]
+pub type ResponseFuture<'a> = Pin<Box<dyn Future<Output = Result<Response, BoxError>> + Send + 'a>>;
+
+/// The future returned by [`Transport::send`].
+///
+/// In the browser, futures are driven on a single thread, so they don't need
+/// to be `Send`.
+#[cfg(target_arch = "wasm32")]
+pub type ResponseFuture<'a> = Pin<Box<dyn Future<Output = Result<Response, BoxError>> + 'a>>;
+
+/// Sends HTTP requests to the VU-Server.
+pub trait Transport: fmt::Debug + Send + Sync + 'static {
+    /// Sends `request`, and returns the VU-Server's response.
+    ///
+    /// This should only return an error if no response was received at all.
+    /// HTTP error statuses are handled by the client.
+    fn send(&self, request: Request) -> ResponseFuture<'_>;
+}
+
+impl Transport for reqwest::Client {
+    fn send(&self, request: Request) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            // `reqwest` has its own versions of the `http` types, so the
+            // request and response are converted field by field.
+            let (parts, body) = request.into_parts();
+            let method = reqwest::Method::from_bytes(parts.method.as_str().as_bytes())?;
+            let url = reqwest::Url::parse(&parts.uri.to_string())?;
+            let mut req = self.request(method, url).body(body);
+            for (name, value) in &parts.headers {
+                req = req.header(name.as_str(), value.as_bytes());
+            }
+
+            let rsp = req.send().await?;
+            let mut builder = http::Response::builder().status(rsp.status().as_u16());
+            for (name, value) in rsp.headers() {
+                builder = builder.header(name.as_str(), value.as_bytes());
+            }
+            let body = rsp.bytes().await?.to_vec();
+            Ok(builder.body(body)?)
+        })
+    }
+}
@@ -0,0 +1,514 @@
+//! A blocking driver for the VU1 hub's serial port.
+//!
+//! This talks to the hub directly, using the [v1 protocol](crate::v1), rather
+//! than going through the VU-Server. Only one process can have the hub's
+//! serial port open at a time, so this can't be used while the VU-Server is
+//! running.
+//!
+//! [`Hub`] is a blocking driver. With the `tokio` feature, [`AsyncHub`] is an
+//! asynchronous one, which shares its framing with `Hub`.
+//!
+//! Both drivers can also [provision](provision) dials, which the VU-Server
+//! otherwise does when it starts.
+use crate::v1::{self, Command, DevicesMap, HubCommand, HubStatusCode, Payload, Response};
+use serialport::{SerialPort, SerialPortType};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+pub mod provision;
+#[cfg(feature = "tokio")]
+mod tokio;
+
+pub use self::provision::DialMap;
+#[cfg(feature = "tokio")]
+pub use self::tokio::{AsyncHub, IMAGE_CHUNK_LEN};
+
+/// A connection to a VU1 hub.
+pub struct Hub {
+    port: Box<dyn SerialPort>,
+    frames: FrameBuffer,
+}
+
+/// A hub found by [`discover`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HubInfo {
+    /// The name of the serial port the hub is connected to, such as
+    /// `/dev/ttyUSB0` or `COM3`.
+    pub port: String,
+    /// The serial number of the hub's USB-serial chip, if it reported one.
+    pub serial_number: Option<String>,
+    /// The protocol version the hub reported when it was probed.
+    pub protocol_version: String,
+}
+
+/// A bus address on the hub, and the dial at it, as returned by
+/// [`Hub::devices`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Device {
+    /// The dial's address on the hub's bus, which is its index.
+    pub address: u8,
+    /// The state of the dial at this address.
+    pub state: DeviceState,
+}
+
+/// Whether a dial is online at an address on the hub's bus.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceState {
+    /// A dial is online at this address.
+    Online {
+        /// The dial's unique ID.
+        uid: Vec<u8>,
+    },
+    /// No dial is online at this address.
+    Offline,
+}
+
+/// Bytes read from the hub that haven't been decoded into responses yet.
+#[derive(Debug, Default)]
+struct FrameBuffer {
+    buf: Vec<u8>,
+}
+
+/// An error talking to the hub.
+#[derive(Debug)]
+pub enum Error {
+    /// The serial port couldn't be opened or enumerated.
+    Serial(serialport::Error),
+    /// Reading from or writing to the serial port failed.
+    Io(io::Error),
+    /// The hub sent a frame that couldn't be decoded.
+    Decode(v1::DecodeError),
+    /// The hub rejected a command.
+    Status {
+        command: HubCommand,
+        status: HubStatusCode,
+    },
+    /// No VU1 hub answered on any serial port whose USB IDs match the hub's.
+    NotFound,
+    /// A percentage was greater than 100.
+    InvalidPercent(u8),
+    /// The hub didn't respond to a command within [`Hub::TIMEOUT`].
+    Timeout(HubCommand),
+    /// The task driving an [`AsyncHub`] has stopped, because an earlier
+    /// error closed the connection.
+    #[cfg(feature = "tokio")]
+    Closed,
+}
+
+// === impl Hub ===
+
+impl Hub {
+    /// The serial port's baud rate.
+    pub const BAUD_RATE: u32 = 115_200;
+
+    /// How long to wait for the hub to respond to a command.
+    pub const TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// The USB vendor ID of the hub's FTDI USB-serial chip.
+    pub const USB_VID: u16 = 0x0403;
+
+    /// The USB product ID of the hub's FTDI USB-serial chip.
+    pub const USB_PID: u16 = 0x6015;
+
+    /// Opens the hub connected to the serial port at `path`, such as
+    /// `/dev/ttyUSB0` or `COM3`.
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let port = serialport::new(path, Self::BAUD_RATE)
+            .timeout(Self::TIMEOUT)
+            .open()?;
+        Ok(Self {
+            port,
+            frames: FrameBuffer::default(),
+        })
+    }
+
+    /// Opens the first hub found by [`discover`].
+    pub fn find() -> Result<Self, Error> {
+        candidate_ports()?
+            .into_iter()
+            .find_map(|(port, _)| Self::probe(&port).map(|(hub, _)| hub))
+            .ok_or(Error::NotFound)
+    }
+
+    /// Sends `command` and waits for the hub's response to it.
+    ///
+    /// Any lines the hub sends that aren't responses, or that are responses
+    /// to other commands, are skipped. If the response is a status code other
+    /// than [`HubStatusCode::Ok`], this returns [`Error::Status`].
+    pub fn send(&mut self, command: &Command<'_>) -> Result<Response, Error> {
+        self.send_frame(command.command, &command.to_bytes())
+    }
+
+    /// Sets the needle of the dial at `dial` to `percent`.
+    pub fn set_percent(&mut self, dial: u8, percent: u8) -> Result<(), Error> {
+        let frame = set_percent_frame(dial, percent)?;
+        self.send_frame(HubCommand::SetDialPercSingle, &frame)?;
+        Ok(())
+    }
+
+    /// Sets the backlight of the dial at `dial`, with each channel given as
+    /// a percentage.
+    pub fn set_backlight(&mut self, dial: u8, red: u8, green: u8, blue: u8) -> Result<(), Error> {
+        let frame = set_backlight_frame(dial, red, green, blue)?;
+        self.send_frame(HubCommand::SetRgbBacklight, &frame)?;
+        Ok(())
+    }
+
+    /// Returns whether a dial is online at each of the hub's addresses.
+    pub fn device_map(&mut self) -> Result<DevicesMap, Error> {
+        let rsp = self.send_frame(HubCommand::GetDevicesMap, &device_map_frame())?;
+        Ok(DevicesMap::decode(&rsp)?)
+    }
+
+    /// Returns the unique ID of the dial at `dial`.
+    pub fn device_uid(&mut self, dial: u8) -> Result<Vec<u8>, Error> {
+        let rsp = self.send_frame(HubCommand::GetDeviceUid, &device_uid_frame(dial))?;
+        Ok(rsp.data)
+    }
+
+    /// Returns every address on the hub's bus, and the UID of the dial
+    /// online at each one.
+    pub fn devices(&mut self) -> Result<Vec<Device>, Error> {
+        let map = self.device_map()?;
+        let mut devices = Vec::with_capacity(map.len());
+        for (address, online) in map.iter() {
+            let state = if online {
+                DeviceState::Online {
+                    uid: self.device_uid(address)?,
+                }
+            } else {
+                DeviceState::Offline
+            };
+            devices.push(Device { address, state });
+        }
+        Ok(devices)
+    }
+
+    /// Returns the version of the serial protocol that the hub speaks.
+    pub fn protocol_info(&mut self) -> Result<String, Error> {
+        let rsp = self.send_frame(HubCommand::GetProtocolInfo, &protocol_info_frame())?;
+        Ok(parse_info(rsp))
+    }
+
+    /// Opens `port` and checks that a hub answers on it, returning the hub
+    /// and the protocol version it reported.
+    fn probe(port: &str) -> Option<(Self, String)> {
+        let mut hub = Self::open(port).ok()?;
+        let version = hub.protocol_info().ok()?;
+        Some((hub, version))
+    }
+
+    /// Asks the hub to look for dials that were connected or disconnected
+    /// since it last checked.
+    pub fn rescan_bus(&mut self) -> Result<(), Error> {
+        self.send_frame(HubCommand::RescanBus, &rescan_bus_frame())?;
+        Ok(())
+    }
+
+    /// Removes every dial's address.
+    ///
+    /// No dial can be sent commands until it's provisioned again, for
+    /// example with [`Hub::reset_and_provision`].
+    pub fn reset_all_devices(&mut self) -> Result<(), Error> {
+        self.send_frame(HubCommand::ResetAllDevices, &reset_all_devices_frame())?;
+        Ok(())
+    }
+
+    /// Gives the next dial without an address the address `address`.
+    ///
+    /// Returns `false` if there are no dials without an address.
+    pub fn provision_device(&mut self, address: u8) -> Result<bool, Error> {
+        let result = self.send_frame(HubCommand::ProvisionDevice, &provision_frame(address));
+        was_provisioned(result)
+    }
+
+    /// Provisions every dial without an address, and records every
+    /// connected dial's UID in `map`.
+    ///
+    /// Dials are given the lowest addresses that aren't in use. Returns the
+    /// addresses of the dials that were provisioned.
+    pub fn provision(&mut self, map: &mut DialMap) -> Result<Vec<u8>, Error> {
+        self.rescan_bus()?;
+        let devices = self.device_map()?;
+        for address in devices.online() {
+            let uid = self.device_uid(address)?;
+            map.insert(address, uid);
+        }
+
+        let mut provisioned = Vec::new();
+        for address in devices.offline() {
+            if !self.provision_device(address)? {
+                break;
+            }
+            let uid = self.device_uid(address)?;
+            map.insert(address, uid);
+            provisioned.push(address);
+        }
+        Ok(provisioned)
+    }
+
+    /// Removes every dial's address, and then provisions them all again.
+    ///
+    /// This recovers from dials whose addresses conflict, or that were left
+    /// half-provisioned. Dials may not get the same addresses they had
+    /// before; compare `map` before and after to see which moved.
+    pub fn reset_and_provision(&mut self, map: &mut DialMap) -> Result<Vec<u8>, Error> {
+        self.reset_all_devices()?;
+        self.provision(map)
+    }
+
+    fn send_frame(&mut self, command: HubCommand, frame: &[u8]) -> Result<Response, Error> {
+        self.port.write_all(frame)?;
+        self.port.flush()?;
+        let mut chunk = [0u8; 64];
+        loop {
+            while let Some(rsp) = self.frames.next_response() {
+                if let Some(rsp) = check_response(command, rsp?)? {
+                    return Ok(rsp);
+                }
+            }
+            let n = match self.port.read(&mut chunk) {
+                Ok(n) => n,
+                Err(error) if error.kind() == io::ErrorKind::TimedOut => {
+                    return Err(Error::Timeout(command))
+                }
+                Err(error) => return Err(error.into()),
+            };
+            if n == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            self.frames.extend(&chunk[..n]);
+        }
+    }
+}
+
+impl fmt::Debug for Hub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hub")
+            .field("port", &self.port.name())
+            .finish_non_exhaustive()
+    }
+}
+
+// === impl FrameBuffer ===
+
+impl FrameBuffer {
+    fn extend(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns the next response frame that's been read in full, skipping
+    /// any other lines.
+    fn next_response(&mut self) -> Option<Result<Response, v1::DecodeError>> {
+        loop {
+            let (frame, rest) = v1::split_frame(&self.buf)?;
+            let rsp = frame
+                .starts_with(&[v1::RESPONSE_START])
+                .then(|| Response::decode(frame));
+            let consumed = self.buf.len() - rest.len();
+            self.buf.drain(..consumed);
+            // The hub sometimes logs debug output on the same port.
+            if rsp.is_some() {
+                return rsp;
+            }
+        }
+    }
+}
+
+/// Finds the VU1 hubs connected to this machine.
+///
+/// Every serial port whose USB IDs match the hub's is opened and asked for
+/// its protocol version. Other devices that use the same USB-serial chip
+/// won't answer, and are skipped, as are ports that are already open in
+/// another process (such as the VU-Server). Probing each port that doesn't
+/// answer waits for up to [`Hub::TIMEOUT`].
+pub fn discover() -> Result<Vec<HubInfo>, Error> {
+    let hubs = candidate_ports()?
+        .into_iter()
+        .filter_map(|(port, serial_number)| {
+            let (_, protocol_version) = Hub::probe(&port)?;
+            Some(HubInfo {
+                port,
+                serial_number,
+                protocol_version,
+            })
+        })
+        .collect();
+    Ok(hubs)
+}
+
+/// Returns the names and USB serial numbers of the serial ports whose USB
+/// IDs match the hub's.
+fn candidate_ports() -> Result<Vec<(String, Option<String>)>, Error> {
+    let ports = serialport::available_ports()?
+        .into_iter()
+        .filter_map(|port| match port.port_type {
+            SerialPortType::UsbPort(usb) if usb.vid == Hub::USB_VID && usb.pid == Hub::USB_PID => {
+                Some((port.port_name, usb.serial_number))
+            }
+            _ => None,
+        })
+        .collect();
+    Ok(ports)
+}
+
+/// Returns `rsp` if it's the response to `command`, `None` if it's a response
+/// to some other command, or an error if the hub rejected `command`.
+///
+/// Responses to other commands are left over from commands that timed out.
+fn check_response(command: HubCommand, rsp: Response) -> Result<Option<Response>, Error> {
+    if rsp.command != command {
+        return Ok(None);
+    }
+    match rsp.status() {
+        Some(HubStatusCode::Ok) | None => Ok(Some(rsp)),
+        Some(status) => Err(Error::Status { command, status }),
+    }
+}
+
+fn set_percent_frame(dial: u8, percent: u8) -> Result<Vec<u8>, Error> {
+    check_percent(percent)?;
+    let payload = Payload::KeyValuePair {
+        key: dial,
+        value: &[percent],
+    };
+    Ok(Command::new(HubCommand::SetDialPercSingle, payload).to_bytes())
+}
+
+fn set_backlight_frame(dial: u8, red: u8, green: u8, blue: u8) -> Result<Vec<u8>, Error> {
+    for channel in [red, green, blue] {
+        check_percent(channel)?;
+    }
+    // The hub also takes a white channel, which the VU1 doesn't have.
+    let payload = Payload::MultipleValue(&[dial, red, green, blue, 0]);
+    Ok(Command::new(HubCommand::SetRgbBacklight, payload).to_bytes())
+}
+
+fn device_map_frame() -> Vec<u8> {
+    DevicesMap::COMMAND.to_bytes()
+}
+
+fn device_uid_frame(dial: u8) -> Vec<u8> {
+    Command::new(HubCommand::GetDeviceUid, Payload::SingleValue(&[dial])).to_bytes()
+}
+
+fn protocol_info_frame() -> Vec<u8> {
+    Command::new(HubCommand::GetProtocolInfo, Payload::None).to_bytes()
+}
+
+/// Parses a response to one of the `Get*Info` commands, which is an ASCII
+/// string, possibly NUL-padded.
+fn parse_info(rsp: Response) -> String {
+    String::from_utf8_lossy(&rsp.data)
+        .trim_end_matches('\0')
+        .trim()
+        .to_owned()
+}
+
+fn rescan_bus_frame() -> Vec<u8> {
+    Command::new(HubCommand::RescanBus, Payload::None).to_bytes()
+}
+
+fn reset_all_devices_frame() -> Vec<u8> {
+    Command::new(HubCommand::ResetAllDevices, Payload::None).to_bytes()
+}
+
+fn provision_frame(address: u8) -> Vec<u8> {
+    Command::new(
+        HubCommand::ProvisionDevice,
+        Payload::SingleValue(&[address]),
+    )
+    .to_bytes()
+}
+
+/// Returns whether a `ProvisionDevice` command provisioned a dial.
+///
+/// The hub answers with [`HubStatusCode::DeviceOffline`] when there's no dial
+/// left to provision.
+fn was_provisioned(result: Result<Response, Error>) -> Result<bool, Error> {
+    match result {
+        Ok(_) => Ok(true),
+        Err(Error::Status {
+            status: HubStatusCode::DeviceOffline,
+            ..
+        }) => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+fn check_percent(percent: u8) -> Result<(), Error> {
+    if percent > 100 {
+        return Err(Error::InvalidPercent(percent));
+    }
+    Ok(())
+}
+
+// === impl DeviceState ===
+
+impl DeviceState {
+    /// Returns the UID of the dial at this address, if one is online.
+    pub fn uid(&self) -> Option<&[u8]> {
+        match self {
+            Self::Online { uid } => Some(uid),
+            Self::Offline => None,
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        matches!(self, Self::Online { .. })
+    }
+}
+
+// === impl Error ===
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serial(error) => write!(f, "serial port error: {error}"),
+            Self::Io(error) => write!(f, "failed to talk to the hub: {error}"),
+            Self::Decode(error) => write!(f, "invalid response from the hub: {error}"),
+            Self::Status { command, status } => {
+                write!(f, "hub rejected {command:?} with status {status:?}")
+            }
+            Self::NotFound => write!(f, "no VU1 hub found"),
+            Self::InvalidPercent(percent) => {
+                write!(f, "invalid percent {percent} (must be in the range 0-100)")
+            }
+            Self::Timeout(command) => write!(f, "hub didn't respond to {command:?}"),
+            #[cfg(feature = "tokio")]
+            Self::Closed => write!(f, "hub connection closed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Serial(error) => Some(error),
+            Self::Io(error) => Some(error),
+            Self::Decode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<serialport::Error> for Error {
+    fn from(error: serialport::Error) -> Self {
+        Self::Serial(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<v1::DecodeError> for Error {
+    fn from(error: v1::DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
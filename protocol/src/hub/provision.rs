@@ -0,0 +1,154 @@
+//! Provisioning dials, by giving them addresses on the hub's bus.
+//!
+//! A dial that's new, or that was reset with
+//! [`Hub::reset_all_devices`](super::Hub::reset_all_devices), has no address,
+//! so the hub can't send it commands. Provisioning finds dials without an
+//! address and assigns each one the next address that isn't in use.
+//!
+//! Which address an unprovisioned dial gets can't be chosen, since the dials
+//! can't be told apart until they have one. A [`DialMap`] records which dial
+//! UID was last seen at each address, so that it can be saved and compared
+//! after the dials are provisioned again.
+use std::{collections::BTreeMap, fmt, fs, io, path::Path, str::FromStr};
+
+/// The UIDs of the dials at each of the hub's addresses.
+///
+/// A `DialMap` is saved as text, with one line per dial: its address in
+/// decimal, then its UID in hex.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DialMap {
+    dials: BTreeMap<u8, Vec<u8>>,
+}
+
+/// An error parsing a [`DialMap`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseDialMapError {
+    line: usize,
+}
+
+// === impl DialMap ===
+
+impl DialMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a dial map from `path`.
+    ///
+    /// If `path` doesn't exist, this returns an empty map.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => text
+                .parse()
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Saves this dial map to `path`.
+    ///
+    /// The map is written to a temporary file that's then renamed over
+    /// `path`, so that a crash mid-write doesn't leave a truncated map
+    /// behind.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.to_string())?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Records that the dial with `uid` is at `address`.
+    ///
+    /// Any other address that the same dial was recorded at is forgotten.
+    /// Returns the UID of the dial previously recorded at `address`, if it
+    /// was a different dial.
+    pub fn insert(&mut self, address: u8, uid: Vec<u8>) -> Option<Vec<u8>> {
+        self.dials
+            .retain(|&other, other_uid| other == address || *other_uid != uid);
+        let prev = self.dials.insert(address, uid.clone());
+        prev.filter(|prev| *prev != uid)
+    }
+
+    /// Returns the UID of the dial recorded at `address`.
+    pub fn uid(&self, address: u8) -> Option<&[u8]> {
+        self.dials.get(&address).map(Vec::as_slice)
+    }
+
+    /// Returns the address that the dial with `uid` was recorded at.
+    pub fn address(&self, uid: &[u8]) -> Option<u8> {
+        self.dials
+            .iter()
+            .find(|(_, other)| other.as_slice() == uid)
+            .map(|(&address, _)| address)
+    }
+
+    /// Returns each recorded address and dial UID, in address order.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &[u8])> + '_ {
+        self.dials
+            .iter()
+            .map(|(&address, uid)| (address, uid.as_slice()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.dials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dials.is_empty()
+    }
+}
+
+impl fmt::Display for DialMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (address, uid) in self.iter() {
+            write!(f, "{address} ")?;
+            for byte in uid {
+                write!(f, "{byte:02X}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DialMap {
+    type Err = ParseDialMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut map = Self::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = || ParseDialMapError { line: i + 1 };
+            let (address, uid) = line.split_once(' ').ok_or_else(err)?;
+            let address = address.parse().map_err(|_| err())?;
+            let uid = uid.trim();
+            if uid.len() % 2 != 0 {
+                return Err(err());
+            }
+            let uid = (0..uid.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(uid.get(i..i + 2)?, 16).ok())
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(err)?;
+            map.insert(address, uid);
+        }
+        Ok(map)
+    }
+}
+
+// === impl ParseDialMapError ===
+
+impl fmt::Display for ParseDialMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid dial map on line {}: expected an address and a hex UID",
+            self.line
+        )
+    }
+}
+
+impl std::error::Error for ParseDialMapError {}
@@ -0,0 +1,360 @@
+//! An asynchronous driver for the VU1 hub, using `tokio-serial`.
+use super::{Device, DeviceState, DialMap, Error, FrameBuffer, Hub};
+use crate::v1::{Command, DevicesMap, HubCommand, Payload, Response};
+use ::tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::{mpsc, oneshot},
+    time,
+};
+use tokio_serial::{SerialPortBuilderExt, SerialStream};
+
+/// An asynchronous connection to a VU1 hub.
+///
+/// The serial port is owned by a task that sends one command at a time, in
+/// the order they're queued, and matches each response to its command.
+/// `AsyncHub` is a handle to that task, which can be cloned to send commands
+/// from several tasks at once. The task stops when every handle has been
+/// dropped.
+///
+/// Dropping the future returned by any of the methods that send a command
+/// cancels it: if the command hasn't been sent yet, it won't be.
+#[derive(Clone, Debug)]
+pub struct AsyncHub {
+    commands: mpsc::Sender<Queued>,
+}
+
+#[derive(Debug)]
+struct Queued {
+    command: HubCommand,
+    frame: Vec<u8>,
+    rsp: oneshot::Sender<Result<Response, Error>>,
+}
+
+/// How many commands can be queued before sending another waits.
+const QUEUE_CAPACITY: usize = 32;
+
+/// The most image data sent to the hub in one command, in bytes.
+pub const IMAGE_CHUNK_LEN: usize = 1000;
+
+// === impl AsyncHub ===
+
+impl AsyncHub {
+    /// Opens the hub connected to the serial port at `path`, such as
+    /// `/dev/ttyUSB0` or `COM3`.
+    ///
+    /// This spawns the task that drives the connection, so it must be called
+    /// from within a Tokio runtime.
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let port = tokio_serial::new(path, Hub::BAUD_RATE).open_native_async()?;
+        let (commands, rx) = mpsc::channel(QUEUE_CAPACITY);
+        ::tokio::spawn(drive(port, rx));
+        Ok(Self { commands })
+    }
+
+    /// Opens the first hub found on a serial port whose USB IDs match the
+    /// hub's.
+    ///
+    /// Like [`discover`](super::discover), this skips ports where nothing
+    /// answers.
+    pub async fn find() -> Result<Self, Error> {
+        for (port, _) in super::candidate_ports()? {
+            let Ok(hub) = Self::open(&port) else {
+                continue;
+            };
+            if hub.protocol_info().await.is_ok() {
+                return Ok(hub);
+            }
+        }
+        Err(Error::NotFound)
+    }
+
+    /// Sends `command` and waits for the hub's response to it.
+    ///
+    /// If the hub doesn't respond within [`Hub::TIMEOUT`], this returns
+    /// [`Error::Timeout`]. If the response is a status code other than
+    /// [`HubStatusCode::Ok`](crate::v1::HubStatusCode::Ok), this returns
+    /// [`Error::Status`].
+    pub async fn send(&self, command: &Command<'_>) -> Result<Response, Error> {
+        self.send_frame(command.command, command.to_bytes()).await
+    }
+
+    /// Sets the needle of the dial at `dial` to `percent`.
+    pub async fn set_percent(&self, dial: u8, percent: u8) -> Result<(), Error> {
+        let frame = super::set_percent_frame(dial, percent)?;
+        self.send_frame(HubCommand::SetDialPercSingle, frame)
+            .await?;
+        Ok(())
+    }
+
+    /// Sets the backlight of the dial at `dial`, with each channel given as
+    /// a percentage.
+    pub async fn set_backlight(&self, dial: u8, red: u8, green: u8, blue: u8) -> Result<(), Error> {
+        let frame = super::set_backlight_frame(dial, red, green, blue)?;
+        self.send_frame(HubCommand::SetRgbBacklight, frame).await?;
+        Ok(())
+    }
+
+    /// Returns the version of the serial protocol that the hub speaks.
+    pub async fn protocol_info(&self) -> Result<String, Error> {
+        let rsp = self
+            .send_frame(HubCommand::GetProtocolInfo, super::protocol_info_frame())
+            .await?;
+        Ok(super::parse_info(rsp))
+    }
+
+    /// Returns whether a dial is online at each of the hub's addresses.
+    pub async fn device_map(&self) -> Result<DevicesMap, Error> {
+        let rsp = self
+            .send_frame(HubCommand::GetDevicesMap, super::device_map_frame())
+            .await?;
+        Ok(DevicesMap::decode(&rsp)?)
+    }
+
+    /// Returns the unique ID of the dial at `dial`.
+    pub async fn device_uid(&self, dial: u8) -> Result<Vec<u8>, Error> {
+        let rsp = self
+            .send_frame(HubCommand::GetDeviceUid, super::device_uid_frame(dial))
+            .await?;
+        Ok(rsp.data)
+    }
+
+    /// Returns every address on the hub's bus, and the UID of the dial
+    /// online at each one.
+    pub async fn devices(&self) -> Result<Vec<Device>, Error> {
+        let map = self.device_map().await?;
+        let mut devices = Vec::with_capacity(map.len());
+        for (address, online) in map.iter() {
+            let state = if online {
+                DeviceState::Online {
+                    uid: self.device_uid(address).await?,
+                }
+            } else {
+                DeviceState::Offline
+            };
+            devices.push(Device { address, state });
+        }
+        Ok(devices)
+    }
+
+    /// Asks the hub to look for dials that were connected or disconnected
+    /// since it last checked.
+    pub async fn rescan_bus(&self) -> Result<(), Error> {
+        self.send_frame(HubCommand::RescanBus, super::rescan_bus_frame())
+            .await?;
+        Ok(())
+    }
+
+    /// Removes every dial's address.
+    ///
+    /// No dial can be sent commands until it's provisioned again, for
+    /// example with [`AsyncHub::reset_and_provision`].
+    pub async fn reset_all_devices(&self) -> Result<(), Error> {
+        self.send_frame(
+            HubCommand::ResetAllDevices,
+            super::reset_all_devices_frame(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Gives the next dial without an address the address `address`.
+    ///
+    /// Returns `false` if there are no dials without an address.
+    pub async fn provision_device(&self, address: u8) -> Result<bool, Error> {
+        let result = self
+            .send_frame(HubCommand::ProvisionDevice, super::provision_frame(address))
+            .await;
+        super::was_provisioned(result)
+    }
+
+    /// Provisions every dial without an address, and records every
+    /// connected dial's UID in `map`.
+    ///
+    /// See [`Hub::provision`] for details.
+    pub async fn provision(&self, map: &mut DialMap) -> Result<Vec<u8>, Error> {
+        self.rescan_bus().await?;
+        let devices = self.device_map().await?;
+        for address in devices.online() {
+            let uid = self.device_uid(address).await?;
+            map.insert(address, uid);
+        }
+
+        let mut provisioned = Vec::new();
+        for address in devices.offline() {
+            if !self.provision_device(address).await? {
+                break;
+            }
+            let uid = self.device_uid(address).await?;
+            map.insert(address, uid);
+            provisioned.push(address);
+        }
+        Ok(provisioned)
+    }
+
+    /// Removes every dial's address, and then provisions them all again.
+    ///
+    /// See [`Hub::reset_and_provision`] for details.
+    pub async fn reset_and_provision(&self, map: &mut DialMap) -> Result<Vec<u8>, Error> {
+        self.reset_all_devices().await?;
+        self.provision(map).await
+    }
+
+    /// Sets how the needle of the dial at `dial` eases towards its target:
+    /// every `period_ms` milliseconds, it moves `step` percent closer.
+    pub async fn set_dial_easing(&self, dial: u8, period_ms: u32, step: u8) -> Result<(), Error> {
+        self.set_easing(
+            [
+                HubCommand::SetDialEasingStep,
+                HubCommand::SetDialEasingPeriod,
+            ],
+            dial,
+            period_ms,
+            step,
+        )
+        .await
+    }
+
+    /// Sets how the backlight of the dial at `dial` eases towards its target:
+    /// every `period_ms` milliseconds, it moves `step` percent closer.
+    pub async fn set_backlight_easing(
+        &self,
+        dial: u8,
+        period_ms: u32,
+        step: u8,
+    ) -> Result<(), Error> {
+        self.set_easing(
+            [
+                HubCommand::SetBacklightEasingStep,
+                HubCommand::SetBacklightEasingPeriod,
+            ],
+            dial,
+            period_ms,
+            step,
+        )
+        .await
+    }
+
+    /// Sets the raw needle position that the dial at `dial` shows at 100%
+    /// (or 50%, if `half` is `true`).
+    pub async fn calibrate(&self, dial: u8, half: bool, raw: u16) -> Result<(), Error> {
+        let command = if half {
+            HubCommand::SetDialCalibrateHalf
+        } else {
+            HubCommand::SetDialCalibrateMax
+        };
+        let payload = Payload::KeyValuePair {
+            key: dial,
+            value: &raw.to_be_bytes(),
+        };
+        self.send(&Command::new(command, payload)).await?;
+        Ok(())
+    }
+
+    /// Draws a 1-bit image on the display of the dial at `dial`.
+    ///
+    /// `pixels` is sent as-is, in chunks of at most [`IMAGE_CHUNK_LEN`]
+    /// bytes, and is then shown.
+    pub async fn show_image(&self, dial: u8, pixels: &[u8]) -> Result<(), Error> {
+        let origin = Payload::KeyValuePair {
+            key: dial,
+            value: &[0, 0, 0, 0],
+        };
+        self.send(&Command::new(HubCommand::DisplayGotoXy, origin))
+            .await?;
+        let mut data = Vec::with_capacity(IMAGE_CHUNK_LEN + 1);
+        for chunk in pixels.chunks(IMAGE_CHUNK_LEN) {
+            data.clear();
+            data.push(dial);
+            data.extend_from_slice(chunk);
+            let payload = Payload::MultipleValue(&data);
+            self.send(&Command::new(HubCommand::DisplayImgData, payload))
+                .await?;
+        }
+        let show = Payload::SingleValue(&[dial]);
+        self.send(&Command::new(HubCommand::DisplayShowImg, show))
+            .await?;
+        Ok(())
+    }
+
+    async fn set_easing(
+        &self,
+        [step_command, period_command]: [HubCommand; 2],
+        dial: u8,
+        period_ms: u32,
+        step: u8,
+    ) -> Result<(), Error> {
+        super::check_percent(step)?;
+        let step = Payload::KeyValuePair {
+            key: dial,
+            value: &[step],
+        };
+        self.send(&Command::new(step_command, step)).await?;
+        let period = Payload::KeyValuePair {
+            key: dial,
+            value: &period_ms.to_be_bytes(),
+        };
+        self.send(&Command::new(period_command, period)).await?;
+        Ok(())
+    }
+
+    async fn send_frame(&self, command: HubCommand, frame: Vec<u8>) -> Result<Response, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.commands
+            .send(Queued {
+                command,
+                frame,
+                rsp: tx,
+            })
+            .await
+            .map_err(|_| Error::Closed)?;
+        rx.await.map_err(|_| Error::Closed)?
+    }
+}
+
+/// Sends queued commands to the hub until every [`AsyncHub`] is dropped, or
+/// the connection fails.
+async fn drive(mut port: SerialStream, mut commands: mpsc::Receiver<Queued>) {
+    let mut frames = FrameBuffer::default();
+    while let Some(queued) = commands.recv().await {
+        // Skip commands whose senders have given up waiting.
+        if queued.rsp.is_closed() {
+            continue;
+        }
+        let result = time::timeout(Hub::TIMEOUT, exchange(&mut port, &mut frames, &queued))
+            .await
+            .unwrap_or(Err(Error::Timeout(queued.command)));
+        // If the connection itself failed, every later command would fail
+        // too, so stop, and let them see `Error::Closed`.
+        let fatal = matches!(result, Err(Error::Io(_)));
+        let _ = queued.rsp.send(result);
+        if fatal {
+            return;
+        }
+    }
+}
+
+/// Writes `queued`'s frame, and reads until its response arrives.
+///
+/// Even if the sender cancels the command while it's in flight, the response
+/// is still read, so that it isn't mistaken for the next command's.
+async fn exchange(
+    port: &mut SerialStream,
+    frames: &mut FrameBuffer,
+    queued: &Queued,
+) -> Result<Response, Error> {
+    port.write_all(&queued.frame).await?;
+    port.flush().await?;
+    let mut chunk = [0u8; 64];
+    loop {
+        while let Some(rsp) = frames.next_response() {
+            if let Some(rsp) = super::check_response(queued.command, rsp?)? {
+                return Ok(rsp);
+            }
+        }
+        let n = port.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        frames.extend(&chunk[..n]);
+    }
+}
@@ -1 +1,3 @@
+#[cfg(feature = "hub")]
+pub mod hub;
 pub mod v1;
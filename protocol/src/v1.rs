@@ -1,3 +1,7 @@
+//! Version 1 of the VU1 hub's serial protocol.
+//!
+//! The hub is sent [`Command`]s, and replies to each with a [`Response`].
+//! Both are framed as ASCII hex lines; see [`CommandHeader`] for the format.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum HubCommand {
@@ -65,7 +69,450 @@ pub enum HubDataType {
     StatusCode = 0x05,
 }
 
-pub struct CommandHeader {}
+/// The header at the start of every command sent to the hub, and every
+/// response it sends back.
+///
+/// On the wire, a frame is a start byte (`>` for commands, `<` for
+/// responses), followed by the header and the payload as uppercase ASCII hex,
+/// followed by [`COMMAND_SUFFIX`]. The header is the command and data type as
+/// one byte each, then the payload's length in bytes as a big-endian `u16`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CommandHeader {
+    pub command: HubCommand,
+    pub data_type: HubDataType,
+    /// The length of the payload, in bytes (not hex digits).
+    pub len: u16,
+}
+
+/// The data sent with a command.
+///
+/// Multi-byte values are big-endian.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Payload<'a> {
+    /// No data.
+    None,
+    /// A single value, such as the index of the dial a command applies to.
+    SingleValue(&'a [u8]),
+    /// A list of values, such as a dial index followed by red, green, blue,
+    /// and white backlight values.
+    MultipleValue(&'a [u8]),
+    /// A key (usually a dial index) and its value.
+    KeyValuePair { key: u8, value: &'a [u8] },
+    /// A status code.
+    StatusCode(HubStatusCode),
+}
+
+/// A command to send to the hub.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Command<'a> {
+    pub command: HubCommand,
+    pub payload: Payload<'a>,
+}
+
+/// A response received from the hub.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    /// The command this is a response to.
+    pub command: HubCommand,
+    pub data_type: HubDataType,
+    /// The response's payload, decoded from hex.
+    pub data: Vec<u8>,
+}
+
+/// The response to [`HubCommand::GetDevicesMap`]: whether a dial is online
+/// at each of the hub's bus addresses.
+///
+/// The hub sends one byte per address, which is nonzero if a provisioned dial
+/// answered at that address when the bus was last scanned.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DevicesMap {
+    online: Vec<bool>,
+}
+
+/// An error decoding a response frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The frame didn't start with [`RESPONSE_START`].
+    MissingStart,
+    /// The frame ended before the header or payload did.
+    Truncated,
+    /// The frame contained a character that isn't a hex digit.
+    InvalidHex(u8),
+    /// The header named a command that doesn't exist.
+    UnknownCommand(u8),
+    /// The header named a data type that doesn't exist.
+    UnknownDataType(u8),
+    /// A status code response's code isn't one that the hub sends.
+    UnknownStatusCode(u16),
+    /// The response was to a different command than the one being decoded.
+    UnexpectedCommand {
+        expected: HubCommand,
+        actual: HubCommand,
+    },
+}
+
+/// The byte that starts a command frame sent to the hub.
+pub const COMMAND_START: u8 = b'>';
+
+/// The byte that starts a response frame sent by the hub.
+pub const RESPONSE_START: u8 = b'<';
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Splits the first complete frame off the front of `buf`.
+///
+/// Returns the frame, without its [`COMMAND_SUFFIX`], and the rest of `buf`,
+/// or `None` if `buf` doesn't contain a complete frame yet.
+pub fn split_frame(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = buf
+        .windows(COMMAND_SUFFIX.len())
+        .position(|window| window == COMMAND_SUFFIX)?;
+    Some((&buf[..end], &buf[end + COMMAND_SUFFIX.len()..]))
+}
+
+// === impl HubCommand ===
+
+impl TryFrom<u8> for HubCommand {
+    type Error = DecodeError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        use HubCommand::*;
+        const COMMANDS: &[HubCommand] = &[
+            SetDialRawSingle,
+            SetDialRawMultiple,
+            SetDialPercSingle,
+            SetDialPercMultiple,
+            SetDialCalibrateMax,
+            SetDialCalibrateHalf,
+            GetDevicesMap,
+            ProvisionDevice,
+            ResetAllDevices,
+            DialPower,
+            GetDeviceUid,
+            RescanBus,
+            DisplayClear,
+            DisplayGotoXy,
+            DisplayImgData,
+            DisplayShowImg,
+            RxBufferSize,
+            ResetCfg,
+            SetRgbBacklight,
+            SetDialEasingStep,
+            SetDialEasingPeriod,
+            SetBacklightEasingStep,
+            SetBacklightEasingPeriod,
+            GetEasingConfig,
+            GetBuildInfo,
+            GetFwInfo,
+            GetHwInfo,
+            GetProtocolInfo,
+            DebugI2cScan,
+            HubBtlJumpToBootloader,
+            DialBtlJumpToBootloader,
+            DialBtlGetInfo,
+            DialBtlGetCrc,
+            DialBtlEraseApp,
+            DialBtlFwupSendPackage,
+            DialBtlFwupFinished,
+            DialBtlExit,
+            DialBtlRestartFwupload,
+            DialBtlReadLastStatus,
+        ];
+        COMMANDS
+            .iter()
+            .copied()
+            .find(|&command| command as u8 == byte)
+            .ok_or(DecodeError::UnknownCommand(byte))
+    }
+}
+
+// === impl HubDataType ===
+
+impl TryFrom<u8> for HubDataType {
+    type Error = DecodeError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            0x01 => Ok(Self::None),
+            0x02 => Ok(Self::SingleValue),
+            0x03 => Ok(Self::MultipleValue),
+            0x04 => Ok(Self::KeyValuePair),
+            0x05 => Ok(Self::StatusCode),
+            _ => Err(DecodeError::UnknownDataType(byte)),
+        }
+    }
+}
+
+// === impl CommandHeader ===
+
+impl CommandHeader {
+    /// The length of an encoded header, in hex digits.
+    pub const ENCODED_LEN: usize = 8;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        push_hex(buf, &[self.command as u8, self.data_type as u8]);
+        push_hex(buf, &self.len.to_be_bytes());
+    }
+
+    fn decode(hex: &[u8]) -> Result<Self, DecodeError> {
+        let hex = hex.get(..Self::ENCODED_LEN).ok_or(DecodeError::Truncated)?;
+        let mut bytes = [0u8; 4];
+        decode_hex(hex, &mut bytes)?;
+        Ok(Self {
+            command: HubCommand::try_from(bytes[0])?,
+            data_type: HubDataType::try_from(bytes[1])?,
+            len: u16::from_be_bytes([bytes[2], bytes[3]]),
+        })
+    }
+}
+
+// === impl Payload ===
+
+impl Payload<'_> {
+    /// Returns the data type that the hub should parse this payload as.
+    pub fn data_type(&self) -> HubDataType {
+        match self {
+            Self::None => HubDataType::None,
+            Self::SingleValue(_) => HubDataType::SingleValue,
+            Self::MultipleValue(_) => HubDataType::MultipleValue,
+            Self::KeyValuePair { .. } => HubDataType::KeyValuePair,
+            Self::StatusCode(_) => HubDataType::StatusCode,
+        }
+    }
+
+    /// Returns the length of this payload, in bytes.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::SingleValue(value) | Self::MultipleValue(value) => value.len(),
+            Self::KeyValuePair { value, .. } => 1 + value.len(),
+            Self::StatusCode(_) => 2,
+        }
+    }
+
+    /// Returns `true` if this payload has no data.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match *self {
+            Self::None => {}
+            Self::SingleValue(value) | Self::MultipleValue(value) => push_hex(buf, value),
+            Self::KeyValuePair { key, value } => {
+                push_hex(buf, &[key]);
+                push_hex(buf, value);
+            }
+            Self::StatusCode(code) => push_hex(buf, &(code as u16).to_be_bytes()),
+        }
+    }
+}
+
+// === impl Command ===
+
+impl<'a> Command<'a> {
+    pub fn new(command: HubCommand, payload: Payload<'a>) -> Self {
+        Self { command, payload }
+    }
+
+    /// Returns this command's header.
+    ///
+    /// # Panics
+    ///
+    /// If the payload is longer than `u16::MAX` bytes.
+    pub fn header(&self) -> CommandHeader {
+        CommandHeader {
+            command: self.command,
+            data_type: self.payload.data_type(),
+            len: u16::try_from(self.payload.len())
+                .expect("command payloads must be at most 65535 bytes"),
+        }
+    }
+
+    /// Returns the length of the encoded command frame, in bytes.
+    pub fn encoded_len(&self) -> usize {
+        1 + CommandHeader::ENCODED_LEN + self.payload.len() * 2 + COMMAND_SUFFIX.len()
+    }
+
+    /// Appends the encoded command frame, including its [`COMMAND_SUFFIX`],
+    /// to `buf`.
+    ///
+    /// # Panics
+    ///
+    /// If the payload is longer than `u16::MAX` bytes.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.reserve(self.encoded_len());
+        buf.push(COMMAND_START);
+        self.header().encode(buf);
+        self.payload.encode(buf);
+        buf.extend_from_slice(COMMAND_SUFFIX);
+    }
+
+    /// Returns the encoded command frame.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode(&mut buf);
+        buf
+    }
+}
+
+// === impl Response ===
+
+impl Response {
+    /// Decodes a response frame.
+    ///
+    /// The frame's [`COMMAND_SUFFIX`] is optional, so frames returned by
+    /// [`split_frame`] can be decoded as-is. Anything after the payload is
+    /// ignored.
+    pub fn decode(frame: &[u8]) -> Result<Self, DecodeError> {
+        let frame = frame.strip_suffix(COMMAND_SUFFIX).unwrap_or(frame);
+        let hex = match frame.split_first() {
+            Some((&RESPONSE_START, hex)) => hex,
+            _ => return Err(DecodeError::MissingStart),
+        };
+        let header = CommandHeader::decode(hex)?;
+        let len = usize::from(header.len);
+        let payload = hex[CommandHeader::ENCODED_LEN..]
+            .get(..len * 2)
+            .ok_or(DecodeError::Truncated)?;
+        let mut data = vec![0; len];
+        decode_hex(payload, &mut data)?;
+        let rsp = Self {
+            command: header.command,
+            data_type: header.data_type,
+            data,
+        };
+        // Check that status codes are ones we know, so that `status` can't
+        // fail later.
+        if rsp.data_type == HubDataType::StatusCode {
+            rsp.decode_status()?;
+        }
+        Ok(rsp)
+    }
+
+    /// Returns the response's status code, if it's a status code response.
+    pub fn status(&self) -> Option<HubStatusCode> {
+        if self.data_type != HubDataType::StatusCode {
+            return None;
+        }
+        self.decode_status().ok()
+    }
+
+    /// Returns the response's data, or an error if it's a status code
+    /// response with a status other than [`HubStatusCode::Ok`].
+    pub fn into_result(self) -> Result<Vec<u8>, HubStatusCode> {
+        match self.status() {
+            Some(HubStatusCode::Ok) | None => Ok(self.data),
+            Some(status) => Err(status),
+        }
+    }
+
+    fn decode_status(&self) -> Result<HubStatusCode, DecodeError> {
+        match self.data[..] {
+            [hi, lo, ..] => HubStatusCode::try_from(u16::from_be_bytes([hi, lo])),
+            _ => Err(DecodeError::Truncated),
+        }
+    }
+}
+
+// === impl DevicesMap ===
+
+impl DevicesMap {
+    /// The command that asks the hub for its devices map.
+    pub const COMMAND: Command<'static> = Command {
+        command: HubCommand::GetDevicesMap,
+        payload: Payload::None,
+    };
+
+    /// Decodes a response to [`DevicesMap::COMMAND`].
+    pub fn decode(rsp: &Response) -> Result<Self, DecodeError> {
+        if rsp.command != HubCommand::GetDevicesMap {
+            return Err(DecodeError::UnexpectedCommand {
+                expected: HubCommand::GetDevicesMap,
+                actual: rsp.command,
+            });
+        }
+        let online = rsp.data.iter().map(|&byte| byte != 0).collect();
+        Ok(Self { online })
+    }
+
+    /// Returns the number of addresses on the hub's bus.
+    pub fn len(&self) -> usize {
+        self.online.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.online.is_empty()
+    }
+
+    /// Returns `true` if a dial is online at `address`.
+    pub fn is_online(&self, address: u8) -> bool {
+        self.online
+            .get(usize::from(address))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Returns the addresses that a dial is online at.
+    pub fn online(&self) -> impl Iterator<Item = u8> + '_ {
+        self.addresses(true)
+    }
+
+    /// Returns the addresses that no dial is online at.
+    pub fn offline(&self) -> impl Iterator<Item = u8> + '_ {
+        self.addresses(false)
+    }
+
+    /// Returns each address, and whether a dial is online at it.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, bool)> + '_ {
+        (0..=u8::MAX).zip(self.online.iter().copied())
+    }
+
+    fn addresses(&self, online: bool) -> impl Iterator<Item = u8> + '_ {
+        self.iter()
+            .filter_map(move |(address, state)| (state == online).then_some(address))
+    }
+}
+
+// === impl DecodeError ===
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingStart => write!(f, "response frame must start with '<'"),
+            Self::Truncated => write!(f, "response frame is truncated"),
+            Self::InvalidHex(byte) => write!(f, "invalid hex digit {:?}", char::from(*byte)),
+            Self::UnknownCommand(byte) => write!(f, "unknown hub command {byte:#04x}"),
+            Self::UnknownDataType(byte) => write!(f, "unknown hub data type {byte:#04x}"),
+            Self::UnknownStatusCode(code) => write!(f, "unknown hub status code {code:#06x}"),
+            Self::UnexpectedCommand { expected, actual } => {
+                write!(f, "expected a response to {expected:?}, not {actual:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+fn push_hex(buf: &mut Vec<u8>, bytes: &[u8]) {
+    for byte in bytes {
+        buf.push(HEX_DIGITS[usize::from(byte >> 4)]);
+        buf.push(HEX_DIGITS[usize::from(byte & 0xF)]);
+    }
+}
+
+fn decode_hex(hex: &[u8], out: &mut [u8]) -> Result<(), DecodeError> {
+    let digit = |c: u8| {
+        char::from(c)
+            .to_digit(16)
+            .map(|d| d as u8)
+            .ok_or(DecodeError::InvalidHex(c))
+    };
+    for (pair, byte) in hex.chunks_exact(2).zip(out) {
+        *byte = (digit(pair[0])? << 4) | digit(pair[1])?;
+    }
+    Ok(())
+}
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u16)]
@@ -95,3 +542,44 @@ pub enum HubStatusCode {
     BootloaderInvalidState = 0xE002,
     BootloaderInvalidRequest = 0xE003,
 }
+
+// === impl HubStatusCode ===
+
+impl TryFrom<u16> for HubStatusCode {
+    type Error = DecodeError;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        use HubStatusCode::*;
+        const CODES: &[HubStatusCode] = &[
+            Ok,
+            Fail,
+            Busy,
+            Timeout,
+            BadData,
+            ProtocolError,
+            NoMemory,
+            InvalidArgument,
+            BadAddress,
+            Forbidden,
+            AlreadyExists,
+            Unsupported,
+            NotImplemented,
+            MalformedPackage,
+            RecursiveCall,
+            DataMismatch,
+            DeviceOffline,
+            ModuleNotInit,
+            I2cError,
+            UsartError,
+            SpiError,
+            BootloaderNoDevice,
+            BootloaderInvalidState,
+            BootloaderInvalidRequest,
+        ];
+        CODES
+            .iter()
+            .copied()
+            .find(|&status| status as u16 == code)
+            .ok_or(DecodeError::UnknownStatusCode(code))
+    }
+}
@@ -1,6 +1,26 @@
 use clap::Parser;
+use std::process::ExitCode;
+use vupdaters::{
+    cli::completions,
+    dialctl::{exit, Args},
+};
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> miette::Result<()> {
-    vupdaters::dialctl::Args::parse().run().await
+fn main() -> ExitCode {
+    // This has to happen before the runtime is started, since completing dial
+    // UIDs and names starts its own runtime to ask the VU-Server.
+    completions::complete::<Args>();
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build Tokio runtime");
+    match rt.block_on(Args::parse().run()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            // This is what `miette` prints when `main` returns an error, but
+            // we want to choose the exit code.
+            eprintln!("Error: {error:?}");
+            ExitCode::from(exit::code(&error))
+        }
+    }
 }
@@ -0,0 +1,12 @@
+use clap::Parser;
+use vupdaters::{cli::completions, server::Args};
+
+fn main() -> miette::Result<()> {
+    completions::complete::<Args>();
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build Tokio runtime");
+    rt.block_on(Args::parse().run())
+}
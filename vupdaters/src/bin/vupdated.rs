@@ -1,15 +1,17 @@
 use clap::Parser;
-use miette::{Context, IntoDiagnostic};
-use tokio::{runtime, task::LocalSet};
+use vupdaters::{cli::completions, daemon::Args};
 
 fn main() -> miette::Result<()> {
-    let app = vupdaters::daemon::Args::parse();
-    let rt = runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()
-        .into_diagnostic()
-        .context("failed to build Tokio runtime! something is very messed up")?;
-
-    let local = LocalSet::new();
-    local.block_on(&rt, app.run())
+    completions::complete::<Args>();
+
+    let mut app = Args::parse();
+    if app.is_service() {
+        return app.run_service();
+    }
+
+    // This must happen before the runtime's threads are started.
+    app.take_activated_sockets();
+
+    let rt = app.runtime()?;
+    rt.block_on(app.run())
 }
@@ -1,11 +1,17 @@
+use camino::Utf8PathBuf;
 use miette::{Context, IntoDiagnostic};
 
+pub mod completions;
+pub(crate) mod man;
+
 #[derive(Clone, Debug, clap::Args)]
 #[command(next_help_heading = "VU-Server Client Options")]
 pub struct ClientArgs {
     /// The server API key.
+    ///
+    /// This is required for all commands that talk to the VU-Server.
     #[clap(long, short = 'k', env = "VU_DIALS_API_KEY")]
-    key: String,
+    key: Option<String>,
 
     /// The hostname of the VU-Server instance to connect to.
     #[clap(
@@ -17,6 +23,19 @@ pub struct ClientArgs {
         global = true
     )]
     server: reqwest::Url,
+
+    /// How long to wait for the VU-Server to accept a connection, and to
+    /// respond to each request, before giving up.
+    ///
+    /// By default, connecting times out after 5 seconds, and each request
+    /// after 30 seconds.
+    #[clap(
+        long,
+        env = "VU_DIALS_TIMEOUT",
+        value_parser = humantime_serde::re::humantime::parse_duration,
+        global = true
+    )]
+    timeout: Option<std::time::Duration>,
 }
 
 #[derive(Clone, Debug, clap::Args)]
@@ -32,9 +51,15 @@ pub struct OutputArgs {
     filter: tracing_subscriber::filter::Targets,
 
     /// If set, log to the system journal, instead of stderr.
-    #[clap(long, global = true)]
+    #[clap(long, global = true, conflicts_with = "log_file")]
     journald: bool,
 
+    /// If set, log to the given file, instead of stderr.
+    ///
+    /// Logs are appended to the file if it already exists.
+    #[clap(long, global = true, value_hint = clap::ValueHint::FilePath)]
+    log_file: Option<Utf8PathBuf>,
+
     /// If set, forcibly disable ANSI colors in stderr output.
     ///
     /// See https://no-color.org/
@@ -48,32 +73,133 @@ pub struct OutputArgs {
 }
 
 impl ClientArgs {
-    pub fn into_client(self) -> Result<vu_api::client::Client, vu_api::client::NewClientError> {
-        vu_api::client::Client::new(self.key, self.server)
+    /// Returns the server API key, if one was provided.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub(crate) fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
+    /// Returns the URL of the VU-Server instance to connect to.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub(crate) fn server(&self) -> &reqwest::Url {
+        &self.server
+    }
+
+    /// Returns these arguments with the VU-Server URL replaced by `server`.
+    pub(crate) fn with_server(self, server: reqwest::Url) -> Self {
+        Self { server, ..self }
+    }
+
+    /// Returns these arguments with the API key replaced by `key`.
+    pub(crate) fn with_key(self, key: String) -> Self {
+        Self {
+            key: Some(key),
+            ..self
+        }
+    }
+
+    pub fn into_client(self) -> miette::Result<vu_api::client::Client> {
+        self.into_client_with(|builder| builder)
+    }
+
+    /// Like [`into_client`](Self::into_client), but `tune` may change the
+    /// client's settings before any set by these arguments are applied.
+    pub(crate) fn into_client_with(
+        self,
+        tune: impl FnOnce(vu_api::client::Builder) -> vu_api::client::Builder,
+    ) -> miette::Result<vu_api::client::Client> {
+        let mut builder = tune(vu_api::Client::builder()).server(self.server);
+        if let Some(key) = self.key {
+            builder = builder.key(key);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.connect_timeout(timeout).timeout(timeout);
+        }
+        let client = builder.build().map_err(|error| match error {
+            vu_api::client::NewClientError::MissingKey => miette::miette!(
+                help =
+                    "set the API key using `--key` or the `VU_DIALS_API_KEY` environment variable",
+                "a VU-Server API key is required"
+            ),
+            error => error.into(),
+        })?;
+        Ok(client)
     }
 }
 
 impl OutputArgs {
+    /// Log to `path` if no other log destination was configured.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub(crate) fn default_log_file(&mut self, path: impl FnOnce() -> Option<Utf8PathBuf>) {
+        if !self.journald && self.log_file.is_none() {
+            self.log_file = path();
+        }
+    }
+
     pub fn init_tracing(self) -> miette::Result<()> {
+        let has_ansi_colors = supports_color::on(supports_color::Stream::Stderr)
+            .map(|colors| colors.has_basic)
+            .unwrap_or(false);
+        self.init_tracing_to(std::io::stderr, has_ansi_colors)
+    }
+
+    /// Like [`init_tracing`](Self::init_tracing), but logs that would be
+    /// written to stderr are written to `writer` instead.
+    ///
+    /// This is used by commands that draw over the terminal.
+    pub(crate) fn init_tracing_to<W>(self, writer: W, ansi: bool) -> miette::Result<()>
+    where
+        W: for<'w> tracing_subscriber::fmt::MakeWriter<'w> + Send + Sync + 'static,
+    {
         use tracing_subscriber::{fmt, prelude::*};
-        let subcriber = tracing_subscriber::registry().with(self.filter);
+        let subcriber = tracing_subscriber::registry();
+        // The console layer has its own filter, which enables the runtime's
+        // task instrumentation, so the log filter only applies to the output
+        // layers.
+        #[cfg(feature = "console")]
+        let subcriber = subcriber.with(console_subscriber::spawn());
+        let filter = self.filter;
         if self.journald {
             let layer = tracing_journald::layer()
                 .into_diagnostic()
                 .context("could not connect to journald!")?;
-            subcriber.with(layer).init();
+            subcriber.with(layer.with_filter(filter)).init();
+        } else if let Some(path) = self.log_file {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)
+                    .into_diagnostic()
+                    .with_context(|| format!("failed to create log directory {dir}"))?;
+            }
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .into_diagnostic()
+                .with_context(|| format!("failed to open log file {path}"))?;
+            let fmt = fmt::layer()
+                .with_span_events(fmt::format::FmtSpan::CLOSE)
+                .with_writer(std::sync::Mutex::new(file))
+                .with_ansi(false);
+            subcriber.with(fmt.with_filter(filter)).init();
         } else {
-            let has_ansi_colors = supports_color::on(supports_color::Stream::Stderr)
-                .map(|colors| colors.has_basic)
-                .unwrap_or(false);
             let fmt = fmt::layer()
                 .with_span_events(fmt::format::FmtSpan::CLOSE)
                 .with_timer(fmt::time::uptime())
-                .with_writer(std::io::stderr)
-                .with_ansi(has_ansi_colors);
-            subcriber.with(fmt).init();
+                .with_writer(writer)
+                .with_ansi(ansi);
+            subcriber.with(fmt.with_filter(filter)).init();
         }
 
         Ok(())
     }
 }
+
+/// Parses a duration that must not be zero, such as the interval between
+/// updates.
+pub(crate) fn parse_nonzero_duration(s: &str) -> Result<std::time::Duration, String> {
+    let duration = humantime_serde::re::humantime::parse_duration(s).map_err(|e| e.to_string())?;
+    if duration.is_zero() {
+        return Err("must be greater than zero".to_owned());
+    }
+    Ok(duration)
+}
@@ -0,0 +1,116 @@
+//! Shell completions.
+//!
+//! Completions are generated dynamically: the script printed by the
+//! `completions` subcommand calls back into the binary with the `COMPLETE`
+//! environment variable set, which is handled by [`complete`] before the
+//! command line is parsed. This lets dial UIDs and names be completed by
+//! asking the VU-Server which dials are connected.
+use clap_complete::{engine::CompletionCandidate, env::Shells, ArgValueCompleter};
+use miette::{Context, IntoDiagnostic};
+use std::{ffi::OsStr, time::Duration};
+
+/// The environment variable that the registration scripts set when asking
+/// for completions.
+const VAR: &str = "COMPLETE";
+
+/// How long to wait for the VU-Server when completing dial UIDs and names.
+///
+/// Completions should feel instant, so if the server is slow or unreachable,
+/// it's better to complete nothing at all.
+const SERVER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Handles a request for completions from a shell, and exits, if the process
+/// was run by a completion script.
+///
+/// This must be called before anything is written to stdout.
+pub fn complete<A: clap::CommandFactory>() {
+    clap_complete::CompleteEnv::with_factory(A::command)
+        .var(VAR)
+        .complete();
+}
+
+/// Writes the completion script for `shell` to stdout.
+pub(crate) fn print_registration(shell: clap_complete::Shell, bin: &str) -> miette::Result<()> {
+    let shell = shell.to_string();
+    let shells = Shells::builtins();
+    let completer = shells.completer(&shell).ok_or_else(|| {
+        miette::miette!(
+            help = format!(
+                "supported shells are: {}",
+                shells.names().collect::<Vec<_>>().join(", ")
+            ),
+            "dynamic completions are not supported for {shell}"
+        )
+    })?;
+    // If the binary was run by a path, rather than from the `PATH`, the
+    // completion script should call back into that same binary.
+    let exe = std::env::args()
+        .next()
+        .map(std::path::PathBuf::from)
+        .filter(|path| path.components().count() > 1)
+        .and_then(|path| Some(std::env::current_dir().ok()?.join(path)))
+        .and_then(|path| path.to_str().map(ToOwned::to_owned))
+        .unwrap_or_else(|| bin.to_owned());
+    completer
+        .write_registration(VAR, bin, bin, &exe, &mut std::io::stdout())
+        .into_diagnostic()
+        .with_context(|| format!("failed to write {shell} completions"))
+}
+
+/// Completes dial UIDs, with each dial's name as its description.
+pub(crate) fn dial_uids() -> ArgValueCompleter {
+    ArgValueCompleter::new(|current: &OsStr| {
+        complete_dials(current, |info| {
+            (info.uid.to_string(), info.dial_name.clone())
+        })
+    })
+}
+
+/// Completes dial names, with each dial's UID as its description.
+pub(crate) fn dial_names() -> ArgValueCompleter {
+    ArgValueCompleter::new(|current: &OsStr| {
+        complete_dials(current, |info| {
+            (info.dial_name.clone(), info.uid.to_string())
+        })
+    })
+}
+
+fn complete_dials(
+    current: &OsStr,
+    candidate: impl Fn(&vu_api::api::DialInfo) -> (String, String),
+) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+    // If the server can't be reached, there's nowhere to report the error,
+    // since anything written to stdout would be displayed as a completion.
+    let Ok(dials) = list_dials() else {
+        return Vec::new();
+    };
+    dials
+        .iter()
+        .map(candidate)
+        .filter(|(value, _)| value.starts_with(current))
+        .map(|(value, help)| CompletionCandidate::new(value).help(Some(help.into())))
+        .collect()
+}
+
+/// Lists the dials connected to the VU-Server.
+///
+/// Completers only see the argument being completed, so the server and API
+/// key are taken from the environment, rather than from `--server` and
+/// `--key`.
+fn list_dials() -> miette::Result<Vec<vu_api::api::DialInfo>> {
+    let client = vu_api::Client::builder().build()?;
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .into_diagnostic()
+        .context("failed to build Tokio runtime")?;
+    let dials = rt
+        .block_on(async { tokio::time::timeout(SERVER_TIMEOUT, client.list_dials()).await })
+        .into_diagnostic()
+        .context("timed out listing dials")??;
+    Ok(dials.into_iter().map(|(_, info)| info).collect())
+}
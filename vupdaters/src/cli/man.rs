@@ -0,0 +1,33 @@
+//! Man pages.
+//!
+//! The docs crate renders the command-line help as Markdown for the book, but
+//! distro packages want roff man pages, so both binaries can print their own,
+//! using `clap_mangen`.
+use camino::Utf8Path;
+use miette::{Context, IntoDiagnostic};
+
+/// Writes man pages for `A`.
+///
+/// If `out_dir` is provided, a page is written to it for the command and for
+/// each of its subcommands. Otherwise, only the command's own page is written
+/// to stdout.
+pub(crate) fn generate<A: clap::CommandFactory>(out_dir: Option<&Utf8Path>) -> miette::Result<()> {
+    let cmd = A::command();
+    let name = cmd.get_name().to_owned();
+    match out_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)
+                .into_diagnostic()
+                .with_context(|| format!("failed to create {dir}"))?;
+            clap_mangen::generate_to(cmd, dir)
+                .into_diagnostic()
+                .with_context(|| format!("failed to write {name} man pages to {dir}"))?;
+            tracing::info!(%dir, "Wrote {name} man pages");
+            Ok(())
+        }
+        None => clap_mangen::Man::new(cmd)
+            .render(&mut std::io::stdout())
+            .into_diagnostic()
+            .with_context(|| format!("failed to write {name} man page")),
+    }
+}
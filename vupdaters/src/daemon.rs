@@ -1,20 +1,43 @@
-use self::config::{Config, DialConfig};
-use crate::MultiError;
-use camino::Utf8PathBuf;
-use futures::TryFutureExt;
+pub use self::metrics::LocalMetric;
+use self::{
+    alert::{Alert, AlertKind},
+    config::{Config, DialConfig, ServerConfig, UnreachableAction, UnreachableConfig},
+    metrics::MetricSource,
+};
+use camino::{Utf8Path, Utf8PathBuf};
 use miette::{Context, IntoDiagnostic};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc};
-use tokio::{sync::watch, task};
+use std::{collections::HashMap, num::NonZeroUsize, sync::Arc, time::Duration};
+use tokio::{
+    runtime,
+    sync::{mpsc, watch},
+    task,
+};
 use tracing::Instrument;
 use vu_api::{
-    client::{Client, Dial},
+    client::{CircuitBreaker, Client, Dial},
     dial::{Backlight, Percent},
+    retry::RetryPolicy,
 };
 
+mod activation;
+mod agent;
+mod alert;
 pub mod config;
+pub mod control;
 #[cfg(all(target_os = "linux", feature = "hotplug"))]
 mod hotplug;
+mod idle;
+mod images;
+mod install;
+#[cfg(target_os = "macos")]
+mod macos;
+mod metrics;
+mod mqtt;
+mod render;
+mod scheduler;
+#[cfg(windows)]
+mod service;
 mod signal;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, clap::ValueEnum)]
@@ -36,6 +59,9 @@ pub enum Metric {
     Swap,
     /// Display the current remaining battery percentage.
     Battery,
+    /// Display values provided by an external plugin process.
+    #[clap(skip)]
+    Plugin(config::PluginConfig),
 }
 
 #[derive(Debug, clap::Parser)]
@@ -56,6 +82,77 @@ pub struct Args {
     )]
     config_path: Utf8PathBuf,
 
+    /// The config profile to activate on startup.
+    ///
+    /// If this is not set, the profile named by the config file's
+    /// `active-profile` setting is used. If the config file does not set an
+    /// active profile, the config file's top-level `dials` table is used.
+    ///
+    /// On Unix systems, sending `vupdated` a SIGUSR1 switches to the next
+    /// profile defined in the config file.
+    #[clap(long, short = 'p', env = "VUPDATED_PROFILE")]
+    profile: Option<String>,
+
+    /// Path to the control socket (or, on Windows, the name of the control
+    /// named pipe).
+    ///
+    /// The `dialctl daemon` subcommands connect to this socket to control the
+    /// running daemon.
+    #[clap(
+        long,
+        default_value_t = control::default_socket_path(),
+        value_hint = clap::ValueHint::FilePath,
+    )]
+    control_socket: Utf8PathBuf,
+
+    /// Path to the state file.
+    ///
+    /// `vupdated` records a hash of the image most recently uploaded to each
+    /// dial in this file, so that images the dials already have aren't
+    /// uploaded again when the daemon restarts.
+    #[clap(
+        long,
+        env = "VUPDATED_STATE_FILE",
+        default_value_t = images::default_state_path(),
+        value_hint = clap::ValueHint::FilePath,
+    )]
+    state_file: Utf8PathBuf,
+
+    /// Path to a file containing the VU-Server API key.
+    ///
+    /// If this is set, the API key is read from this file, rather than from
+    /// `--key`. The file is read again whenever the config is reloaded (such
+    /// as on SIGHUP), so the key can be rotated without restarting
+    /// `vupdated`.
+    #[clap(long, env = "VUPDATED_KEY_FILE", value_hint = clap::ValueHint::FilePath)]
+    key_file: Option<Utf8PathBuf>,
+
+    /// Run as a Windows service.
+    ///
+    /// When this is set, `vupdated` registers with the Windows Service Control
+    /// Manager, and shuts down when the service is stopped, rather than on
+    /// Ctrl-C. Unless `--log-file` is set, logs are written to
+    /// `vupdate\vupdated.log` in the local application data directory.
+    ///
+    /// This should only be set when `vupdated` is started by the Service
+    /// Control Manager, and is only supported on Windows.
+    #[clap(long)]
+    service: bool,
+
+    /// Run on a multi-threaded runtime with this many worker threads.
+    ///
+    /// By default, `vupdated` runs all of its tasks on a single thread, which
+    /// is plenty for a handful of dials. With many dials, or slow metric
+    /// plugins, a multi-threaded runtime may keep the dials updating more
+    /// smoothly.
+    #[clap(long, env = "VUPDATED_WORKER_THREADS")]
+    worker_threads: Option<NonZeroUsize>,
+
+    /// Sockets passed by systemd socket activation, taken by
+    /// [`Args::take_activated_sockets`].
+    #[clap(skip)]
+    activated: activation::Sockets,
+
     #[clap(flatten)]
     client_args: crate::cli::ClientArgs,
 
@@ -112,38 +209,300 @@ enum Subcommand {
         )]
         metrics: Vec<Metric>,
     },
+
+    /// Migrate a config file from the legacy `vupdate` daemon.
+    ///
+    /// The legacy config file is read from the provided path, and the migrated
+    /// config is written to the path given by `--config`. Because the legacy
+    /// format identifies dials by UID, the VU-Server must be running so that
+    /// each dial's index can be looked up.
+    MigrateConfig {
+        /// Path to the legacy `vupdate` config file.
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        legacy_config: Utf8PathBuf,
+    },
+
+    /// Run as an agent, publishing metrics from this machine for a `vupdated`
+    /// on another machine to display.
+    ///
+    /// The agent samples the metrics listed in the config file's `[agent]`
+    /// table, and publishes them to the MQTT broker configured in its `[mqtt]`
+    /// table. It doesn't talk to a VU-Server, so no API key is required.
+    Agent,
+
+    /// Install `vupdated` as a service that starts automatically.
+    ///
+    /// On Linux, this writes a systemd unit, along with an environment file
+    /// containing the API key. On macOS, this installs a launchd agent, like
+    /// `install-launchd`. On Windows, this registers `vupdated` with the
+    /// Service Control Manager.
+    ///
+    /// The service runs `vupdated` with the current `--config`, `--profile`,
+    /// VU-Server client, and USB hotplug settings.
+    InstallService {
+        /// Install a system-wide service, rather than a per-user one.
+        ///
+        /// This only affects systemd units. Windows services are always
+        /// system-wide, and launchd agents are always per-user.
+        #[arg(long)]
+        system: bool,
+
+        /// Overwrite an existing service.
+        #[arg(long)]
+        force: bool,
+
+        /// Enable and start the service after installing it.
+        #[arg(long)]
+        start: bool,
+    },
+
+    /// Install a launchd agent that runs `vupdated` when you log in.
+    ///
+    /// This writes a launchd property list to `~/Library/LaunchAgents`, which
+    /// runs `vupdated` with the current `--config`, `--profile`, and VU-Server
+    /// client settings. Logs are written to `~/Library/Logs/vupdated.log`.
+    ///
+    /// This command is only supported on macOS.
+    InstallLaunchd {
+        /// Overwrite an existing launchd agent.
+        #[arg(long)]
+        force: bool,
+
+        /// Load the agent with `launchctl` after installing it, starting
+        /// `vupdated` immediately.
+        #[arg(long)]
+        load: bool,
+    },
+
+    /// Print a shell completion script.
+    Completions {
+        /// The shell to generate completions for.
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Print roff man pages for `vupdated`.
+    ///
+    /// By default, only the page for `vupdated` itself is printed to stdout.
+    /// With `--out-dir`, a page is written for every subcommand as well.
+    Man {
+        /// The directory to write man pages to.
+        #[arg(long, value_hint = clap::ValueHint::DirPath)]
+        out_dir: Option<Utf8PathBuf>,
+    },
+}
+
+/// Commands sent to the task that supervises the dial managers.
+#[derive(Debug, Clone)]
+enum ManagerCommand {
+    /// Reload the config file and restart all dial managers.
+    Reload,
+    /// Switch to the next profile in the config file and restart all dial
+    /// managers.
+    NextProfile,
+    /// Switch to the named profile and restart all dial managers.
+    SwitchProfile(String),
+    /// Restart all dial managers, re-applying each dial's settings.
+    Resetup,
+}
+
+/// The settings for running the daemon itself, from the command line.
+struct DaemonSettings {
+    /// The file to read the VU-Server API key from, and re-read on reload.
+    key_file: Option<Utf8PathBuf>,
+    config_path: Utf8PathBuf,
+    /// The profile to start with, overriding the config's active profile.
+    profile: Option<String>,
+    control_socket: Utf8PathBuf,
+    state_file: Utf8PathBuf,
+    hotplug: HotplugSettings,
+    /// Sockets passed by systemd, rather than bound by the daemon.
+    activated: activation::Sockets,
 }
 
 struct DialManager {
     config: DialConfig,
+    source: metrics::Shared,
     dial: Dial,
     name: String,
-    backoff: backoff::ExponentialBackoffBuilder,
+    retry: RetryPolicy,
+    unreachable: UnreachableConfig,
+    errors: config::ErrorBudget,
+    alert: config::AlertConfig,
     running: watch::Receiver<bool>,
+    idle: watch::Receiver<bool>,
+    state: control::DaemonState,
+    scheduler: scheduler::Scheduler,
 }
 
 impl Args {
+    /// Returns `true` if `vupdated` should run as a Windows service.
+    ///
+    /// If this returns `true`, [`Args::run_service`] should be called instead
+    /// of [`Args::run`].
+    pub fn is_service(&self) -> bool {
+        self.service
+    }
+
+    /// Runs `vupdated` as a Windows service.
+    ///
+    /// This blocks the calling thread until the service is stopped, and must
+    /// be called from the process's main thread, *outside* of a Tokio runtime.
+    pub fn run_service(self) -> miette::Result<()> {
+        #[cfg(windows)]
+        {
+            service::run(self)
+        }
+
+        #[cfg(not(windows))]
+        {
+            miette::bail!("running as a service is only supported on Windows!")
+        }
+    }
+
+    /// Takes any sockets passed to `vupdated` by systemd socket activation.
+    ///
+    /// This clears the socket activation environment variables, which isn't
+    /// thread-safe, so it must be called before [`Args::runtime`] builds the
+    /// runtime.
+    pub fn take_activated_sockets(&mut self) {
+        self.activated = activation::Sockets::from_env();
+    }
+
+    /// Builds the Tokio runtime that `vupdated` should run on.
+    pub fn runtime(&self) -> miette::Result<runtime::Runtime> {
+        let mut builder = match self.worker_threads {
+            Some(threads) => {
+                let mut builder = runtime::Builder::new_multi_thread();
+                builder.worker_threads(threads.get());
+                builder
+            }
+            None => runtime::Builder::new_current_thread(),
+        };
+        builder
+            .enable_all()
+            .build()
+            .into_diagnostic()
+            .context("failed to build Tokio runtime! something is very messed up")
+    }
+
     pub async fn run(self) -> miette::Result<()> {
+        self.run_with_signals(None).await
+    }
+
+    async fn run_with_signals(self, signals: Option<signal::SignalListener>) -> miette::Result<()> {
         let Self {
             subcommand,
             client_args,
             output_args,
             config_path,
+            profile,
+            control_socket,
+            state_file,
+            key_file,
             hotplug,
+            service: _,
+            worker_threads: _,
+            activated,
         } = self;
         output_args.init_tracing()?;
-        let client = client_args
-            .into_client()
-            .context("failed to build client")?;
+
         match subcommand {
+            Some(Subcommand::Agent) => {
+                let config = Config::load(&config_path)?;
+                let options = config.source_options();
+                options.ensure_supported()?;
+                let agent = config.agent.ok_or_else(|| {
+                    miette::miette!(
+                        help = "add an `[agent]` table to the config file",
+                        "no agent is configured in {config_path}"
+                    )
+                })?;
+                let mqtt = config.mqtt.ok_or_else(|| {
+                    miette::miette!(
+                        help = "agents publish metrics over MQTT; add an `[mqtt]` table",
+                        "no MQTT broker is configured in {config_path}"
+                    )
+                })?;
+                let signals = match signals {
+                    Some(signals) => signals,
+                    None => signal::SignalListener::new()?,
+                };
+                return agent::run(agent, options, mqtt, config.retries, signals).await;
+            }
+            Some(Subcommand::InstallService {
+                system,
+                force,
+                start,
+            }) => {
+                return install::Install {
+                    config_path: &config_path,
+                    profile: profile.as_deref(),
+                    client_args: &client_args,
+                    hotplug: &hotplug,
+                    system,
+                    force,
+                    start,
+                }
+                .install();
+            }
+            Some(Subcommand::InstallLaunchd { force, load }) => {
+                #[cfg(target_os = "macos")]
+                return macos::install_launchd(&install::Install {
+                    config_path: &config_path,
+                    profile: profile.as_deref(),
+                    client_args: &client_args,
+                    hotplug: &hotplug,
+                    system: false,
+                    force,
+                    start: load,
+                });
+                #[cfg(not(target_os = "macos"))]
+                {
+                    let _ = (force, load);
+                    miette::bail!("launchd agents are only supported on macOS!");
+                }
+            }
+            Some(Subcommand::Completions { shell }) => {
+                return crate::cli::completions::print_registration(shell, "vupdated");
+            }
+            Some(Subcommand::Man { out_dir }) => {
+                return crate::cli::man::generate::<Args>(out_dir.as_deref());
+            }
             Some(Subcommand::GenConfig { metrics }) => {
+                let client = build_client(client_args, key_file.as_deref())?;
                 Config::generate(&client, metrics)
                     .await?
                     .write(&config_path)?;
             }
+            Some(Subcommand::MigrateConfig { legacy_config }) => {
+                let client = build_client(client_args, key_file.as_deref())?;
+                config::LegacyConfig::load(&legacy_config)?
+                    .migrate(&client)
+                    .await
+                    .with_context(|| format!("failed to migrate legacy config '{legacy_config}'"))?
+                    .write(&config_path)?;
+            }
             None => {
                 tracing::info!("starting daemon...");
-                run_daemon(client, config_path, hotplug).await?;
+                let clients = Clients {
+                    default: build_client(client_args.clone(), key_file.as_deref())?,
+                    args: client_args,
+                };
+                let signals = match signals {
+                    Some(signals) => signals,
+                    None => signal::SignalListener::new()?,
+                };
+                let settings = DaemonSettings {
+                    key_file,
+                    config_path,
+                    profile,
+                    control_socket,
+                    state_file,
+                    hotplug,
+                    activated,
+                };
+                run_daemon(clients, settings, signals).await?;
             }
         }
 
@@ -151,115 +510,211 @@ impl Args {
     }
 }
 
-impl Metric {
-    fn dial_name(&self) -> String {
-        match self {
-            Metric::Battery => "Battery Remaining".to_owned(),
-            Metric::DiskUsage => "Disk Usage".to_owned(),
-            // Metric::FsUsage { filesystem } => format!("{} Usage", filesystem),
-            Metric::CpuLoad => "CPU Load".to_owned(),
-            Metric::CpuTemp => "CPU Temperature".to_owned(),
-            Metric::Swap => "Swap Usage".to_owned(),
-            Metric::Mem => "Memory Usage".to_owned(),
-        }
-    }
-
-    fn img_file(&self) -> Option<&'static ImgFile> {
-        macro_rules! imgfile {
-            ($name: literal) => {
-                ImgFile {
-                    name: $name,
-                    image: include_bytes!(concat!("../assets/", $name)),
-                }
-            };
-        }
-        static MEM_IMG: ImgFile = imgfile!("mem.png");
-        static CPU_LOAD_IMG: ImgFile = imgfile!("cpu_load.png");
-        static CPU_TEMP_IMG: ImgFile = imgfile!("cpu_temp.png");
-        static SWAP_IMG: ImgFile = imgfile!("swap.png");
-        static DISK_IMG: ImgFile = imgfile!("disk.png");
-        static BATT_IMG: ImgFile = imgfile!("battery.png");
-
-        match self {
-            Metric::Swap => Some(&SWAP_IMG),
-            Metric::CpuLoad => Some(&CPU_LOAD_IMG),
-            Metric::CpuTemp => Some(&CPU_TEMP_IMG),
-            Metric::Mem => Some(&MEM_IMG),
-            Metric::DiskUsage => Some(&DISK_IMG),
-            Metric::Battery => Some(&BATT_IMG),
-        }
-    }
-}
-
-struct ImgFile {
-    name: &'static str,
-    image: &'static [u8],
-}
-
-pub async fn run_daemon(
-    client: Client,
-    config_path: Utf8PathBuf,
-    hotplug: HotplugSettings,
+async fn run_daemon(
+    clients: Clients,
+    settings: DaemonSettings,
+    mut signals: signal::SignalListener,
 ) -> miette::Result<()> {
-    use signal::{SignalAction, SignalListener};
+    use signal::SignalAction;
 
-    let mut signals = SignalListener::new()?;
+    let DaemonSettings {
+        key_file,
+        config_path,
+        profile,
+        control_socket,
+        state_file,
+        hotplug,
+        activated,
+    } = settings;
 
-    let (_running_tx, running) = watch::channel(true);
+    let (running_tx, running) = watch::channel(true);
+    let running_tx = Arc::new(running_tx);
 
     if hotplug.enabled {
         #[cfg(all(target_os = "linux", feature = "hotplug"))]
-        task::spawn_local(hotplug::run(hotplug, _running_tx));
+        hotplug::spawn(hotplug, running_tx.clone())?;
         #[cfg(all(target_os = "linux", not(feature = "hotplug")))]
         miette::bail!("hotplug support requires `vupdated` to be built with `--features hotplug`!");
         #[cfg(not(target_os = "linux"))]
         miette::bail!("hotplug support is currently only available on Linux!");
     };
 
-    let config_reloaded = Arc::new(tokio::sync::Notify::new());
-    let mut dial_managers = tokio::task::spawn_local({
-        let config_reloaded = config_reloaded.clone();
-        async move {
-            let mut tasks = task::JoinSet::new();
-            loop {
-                let config = Config::load(&config_path)?;
-                config
-                    .spawn_dial_managers(&client, &running, &mut tasks)
+    let (commands, mut command_rx) = mpsc::channel(8);
+    let state = control::DaemonState::new(images::ImageCache::load(state_file));
+    let controller = control::Controller {
+        config_path: config_path.clone(),
+        commands: commands.clone(),
+        running: running_tx.clone(),
+        state: state.clone(),
+    };
+    let control = task::spawn(controller.listen(control_socket, activated));
+
+    let (idle_tx, idle) = watch::channel(false);
+    task::spawn(idle::run(state.clone(), running.clone(), idle_tx));
+
+    // The MQTT connection is only configured at startup, and isn't changed
+    // when the config is reloaded.
+    if let Some(mqtt) = Config::load(&config_path)?.mqtt {
+        task::spawn(mqtt::run(mqtt, state.clone()));
+    }
+
+    let daemon_state = state.clone();
+    let signals_config_path = config_path.clone();
+    let mut dial_managers = task::spawn(async move {
+        let mut tasks = task::JoinSet::new();
+        let mut profile = profile;
+        let mut started = false;
+        loop {
+            let spawned = async {
+                Config::load(&config_path)?
+                    .spawn_dial_managers(
+                        profile.as_deref(),
+                        &clients,
+                        &running,
+                        &idle,
+                        &state,
+                        &mut tasks,
+                    )
                     .await
-                    .context("failed to spawn dial managers")?;
-
-                tokio::select! {
-                    _ = config_reloaded.notified() => {
-                        tracing::info!("Received reload signal, reloading config...");
-                        tasks.shutdown().await;
-                    },
-                    join = tasks.join_next() => {
-                        match join {
-                            Some(error) => {
-                                error.into_diagnostic()
-                                    .context("a dial manager task panicked")?
-                                    .context("a dial manager task failed")?;
-                                break;
-                            },
-                            None => break,
+            }
+            .await
+            .context("failed to spawn dial managers");
+            let spawned = match spawned {
+                Ok(()) => true,
+                // If we can't start at all, bail out; but once the daemon is
+                // running, a failed reload (e.g. the VU-Server being briefly
+                // unreachable, or a bad edit to the config file) shouldn't
+                // kill it. Wait for the next command and try again.
+                Err(error) if started => {
+                    tracing::error!(?error, "failed to reload dial managers, waiting for the next reload...");
+                    tasks.shutdown().await;
+                    false
+                }
+                Err(error) => return Err(error),
+            };
+            started = true;
+
+            tokio::select! {
+                command = command_rx.recv() => {
+                    match command {
+                        Some(ManagerCommand::Reload) => {
+                            tracing::info!("Received reload signal, reloading config...");
+                            if let Some(ref path) = key_file {
+                                // Changing the key changes it for every dial,
+                                // since they all share the client's key.
+                                match read_key_file(path) {
+                                    Ok(key) => {
+                                        tracing::info!("reloaded API key from {path}");
+                                        clients.default.set_key(key);
+                                    }
+                                    Err(error) => {
+                                        tracing::warn!(%error, "failed to reload API key, keeping the current key");
+                                    }
+                                }
+                            }
+                        }
+                        Some(ManagerCommand::NextProfile) => {
+                            match Config::load(&config_path).map(|config| config.next_profile(profile.as_deref()).map(ToOwned::to_owned)) {
+                                Ok(Some(next)) => {
+                                    tracing::info!("Switching to profile {next:?}...");
+                                    profile = Some(next);
+                                }
+                                Ok(None) => {
+                                    tracing::warn!("No profiles defined in config file, reloading config...");
+                                }
+                                Err(error) => {
+                                    tracing::warn!(?error, "failed to load config file, keeping the current profile");
+                                }
+                            }
+                        }
+                        Some(ManagerCommand::SwitchProfile(next)) => {
+                            tracing::info!("Switching to profile {next:?}...");
+                            profile = Some(next);
+                        }
+                        Some(ManagerCommand::Resetup) => {
+                            tracing::info!("Re-configuring all dials...");
+                            // Re-upload every dial's image, too.
+                            state.images().clear();
                         }
+                        None => break,
+                    }
+                    tasks.shutdown().await;
+                },
+                join = tasks.join_next(), if spawned => {
+                    match join {
+                        Some(error) => {
+                            error.into_diagnostic()
+                                .context("a dial manager task panicked")?
+                                .context("a dial manager task failed")?;
+                            break;
+                        },
+                        None => break,
                     }
                 }
             }
-            Ok::<(), miette::Error>(())
         }
+        Ok::<(), miette::Error>(())
     })
     .instrument(tracing::info_span!("dial-managers"));
 
     loop {
-        tokio::select! {
+        let command = tokio::select! {
             signal = signals.next_signal() => {
                 match signal {
-                    SignalAction::Reload => {
-                        config_reloaded.notify_one();
+                    SignalAction::Reload => ManagerCommand::Reload,
+                    SignalAction::UserDefined1 | SignalAction::UserDefined2 => {
+                        // Load the signal config when the signal is received,
+                        // so that changing it doesn't require a restart.
+                        let config = Config::load(&signals_config_path)
+                            .map(|config| config.signals)
+                            .unwrap_or_else(|error| {
+                                tracing::warn!(%error, "failed to load config, using default signal actions");
+                                config::SignalConfig::default()
+                            });
+                        let action = if signal == SignalAction::UserDefined1 {
+                            config.sigusr1
+                        } else {
+                            config.sigusr2
+                        };
+                        tracing::info!(?action, "performing signal action");
+                        match action {
+                            config::SignalAction::NextProfile => ManagerCommand::NextProfile,
+                            config::SignalAction::Resetup => ManagerCommand::Resetup,
+                            config::SignalAction::Identify => {
+                                task::spawn(identify_dials(daemon_state.clone()));
+                                continue;
+                            }
+                            config::SignalAction::Pause => {
+                                tracing::info!("pausing dial updates");
+                                running_tx.send_replace(false);
+                                continue;
+                            }
+                            config::SignalAction::Resume => {
+                                tracing::info!("resuming dial updates");
+                                running_tx.send_replace(true);
+                                continue;
+                            }
+                            config::SignalAction::TogglePause => {
+                                running_tx.send_modify(|running| {
+                                    *running = !*running;
+                                    if *running {
+                                        tracing::info!("resuming dial updates");
+                                    } else {
+                                        tracing::info!("pausing dial updates");
+                                    }
+                                });
+                                continue;
+                            }
+                            config::SignalAction::Ignore => continue,
+                        }
                     }
                     SignalAction::Shutdown => {
                         tracing::info!("Received shutdown signal, shutting down");
+                        // Stop the dial managers first, so that they don't
+                        // overwrite the shutdown values.
+                        dial_managers.inner().abort();
+                        let _ = (&mut dial_managers).await;
+                        shutdown_dials(&daemon_state).await;
                         break;
                     }
                 }
@@ -270,44 +725,290 @@ pub async fn run_daemon(
                     .context("dial manager task panicked")??;
                 break;
             }
+        };
 
-        }
+        // If the dial managers task has terminated, the next iteration of the
+        // loop will observe its result, so it's fine to ignore send errors.
+        let _ = commands.send(command).await;
     }
 
+    // Stop the control listener, so that it removes its socket.
+    control.abort();
+    let _ = control.await;
+
     Ok(())
 }
 
+/// Tunes a VU-Server client's connection pool for the daemon.
+///
+/// The daemon sends each dial a request every second or so, so its
+/// connections are rarely idle for long, and since the VU-Server handles
+/// requests one at a time, more than [`MAX_CONCURRENT_SETS`] connections
+/// don't help. On a flaky network, though, pooled connections may be dropped
+/// without either end noticing, and every dial's next request then fails at
+/// once. TCP keepalive notices dead connections before they're reused, and
+/// keeping only a few idle connections, for a short time, limits how many
+/// have to be reopened.
+///
+/// [`MAX_CONCURRENT_SETS`]: vu_api::client::MAX_CONCURRENT_SETS
+fn tune_client(builder: vu_api::client::Builder) -> vu_api::client::Builder {
+    const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+    const TCP_KEEPALIVE: Duration = Duration::from_secs(15);
+    builder
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .pool_max_idle_per_host(vu_api::client::MAX_CONCURRENT_SETS)
+        .tcp_keepalive(TCP_KEEPALIVE)
+}
+
+/// Clients for the VU-Servers that the daemon's dials are attached to.
+struct Clients {
+    /// The client for the VU-Server given on the command line.
+    default: Client,
+    /// The client settings given on the command line, which are also used
+    /// for any other VU-Servers configured in the config file.
+    args: crate::cli::ClientArgs,
+}
+
+// === impl Clients ===
+
+impl Clients {
+    /// Returns a client for `server`, or for the VU-Server given on the
+    /// command line if it's `None`.
+    fn get(&self, server: Option<&ServerConfig>) -> miette::Result<Client> {
+        let Some(ServerConfig { url, key }) = server else {
+            return Ok(self.default.clone());
+        };
+        Ok(self
+            .args
+            .clone()
+            .with_server(url.clone())
+            .with_key(key.clone())
+            .into_client_with(tune_client)
+            .with_context(|| format!("failed to build client for VU-Server at {url}"))?
+            .with_circuit_breaker(CircuitBreaker::default()))
+    }
+}
+
+/// Builds the client for the VU-Server given on the command line, reading the
+/// API key from `key_file` if there is one.
+fn build_client(
+    client_args: crate::cli::ClientArgs,
+    key_file: Option<&Utf8Path>,
+) -> miette::Result<Client> {
+    let client_args = match key_file {
+        Some(path) => client_args.with_key(read_key_file(path)?),
+        None => client_args,
+    };
+    Ok(client_args
+        .into_client_with(tune_client)
+        .context("failed to build client")?
+        .with_circuit_breaker(CircuitBreaker::default()))
+}
+
+/// Reads a VU-Server API key from `path`, ignoring surrounding whitespace.
+fn read_key_file(path: &Utf8Path) -> miette::Result<String> {
+    let key = std::fs::read_to_string(path)
+        .into_diagnostic()
+        .with_context(|| format!("failed to read API key file {path}"))?;
+    let key = key.trim();
+    if key.is_empty() {
+        miette::bail!("API key file {path} is empty");
+    }
+    Ok(key.to_owned())
+}
+
+/// Blinks the backlights of all managed dials, so that they can be identified,
+/// and then restores their previous backlight colors.
+///
+/// This is best-effort: failures are logged, not retried.
+async fn identify_dials(state: control::DaemonState) {
+    const BLINKS: usize = 3;
+    const BLINK_INTERVAL: Duration = Duration::from_millis(300);
+    let on = Backlight::WHITE;
+    let off = Backlight::OFF;
+
+    let mut tasks = task::JoinSet::new();
+    for (name, status) in state.dial_statuses() {
+        let Some(dial) = state.dial(&name) else {
+            continue;
+        };
+        tasks.spawn(async move {
+            tracing::debug!(dial = %name, "identifying dial");
+            for _ in 0..BLINKS {
+                for color in [on, off] {
+                    if let Err(error) = dial.set_backlight(color).await {
+                        tracing::warn!(dial = %name, %error, "failed to blink dial backlight");
+                        return;
+                    }
+                    tokio::time::sleep(BLINK_INTERVAL).await;
+                }
+            }
+            if let Some(backlight) = status.backlight {
+                if let Err(error) = dial.set_backlight(backlight).await {
+                    tracing::warn!(dial = %name, %error, "failed to restore dial backlight");
+                }
+            }
+        });
+    }
+    while tasks.join_next().await.is_some() {}
+}
+
+/// Applies the configured shutdown action to all managed dials.
+///
+/// This is best-effort: failures are logged, not retried, and if the dials
+/// can't all be updated before the configured timeout, we give up on them.
+async fn shutdown_dials(state: &control::DaemonState) {
+    let (shutdown, dials) = state.shutdown();
+    if !shutdown.has_action() || dials.is_empty() {
+        return;
+    }
+
+    tracing::info!(
+        ?shutdown,
+        "applying shutdown action to {} dials...",
+        dials.len()
+    );
+    let config::ShutdownConfig {
+        value,
+        backlight,
+        timeout,
+    } = shutdown;
+    let mut tasks = task::JoinSet::new();
+    for dial in dials {
+        tasks.spawn(async move {
+            if let Some(value) = value {
+                if let Err(error) = dial.set(value).await {
+                    tracing::warn!(%dial, %error, "failed to set dial value on shutdown");
+                }
+            }
+            if let Some(mode) = backlight {
+                if let Err(error) = dial.set_backlight(mode.color()).await {
+                    tracing::warn!(%dial, %error, "failed to set dial backlight on shutdown");
+                }
+            }
+        });
+    }
+
+    let all_done = async { while tasks.join_next().await.is_some() {} };
+    if tokio::time::timeout(timeout, all_done).await.is_err() {
+        tracing::warn!("timed out after {timeout:?} applying shutdown action to dials");
+    }
+}
+
 impl Config {
     async fn spawn_dial_managers(
         &self,
-        client: &Client,
+        profile: Option<&str>,
+        clients: &Clients,
         running: &watch::Receiver<bool>,
+        idle: &watch::Receiver<bool>,
+        state: &control::DaemonState,
         tasks: &mut task::JoinSet<miette::Result<()>>,
     ) -> miette::Result<()> {
-        let mut dials_by_index = HashMap::new();
-        let backoff = self.retries.backoff_builder();
-        let dials = retry(&backoff, "list dials", || client.list_dials()).await?;
-        for (dial, _) in dials {
-            let index = dial
-                .status()
+        let dial_configs = self.profile_dials(profile)?;
+        self.source_options().ensure_supported()?;
+        if self.mqtt.is_none() {
+            if let Some((name, _)) = dial_configs.iter().find(|(_, dial)| dial.host.is_some()) {
+                return Err(miette::miette!(
+                    help = "metrics from other hosts are received over MQTT; add an `[mqtt]` table",
+                    "dial {name:?} displays a metric from another host, but MQTT is not configured"
+                ));
+            }
+        }
+        let profile = profile.or(self.active_profile.as_deref());
+        if let Some(profile) = profile {
+            tracing::info!("using profile {profile:?}");
+        }
+        state.set_profile(profile.map(ToOwned::to_owned));
+        state.set_shutdown(self.shutdown.clone());
+        state.set_idle_config(self.idle.clone());
+        state.clear_dials();
+
+        // Build a separate client for each distinct VU-Server instance that
+        // dials are attached to, and look up the dials on each server.
+        let mut retry = self.retries.retry_policy();
+        if self.wait_for_server {
+            // The VU-Server may not have started yet (e.g. if we're racing it
+            // at boot), so keep retrying until it comes up.
+            tracing::info!("waiting for VU-Server to become reachable...");
+            retry = retry.max_elapsed_time(None);
+        }
+        let mut configured = Vec::new();
+        for config in dial_configs.values() {
+            if !configured.contains(&config.server.as_ref()) {
+                configured.push(config.server.as_ref());
+            }
+        }
+        // Each server is retried separately, so that one slow server doesn't
+        // hold up the others.
+        let lookups = configured.into_iter().map(|server| {
+            let retry = &retry;
+            async move {
+                let dials = async {
+                    let client = clients.get(server)?;
+                    dials_by_index(&client, retry).await
+                }
                 .await
-                .with_context(|| format!("failed to get status for {}", dial.id()))?
-                .index;
-            dials_by_index.insert(index, dial);
+                .with_context(|| match server {
+                    Some(ServerConfig { url, .. }) => {
+                        format!("failed to list dials on VU-Server at {url}")
+                    }
+                    None => "failed to list dials".to_owned(),
+                });
+                (server, dials)
+            }
+        });
+        let mut servers = HashMap::new();
+        let mut failed = None;
+        for (server, dials) in futures::future::join_all(lookups).await {
+            match dials {
+                Ok(dials) => {
+                    servers.insert(server, dials);
+                }
+                // If one VU-Server is unreachable, still manage the dials on
+                // the others. Its dials are picked up on the next reload.
+                Err(error) => {
+                    tracing::error!(?error, "skipping dials on unreachable VU-Server");
+                    failed.get_or_insert(error);
+                }
+            }
         }
-        if dials_by_index.len() < self.dials.len() {
+        if let (true, Some(error)) = (servers.is_empty(), failed) {
+            return Err(error);
+        }
+
+        if servers.values().map(HashMap::len).sum::<usize>() < dial_configs.len() {
             tracing::warn!("not enough dials for all dials in config file!");
         }
 
         let mut dials_spawned = 0;
-        for (name, config) in &self.dials {
-            if let Some(dial) = dials_by_index.remove(&config.index) {
+        let mut sampler = metrics::Sampler::new(state.remotes(), self.source_options());
+        let (scheduler, scheduler_task) = scheduler::new(self.scheduler.clone());
+        for (name, config) in dial_configs {
+            let dial = servers
+                .get_mut(&config.server.as_ref())
+                .and_then(|dials| dials.remove(&config.index));
+            if let Some(dial) = dial {
+                state.add_dial(name, &dial, config.index, config.metric.clone());
+                let backoff = self.retries.backoff_builder();
                 let dial_manager = DialManager {
                     name: name.clone(),
                     config: config.clone(),
+                    source: sampler.subscribe(
+                        &config.metric,
+                        config.host.as_deref(),
+                        config.update_interval,
+                        &backoff,
+                    ),
                     dial,
-                    backoff: self.retries.backoff_builder(),
+                    retry: self.retries.retry_policy(),
+                    unreachable: self.unreachable.clone(),
+                    errors: self.errors.clone(),
+                    alert: self.alert.clone(),
                     running: running.clone(),
+                    idle: idle.clone(),
+                    state: state.clone(),
+                    scheduler: scheduler.clone(),
                 };
                 tasks.spawn(dial_manager.run());
                 dials_spawned += 1;
@@ -319,11 +1020,30 @@ impl Config {
             }
         }
 
+        sampler.spawn(tasks);
+        tasks.spawn(scheduler_task.run());
         miette::ensure!(dials_spawned > 0, "no dials are connected!");
         Ok(())
     }
 }
 
+async fn dials_by_index(
+    client: &Client,
+    retry: &RetryPolicy,
+) -> miette::Result<HashMap<usize, Dial>> {
+    let mut dials_by_index = HashMap::new();
+    let dials = retry.retry("list dials", || client.list_dials()).await?;
+    for (dial, _) in dials {
+        let index = retry
+            .retry("get dial status", || dial.status())
+            .await
+            .with_context(|| format!("failed to get status for {}", dial.id()))?
+            .index;
+        dials_by_index.insert(index, dial);
+    }
+    Ok(dials_by_index)
+}
+
 impl DialManager {
     #[tracing::instrument(
         level = tracing::Level::INFO,
@@ -332,78 +1052,138 @@ impl DialManager {
         skip_all
         err(Display),
     )]
-    async fn run(self) -> miette::Result<()> {
-        const MAX_ERRORS: usize = 4;
+    async fn run(mut self) -> miette::Result<()> {
+        loop {
+            let error = match self.run_connected().await {
+                Ok(()) => return Ok(()),
+                Err(error) if error.downcast_ref::<ServerUnreachable>().is_some() => error,
+                Err(error) => {
+                    Alert::new(AlertKind::Failed, &self.name, &error)
+                        .send(&self.alert)
+                        .await;
+                    return Err(error);
+                }
+            };
 
-        use systemstat::Platform;
+            Alert::new(AlertKind::Unreachable, &self.name, &error)
+                .send(&self.alert)
+                .await;
+            if self.unreachable.action == UnreachableAction::Exit {
+                return Err(error);
+            }
+
+            tracing::error!(
+                %error,
+                action = ?self.unreachable.action,
+                "VU-Server is unreachable, waiting for it to come back...",
+            );
+            self.wait_for_server().await;
+        }
+    }
+
+    async fn run_connected(&mut self) -> miette::Result<()> {
         let DialManager {
-            dial,
-            name,
+            ref dial,
+            ref name,
             config:
                 DialConfig {
-                    metric,
+                    ref metric,
                     update_interval,
                     easing,
                     backlight,
+                    ref value_image,
                     ..
                 },
-            backoff,
-            mut running,
+            ref mut source,
+            ref retry,
+            ref mut running,
+            ref mut idle,
+            ref state,
+            ref scheduler,
+            ref errors,
             ..
-        } = self;
+        } = *self;
 
         tracing::info!("configuring dial...");
 
         tracing::info!("setting dial name...");
-        retry(&backoff, "set dial name", || dial.set_name(&name)).await?;
-
-        if let Some(config::Easing { period, step }) = easing {
-            tracing::info!(?period, %step, "setting dial easing...");
+        retry_dial(retry, "set dial name", || dial.set_name(name)).await?;
 
-            retry(&backoff, "set dial easing", || {
-                dial.set_dial_easing(period, step)
+        let dial_easing = easing.map(config::Easing::into_config);
+        let backlight_easing = backlight.easing.map(config::Easing::into_config);
+        if dial_easing.is_some() || backlight_easing.is_some() {
+            tracing::info!(?dial_easing, ?backlight_easing, "setting easing...");
+            retry_dial(retry, "set easing", || {
+                dial.set_easing(dial_easing, backlight_easing)
             })
             .await?;
         }
 
-        if let Some(config::Easing { period, step }) = backlight.easing {
-            tracing::info!(?period, %step, "setting backlight easing...");
-            retry(&backoff, "set backlight easing", || {
-                dial.set_backlight_easing(period, step)
+        let mut value_image = value_image.clone().map(|config| {
+            render::ValueImage::new(config, source.image(), source.unit(), dial, state.images())
+        });
+
+        // N.B. that we apparently need to reset the backlight every time we
+        // reconnect to the VU-Server, because it apparently doesn't persist
+        // backlight state when restarted. IDK why. So, the backlight and image
+        // are set both now and whenever updates are resumed after a pause.
+        //
+        // If the dial's value is drawn onto its image, the plain background
+        // image isn't uploaded, to avoid writing the dial's flash twice.
+        let backlight_color = backlight.mode.color();
+        let image = match value_image {
+            Some(_) => None,
+            None => source.image(),
+        };
+        let images = state.images();
+        let images = &images;
+        let set_appearance = || async move {
+            tracing::info!(?backlight, "setting dial backlight...");
+            retry_dial(retry, "set dial backlight", || {
+                scheduler.set_backlight(dial, backlight_color)
             })
             .await?;
-        }
+            state.record_backlight(name, backlight_color);
 
-        let backlight_color = match backlight.mode {
-            config::BacklightMode::Static(color) => color,
-            config::BacklightMode::Off => {
-                Backlight::new(0, 0, 0).expect("0,0,0 must be a valid backlight color")
+            if let Some(img) = image {
+                if images.is_current(dial, img.name, img.image) {
+                    tracing::debug!("dial already has image {}", img.name);
+                } else {
+                    retry_dial(retry, "set dial image", || {
+                        tracing::info!("setting image for {} to {}", dial.id(), img.name);
+                        dial.set_image(img.name, img.image, false)
+                    })
+                    .await?;
+                    images.record(dial, img.name, img.image);
+                }
             }
+
+            miette::Result::<()>::Ok(())
         };
+        set_appearance().await?;
 
-        tracing::info!(?backlight, "setting dial backlight...");
-        retry(&backoff, "set dial backlight", || {
-            dial.set_backlight(backlight_color)
-        })
-        .await?;
-
-        if let Some(img) = metric.img_file() {
-            retry(&backoff, "set dial image", || {
-                use reqwest::multipart::Part;
-                let part = Part::bytes(img.image);
-                tracing::info!("setting image for {} to {}", dial.id(), img.name);
-                dial.set_image(img.name, part, false)
-            })
-            .await?;
+        if !source.is_supported() {
+            // There's nothing to update, but don't return an error, since that
+            // would shut down every other dial manager, too.
+            tracing::warn!(
+                "{metric:?} is not supported on this platform, so this dial will not be updated"
+            );
+            return futures::future::pending().await;
         }
 
         tracing::info!("updating dial with {metric:?} every {update_interval:?}");
         let mut interval = tokio::time::interval(update_interval);
-        let mut systemstat_errs =
-            MultiError::with_max_errors("reading metric data failed 4 times in a row", MAX_ERRORS);
-        let systemstat = systemstat::System::new();
+        // If updates were paused, don't try to catch up on all the missed
+        // updates when resuming.
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // Errors reading the metric and errors updating the dial both count
+        // towards the same budget. If the VU-Server is unreachable, though,
+        // that's handled separately.
+        let mut errs = errors.tracker("updating the dial");
 
         loop {
+            interval.tick().await;
+
             if !(*running.borrow()) {
                 tracing::info!("dial updates paused, waiting to restart...");
                 while !(*running.borrow_and_update()) {
@@ -415,167 +1195,141 @@ impl DialManager {
                         .context("watch channel closed")?;
                 }
 
-                // N.B. that we apparently need to reset the backlight every
-                // time we reconnect to the VU-Server, because it apparently
-                // doesn't persist backlight state when restarted. IDK why.
-                let backlight = Backlight::new(50, 50, 50)?;
-                tracing::info!(?backlight, "setting dial backlight...");
-                retry(&backoff, "set dial backlight", || {
-                    dial.set_backlight(backlight)
-                })
-                .await?;
+                tracing::info!("dial updates resumed");
+                set_appearance().await?;
+                if let Some(ref mut value_image) = value_image {
+                    value_image.reset();
+                }
             }
 
-            let value = match metric {
-                Metric::CpuLoad => {
-                    let load = match systemstat.cpu_load_aggregate().into_diagnostic() {
-                        Ok(load) => load,
-                        Err(error) => {
-                            tracing::warn!(%error, "failed to start load aggregate measurement");
-                            systemstat_errs.push_error(error)?;
-                            continue;
-                        }
-                    };
-                    interval.tick().await;
-
-                    match load.done().into_diagnostic() {
-                        Ok(load) => {
-                            let percent =
-                                (load.user + load.system + load.interrupt + load.nice) * 100.0;
-                            tracing::debug!("CPU Load: {percent}%");
-                            Percent::new(percent as u8)?
-                        }
-                        Err(error) => {
-                            tracing::warn!(%error, "failed to read load aggregate");
-                            systemstat_errs.push_error(error)?;
-                            continue;
-                        }
-                    }
+            if *idle.borrow() {
+                // The idle coordinator is animating the dials, so stay out of
+                // its way until the system is active again.
+                tracing::debug!("system is idle, waiting for activity...");
+                while *idle.borrow_and_update() {
+                    idle.changed()
+                        .await
+                        .into_diagnostic()
+                        .context("watch channel closed")?;
                 }
-                Metric::Mem => {
-                    let mem = systemstat.memory().into_diagnostic();
-                    // tracing::info!("Memory: {mem:?}");
-                    match mem {
-                        Ok(systemstat::Memory { total, free, .. }) => {
-                            let percent_free = free.0 / (total.0 / 100);
-                            let percent_used = 100 - percent_free;
-                            tracing::debug!("Memory: {percent_used}% used");
-                            Percent::new(percent_used as u8)?
-                        }
-                        Err(error) => {
-                            tracing::warn!(%error, "failed to read memory usage");
-                            systemstat_errs.push_error(error)?;
-                            continue;
-                        }
-                    }
+                continue;
+            }
+
+            let value = match source.sample().await {
+                Ok(value) => value,
+                Err(error) if error.downcast_ref::<metrics::Unavailable>().is_some() => {
+                    tracing::debug!(%error, "{metric:?} is unavailable, skipping update");
+                    continue;
                 }
-                Metric::Swap => {
-                    let swap = systemstat.swap().into_diagnostic();
-                    match swap {
-                        Ok(systemstat::Swap { total, free, .. }) => {
-                            let percent_free = free.0 / (total.0 / 100);
-                            let percent_used = 100 - percent_free;
-                            tracing::debug!("Swap: {percent_used}% used");
-                            Percent::new(percent_used as u8)?
-                        }
-                        Err(error) => {
-                            tracing::warn!(%error, "failed to read swap usage");
-                            systemstat_errs.push_error(error)?;
-                            continue;
-                        }
-                    }
+                Err(error) => {
+                    tracing::warn!(%error, "failed to sample {metric:?}");
+                    errs.push_error(error)?;
+                    continue;
                 }
-                Metric::CpuTemp => {
-                    let temp = systemstat.cpu_temp().into_diagnostic();
-                    match temp {
-                        Ok(temp) => {
-                            tracing::debug!("CPU temp: {temp}°C");
-                            Percent::new(temp as u8)?
-                        }
-                        Err(error) => {
-                            tracing::warn!(%error, "failed to read CPU temp");
-                            continue;
-                        }
-                    }
+            };
+            let value = match state.manual_value(name) {
+                Some(manual) => {
+                    tracing::debug!(%manual, "displaying manually-set value");
+                    manual
                 }
-                Metric::Battery => {
-                    let battery = systemstat.battery_life().into_diagnostic();
-                    match battery {
-                        Ok(battery) => {
-                            let remaining = battery.remaining_capacity * 100.0;
-                            tracing::debug!("Battery: {remaining}% remaining");
-                            Percent::new(remaining as u8)?
-                        }
-                        Err(error) => {
-                            tracing::warn!(%error, "failed to read battery status");
-                            systemstat_errs.push_error(error)?;
-                            continue;
-                        }
+                None => value,
+            };
+            match retry_dial(retry, "set value", || scheduler.set(dial, value)).await {
+                Ok(()) => {
+                    state.record_value(name, value);
+                    errs.success();
+                    if let Some(ref mut value_image) = value_image {
+                        value_image.update(dial, value).await;
                     }
                 }
-                Metric::DiskUsage => {
-                    let mounts = systemstat.mounts().into_diagnostic();
-                    let filesystems = match mounts {
-                        Ok(mounts) => mounts,
-                        Err(error) => {
-                            tracing::warn!(%error, "failed to read mounts");
-                            systemstat_errs.push_error(error)?;
-                            continue;
-                        }
-                    };
-                    let (total, free) = filesystems.iter().fold((0, 0), |(total, free), fs| {
-                        let total = total + fs.total.as_u64();
-                        let free = free + fs.free.as_u64();
-                        tracing::trace!(
-                            "filesystem {} has {} bytes free, {} bytes total",
-                            fs.fs_mounted_on,
-                            fs.free,
-                            fs.total
-                        );
-                        (total, free)
-                    });
-
-                    let percent_free = free / (total / 100);
-                    let percent_used = 100 - percent_free;
-                    tracing::debug!("Disk: {percent_used}% used");
-                    Percent::new(percent_used as u8)?
+                Err(error) if error.downcast_ref::<ServerUnreachable>().is_some() => {
+                    return Err(error)
+                }
+                Err(error) => {
+                    let error =
+                        error.wrap_err(format!("failed to set value for {name} to {value}"));
+                    tracing::warn!(%error, "failed to update dial");
+                    errs.push_error(error)?;
                 }
-            };
-            retry(&backoff, "set value", || dial.set(value))
-                .await
-                .with_context(|| format!("failed to set value for {name} to {value}"))?;
-            systemstat_errs.clear();
-            if metric != Metric::CpuLoad {
-                interval.tick().await;
             }
         }
     }
+
+    /// Waits for the VU-Server to become reachable again, applying the
+    /// configured [`UnreachableAction`] in the meantime.
+    async fn wait_for_server(&self) {
+        const BLINK_INTERVAL: Duration = Duration::from_secs(1);
+        let off = Backlight::OFF;
+
+        let UnreachableConfig {
+            action,
+            probe_interval,
+        } = self.unreachable;
+        if action == UnreachableAction::Zero {
+            // This is best-effort: the server is probably unreachable, so
+            // don't bother retrying.
+            let zero = Percent::MIN;
+            if let Err(error) = self.dial.set(zero).await {
+                tracing::debug!(%error, "failed to set dial to zero");
+            }
+            if let Err(error) = self.dial.set_backlight(off).await {
+                tracing::debug!(%error, "failed to turn off dial backlight");
+            }
+        }
+
+        let color = self.config.backlight.mode.color();
+        let mut blink = tokio::time::interval(BLINK_INTERVAL);
+        let mut probe = tokio::time::interval(probe_interval);
+        let mut lit = false;
+        loop {
+            tokio::select! {
+                _ = blink.tick(), if action == UnreachableAction::Blink => {
+                    lit = !lit;
+                    let color = if lit { color } else { off };
+                    if let Err(error) = self.dial.set_backlight(color).await {
+                        tracing::trace!(%error, "failed to blink dial backlight");
+                    }
+                }
+                _ = probe.tick() => match self.dial.status().await {
+                    Ok(_) => {
+                        tracing::info!("VU-Server is reachable again, resuming updates...");
+                        return;
+                    }
+                    Err(error) => tracing::debug!(%error, "VU-Server is still unreachable"),
+                },
+            }
+        }
+    }
+}
+
+/// Error returned by a dial manager when the VU-Server could not be reached
+/// after exhausting the retry budget.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+#[error("VU-Server is unreachable: failed to {action}")]
+#[diagnostic(code(vupdated::ServerUnreachable))]
+struct ServerUnreachable {
+    action: &'static str,
+    #[source]
+    #[diagnostic_source]
+    error: vu_api::client::Error,
 }
 
-async fn retry<F, T>(
-    backoff: &backoff::ExponentialBackoffBuilder,
-    name: &'static str,
+/// Like [`RetryPolicy::retry`], but if a transient error persists after the
+/// retry budget is exhausted, or the client's circuit breaker is open, a
+/// [`ServerUnreachable`] error is returned.
+async fn retry_dial<F, T>(
+    retry: &RetryPolicy,
+    action: &'static str,
     f: impl Fn() -> F,
-) -> Result<T, vu_api::client::Error>
+) -> miette::Result<T>
 where
     F: std::future::Future<Output = Result<T, vu_api::client::Error>>,
 {
-    backoff::future::retry_notify(
-        backoff.build(),
-        || f().map_err(backoff_error),
-        |error, retry_after| {
-            tracing::warn!(%error, ?retry_after, "failed to {name}, retrying...");
-        },
-    )
-    .await
-}
-
-fn backoff_error(error: vu_api::client::Error) -> backoff::Error<vu_api::client::Error> {
-    use vu_api::client::Error;
-    match error {
-        error @ Error::BuildUrl(_) | error @ Error::BuildRequest(_) => {
-            backoff::Error::permanent(error)
+    retry.retry(action, f).await.map_err(|error| {
+        if error.is_transient() || error.is_unreachable() {
+            ServerUnreachable { action, error }.into()
+        } else {
+            miette::Report::from(error).wrap_err(format!("failed to {action}"))
         }
-        error => backoff::Error::transient(error),
-    }
+    })
 }
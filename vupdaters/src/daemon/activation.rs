@@ -0,0 +1,133 @@
+//! systemd socket activation.
+//!
+//! When `vupdated` is started by a systemd socket unit, systemd binds the
+//! daemon's sockets itself, and passes them to `vupdated` as file descriptors
+//! starting at 3, described by the `LISTEN_PID`, `LISTEN_FDS`, and
+//! `LISTEN_FDNAMES` environment variables. See `man sd_listen_fds` for
+//! details.
+//!
+//! Each listener in the daemon asks for its socket by name, using the socket
+//! unit's `FileDescriptorName=`, and binds its own socket if systemd didn't
+//! pass one. On Windows, there are never any sockets to take.
+#[cfg(unix)]
+use miette::{Context, IntoDiagnostic};
+#[cfg(unix)]
+use std::{
+    collections::HashMap,
+    os::fd::{FromRawFd, OwnedFd, RawFd},
+};
+
+/// The first file descriptor passed by systemd.
+#[cfg(unix)]
+const LISTEN_FDS_START: RawFd = 3;
+
+/// The sockets passed to `vupdated` by systemd.
+#[derive(Debug, Default)]
+pub(super) struct Sockets {
+    #[cfg(unix)]
+    fds: HashMap<String, OwnedFd>,
+}
+
+// === impl Sockets ===
+
+impl Sockets {
+    /// Takes the sockets passed by systemd, if there are any.
+    ///
+    /// This also removes the socket activation environment variables, so that
+    /// they aren't inherited by child processes. Since modifying the
+    /// environment isn't thread-safe, this must be called before the Tokio
+    /// runtime (or any other thread) is started.
+    pub(super) fn from_env() -> Self {
+        Self {
+            #[cfg(unix)]
+            fds: listen_fds(),
+        }
+    }
+
+    /// Takes the Unix socket listener named `name` that was passed by
+    /// systemd, if there is one.
+    ///
+    /// If systemd passed a single socket without naming it, that socket is
+    /// used for any name.
+    #[cfg(unix)]
+    pub(super) fn take_listener(
+        &mut self,
+        name: &str,
+    ) -> miette::Result<Option<tokio::net::UnixListener>> {
+        let fds = &mut self.fds;
+        let fd = match fds.remove(name) {
+            Some(fd) => fd,
+            None if fds.len() == 1 && !fds.keys().any(|name| is_explicit_name(name)) => {
+                let name = fds.keys().next().cloned().expect("there's one fd");
+                fds.remove(&name).expect("we just found this fd")
+            }
+            None => return Ok(None),
+        };
+
+        // Duplicate the descriptor, so that the copy we keep is close-on-exec,
+        // and isn't leaked to plugins and alert commands.
+        let fd = fd
+            .try_clone()
+            .into_diagnostic()
+            .with_context(|| format!("failed to duplicate socket {name:?} passed by systemd"))?;
+        let listener = std::os::unix::net::UnixListener::from(fd);
+        // This fails if the socket isn't a Unix domain socket.
+        let addr = listener
+            .local_addr()
+            .into_diagnostic()
+            .with_context(|| format!("socket {name:?} passed by systemd isn't a Unix socket"))?;
+        listener
+            .set_nonblocking(true)
+            .into_diagnostic()
+            .context("failed to make socket non-blocking")?;
+        tracing::debug!(?addr, "using socket {name:?} passed by systemd");
+        tokio::net::UnixListener::from_std(listener)
+            .into_diagnostic()
+            .context("failed to register socket with the runtime")
+            .map(Some)
+    }
+}
+
+/// Returns the file descriptors passed by systemd, by name, and removes the
+/// socket activation environment variables.
+#[cfg(unix)]
+fn listen_fds() -> HashMap<String, OwnedFd> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let count = std::env::var("LISTEN_FDS").ok();
+    let names = std::env::var("LISTEN_FDNAMES").ok();
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+
+    // The variables are meant for the process systemd started; if they were
+    // inherited from a parent, the file descriptors aren't ours.
+    if pid.and_then(|pid| pid.parse::<u32>().ok()) != Some(std::process::id()) {
+        return HashMap::new();
+    }
+    let Some(count) = count.and_then(|count| count.parse::<RawFd>().ok()) else {
+        return HashMap::new();
+    };
+    let mut names = names
+        .as_deref()
+        .unwrap_or_default()
+        .split(':')
+        .map(ToOwned::to_owned);
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            let name = names
+                .next()
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| "unknown".to_owned());
+            // Safety: systemd passed us these file descriptors, and nothing
+            // else in `vupdated` uses them.
+            (name, unsafe { OwnedFd::from_raw_fd(fd) })
+        })
+        .collect()
+}
+
+/// Returns `false` for the names systemd gives sockets when the socket unit
+/// doesn't set `FileDescriptorName=`.
+#[cfg(unix)]
+fn is_explicit_name(name: &str) -> bool {
+    name != "unknown" && !name.ends_with(".socket")
+}
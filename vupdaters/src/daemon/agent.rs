@@ -0,0 +1,240 @@
+//! Metrics from other hosts.
+//!
+//! `vupdated agent` runs on machines that don't have dials attached. It
+//! samples the metrics listed in the config file's `[agent]` table, and
+//! publishes them to an MQTT broker. A `vupdated` that owns the dials can then
+//! display those metrics by setting `host` in a dial's config to the agent's
+//! host name: the daemon's [MQTT connection](super::mqtt) receives the samples
+//! and hands them to the [`Remotes`] registry, which the dials displaying them
+//! subscribe to.
+//!
+//! Each sample is published as a JSON message to
+//! `<topic-prefix>/agents/<host>/metrics/<metric>`, where `<metric>` is the
+//! metric's [key](super::Metric::key). Agents also publish `online` or
+//! `offline` to `<topic-prefix>/agents/<host>/status`, using the MQTT last
+//! will to report when they disconnect unexpectedly.
+use super::{
+    config::{AgentConfig, MqttConfig, RetryConfig},
+    metrics::{MetricSource, Sample, SourceOptions, Unavailable},
+    mqtt::RECONNECT_DELAY,
+    signal::{SignalAction, SignalListener},
+};
+use miette::{Context, IntoDiagnostic};
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Outgoing, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::watch, task::JoinSet};
+use vu_api::dial::Percent;
+
+/// The most recent samples received from agents on other hosts, by host and
+/// metric key.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Remotes(Arc<Mutex<RemoteSamples>>);
+
+type RemoteSamples = HashMap<(String, String), watch::Sender<Option<Sample>>>;
+
+/// A message published by an agent for each sample.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Report {
+    Sample { value: Percent },
+    Error { message: String },
+}
+
+#[tracing::instrument(
+    level = tracing::Level::INFO,
+    name = "agent",
+    fields(host = %agent.host),
+    skip_all,
+    err(Display),
+)]
+pub(super) async fn run(
+    agent: AgentConfig,
+    sources: SourceOptions,
+    mqtt: MqttConfig,
+    retries: RetryConfig,
+    mut signals: SignalListener,
+) -> miette::Result<()> {
+    let prefix = format!(
+        "{}/agents/{}",
+        mqtt.topic_prefix.trim_end_matches('/'),
+        agent.host
+    );
+    let status = format!("{prefix}/status");
+
+    // The daemon that owns the dials may be connected to the same broker with
+    // the configured client ID, so make sure the agent's is different.
+    let client_id = format!("{}-agent-{}", mqtt.client_id, agent.host);
+    let mut options = MqttOptions::new(client_id, &mqtt.host, mqtt.port);
+    options
+        .set_keep_alive(Duration::from_secs(30))
+        .set_last_will(LastWill::new(&status, "offline", QoS::AtLeastOnce, true));
+    if let Some(ref username) = mqtt.username {
+        options.set_credentials(username, mqtt.password.as_deref().unwrap_or_default());
+    }
+    let (client, mut eventloop) = AsyncClient::new(options, 64);
+
+    let backoff = retries.backoff_builder();
+    let mut tasks = JoinSet::new();
+    for metric in &agent.metrics {
+        let source = metric.source(sources, &backoff);
+        if !source.is_supported() {
+            tracing::warn!(
+                "{metric:?} is not supported on this platform, so it will not be published"
+            );
+            continue;
+        }
+        let topic = format!("{prefix}/metrics/{}", metric.key());
+        tracing::info!(%topic, "publishing {metric:?} every {:?}", agent.interval);
+        tasks.spawn(publish(client.clone(), topic, source, agent.interval));
+    }
+    if tasks.is_empty() {
+        miette::bail!("none of the agent's metrics can be published on this platform");
+    }
+
+    loop {
+        tokio::select! {
+            event = eventloop.poll() => match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    tracing::info!(broker = %format_args!("{}:{}", mqtt.host, mqtt.port), "connected to MQTT broker");
+                    // Waiting for room in the request queue here would
+                    // deadlock, since the event loop isn't being polled.
+                    if let Err(error) = client.try_publish(&status, QoS::AtLeastOnce, true, "online") {
+                        tracing::warn!(%error, "failed to publish to {status}");
+                    }
+                }
+                Ok(event) => tracing::trace!(?event),
+                Err(error) => {
+                    // The event loop reconnects the next time it's polled.
+                    tracing::warn!(%error, "MQTT connection failed, reconnecting in {RECONNECT_DELAY:?}...");
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            },
+            Some(result) = tasks.join_next() => {
+                result.into_diagnostic().context("a metric publishing task panicked")??;
+            },
+            signal = signals.next_signal() => {
+                if signal != SignalAction::Shutdown {
+                    tracing::debug!(?signal, "ignoring signal");
+                    continue;
+                }
+                tracing::info!("Received shutdown signal, shutting down");
+                tasks.shutdown().await;
+                // The last will is only published if the agent disconnects
+                // unexpectedly, so say goodbye explicitly.
+                let _ = client.try_publish(&status, QoS::AtLeastOnce, true, "offline");
+                let _ = client.try_disconnect();
+                let flush = async {
+                    while let Ok(event) = eventloop.poll().await {
+                        if let Event::Outgoing(Outgoing::Disconnect) = event {
+                            break;
+                        }
+                    }
+                };
+                let _ = tokio::time::timeout(Duration::from_secs(2), flush).await;
+                return Ok(());
+            },
+        }
+    }
+}
+
+/// Samples `source` every `interval`, publishing each sample to `topic`.
+async fn publish(
+    client: AsyncClient,
+    topic: String,
+    mut source: Box<dyn MetricSource>,
+    interval: Duration,
+) -> miette::Result<()> {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let report = match source.sample().await {
+            Ok(value) => Report::Sample { value },
+            Err(error) => {
+                tracing::debug!(%error, %topic, "failed to sample metric");
+                Report::Error {
+                    message: error.to_string(),
+                }
+            }
+        };
+        let payload = serde_json::to_vec(&report).into_diagnostic()?;
+        // Stale samples aren't worth displaying, so they're neither retained
+        // nor queued up while the broker is unreachable.
+        if let Err(error) = client.try_publish(&topic, QoS::AtMostOnce, false, payload) {
+            tracing::debug!(%error, %topic, "dropping sample");
+        }
+    }
+}
+
+// === impl Remotes ===
+
+impl Remotes {
+    fn lock(&self) -> std::sync::MutexGuard<'_, RemoteSamples> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns a receiver for samples of `metric` (a [metric
+    /// key](super::Metric::key)) published by the agent on `host`.
+    pub(super) fn subscribe(&self, host: &str, metric: &str) -> watch::Receiver<Option<Sample>> {
+        self.lock()
+            .entry((host.to_owned(), metric.to_owned()))
+            .or_insert_with(|| watch::channel(None).0)
+            .subscribe()
+    }
+
+    /// Handles a message published to an agent topic, relative to
+    /// `<topic-prefix>/agents/`.
+    pub(super) fn handle(&self, topic: &str, payload: &[u8]) {
+        let Some((host, topic)) = topic.split_once('/') else {
+            tracing::debug!(%topic, "ignoring message on unknown agent topic");
+            return;
+        };
+
+        if topic == "status" {
+            match payload {
+                b"online" => tracing::info!(%host, "agent is online"),
+                b"offline" => {
+                    tracing::warn!(%host, "agent is offline");
+                    // Rather than displaying the last values the agent sent
+                    // forever, treat its metrics as unavailable until it's
+                    // back. That's not the dials' fault, so it doesn't count
+                    // towards their error budgets.
+                    let error = Unavailable(format!("the agent on {host} is offline"));
+                    let error = Arc::new(miette::Report::from(error));
+                    for ((agent, _), samples) in self.lock().iter() {
+                        if agent == host {
+                            samples.send_replace(Some(Err(error.clone())));
+                        }
+                    }
+                }
+                _ => tracing::debug!(%host, "ignoring unknown agent status"),
+            }
+            return;
+        }
+
+        let Some(metric) = topic.strip_prefix("metrics/") else {
+            tracing::debug!(%host, %topic, "ignoring message on unknown agent topic");
+            return;
+        };
+        let sample = match serde_json::from_slice::<Report>(payload) {
+            Ok(Report::Sample { value }) => Ok(value),
+            Ok(Report::Error { message }) => Err(Arc::new(miette::miette!("{host}: {message}"))),
+            Err(error) => {
+                tracing::warn!(%host, %metric, %error, "received invalid sample from agent");
+                return;
+            }
+        };
+        tracing::trace!(%host, %metric, ?sample, "received sample from agent");
+        self.lock()
+            .entry((host.to_owned(), metric.to_owned()))
+            .or_insert_with(|| watch::channel(None).0)
+            .send_replace(Some(sample));
+    }
+}
@@ -0,0 +1,107 @@
+//! Alerts on persistent failures.
+//!
+//! When a dial manager gives up after exhausting its error budget, or the
+//! VU-Server it updates stays unreachable, the daemon runs the configured
+//! alert command and/or posts to the configured webhook, so that the user can
+//! find out that their dials have gone stale.
+use super::config::AlertConfig;
+use serde::Serialize;
+use std::fmt;
+
+/// An alert about a dial that has stopped updating.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct Alert<'a> {
+    pub(super) kind: AlertKind,
+    /// The name of the dial.
+    pub(super) dial: &'a str,
+    /// A summary of the error that caused the alert.
+    pub(super) error: String,
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum AlertKind {
+    /// The dial manager gave up after exhausting its error budget.
+    Failed,
+    /// The VU-Server is still unreachable after exhausting the retry budget.
+    Unreachable,
+}
+
+// === impl Alert ===
+
+impl<'a> Alert<'a> {
+    pub(super) fn new(kind: AlertKind, dial: &'a str, error: &miette::Report) -> Self {
+        let error = error
+            .chain()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(": ");
+        Self { kind, dial, error }
+    }
+
+    /// Sends the alert using the configured command and webhook.
+    ///
+    /// This is best-effort: if the command or webhook fail, or don't finish
+    /// within the configured timeout, the failure is logged and otherwise
+    /// ignored.
+    pub(super) async fn send(&self, config: &AlertConfig) {
+        if !config.is_enabled() {
+            return;
+        }
+
+        tracing::info!(kind = %self.kind, dial = %self.dial, "sending alert");
+        let send = async {
+            futures::join!(self.run_command(config), self.post_webhook(config));
+        };
+        if tokio::time::timeout(config.timeout, send).await.is_err() {
+            tracing::warn!("timed out after {:?} sending alert", config.timeout);
+        }
+    }
+
+    async fn run_command(&self, config: &AlertConfig) {
+        let Some((program, args)) = config.command.split_first() else {
+            return;
+        };
+        let status = tokio::process::Command::new(program)
+            .args(args)
+            .env("VUPDATED_ALERT", self.kind.to_string())
+            .env("VUPDATED_DIAL", self.dial)
+            .env("VUPDATED_ERROR", &self.error)
+            .kill_on_drop(true)
+            .status()
+            .await;
+        match status {
+            Ok(status) if status.success() => tracing::debug!("alert command succeeded"),
+            Ok(status) => tracing::warn!("alert command `{program}` failed ({status})"),
+            Err(error) => tracing::warn!(%error, "failed to run alert command `{program}`"),
+        }
+    }
+
+    async fn post_webhook(&self, config: &AlertConfig) {
+        let Some(ref url) = config.webhook else {
+            return;
+        };
+        let rsp = reqwest::Client::new()
+            .post(url.clone())
+            .json(self)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        match rsp {
+            Ok(_) => tracing::debug!(%url, "alert webhook succeeded"),
+            Err(error) => tracing::warn!(%url, %error, "alert webhook failed"),
+        }
+    }
+}
+
+// === impl AlertKind ===
+
+impl fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed => f.write_str("failed"),
+            Self::Unreachable => f.write_str("unreachable"),
+        }
+    }
+}
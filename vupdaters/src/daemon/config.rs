@@ -1,16 +1,89 @@
-use super::Metric;
+use super::{metrics::SourceOptions, Metric};
+use crate::MultiError;
 use camino::{Utf8Path, Utf8PathBuf};
 use miette::{Context, IntoDiagnostic};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, time::Duration};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    str::FromStr,
+    time::Duration,
+};
 use vu_api::dial::{Backlight, Percent};
 
+mod legacy;
+pub(super) use self::legacy::LegacyConfig;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
 pub struct Config {
+    /// The name of the profile to use when no profile is selected on the
+    /// command line.
+    ///
+    /// If this is not set, the top-level `dials` table is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) active_profile: Option<String>,
+
+    #[serde(default)]
     pub(super) dials: HashMap<String, DialConfig>,
 
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub(super) profiles: HashMap<String, Profile>,
+
     #[serde(default)]
     pub(super) retries: RetryConfig,
+
+    /// If `true`, wait indefinitely for the VU-Server to become reachable when
+    /// starting up, rather than giving up after the `retries.max-elapsed-time`.
+    #[serde(default)]
+    pub(super) wait_for_server: bool,
+
+    #[serde(default)]
+    pub(super) unreachable: UnreachableConfig,
+
+    #[serde(default)]
+    pub(super) errors: ErrorBudget,
+
+    #[serde(default)]
+    pub(super) alert: AlertConfig,
+
+    #[serde(default)]
+    pub(super) signals: SignalConfig,
+
+    #[serde(default)]
+    pub(super) scheduler: SchedulerConfig,
+
+    #[serde(default)]
+    pub(super) shutdown: ShutdownConfig,
+
+    #[serde(default)]
+    pub(super) idle: IdleConfig,
+
+    /// Which library to read system metrics with.
+    #[serde(default)]
+    pub(super) metric_backend: MetricBackend,
+
+    /// Whether to read CPU load and memory usage for the whole host, or for
+    /// the container `vupdated` is running in.
+    #[serde(default)]
+    pub(super) metric_scope: MetricScope,
+
+    /// If set, publish dial state to an MQTT broker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) mqtt: Option<MqttConfig>,
+
+    /// Configures `vupdated agent`, which publishes metrics sampled on this
+    /// machine for a `vupdated` on another machine to display.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) agent: Option<AgentConfig>,
+}
+
+/// A named set of dial assignments, which may be switched between at runtime.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Profile {
+    pub(super) dials: HashMap<String, DialConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +108,261 @@ pub struct RetryConfig {
     max_elapsed_time: Option<Duration>,
 }
 
+/// Configures what happens to dials when the VU-Server is unreachable after
+/// the retry budget is exhausted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct UnreachableConfig {
+    /// What to do with the dials while the VU-Server is unreachable.
+    #[serde(default)]
+    pub(super) action: UnreachableAction,
+
+    /// How often to check whether the VU-Server is reachable again.
+    #[serde(
+        with = "nonzero_duration",
+        default = "UnreachableConfig::default_probe_interval"
+    )]
+    pub(super) probe_interval: Duration,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnreachableAction {
+    /// Leave the dials displaying their last value until the VU-Server comes
+    /// back.
+    #[default]
+    Keep,
+    /// Drive the dials to zero and turn off their backlights, if possible.
+    Zero,
+    /// Blink the dials' backlights until the VU-Server comes back.
+    Blink,
+    /// Exit the daemon.
+    Exit,
+}
+
+/// The library used to read system metrics.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetricBackend {
+    /// Use `systemstat`, or performance counters on Windows.
+    #[default]
+    Native,
+    /// Use the `sysinfo` crate.
+    ///
+    /// This requires `vupdated` to be built with the `sysinfo` feature.
+    Sysinfo,
+}
+
+/// Whether metrics are read for the whole host, or for the container that
+/// `vupdated` is running in.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetricScope {
+    /// Read container metrics if `vupdated` appears to be running in a
+    /// container, and host metrics otherwise.
+    #[default]
+    Auto,
+    /// Read CPU load and memory usage from the cgroup `vupdated` is running
+    /// in, relative to the cgroup's limits.
+    Container,
+    /// Read metrics for the whole host.
+    Host,
+}
+
+/// Configures how many errors a dial manager tolerates before giving up.
+///
+/// Both failures to read a dial's metric and failures to update the dial count
+/// towards the error budget.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ErrorBudget {
+    /// The number of errors after which the dial manager gives up.
+    #[serde(default = "ErrorBudget::default_max_errors")]
+    pub(super) max_errors: usize,
+
+    /// If set, give up after `max-errors` errors within this window, even if
+    /// some operations succeeded in between. Otherwise, only consecutive
+    /// errors count.
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(super) window: Option<Duration>,
+}
+
+/// Configures alerts sent when a dial stops updating.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AlertConfig {
+    /// A command to run when an alert fires, as a program followed by its
+    /// arguments.
+    ///
+    /// Details about the alert are passed to the command in the
+    /// `VUPDATED_ALERT`, `VUPDATED_DIAL`, and `VUPDATED_ERROR` environment
+    /// variables.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) command: Vec<String>,
+
+    /// A URL to POST a JSON description of the alert to when an alert fires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) webhook: Option<url::Url>,
+
+    /// How long to wait for the alert command and webhook to complete.
+    #[serde(with = "humantime_serde", default = "AlertConfig::default_timeout")]
+    pub(super) timeout: Duration,
+}
+
+/// Configures the actions performed when `vupdated` receives user-defined
+/// signals (on Unix systems).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SignalConfig {
+    #[serde(default = "SignalConfig::default_sigusr1")]
+    pub(super) sigusr1: SignalAction,
+    #[serde(default = "SignalConfig::default_sigusr2")]
+    pub(super) sigusr2: SignalAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignalAction {
+    /// Switch to the next profile in the config file.
+    NextProfile,
+    /// Blink the backlights of all dials, to identify them.
+    Identify,
+    /// Restart all dial managers, re-applying each dial's name, easing,
+    /// backlight, and image.
+    Resetup,
+    /// Pause dial updates, leaving the dials as they are.
+    Pause,
+    /// Resume dial updates, re-applying each dial's backlight and image.
+    Resume,
+    /// Pause dial updates if they're running, or resume them if they're
+    /// paused.
+    TogglePause,
+    /// Do nothing.
+    Ignore,
+}
+
+/// Configures how dial updates are sent to the VU-Server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SchedulerConfig {
+    /// How long to wait for other dials' updates before sending a batch of
+    /// updates to the VU-Server.
+    ///
+    /// Updates to the same dial within a batch are coalesced, so only the most
+    /// recent one is sent.
+    #[serde(
+        with = "humantime_serde",
+        default = "SchedulerConfig::default_batch_window"
+    )]
+    pub(super) batch_window: Duration,
+
+    /// The maximum number of requests to send to the VU-Server at once.
+    #[serde(default = "SchedulerConfig::default_max_concurrency")]
+    pub(super) max_concurrency: usize,
+
+    /// If set, wait this long between starting each request in a batch.
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(super) pacing: Option<Duration>,
+}
+
+/// Configures what happens to dials when the daemon shuts down.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ShutdownConfig {
+    /// The value to set dials to on shutdown.
+    ///
+    /// If this is not set, dials are left displaying their last value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) value: Option<Percent>,
+
+    /// The backlight mode to set on shutdown.
+    ///
+    /// If this is not set, backlights are left unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) backlight: Option<BacklightMode>,
+
+    /// How long to wait for the dials to be updated before exiting anyway.
+    #[serde(with = "humantime_serde", default = "ShutdownConfig::default_timeout")]
+    pub(super) timeout: Duration,
+}
+
+/// Configures idle mode, in which the daemon stops displaying metrics and plays
+/// an animation across all dials instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IdleConfig {
+    /// Enter idle mode after the system has been idle for this long.
+    ///
+    /// If this is not set, idle mode is only entered during the configured
+    /// `schedule`.
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(super) after: Option<Duration>,
+
+    /// Times of day during which idle mode is always active.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) schedule: Vec<IdleSchedule>,
+
+    /// The animation to play while idle.
+    #[serde(default)]
+    pub(super) animation: IdleAnimation,
+
+    /// How long one cycle of the animation takes.
+    #[serde(with = "humantime_serde", default = "IdleConfig::default_period")]
+    pub(super) period: Duration,
+
+    /// How often to update the dials while animating.
+    #[serde(
+        with = "humantime_serde",
+        default = "IdleConfig::default_frame_interval"
+    )]
+    pub(super) frame_interval: Duration,
+
+    /// How often to check whether the system is idle.
+    #[serde(
+        with = "humantime_serde",
+        default = "IdleConfig::default_poll_interval"
+    )]
+    pub(super) poll_interval: Duration,
+}
+
+/// A daily time range during which idle mode is active.
+///
+/// If `end` is earlier than `start`, the range wraps around midnight.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct IdleSchedule {
+    pub(super) start: TimeOfDay,
+    pub(super) end: TimeOfDay,
+}
+
+/// A local time of day, formatted as `HH:MM` or `HH:MM:SS`.
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, SerializeDisplay, DeserializeFromStr,
+)]
+pub(super) struct TimeOfDay(chrono::NaiveTime);
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum IdleAnimation {
+    /// All dials sweep back and forth together.
+    Sweep,
+    /// A wave travels across the dials, in index order.
+    #[default]
+    Wave,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct DialConfig {
@@ -46,6 +374,149 @@ pub struct DialConfig {
     pub(super) easing: Option<Easing>,
 
     pub(super) backlight: BacklightSettings,
+
+    /// Overrides the VU-Server instance that this dial is attached to.
+    ///
+    /// If this is not set, the server configured on the command line is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) server: Option<ServerConfig>,
+
+    /// If set, the dial's current value is drawn onto its background image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) value_image: Option<ValueImageConfig>,
+
+    /// If set, the dial displays the metric as sampled by `vupdated agent` on
+    /// the named host, rather than sampling it on this machine.
+    ///
+    /// Remote metrics are received over MQTT, so this requires an `[mqtt]`
+    /// table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) host: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct ServerConfig {
+    /// The base URL of the VU-Server instance.
+    pub(super) url: url::Url,
+    /// The API key to use when connecting to the VU-Server instance.
+    pub(super) key: String,
+}
+
+/// Configures drawing a dial's current value onto its background image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ValueImageConfig {
+    /// The minimum amount of time between image uploads.
+    ///
+    /// Every upload rewrites the dial's flash storage, so this should be much
+    /// longer than the dial's update interval.
+    #[serde(
+        with = "humantime_serde",
+        default = "ValueImageConfig::default_interval"
+    )]
+    pub(super) interval: Duration,
+
+    /// The minimum change in the dial's value, in percentage points, before
+    /// the image is uploaded again.
+    #[serde(default = "ValueImageConfig::default_min_change")]
+    pub(super) min_change: u8,
+
+    /// The unit to draw after the value.
+    ///
+    /// If this is not set, the metric's unit is used (e.g. `%`, or `°C` for
+    /// CPU temperature).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) unit: Option<String>,
+}
+
+/// Configures publishing dial state to an MQTT broker, with Home Assistant
+/// discovery.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MqttConfig {
+    /// The hostname or IP address of the MQTT broker.
+    pub(super) host: String,
+
+    #[serde(default = "MqttConfig::default_port")]
+    pub(super) port: u16,
+
+    /// The MQTT client ID. This is also used to identify the dials' device in
+    /// Home Assistant, so it should be unique if multiple `vupdated` instances
+    /// publish to the same broker.
+    #[serde(default = "MqttConfig::default_client_id")]
+    pub(super) client_id: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) username: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) password: Option<String>,
+
+    /// The topic prefix that Home Assistant listens for discovery messages on.
+    #[serde(default = "MqttConfig::default_discovery_prefix")]
+    pub(super) discovery_prefix: String,
+
+    /// The prefix for the topics that dial state is published to.
+    #[serde(default = "MqttConfig::default_topic_prefix")]
+    pub(super) topic_prefix: String,
+
+    /// If `true`, accept commands to set dial values and backlights over MQTT.
+    #[serde(default)]
+    pub(super) commands: bool,
+
+    /// How often to check for changes to the dials' state to publish.
+    #[serde(
+        with = "humantime_serde",
+        default = "MqttConfig::default_publish_interval"
+    )]
+    pub(super) publish_interval: Duration,
+}
+
+/// Configures `vupdated agent`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AgentConfig {
+    /// The name of this host, which dial configs use to refer to its metrics.
+    pub(super) host: String,
+
+    /// The metrics to sample and publish.
+    pub(super) metrics: Vec<Metric>,
+
+    /// How often to sample each metric.
+    #[serde(with = "nonzero_duration", default = "AgentConfig::default_interval")]
+    pub(super) interval: Duration,
+}
+
+/// Configures an external metric plugin.
+///
+/// The plugin executable is run as a child process, and provides values for
+/// the dial by exchanging newline-delimited JSON messages with the daemon over
+/// its stdin and stdout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PluginConfig {
+    /// The plugin executable to run.
+    pub(super) command: String,
+
+    /// Arguments to pass to the plugin executable.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) args: Vec<String>,
+
+    /// Additional environment variables to set for the plugin process.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) env: BTreeMap<String, String>,
+
+    /// The default name for dials displaying this plugin's metric.
+    ///
+    /// If this is not set, the name of the plugin executable is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) name: Option<String>,
+
+    /// How long to wait for the plugin to respond to a request before
+    /// restarting it.
+    #[serde(with = "humantime_serde", default = "PluginConfig::default_timeout")]
+    pub(super) timeout: Duration,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -55,6 +526,15 @@ pub(super) struct Easing {
     pub(super) step: Percent,
 }
 
+impl Easing {
+    pub(super) fn into_config(self) -> vu_api::dial::EasingConfig {
+        vu_api::dial::EasingConfig {
+            period: self.period,
+            step: self.step,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub(super) struct BacklightSettings {
@@ -77,10 +557,27 @@ serde_with::with_prefix!(prefix_easing "easing-");
 // === impl Config ===
 
 impl Config {
+    /// Returns the options for reading built-in metrics.
+    pub(super) fn source_options(&self) -> SourceOptions {
+        SourceOptions {
+            backend: self.metric_backend,
+            scope: self.metric_scope,
+        }
+    }
+
     pub(super) async fn generate(
         client: &vu_api::client::Client,
         metrics: Vec<Metric>,
     ) -> miette::Result<Self> {
+        let (metrics, unsupported): (Vec<_>, Vec<_>) =
+            metrics.into_iter().partition(Metric::is_supported);
+        if !unsupported.is_empty() {
+            tracing::warn!(
+                "the following metrics are not supported on this platform, and will not be \
+                included in the generated config: {unsupported:?}"
+            );
+        }
+
         tracing::info!("generating config with metrics: {metrics:?}");
         let mut config = Self::default();
         let dials = client.list_dials().await?;
@@ -116,6 +613,9 @@ impl Config {
                             step: dial.easing.backlight_step,
                         }),
                     },
+                    server: None,
+                    value_image: None,
+                    host: None,
                 },
             );
         }
@@ -152,9 +652,57 @@ impl Config {
         let file = fs::read_to_string(path)
             .into_diagnostic()
             .with_context(|| format!("failed to read config file '{path}'"))?;
-        toml::from_str(&file)
-            .into_diagnostic()
-            .with_context(|| format!("failed to parse config file '{path}'"))
+        toml::from_str(&file).or_else(|error| {
+            // Point users of the legacy `vupdate` daemon at the migration,
+            // rather than at whichever field its config is missing.
+            if toml::from_str::<LegacyConfig>(&file).is_ok() {
+                miette::bail!(
+                    help = format!(
+                        "convert it with `vupdated --config <NEW_CONFIG> migrate-config {path}`"
+                    ),
+                    "'{path}' is a config file for the legacy `vupdate` daemon"
+                );
+            }
+            Err(error)
+                .into_diagnostic()
+                .with_context(|| format!("failed to parse config file '{path}'"))
+        })
+    }
+
+    /// Returns the dial configurations for the selected profile.
+    ///
+    /// If `profile` is `None`, the config file's `active-profile` is used,
+    /// falling back to the top-level `dials` table if no profile is active.
+    pub(super) fn profile_dials(
+        &self,
+        profile: Option<&str>,
+    ) -> miette::Result<&HashMap<String, DialConfig>> {
+        match profile.or(self.active_profile.as_deref()) {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .map(|profile| &profile.dials)
+                .ok_or_else(|| {
+                    miette::miette!(
+                        help = "profiles are defined in `[profiles.<name>.dials]` tables",
+                        "no profile named {name:?} in config file"
+                    )
+                }),
+            None => Ok(&self.dials),
+        }
+    }
+
+    /// Returns the name of the profile following `current`, in lexicographic
+    /// order, wrapping around after the last profile.
+    pub(super) fn next_profile(&self, current: Option<&str>) -> Option<&str> {
+        let mut names = self.profiles.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+        let next = current
+            .or(self.active_profile.as_deref())
+            .and_then(|current| names.iter().position(|&name| name == current))
+            .map(|idx| (idx + 1) % names.len())
+            .unwrap_or(0);
+        names.get(next).copied()
     }
 
     pub(super) fn default_path() -> Utf8PathBuf {
@@ -212,13 +760,331 @@ impl RetryConfig {
             .with_max_elapsed_time(self.max_elapsed_time);
         builder
     }
+
+    /// Returns the policy for retrying VU-Server requests.
+    pub(super) fn retry_policy(&self) -> vu_api::retry::RetryPolicy {
+        vu_api::retry::RetryPolicy::new()
+            .initial_interval(self.initial_backoff)
+            .randomization_factor(self.jitter)
+            .multiplier(self.multiplier)
+            .max_interval(self.max_backoff)
+            .max_elapsed_time(self.max_elapsed_time)
+    }
+}
+
+// === impl MqttConfig ===
+
+impl MqttConfig {
+    const fn default_port() -> u16 {
+        1883
+    }
+
+    fn default_client_id() -> String {
+        "vupdated".to_owned()
+    }
+
+    fn default_discovery_prefix() -> String {
+        "homeassistant".to_owned()
+    }
+
+    fn default_topic_prefix() -> String {
+        "vupdated".to_owned()
+    }
+
+    const fn default_publish_interval() -> Duration {
+        Duration::from_secs(1)
+    }
+}
+
+// === impl AgentConfig ===
+
+impl AgentConfig {
+    const fn default_interval() -> Duration {
+        Duration::from_secs(1)
+    }
+}
+
+// === impl PluginConfig ===
+
+impl PluginConfig {
+    const fn default_timeout() -> Duration {
+        Duration::from_secs(5)
+    }
+}
+
+// === impl UnreachableConfig ===
+
+impl Default for UnreachableConfig {
+    fn default() -> Self {
+        Self {
+            action: UnreachableAction::default(),
+            probe_interval: Self::default_probe_interval(),
+        }
+    }
+}
+
+impl UnreachableConfig {
+    const fn default_probe_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+// === impl ErrorBudget ===
+
+impl Default for ErrorBudget {
+    fn default() -> Self {
+        Self {
+            max_errors: Self::default_max_errors(),
+            window: None,
+        }
+    }
+}
+
+impl ErrorBudget {
+    const fn default_max_errors() -> usize {
+        4
+    }
+
+    /// Returns a [`MultiError`] that tracks errors against this budget.
+    pub(super) fn tracker(&self, what: &str) -> MultiError {
+        let max_errors = self.max_errors.max(1);
+        match self.window {
+            Some(window) => MultiError::with_max_errors_in(
+                format!(
+                    "{what} failed {max_errors} times within {}",
+                    humantime_serde::re::humantime::format_duration(window)
+                ),
+                max_errors,
+                window,
+            ),
+            None => MultiError::with_max_errors(
+                format!("{what} failed {max_errors} times in a row"),
+                max_errors,
+            ),
+        }
+    }
+}
+
+// === impl AlertConfig ===
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            command: Vec::new(),
+            webhook: None,
+            timeout: Self::default_timeout(),
+        }
+    }
+}
+
+impl AlertConfig {
+    const fn default_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        !self.command.is_empty() || self.webhook.is_some()
+    }
+}
+
+// === impl SignalConfig ===
+
+impl Default for SignalConfig {
+    fn default() -> Self {
+        Self {
+            sigusr1: Self::default_sigusr1(),
+            sigusr2: Self::default_sigusr2(),
+        }
+    }
+}
+
+impl SignalConfig {
+    const fn default_sigusr1() -> SignalAction {
+        SignalAction::NextProfile
+    }
+
+    const fn default_sigusr2() -> SignalAction {
+        SignalAction::Identify
+    }
+}
+
+// === impl ValueImageConfig ===
+
+impl Default for ValueImageConfig {
+    fn default() -> Self {
+        Self {
+            interval: Self::default_interval(),
+            min_change: Self::default_min_change(),
+            unit: None,
+        }
+    }
+}
+
+impl ValueImageConfig {
+    const fn default_interval() -> Duration {
+        Duration::from_secs(5 * 60)
+    }
+
+    const fn default_min_change() -> u8 {
+        1
+    }
+}
+
+// === impl SchedulerConfig ===
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            batch_window: Self::default_batch_window(),
+            max_concurrency: Self::default_max_concurrency(),
+            pacing: None,
+        }
+    }
+}
+
+impl SchedulerConfig {
+    const fn default_batch_window() -> Duration {
+        Duration::from_millis(10)
+    }
+
+    const fn default_max_concurrency() -> usize {
+        4
+    }
+}
+
+// === impl ShutdownConfig ===
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            value: None,
+            backlight: None,
+            timeout: Self::default_timeout(),
+        }
+    }
+}
+
+impl ShutdownConfig {
+    const fn default_timeout() -> Duration {
+        Duration::from_secs(2)
+    }
+
+    /// Returns `true` if the dials should be changed on shutdown.
+    pub(super) fn has_action(&self) -> bool {
+        self.value.is_some() || self.backlight.is_some()
+    }
+}
+
+// === impl IdleConfig ===
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            after: None,
+            schedule: Vec::new(),
+            animation: IdleAnimation::default(),
+            period: Self::default_period(),
+            frame_interval: Self::default_frame_interval(),
+            poll_interval: Self::default_poll_interval(),
+        }
+    }
+}
+
+impl IdleConfig {
+    const fn default_period() -> Duration {
+        Duration::from_secs(8)
+    }
+
+    const fn default_frame_interval() -> Duration {
+        Duration::from_millis(500)
+    }
+
+    const fn default_poll_interval() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    /// Returns `true` if idle mode can ever be entered.
+    pub(super) fn is_enabled(&self) -> bool {
+        self.after.is_some() || !self.schedule.is_empty()
+    }
+
+    /// Returns `true` if the current local time is within the idle schedule.
+    pub(super) fn is_scheduled(&self) -> bool {
+        let now = TimeOfDay(chrono::Local::now().time());
+        self.schedule.iter().any(|schedule| schedule.contains(now))
+    }
+}
+
+// === impl IdleSchedule ===
+
+impl IdleSchedule {
+    fn contains(&self, time: TimeOfDay) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // The schedule wraps around midnight.
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+// === impl TimeOfDay ===
+
+impl FromStr for TimeOfDay {
+    type Err = chrono::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        chrono::NaiveTime::parse_from_str(s, "%H:%M")
+            .or_else(|_| chrono::NaiveTime::parse_from_str(s, "%H:%M:%S"))
+            .map(Self)
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use chrono::Timelike;
+        if self.0.second() == 0 {
+            self.0.format("%H:%M").fmt(f)
+        } else {
+            self.0.format("%H:%M:%S").fmt(f)
+        }
+    }
 }
 
 // === impl BacklightMode ===
 
+impl BacklightMode {
+    /// Returns the backlight color for this mode.
+    pub(super) fn color(&self) -> Backlight {
+        match *self {
+            Self::Static(color) => color,
+            Self::Off => Backlight::OFF,
+        }
+    }
+}
+
 impl Default for BacklightMode {
     fn default() -> Self {
         let color = Backlight::new(50, 50, 50).expect("50 is a valid percent");
         Self::Static(color)
     }
 }
+
+/// (De)serializes a human-readable duration that must not be zero, such as
+/// the period of a `tokio::time::interval`.
+mod nonzero_duration {
+    use serde::de;
+    use std::time::Duration;
+
+    pub(super) use humantime_serde::serialize;
+
+    pub(super) fn deserialize<'de, D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        let duration: Duration = humantime_serde::deserialize(deserializer)?;
+        if duration.is_zero() {
+            return Err(de::Error::custom("must be greater than zero"));
+        }
+        Ok(duration)
+    }
+}
@@ -0,0 +1,168 @@
+//! Support for migrating config files from the legacy `vupdate` daemon.
+//!
+//! The old `vupdate` daemon used a config format keyed by dial UID, where each
+//! dial's displayed metric was selected by a `Data` enum. The new format is
+//! keyed by dial name and selects dials by index, so migrating a config
+//! requires asking the VU-Server which index each UID corresponds to.
+use super::{BacklightMode, BacklightSettings, Config, DialConfig, Easing, RetryConfig};
+use crate::daemon::Metric;
+use camino::Utf8Path;
+use miette::{Context, IntoDiagnostic};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, time::Duration};
+use vu_api::dial::{self, Backlight};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(in crate::daemon) struct LegacyConfig {
+    dials: HashMap<dial::Id, LegacyDial>,
+
+    #[serde(default)]
+    retries: Option<RetryConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct LegacyDial {
+    #[serde(default)]
+    name: Option<String>,
+
+    data: Data,
+
+    #[serde(
+        with = "humantime_serde",
+        default = "LegacyDial::default_update_interval",
+        alias = "update_interval"
+    )]
+    update_interval: Duration,
+
+    #[serde(default)]
+    backlight: Option<Backlight>,
+
+    #[serde(default)]
+    easing: Option<LegacyEasing>,
+}
+
+/// The legacy `vupdate` daemon's metric selection.
+#[derive(Debug, Copy, Clone, Deserialize, Eq, PartialEq)]
+enum Data {
+    #[serde(alias = "cpu-load", alias = "cpu_load")]
+    CpuLoad,
+    #[serde(alias = "mem", alias = "Memory", alias = "memory")]
+    Mem,
+    #[serde(alias = "disk", alias = "disk-usage", alias = "DiskUsage")]
+    Disk,
+    #[serde(alias = "cpu-temp", alias = "cpu_temp")]
+    CpuTemp,
+    #[serde(alias = "swap")]
+    Swap,
+    #[serde(alias = "battery")]
+    Battery,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct LegacyEasing {
+    #[serde(default)]
+    dial: Option<Easing>,
+    #[serde(default)]
+    backlight: Option<Easing>,
+}
+
+// === impl LegacyConfig ===
+
+impl LegacyConfig {
+    pub(in crate::daemon) fn load(path: impl AsRef<Utf8Path>) -> miette::Result<Self> {
+        let path = path.as_ref();
+        tracing::info!("loading legacy config from {path}...");
+
+        let file = fs::read_to_string(path)
+            .into_diagnostic()
+            .with_context(|| format!("failed to read legacy config file '{path}'"))?;
+        toml::from_str(&file)
+            .into_diagnostic()
+            .with_context(|| format!("failed to parse legacy config file '{path}'"))
+    }
+
+    /// Converts this legacy config into the current config format.
+    ///
+    /// Because the legacy format identifies dials by UID, while the new format
+    /// identifies them by index, this looks up each dial's index from the
+    /// VU-Server.
+    pub(in crate::daemon) async fn migrate(
+        self,
+        client: &vu_api::client::Client,
+    ) -> miette::Result<Config> {
+        let mut config = Config {
+            retries: self.retries.unwrap_or_default(),
+            ..Config::default()
+        };
+
+        for (uid, legacy) in self.dials {
+            let status = client
+                .dial(uid.clone())
+                .into_diagnostic()?
+                .status()
+                .await
+                .with_context(|| format!("failed to get status for dial {uid}"))?;
+            let metric = Metric::from(legacy.data);
+            let name = legacy.name.unwrap_or_else(|| metric.dial_name());
+            tracing::info!(
+                dial.uid = %uid,
+                dial.index = status.index,
+                "migrating {name} ({metric:?})"
+            );
+
+            let easing = legacy.easing.unwrap_or(LegacyEasing {
+                dial: None,
+                backlight: None,
+            });
+            let dial = DialConfig {
+                index: status.index,
+                metric,
+                update_interval: legacy.update_interval,
+                easing: easing.dial,
+                backlight: BacklightSettings {
+                    mode: legacy
+                        .backlight
+                        .map(BacklightMode::Static)
+                        .unwrap_or_default(),
+                    easing: easing.backlight,
+                },
+                server: None,
+                value_image: None,
+                host: None,
+            };
+
+            if config.dials.insert(name.clone(), dial).is_some() {
+                miette::bail!(
+                    "multiple dials in the legacy config would be named {name:?}; \
+                    set a unique `name` for each dial and try again"
+                );
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+// === impl LegacyDial ===
+
+impl LegacyDial {
+    const fn default_update_interval() -> Duration {
+        Duration::from_secs(1)
+    }
+}
+
+impl From<Data> for Metric {
+    fn from(data: Data) -> Self {
+        match data {
+            Data::CpuLoad => Metric::CpuLoad,
+            Data::Mem => Metric::Mem,
+            Data::Disk => Metric::DiskUsage,
+            Data::CpuTemp => Metric::CpuTemp,
+            Data::Swap => Metric::Swap,
+            Data::Battery => Metric::Battery,
+        }
+    }
+}
@@ -0,0 +1,560 @@
+//! The `vupdated` control API.
+//!
+//! A running `vupdated` daemon listens for control requests on a Unix domain
+//! socket (on Linux and macOS) or a named pipe (on Windows). Requests and
+//! responses are newline-delimited JSON objects, one [`Request`] per line,
+//! answered by exactly one [`Response`] per line.
+use super::{
+    agent::Remotes,
+    config::{IdleConfig, ShutdownConfig},
+    images::ImageCache,
+    ManagerCommand, Metric,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::{mpsc, watch},
+};
+use vu_api::{
+    client::Dial,
+    dial::{Backlight, Percent},
+};
+
+/// A request sent to the daemon's control API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Reload the config file and restart all dial managers.
+    Reload,
+    /// Pause dial updates.
+    Pause,
+    /// Resume dial updates.
+    Resume,
+    /// Switch to the named config profile.
+    SwitchProfile { profile: String },
+    /// Return the daemon's current status.
+    Status,
+    /// Display a manually-set value on a dial, instead of its metric.
+    ///
+    /// If `value` is `None`, the dial resumes displaying its metric.
+    SetValue {
+        /// The name or index of the dial.
+        dial: String,
+        value: Option<Percent>,
+    },
+}
+
+/// A response from the daemon's control API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum Response {
+    /// The request was performed successfully.
+    Ok,
+    /// The daemon's current status, in response to a [`Request::Status`].
+    Status(DaemonStatus),
+    /// The request failed.
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DaemonStatus {
+    /// Whether dial updates are currently paused.
+    pub paused: bool,
+    /// Whether the daemon is currently in idle mode.
+    #[serde(default)]
+    pub idle: bool,
+    /// The currently active config profile, if any.
+    pub profile: Option<String>,
+    /// The dials currently managed by the daemon, by name.
+    pub dials: BTreeMap<String, DialStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DialStatus {
+    pub index: usize,
+    pub metric: Metric,
+    /// The value most recently displayed on the dial.
+    pub value: Option<Percent>,
+    /// The manually-set value overriding the dial's metric, if any.
+    pub manual_value: Option<Percent>,
+    /// The backlight color most recently set on the dial.
+    #[serde(default)]
+    pub backlight: Option<Backlight>,
+}
+
+/// State shared between the dial managers and the control API.
+#[derive(Debug, Clone, Default)]
+pub(super) struct DaemonState(Arc<Mutex<StateInner>>);
+
+#[derive(Debug, Default)]
+struct StateInner {
+    profile: Option<String>,
+    dials: BTreeMap<String, DialStatus>,
+    /// Handles to the dials currently being managed, and their indices, so
+    /// that they can be updated on shutdown after the dial managers have
+    /// stopped, and animated in idle mode.
+    handles: Vec<(usize, Dial)>,
+    shutdown: ShutdownConfig,
+    idle_config: IdleConfig,
+    idle: bool,
+    images: ImageCache,
+    remotes: Remotes,
+}
+
+/// Handles control API requests.
+#[derive(Debug, Clone)]
+pub(super) struct Controller {
+    pub(super) config_path: Utf8PathBuf,
+    pub(super) commands: mpsc::Sender<ManagerCommand>,
+    pub(super) running: Arc<watch::Sender<bool>>,
+    pub(super) state: DaemonState,
+}
+
+/// Returns the default path of the control socket (or, on Windows, the name of
+/// the control named pipe).
+pub fn default_socket_path() -> Utf8PathBuf {
+    #[cfg(windows)]
+    {
+        Utf8PathBuf::from(r"\\.\pipe\vupdated")
+    }
+
+    // Anyone who can connect to the socket can control the daemon, so it
+    // mustn't be somewhere other users can reach, such as `/tmp`.
+    #[cfg(not(windows))]
+    {
+        let dirs = directories::BaseDirs::new();
+        dirs.as_ref()
+            .and_then(|dirs| Utf8Path::from_path(dirs.runtime_dir()?).map(Utf8Path::to_path_buf))
+            .or_else(default_runtime_dir)
+            .or_else(|| {
+                let dir = Utf8Path::from_path(dirs.as_ref()?.data_local_dir())?;
+                Some(dir.join("vupdated"))
+            })
+            .unwrap_or_else(|| Utf8PathBuf::from("/run"))
+            .join("vupdated.sock")
+    }
+}
+
+/// Returns the per-user runtime directory, when `$XDG_RUNTIME_DIR` isn't set.
+#[cfg(target_os = "linux")]
+fn default_runtime_dir() -> Option<Utf8PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+    match std::fs::metadata("/proc/self").ok()?.uid() {
+        0 => Some(Utf8PathBuf::from("/run")),
+        uid => {
+            let dir = Utf8PathBuf::from(format!("/run/user/{uid}"));
+            dir.is_dir().then_some(dir)
+        }
+    }
+}
+
+/// Returns the per-user runtime directory, when `$XDG_RUNTIME_DIR` isn't set.
+///
+/// On macOS, `$TMPDIR` is a private, per-user directory.
+#[cfg(target_os = "macos")]
+fn default_runtime_dir() -> Option<Utf8PathBuf> {
+    Utf8PathBuf::from_path_buf(std::env::temp_dir()).ok()
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn default_runtime_dir() -> Option<Utf8PathBuf> {
+    None
+}
+
+/// Sends a single request to the daemon listening on `path`, and returns its
+/// response.
+pub async fn send(path: &Utf8Path, request: &Request) -> miette::Result<Response> {
+    let conn = connect(path)
+        .await
+        .with_context(|| format!("failed to connect to vupdated at {path}; is it running?"))?;
+    let (read, mut write) = tokio::io::split(conn);
+
+    let mut json = serde_json::to_string(request).into_diagnostic()?;
+    json.push('\n');
+    write
+        .write_all(json.as_bytes())
+        .await
+        .into_diagnostic()
+        .context("failed to send request to vupdated")?;
+
+    let line = BufReader::new(read)
+        .lines()
+        .next_line()
+        .await
+        .into_diagnostic()
+        .context("failed to read response from vupdated")?
+        .ok_or_else(|| miette::miette!("vupdated closed the connection without responding"))?;
+    serde_json::from_str(&line)
+        .into_diagnostic()
+        .context("failed to parse response from vupdated")
+}
+
+#[cfg(unix)]
+async fn connect(path: &Utf8Path) -> miette::Result<tokio::net::UnixStream> {
+    tokio::net::UnixStream::connect(path)
+        .await
+        .into_diagnostic()
+}
+
+#[cfg(windows)]
+async fn connect(
+    path: &Utf8Path,
+) -> miette::Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(path.as_str())
+        .into_diagnostic()
+}
+
+// === impl DaemonState ===
+
+impl DaemonState {
+    pub(super) fn new(images: ImageCache) -> Self {
+        Self(Arc::new(Mutex::new(StateInner {
+            images,
+            ..StateInner::default()
+        })))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, StateInner> {
+        // If a dial manager panicked while holding the lock, the state is
+        // still fine to read, so ignore poisoning.
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(super) fn set_profile(&self, profile: Option<String>) {
+        self.lock().profile = profile;
+    }
+
+    pub(super) fn set_shutdown(&self, shutdown: ShutdownConfig) {
+        self.lock().shutdown = shutdown;
+    }
+
+    pub(super) fn set_idle_config(&self, config: IdleConfig) {
+        self.lock().idle_config = config;
+    }
+
+    pub(super) fn idle_config(&self) -> IdleConfig {
+        self.lock().idle_config.clone()
+    }
+
+    pub(super) fn set_idle(&self, idle: bool) {
+        self.lock().idle = idle;
+    }
+
+    /// Returns the cache of images uploaded to the dials.
+    pub(super) fn images(&self) -> ImageCache {
+        self.lock().images.clone()
+    }
+
+    /// Returns the registry of metrics received from other hosts.
+    pub(super) fn remotes(&self) -> Remotes {
+        self.lock().remotes.clone()
+    }
+
+    pub(super) fn clear_dials(&self) {
+        let mut state = self.lock();
+        state.dials.clear();
+        state.handles.clear();
+    }
+
+    pub(super) fn add_dial(&self, name: &str, dial: &Dial, index: usize, metric: Metric) {
+        let mut state = self.lock();
+        state.dials.insert(
+            name.to_owned(),
+            DialStatus {
+                index,
+                metric,
+                value: None,
+                manual_value: None,
+                backlight: None,
+            },
+        );
+        state.handles.push((index, dial.clone()));
+    }
+
+    /// Returns handles to all managed dials, in index order.
+    pub(super) fn dials(&self) -> Vec<Dial> {
+        let mut handles = self.lock().handles.clone();
+        handles.sort_by_key(|&(index, _)| index);
+        handles.into_iter().map(|(_, dial)| dial).collect()
+    }
+
+    /// Returns the shutdown config and the dials it should be applied to.
+    pub(super) fn shutdown(&self) -> (ShutdownConfig, Vec<Dial>) {
+        let shutdown = self.lock().shutdown.clone();
+        (shutdown, self.dials())
+    }
+
+    pub(super) fn record_value(&self, name: &str, value: Percent) {
+        if let Some(dial) = self.lock().dials.get_mut(name) {
+            dial.value = Some(value);
+        }
+    }
+
+    pub(super) fn record_backlight(&self, name: &str, backlight: Backlight) {
+        if let Some(dial) = self.lock().dials.get_mut(name) {
+            dial.backlight = Some(backlight);
+        }
+    }
+
+    /// Returns the status of each managed dial, by name.
+    pub(super) fn dial_statuses(&self) -> BTreeMap<String, DialStatus> {
+        self.lock().dials.clone()
+    }
+
+    /// Returns a handle to the dial with the given name.
+    pub(super) fn dial(&self, name: &str) -> Option<Dial> {
+        let state = self.lock();
+        let index = state.dials.get(name)?.index;
+        state
+            .handles
+            .iter()
+            .find(|&&(i, _)| i == index)
+            .map(|(_, dial)| dial.clone())
+    }
+
+    pub(super) fn manual_value(&self, name: &str) -> Option<Percent> {
+        self.lock().dials.get(name)?.manual_value
+    }
+
+    /// Sets the manual value for the dial with the given name or index,
+    /// returning an error if no such dial exists.
+    pub(super) fn set_manual_value(
+        &self,
+        dial: &str,
+        value: Option<Percent>,
+    ) -> miette::Result<()> {
+        let mut state = self.lock();
+        let index = dial.parse::<usize>().ok();
+        let status = match state.dials.get_mut(dial) {
+            Some(status) => status,
+            None => state
+                .dials
+                .values_mut()
+                .find(|status| Some(status.index) == index)
+                .ok_or_else(|| miette::miette!("no dial named {dial:?} is managed by vupdated"))?,
+        };
+        status.manual_value = value;
+        Ok(())
+    }
+
+    fn status(&self, paused: bool) -> DaemonStatus {
+        let state = self.lock();
+        DaemonStatus {
+            paused,
+            idle: state.idle,
+            profile: state.profile.clone(),
+            dials: state.dials.clone(),
+        }
+    }
+}
+
+// === impl Controller ===
+
+/// Removes the control socket when the listener stops.
+#[cfg(unix)]
+struct SocketGuard(Utf8PathBuf);
+
+#[cfg(unix)]
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_file(&self.0) {
+            tracing::debug!(%error, "failed to remove control socket {}", self.0);
+        }
+    }
+}
+
+impl Controller {
+    /// Listens for control API connections on `path` until the listener fails.
+    ///
+    /// If `vupdated` was started by a systemd socket unit, the socket passed
+    /// by systemd in `sockets` is used instead of binding `path`. A socket
+    /// bound at `path` is only accessible to the current user, and is removed
+    /// when the listener stops.
+    #[tracing::instrument(
+        level = tracing::Level::INFO,
+        name = "control",
+        skip(self, sockets),
+        err(Display),
+    )]
+    pub(super) async fn listen(
+        self,
+        path: Utf8PathBuf,
+        sockets: super::activation::Sockets,
+    ) -> miette::Result<()> {
+        #[cfg(unix)]
+        {
+            let mut sockets = sockets;
+            let activated = sockets.take_listener("control")?;
+
+            // Sockets passed by systemd belong to systemd, so only remove
+            // sockets that we bound ourselves.
+            let (listener, _guard) = match activated {
+                Some(listener) => {
+                    tracing::info!("listening for control requests on socket passed by systemd");
+                    (listener, None)
+                }
+                None => (Self::bind(&path).await?, Some(SocketGuard(path))),
+            };
+            loop {
+                let (conn, _) = listener
+                    .accept()
+                    .await
+                    .into_diagnostic()
+                    .context("failed to accept control connection")?;
+                tokio::spawn(self.clone().serve(conn));
+            }
+        }
+
+        #[cfg(windows)]
+        {
+            use tokio::net::windows::named_pipe::ServerOptions;
+
+            // systemd never passes us anything on Windows.
+            let _ = sockets;
+            let mut server = ServerOptions::new()
+                .first_pipe_instance(true)
+                .create(path.as_str())
+                .into_diagnostic()
+                .with_context(|| format!("failed to create control pipe {path}"))?;
+            tracing::info!("listening for control requests on {path}");
+            loop {
+                server
+                    .connect()
+                    .await
+                    .into_diagnostic()
+                    .context("failed to accept control connection")?;
+                let conn = server;
+                server = ServerOptions::new()
+                    .create(path.as_str())
+                    .into_diagnostic()
+                    .with_context(|| format!("failed to create control pipe {path}"))?;
+                tokio::spawn(self.clone().serve(conn));
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    async fn bind(path: &Utf8Path) -> miette::Result<tokio::net::UnixListener> {
+        // If there's already a socket at this path, check whether another
+        // daemon is listening on it before clobbering it.
+        if path.exists() {
+            if tokio::net::UnixStream::connect(path).await.is_ok() {
+                miette::bail!("another vupdated is already listening on {path}");
+            }
+            std::fs::remove_file(path)
+                .into_diagnostic()
+                .with_context(|| format!("failed to remove stale control socket {path}"))?;
+        }
+
+        if let Some(parent) = path.parent().filter(|parent| !parent.exists()) {
+            use std::os::unix::fs::DirBuilderExt;
+            std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(parent)
+                .into_diagnostic()
+                .with_context(|| format!("failed to create {parent}"))?;
+        }
+
+        let listener = tokio::net::UnixListener::bind(path)
+            .into_diagnostic()
+            .with_context(|| format!("failed to bind control socket {path}"))?;
+        // Only the user running the daemon may control it.
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+                .into_diagnostic()
+                .with_context(|| format!("failed to set permissions on control socket {path}"))?;
+        }
+        tracing::info!("listening for control requests on {path}");
+        Ok(listener)
+    }
+
+    async fn serve(self, conn: impl AsyncRead + AsyncWrite) {
+        let (read, mut write) = tokio::io::split(conn);
+        let mut lines = BufReader::new(read).lines();
+        loop {
+            let line = match lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => return,
+                Err(error) => {
+                    tracing::warn!(%error, "failed to read control request");
+                    return;
+                }
+            };
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => {
+                    tracing::debug!(?request, "received control request");
+                    self.handle(request)
+                        .await
+                        .unwrap_or_else(|error| Response::Error {
+                            message: format!("{error:?}"),
+                        })
+                }
+                Err(error) => Response::Error {
+                    message: format!("invalid request: {error}"),
+                },
+            };
+
+            let mut json = match serde_json::to_string(&response) {
+                Ok(json) => json,
+                Err(error) => {
+                    tracing::warn!(%error, "failed to serialize control response");
+                    return;
+                }
+            };
+            json.push('\n');
+            if let Err(error) = write.write_all(json.as_bytes()).await {
+                tracing::warn!(%error, "failed to write control response");
+                return;
+            }
+        }
+    }
+
+    async fn handle(&self, request: Request) -> miette::Result<Response> {
+        match request {
+            Request::Reload => self.send_command(ManagerCommand::Reload).await?,
+            Request::Pause => {
+                tracing::info!("pausing dial updates");
+                self.running.send_replace(false);
+            }
+            Request::Resume => {
+                tracing::info!("resuming dial updates");
+                self.running.send_replace(true);
+            }
+            Request::SwitchProfile { profile } => {
+                // Make sure the profile exists before asking the dial managers
+                // to switch to it, so that a typo doesn't stop all the dials.
+                super::Config::load(&self.config_path)?.profile_dials(Some(&profile))?;
+                self.send_command(ManagerCommand::SwitchProfile(profile))
+                    .await?;
+            }
+            Request::Status => {
+                let paused = !*self.running.borrow();
+                return Ok(Response::Status(self.state.status(paused)));
+            }
+            Request::SetValue { dial, value } => self.state.set_manual_value(&dial, value)?,
+        }
+
+        Ok(Response::Ok)
+    }
+
+    async fn send_command(&self, command: ManagerCommand) -> miette::Result<()> {
+        self.commands
+            .send(command)
+            .await
+            .map_err(|_| miette::miette!("dial managers are not running"))
+    }
+}
@@ -1,8 +1,8 @@
 use super::HotplugSettings;
 use futures::stream::StreamExt;
 use miette::{Context, IntoDiagnostic};
-use std::convert::TryInto;
-use tokio::sync::watch;
+use std::{convert::TryInto, sync::Arc};
+use tokio::{runtime, sync::watch};
 use tokio_udev::{AsyncMonitorSocket, EventType, MonitorBuilder};
 use zbus_systemd::{systemd1, zbus};
 
@@ -11,16 +11,35 @@ const USB_MODEL_ID: &str = "ID_USB_VENDOR_ID";
 const DIAL_HUB_USB_VENDOR_ID: &str = "0403"; // FDTI
 const DIAL_HUB_USB_MODEL_ID: &str = "6015";
 
+/// Spawns the hotplug event watcher on a dedicated thread.
+///
+/// The udev monitor socket can't be moved between threads, so the watcher
+/// runs on its own single-threaded runtime, regardless of which runtime the
+/// rest of the daemon is running on.
+pub(crate) fn spawn(
+    settings: HotplugSettings,
+    running: Arc<watch::Sender<bool>>,
+) -> miette::Result<()> {
+    let rt = runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .into_diagnostic()
+        .context("failed to build hotplug runtime")?;
+    std::thread::Builder::new()
+        .name("hotplug".to_owned())
+        .spawn(move || rt.block_on(run(settings, running)))
+        .into_diagnostic()
+        .context("failed to spawn hotplug thread")?;
+    Ok(())
+}
+
 #[tracing::instrument(
     level = tracing::Level::INFO,
     name = "hotplug",
     skip(settings, running),
     fields(hotplug_service = %settings.hotplug_service),
     err(Display),)]
-pub(crate) async fn run(
-    settings: HotplugSettings,
-    running: watch::Sender<bool>,
-) -> miette::Result<()> {
+async fn run(settings: HotplugSettings, running: Arc<watch::Sender<bool>>) -> miette::Result<()> {
     let HotplugSettings {
         enabled,
         hotplug_service,
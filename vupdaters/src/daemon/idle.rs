@@ -0,0 +1,249 @@
+//! Idle mode.
+//!
+//! The idle coordinator runs above the per-dial managers. When the system has
+//! been idle for the configured amount of time, or the current time is within
+//! the configured idle schedule, it tells the dial managers to stop updating
+//! their dials, and plays an animation across all dials until the system is
+//! active again.
+use super::{
+    config::{IdleAnimation, IdleConfig},
+    control::DaemonState,
+};
+use miette::{Context, IntoDiagnostic};
+use std::{
+    f64::consts::PI,
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+use vu_api::dial::Percent;
+
+#[tracing::instrument(level = tracing::Level::INFO, name = "idle", skip_all, err(Display))]
+pub(super) async fn run(
+    state: DaemonState,
+    running: watch::Receiver<bool>,
+    idle: watch::Sender<bool>,
+) -> miette::Result<()> {
+    let mut animation_started = Instant::now();
+    let mut last_poll: Option<Instant> = None;
+    loop {
+        let config = state.idle_config();
+
+        // Checking whether the system is idle may involve running a
+        // subprocess, so only do it every `poll_interval`, rather than on
+        // every animation frame.
+        let should_poll = match last_poll {
+            Some(last) => last.elapsed() >= config.poll_interval,
+            None => true,
+        };
+        if should_poll {
+            last_poll = Some(Instant::now());
+            let is_idle = *running.borrow() && is_idle(&config).await;
+            if is_idle != *idle.borrow() {
+                if is_idle {
+                    tracing::info!("system is idle, starting {:?} animation", config.animation);
+                    animation_started = Instant::now();
+                } else {
+                    tracing::info!("system is no longer idle, resuming metrics");
+                }
+                state.set_idle(is_idle);
+                idle.send_replace(is_idle);
+            }
+        }
+
+        if !*idle.borrow() {
+            tokio::time::sleep(config.poll_interval).await;
+            continue;
+        }
+
+        let elapsed = animation_started.elapsed();
+        let dials = state.dials();
+        for (position, dial) in dials.iter().enumerate() {
+            let value = config
+                .animation
+                .value(elapsed, config.period, position, dials.len());
+            // This is best-effort: if the VU-Server is unreachable, the dial
+            // managers will deal with it when they resume.
+            if let Err(error) = dial.set(value).await {
+                tracing::debug!(%dial, %error, "failed to set idle animation value");
+            }
+        }
+        tokio::time::sleep(config.frame_interval).await;
+    }
+}
+
+async fn is_idle(config: &IdleConfig) -> bool {
+    if !config.is_enabled() {
+        return false;
+    }
+
+    if config.is_scheduled() {
+        return true;
+    }
+
+    let Some(after) = config.after else {
+        return false;
+    };
+
+    match system_idle_time().await {
+        Ok(idle_time) => {
+            tracing::trace!(?idle_time, "checked system idle time");
+            idle_time >= after
+        }
+        Err(error) => {
+            tracing::warn!(%error, "failed to determine system idle time");
+            false
+        }
+    }
+}
+
+// === impl IdleAnimation ===
+
+impl IdleAnimation {
+    /// Returns the value of the dial at `position` (out of `dials`), `elapsed`
+    /// into the animation.
+    fn value(&self, elapsed: Duration, period: Duration, position: usize, dials: usize) -> Percent {
+        let phase = (elapsed.as_secs_f64() / period.as_secs_f64().max(f64::EPSILON)).fract();
+        let level = match self {
+            // A triangle wave, going from 0 to 1 and back again.
+            Self::Sweep => 1.0 - (2.0 * phase - 1.0).abs(),
+            // A raised cosine, offset by each dial's position, so that the
+            // peak travels across the dials.
+            Self::Wave => {
+                let offset = position as f64 / dials.max(1) as f64;
+                (1.0 - (2.0 * PI * (phase - offset)).cos()) / 2.0
+            }
+        };
+        Percent::from_f64_clamped(level * 100.0)
+    }
+}
+
+// === system idle time ===
+
+/// Returns how long the system has been idle.
+///
+/// On Linux, this uses the idle hints that desktop environments report to
+/// `systemd-logind`. The system is considered idle only if every user session
+/// is idle, and if no users are logged in at all, it is always idle.
+#[cfg(target_os = "linux")]
+async fn system_idle_time() -> miette::Result<Duration> {
+    use std::time::SystemTime;
+
+    let sessions = loginctl(&["list-sessions", "--no-legend"]).await?;
+    let now = SystemTime::now();
+    let mut idle_time = Duration::MAX;
+    for session in sessions
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+    {
+        let props = loginctl(&[
+            "show-session",
+            session,
+            "-p",
+            "Class",
+            "-p",
+            "IdleHint",
+            "-p",
+            "IdleSinceHint",
+        ])
+        .await?;
+        let prop = |name: &str| {
+            props
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+        };
+
+        // Ignore greeters, lock screens, and background sessions.
+        if prop("Class") != Some("user") {
+            continue;
+        }
+
+        if prop("IdleHint") != Some("yes") {
+            return Ok(Duration::ZERO);
+        }
+
+        let since = prop("IdleSinceHint")
+            .and_then(|usecs| usecs.parse::<u64>().ok())
+            .map(|usecs| SystemTime::UNIX_EPOCH + Duration::from_micros(usecs))
+            .ok_or_else(|| miette::miette!("session {session} has no IdleSinceHint"))?;
+        idle_time = idle_time.min(now.duration_since(since).unwrap_or_default());
+    }
+
+    Ok(idle_time)
+}
+
+#[cfg(target_os = "linux")]
+async fn loginctl(args: &[&str]) -> miette::Result<String> {
+    let output = tokio::process::Command::new("loginctl")
+        .args(args)
+        .output()
+        .await
+        .into_diagnostic()
+        .context("failed to run `loginctl`")?;
+    if !output.status.success() {
+        miette::bail!("`loginctl {}` failed ({})", args.join(" "), output.status);
+    }
+    String::from_utf8(output.stdout)
+        .into_diagnostic()
+        .context("`loginctl` output was not UTF-8")
+}
+
+/// Returns how long the system has been idle.
+///
+/// On macOS, this reads the `HIDIdleTime` property of the `IOHIDSystem`
+/// service, which is the time since the last keyboard or mouse input, in
+/// nanoseconds.
+#[cfg(target_os = "macos")]
+async fn system_idle_time() -> miette::Result<Duration> {
+    let output = tokio::process::Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .await
+        .into_diagnostic()
+        .context("failed to run `ioreg`")?;
+    if !output.status.success() {
+        miette::bail!("`ioreg` failed ({})", output.status);
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| {
+            let (_, nanos) = line.split_once("\"HIDIdleTime\" = ")?;
+            nanos.trim().parse::<u64>().ok()
+        })
+        .map(Duration::from_nanos)
+        .ok_or_else(|| miette::miette!("no HIDIdleTime in `ioreg` output"))
+}
+
+/// Returns how long the system has been idle.
+///
+/// On Windows, this is the time since the last input event in the current
+/// session. Note that services run in a separate session, so this will never
+/// report the system as idle when `vupdated` is running as a service.
+#[cfg(windows)]
+async fn system_idle_time() -> miette::Result<Duration> {
+    use windows_sys::Win32::{
+        System::SystemInformation::GetTickCount,
+        UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO},
+    };
+
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    // Safety: `info` is a valid, initialized `LASTINPUTINFO` with `cbSize`
+    // set, as `GetLastInputInfo` requires.
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return Err(std::io::Error::last_os_error())
+            .into_diagnostic()
+            .context("GetLastInputInfo failed");
+    }
+    // Safety: `GetTickCount` has no preconditions.
+    let now = unsafe { GetTickCount() };
+    // The tick count wraps around every 49.7 days.
+    Ok(Duration::from_millis(now.wrapping_sub(info.dwTime).into()))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+async fn system_idle_time() -> miette::Result<Duration> {
+    miette::bail!("detecting system idle time is not supported on this platform")
+}
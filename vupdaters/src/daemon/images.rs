@@ -0,0 +1,168 @@
+//! Deduplication of dial image uploads.
+//!
+//! Uploading an image to a dial is slow, and rewrites the dial's flash
+//! storage, so `vupdated` records a hash of the image it most recently uploaded
+//! to each dial, and skips uploading an image that the dial already has. The
+//! hashes are persisted to a small state file, so that restarting the daemon
+//! doesn't re-upload every dial's image.
+use camino::{Utf8Path, Utf8PathBuf};
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use vu_api::client::Dial;
+
+/// Tracks the image most recently uploaded to each dial.
+#[derive(Debug, Clone, Default)]
+pub(super) struct ImageCache(Arc<Mutex<CacheInner>>);
+
+#[derive(Debug, Default)]
+struct CacheInner {
+    /// The state file to persist uploads to, if any.
+    path: Option<Utf8PathBuf>,
+    state: StateFile,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct StateFile {
+    /// The most recently uploaded image for each dial, by dial UID.
+    #[serde(default)]
+    images: BTreeMap<String, Upload>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Upload {
+    file: String,
+    hash: String,
+}
+
+/// Returns the default path of the state file.
+pub(super) fn default_state_path() -> Utf8PathBuf {
+    directories::BaseDirs::new()
+        .and_then(|dirs| {
+            let path = Utf8Path::from_path(dirs.data_local_dir())?.join("vupdate/state.json");
+            Some(path)
+        })
+        .unwrap_or_else(|| {
+            ["$HOME", ".local", "share", "vupdate", "state.json"]
+                .iter()
+                .collect()
+        })
+}
+
+// === impl ImageCache ===
+
+impl ImageCache {
+    /// Loads the image cache from the state file at `path`.
+    ///
+    /// If the state file doesn't exist or can't be read, this starts with an
+    /// empty cache, so every dial's image will be uploaded once.
+    pub(super) fn load(path: Utf8PathBuf) -> Self {
+        let state = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|error| {
+                tracing::warn!(%error, "failed to parse state file {path}, ignoring it");
+                StateFile::default()
+            }),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => StateFile::default(),
+            Err(error) => {
+                tracing::warn!(%error, "failed to read state file {path}, ignoring it");
+                StateFile::default()
+            }
+        };
+        tracing::debug!(%path, dials = state.images.len(), "loaded image cache");
+        Self(Arc::new(Mutex::new(CacheInner {
+            path: Some(path),
+            state,
+        })))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheInner> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns `true` if `image` is the image most recently uploaded to
+    /// `dial`.
+    pub(super) fn is_current(&self, dial: &Dial, file: &str, image: &[u8]) -> bool {
+        let upload = Upload::new(file, image);
+        self.lock().state.images.get(&dial.id().to_string()) == Some(&upload)
+    }
+
+    /// Records that `image` was uploaded to `dial`.
+    ///
+    /// This is best-effort: if the state file can't be written, the image
+    /// will just be uploaded again the next time the daemon starts.
+    pub(super) fn record(&self, dial: &Dial, file: &str, image: &[u8]) {
+        let mut inner = self.lock();
+        inner
+            .state
+            .images
+            .insert(dial.id().to_string(), Upload::new(file, image));
+        if let Err(error) = inner.persist() {
+            tracing::warn!(%error, "failed to write image cache");
+        }
+    }
+
+    /// Forgets every recorded upload, so that every dial's image will be
+    /// uploaded again.
+    pub(super) fn clear(&self) {
+        let mut inner = self.lock();
+        inner.state.images.clear();
+        if let Err(error) = inner.persist() {
+            tracing::warn!(%error, "failed to write image cache");
+        }
+    }
+}
+
+// === impl CacheInner ===
+
+impl CacheInner {
+    fn persist(&self) -> miette::Result<()> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .into_diagnostic()
+                .with_context(|| format!("failed to create {parent}"))?;
+        }
+        let json = serde_json::to_string_pretty(&self.state).into_diagnostic()?;
+        // Write to a temporary file and rename it into place, so that a crash
+        // mid-write doesn't leave a truncated state file behind.
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)
+            .into_diagnostic()
+            .with_context(|| format!("failed to write {tmp}"))?;
+        std::fs::rename(&tmp, path)
+            .into_diagnostic()
+            .with_context(|| format!("failed to rename {tmp} to {path}"))
+    }
+}
+
+// === impl Upload ===
+
+impl Upload {
+    fn new(file: &str, image: &[u8]) -> Self {
+        Self {
+            file: file.to_owned(),
+            hash: format!("fnv1a64:{:016x}", fnv1a(image)),
+        }
+    }
+}
+
+/// 64-bit FNV-1a.
+///
+/// This doesn't need to be cryptographically secure, but it does need to be
+/// stable across builds, which `std`'s hashers aren't guaranteed to be.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
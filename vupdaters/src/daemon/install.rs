@@ -0,0 +1,284 @@
+//! Installing `vupdated` as a system service.
+//!
+//! The `install-service` subcommand installs `vupdated` using whatever service
+//! manager is native to the current platform: a systemd unit on Linux, a
+//! launchd agent on macOS, or a Windows service. In each case, the service
+//! runs `vupdated` with the config path, profile, VU-Server client settings,
+//! and hotplug settings that `install-service` was invoked with.
+use super::HotplugSettings;
+use crate::cli::ClientArgs;
+use camino::{Utf8Path, Utf8PathBuf};
+use miette::{Context, IntoDiagnostic};
+
+/// Settings for the service being installed.
+#[derive(Debug)]
+pub(super) struct Install<'a> {
+    pub(super) config_path: &'a Utf8Path,
+    pub(super) profile: Option<&'a str>,
+    pub(super) client_args: &'a ClientArgs,
+    pub(super) hotplug: &'a HotplugSettings,
+    /// Install a system-wide service rather than a per-user one.
+    pub(super) system: bool,
+    /// Overwrite an existing service.
+    pub(super) force: bool,
+    /// Start the service after installing it.
+    pub(super) start: bool,
+}
+
+// === impl Install ===
+
+impl Install<'_> {
+    pub(super) fn install(&self) -> miette::Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            self.install_systemd()
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            if self.system {
+                miette::bail!("system-wide launchd daemons are not supported, omit `--system`");
+            }
+            super::macos::install_launchd(self)
+        }
+
+        #[cfg(windows)]
+        {
+            super::service::install(self)
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+        {
+            miette::bail!("installing vupdated as a service is not supported on this platform")
+        }
+    }
+
+    /// Returns the VU-Server API key, which the service will need in order to
+    /// talk to the VU-Server.
+    pub(super) fn api_key(&self) -> miette::Result<&str> {
+        self.client_args.key().ok_or_else(|| {
+            miette::miette!(
+                help =
+                    "set the API key using `--key` or the `VU_DIALS_API_KEY` environment variable",
+                "a VU-Server API key is required to install vupdated as a service"
+            )
+        })
+    }
+
+    /// Returns the command-line arguments the service should run `vupdated`
+    /// with, not including the executable path or the client settings.
+    pub(super) fn args(&self) -> miette::Result<Vec<String>> {
+        let mut args = vec![
+            "--config".to_owned(),
+            absolute_path(self.config_path)?.into_string(),
+        ];
+        if let Some(profile) = self.profile {
+            args.push("--profile".to_owned());
+            args.push(profile.to_owned());
+        }
+        if self.hotplug.enabled {
+            args.push("--hotplug".to_owned());
+            args.push("--hotplug-service".to_owned());
+            args.push(self.hotplug.hotplug_service.clone());
+        }
+        Ok(args)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn install_systemd(&self) -> miette::Result<()> {
+        use std::process::Command;
+
+        const UNIT: &str = "vupdated.service";
+
+        let (unit_dir, env_path) = if self.system {
+            (
+                Utf8PathBuf::from("/etc/systemd/system"),
+                Utf8PathBuf::from("/etc/vupdate/vupdated.env"),
+            )
+        } else {
+            let dirs = directories::BaseDirs::new()
+                .ok_or_else(|| miette::miette!("could not determine home directory"))?;
+            let config_dir = Utf8Path::from_path(dirs.config_dir())
+                .ok_or_else(|| miette::miette!("config directory path is not UTF-8"))?;
+            (
+                config_dir.join("systemd/user"),
+                config_dir.join("vupdate/vupdated.env"),
+            )
+        };
+        let unit_path = unit_dir.join(UNIT);
+
+        if unit_path.exists() && !self.force {
+            return Err(miette::miette!(
+                help = "use `--force` to overwrite it",
+                "a systemd unit already exists at {unit_path}"
+            ));
+        }
+
+        if self.hotplug.enabled && !self.system {
+            tracing::warn!(
+                "restarting the VU-Server service on hotplug events usually requires \
+                a system service; consider passing `--system`"
+            );
+        }
+
+        // The API key goes in a separate environment file that only the owner
+        // can read, rather than in the unit itself, which is world-readable.
+        let env = format!(
+            "VU_DIALS_API_KEY={}\nVU_DIALS_SERVER_ADDR={}\n",
+            self.api_key()?,
+            self.client_args.server(),
+        );
+        write_private_file(&env_path, &env)?;
+        tracing::info!("wrote API key environment file to {env_path}");
+
+        let exec_start = std::iter::once(current_exe()?.into_string())
+            .chain(self.args()?)
+            .map(|arg| systemd_quote(&arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let (after, wanted_by) = if self.system {
+            ("network-online.target", "multi-user.target")
+        } else {
+            ("network.target", "default.target")
+        };
+        let unit = format!(
+            "[Unit]\n\
+            Description=vupdated: VU-1 dial update daemon\n\
+            Wants={after}\n\
+            After={after}\n\
+            \n\
+            [Service]\n\
+            Type=simple\n\
+            ExecStart={exec_start}\n\
+            EnvironmentFile={env_path}\n\
+            Restart=on-failure\n\
+            RestartSec=5\n\
+            \n\
+            [Install]\n\
+            WantedBy={wanted_by}\n"
+        );
+        write_file(&unit_path, |path| std::fs::write(path, &unit))?;
+        tracing::info!("wrote systemd unit to {unit_path}");
+
+        let scope = if self.system { "--system" } else { "--user" };
+        if self.start {
+            for args in [&["daemon-reload"][..], &["enable", "--now", UNIT]] {
+                let status = Command::new("systemctl")
+                    .arg(scope)
+                    .args(args)
+                    .status()
+                    .into_diagnostic()
+                    .context("failed to run `systemctl`")?;
+                if !status.success() {
+                    miette::bail!("`systemctl {scope} {}` failed ({status})", args.join(" "));
+                }
+            }
+            tracing::info!("started systemd unit {UNIT}");
+        } else {
+            tracing::info!(
+                "run `systemctl {scope} daemon-reload && systemctl {scope} enable --now {UNIT}` \
+                to start vupdated"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the path to the currently running `vupdated` executable.
+pub(super) fn current_exe() -> miette::Result<Utf8PathBuf> {
+    let exe = std::env::current_exe()
+        .into_diagnostic()
+        .context("failed to determine path to the vupdated executable")?;
+    Utf8PathBuf::from_path_buf(exe)
+        .map_err(|path| miette::miette!("vupdated path {} is not UTF-8", path.display()))
+}
+
+/// Resolves `path` relative to the current directory.
+///
+/// Service managers don't run `vupdated` in the directory we're running in, so
+/// any paths passed to the service must be absolute.
+pub(super) fn absolute_path(path: &Utf8Path) -> miette::Result<Utf8PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    let cwd = std::env::current_dir()
+        .into_diagnostic()
+        .context("failed to determine current directory")?;
+    Ok(Utf8PathBuf::from_path_buf(cwd)
+        .map_err(|path| miette::miette!("current directory {} is not UTF-8", path.display()))?
+        .join(path))
+}
+
+/// Writes `contents` to `path`, creating its parent directory if needed, so
+/// that only the file's owner can read it.
+///
+/// This is used for files containing the VU-Server API key.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub(super) fn write_private_file(path: &Utf8Path, contents: &str) -> miette::Result<()> {
+    use std::{
+        fs::Permissions,
+        io::Write,
+        os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .into_diagnostic()
+            .with_context(|| format!("failed to create {parent}"))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .into_diagnostic()
+        .with_context(|| format!("failed to open {path}"))?;
+    // The mode passed to `open` only applies if the file is created, so an
+    // existing file has to be locked down before the key is written to it.
+    file.set_permissions(Permissions::from_mode(0o600))
+        .into_diagnostic()
+        .with_context(|| format!("failed to set permissions on {path}"))?;
+    file.write_all(contents.as_bytes())
+        .into_diagnostic()
+        .with_context(|| format!("failed to write {path}"))
+}
+
+#[cfg(target_os = "linux")]
+fn write_file(
+    path: &Utf8Path,
+    write: impl FnOnce(&Utf8Path) -> std::io::Result<()>,
+) -> miette::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .into_diagnostic()
+            .with_context(|| format!("failed to create {parent}"))?;
+    }
+    write(path)
+        .into_diagnostic()
+        .with_context(|| format!("failed to write {path}"))
+}
+
+/// Quotes an argument for a systemd `ExecStart=` line.
+///
+/// See the "Command lines" section of `man systemd.service`.
+#[cfg(target_os = "linux")]
+fn systemd_quote(arg: &str) -> String {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            // `%` introduces a specifier, and `$` an environment variable.
+            '%' => quoted.push_str("%%"),
+            '$' => quoted.push_str("$$"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
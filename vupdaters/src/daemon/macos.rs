@@ -0,0 +1,127 @@
+//! macOS-specific functionality.
+use super::install::{self, Install};
+use camino::Utf8Path;
+use miette::{Context, IntoDiagnostic};
+use serde::Serialize;
+use std::{collections::BTreeMap, process::Command};
+
+/// The launchd label for the `vupdated` agent.
+const LABEL: &str = "website.elizas.vupdated";
+
+/// A launchd agent property list.
+///
+/// See `man launchd.plist` for details.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct LaunchAgent<'a> {
+    label: &'a str,
+    program_arguments: Vec<String>,
+    environment_variables: BTreeMap<&'a str, String>,
+    run_at_load: bool,
+    keep_alive: bool,
+    process_type: &'a str,
+    standard_out_path: String,
+    standard_error_path: String,
+}
+
+/// Writes a launchd agent that runs `vupdated` with the provided settings
+/// when the current user logs in.
+pub(super) fn install_launchd(install: &Install<'_>) -> miette::Result<()> {
+    let dirs = directories::BaseDirs::new()
+        .ok_or_else(|| miette::miette!("could not determine home directory"))?;
+    let home = Utf8Path::from_path(dirs.home_dir())
+        .ok_or_else(|| miette::miette!("home directory path is not UTF-8"))?;
+    let plist_path = home
+        .join("Library/LaunchAgents")
+        .join(format!("{LABEL}.plist"));
+    let log_path = home.join("Library/Logs/vupdated.log");
+
+    if plist_path.exists() && !install.force {
+        return Err(miette::miette!(
+            help = "use `--force` to overwrite it",
+            "a launchd agent already exists at {plist_path}"
+        ));
+    }
+
+    // The plist is readable by anyone, so the API key goes in a separate file
+    // that only the current user can read, and the agent passes it to
+    // `vupdated` with `--key-file`.
+    let data_dir = Utf8Path::from_path(dirs.data_dir())
+        .ok_or_else(|| miette::miette!("application support directory path is not UTF-8"))?;
+    let key_path = data_dir.join("vupdate/api-key");
+    install::write_private_file(&key_path, install.api_key()?)?;
+    tracing::info!("wrote API key to {key_path}");
+
+    let program_arguments = std::iter::once(install::current_exe()?.into_string())
+        .chain(install.args()?)
+        .chain(["--key-file".to_owned(), key_path.into_string()])
+        .collect();
+
+    let environment_variables = BTreeMap::from([(
+        "VU_DIALS_SERVER_ADDR",
+        install.client_args.server().to_string(),
+    )]);
+
+    let agent = LaunchAgent {
+        label: LABEL,
+        program_arguments,
+        environment_variables,
+        run_at_load: true,
+        keep_alive: true,
+        process_type: "Background",
+        standard_out_path: log_path.to_string(),
+        standard_error_path: log_path.into_string(),
+    };
+
+    if let Some(parent) = plist_path.parent() {
+        std::fs::create_dir_all(parent)
+            .into_diagnostic()
+            .with_context(|| format!("failed to create {parent}"))?;
+    }
+    plist::to_file_xml(&plist_path, &agent)
+        .into_diagnostic()
+        .with_context(|| format!("failed to write launchd agent to {plist_path}"))?;
+    tracing::info!("wrote launchd agent to {plist_path}");
+
+    if install.start {
+        let status = Command::new("launchctl")
+            .args(["load", "-w", plist_path.as_str()])
+            .status()
+            .into_diagnostic()
+            .context("failed to run `launchctl`")?;
+        if !status.success() {
+            miette::bail!("`launchctl load {plist_path}` failed ({status})");
+        }
+        tracing::info!("loaded launchd agent {LABEL}");
+    } else {
+        tracing::info!("run `launchctl load -w {plist_path}` to start vupdated");
+    }
+
+    Ok(())
+}
+
+/// Returns the remaining battery percentage, as reported by `pmset`.
+///
+/// `systemstat` doesn't support reading battery status on macOS, so shell out
+/// to `pmset -g batt`, which prints something like:
+///
+/// ```text
+/// Now drawing from 'Battery Power'
+///  -InternalBattery-0 (id=1234567)    95%; discharging; 4:20 remaining present: true
+/// ```
+pub(super) fn battery_percent() -> miette::Result<f32> {
+    let output = Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .into_diagnostic()
+        .context("failed to run `pmset`")?;
+    if !output.status.success() {
+        miette::bail!("`pmset -g batt` failed ({})", output.status);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .split_whitespace()
+        .find_map(|word| word.strip_suffix("%;")?.parse::<f32>().ok())
+        .ok_or_else(|| miette::miette!("no battery found in `pmset` output"))
+}
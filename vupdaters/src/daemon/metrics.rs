@@ -0,0 +1,681 @@
+//! Sources of metric data to display on dials.
+//!
+//! Each [`Metric`] that can be configured for a dial is implemented by a
+//! [`MetricSource`], which samples the current value of the metric, and
+//! provides metadata such as the default dial name and background image.
+//! [`Metric::source`] constructs the source for each [`Metric`], so that the
+//! dial managers don't need to know anything about individual metrics.
+//!
+//! To add a new metric, add a variant to [`Metric`], implement
+//! [`MetricSource`] for it, and construct it in [`Metric::source`]. Metrics that
+//! aren't built in to the daemon can be provided by external [`plugin`]s.
+//!
+//! Most metrics are read using `systemstat`, except on Windows, where they're
+//! read from performance counters instead. If the `sysinfo` feature is
+//! enabled, the config file may select the [`sysinfo`](self::sysinfo) backend
+//! instead. On Linux, CPU load and memory usage may also be read from the
+//! [`cgroup`] that `vupdated` is running in, when it's running in a container.
+use super::{
+    agent::Remotes,
+    config::{MetricBackend, MetricScope, RetryConfig},
+    Metric,
+};
+use miette::{Context, IntoDiagnostic};
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+use systemstat::Platform;
+#[cfg(not(windows))]
+use systemstat::{CPULoad, DelayedMeasurement};
+use tokio::{sync::watch, task::JoinSet};
+use vu_api::dial::Percent;
+
+mod plugin;
+
+pub(super) type SampleFuture<'a> =
+    Pin<Box<dyn Future<Output = miette::Result<Percent>> + Send + 'a>>;
+
+/// A source of values to display on a dial.
+pub(super) trait MetricSource: Send {
+    /// Returns the default name for a dial displaying this metric.
+    fn name(&self) -> &str;
+
+    /// Returns the background image for a dial displaying this metric, if
+    /// there is one.
+    fn image(&self) -> Option<&'static ImgFile> {
+        None
+    }
+
+    /// Returns the unit of the metric's values, for display on the dial.
+    fn unit(&self) -> &str {
+        "%"
+    }
+
+    /// Returns `true` if this metric can be read on the current platform.
+    fn is_supported(&self) -> bool {
+        true
+    }
+
+    /// Samples the current value of the metric.
+    ///
+    /// This is called once per dial update interval.
+    fn sample(&mut self) -> SampleFuture<'_>;
+}
+
+/// Samples each metric once per tick, and shares the samples between all of
+/// the dials displaying that metric.
+///
+/// Dials subscribe to a metric using [`Sampler::subscribe`], and once all of
+/// the dials have subscribed, [`Sampler::spawn`] spawns a single task for each
+/// metric, which samples it at the shortest update interval of the dials
+/// displaying it.
+pub(super) struct Sampler {
+    metrics: Vec<SharedMetric>,
+    remotes: Remotes,
+    options: SourceOptions,
+}
+
+/// Configures how the built-in metrics are read.
+#[derive(Debug, Copy, Clone)]
+pub(super) struct SourceOptions {
+    pub(super) backend: MetricBackend,
+    pub(super) scope: MetricScope,
+}
+
+struct SharedMetric {
+    metric: Metric,
+    interval: Duration,
+    source: Box<dyn MetricSource>,
+    samples: watch::Sender<Option<Sample>>,
+}
+
+pub(super) type Sample = Result<Percent, Arc<miette::Report>>;
+
+/// Error returned by a [`MetricSource`] when it has no value to display right
+/// now, but isn't failing, such as when the agent sampling a remote metric is
+/// offline, or the system has no CPU temperature sensor.
+///
+/// Dial managers log these and skip the update, but they don't count towards
+/// the dial's error budget.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+#[error("{0}")]
+pub(super) struct Unavailable(pub(super) String);
+
+/// A [`MetricSource`] that returns the most recent sample taken by a
+/// [`Sampler`] task.
+pub(super) struct Shared {
+    name: String,
+    image: Option<&'static ImgFile>,
+    unit: String,
+    is_supported: bool,
+    samples: watch::Receiver<Option<Sample>>,
+}
+
+/// A single metric, sampled on demand, outside of the daemon.
+///
+/// This lets `dialctl gauge` display a metric on a dial without a config
+/// file, using the same sources as the daemon.
+pub struct LocalMetric {
+    source: Box<dyn MetricSource>,
+}
+
+pub(super) struct ImgFile {
+    pub(super) name: &'static str,
+    pub(super) image: &'static [u8],
+}
+
+macro_rules! imgfile {
+    ($name: literal) => {
+        ImgFile {
+            name: $name,
+            image: include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/", $name)),
+        }
+    };
+}
+
+#[cfg(target_os = "linux")]
+mod cgroup;
+#[cfg(feature = "sysinfo")]
+mod sysinfo;
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+use self::windows::{CpuLoad, CpuTemp, DiskUsage, Memory, Swap};
+
+// === impl Metric ===
+
+impl Metric {
+    /// Returns a new [`MetricSource`] for this metric, read according to
+    /// `options`.
+    ///
+    /// Sources that may need to be restarted, such as [plugins](plugin), wait
+    /// between restarts according to the provided `backoff`.
+    pub(super) fn source(
+        &self,
+        options: SourceOptions,
+        backoff: &backoff::ExponentialBackoffBuilder,
+    ) -> Box<dyn MetricSource> {
+        let source: Box<dyn MetricSource> = match self {
+            Metric::CpuLoad => Box::<CpuLoad>::default(),
+            Metric::Mem => Box::<Memory>::default(),
+            Metric::DiskUsage => Box::<DiskUsage>::default(),
+            Metric::CpuTemp => Box::<CpuTemp>::default(),
+            Metric::Swap => Box::<Swap>::default(),
+            Metric::Battery => Box::<Battery>::default(),
+            Metric::Plugin(config) => {
+                return Box::new(plugin::Plugin::new(config.clone(), backoff))
+            }
+        };
+        let source = match options.backend {
+            MetricBackend::Native => source,
+            #[cfg(feature = "sysinfo")]
+            MetricBackend::Sysinfo => sysinfo::source(self, source),
+            // Configs that select `sysinfo` when it isn't enabled are rejected
+            // by `SourceOptions::ensure_supported`.
+            #[cfg(not(feature = "sysinfo"))]
+            MetricBackend::Sysinfo => source,
+        };
+        #[cfg(target_os = "linux")]
+        let source = cgroup::source(self, options.scope, source);
+        source
+    }
+
+    /// Returns `true` if this metric can be read on the current platform.
+    pub(super) fn is_supported(&self) -> bool {
+        self.describe().is_supported()
+    }
+
+    /// Returns a short, stable identifier for this metric, which is used to
+    /// refer to metrics sampled on other hosts.
+    pub(super) fn key(&self) -> String {
+        use clap::ValueEnum;
+        match self.to_possible_value() {
+            Some(value) => value.get_name().to_owned(),
+            // Plugins aren't command-line values, so identify them by name.
+            None => format!("plugin-{}", self.dial_name()),
+        }
+    }
+
+    pub(super) fn dial_name(&self) -> String {
+        self.describe().name().to_owned()
+    }
+
+    /// Returns a [`MetricSource`] for this metric that's only used for its
+    /// metadata, and won't be sampled.
+    fn describe(&self) -> Box<dyn MetricSource> {
+        let options = SourceOptions {
+            backend: MetricBackend::Native,
+            scope: MetricScope::Host,
+        };
+        self.source(options, &RetryConfig::default().backoff_builder())
+    }
+}
+
+// === impl LocalMetric ===
+
+impl LocalMetric {
+    /// Returns a new `LocalMetric` that samples `metric` using the default
+    /// [`SourceOptions`].
+    pub fn new(metric: &Metric) -> miette::Result<Self> {
+        let options = SourceOptions {
+            backend: MetricBackend::default(),
+            scope: MetricScope::default(),
+        };
+        let source = metric.source(options, &RetryConfig::default().backoff_builder());
+        if !source.is_supported() {
+            miette::bail!(
+                "the {} metric is not supported on this platform",
+                metric.key()
+            );
+        }
+        Ok(Self { source })
+    }
+
+    /// Returns the metric's human-readable name.
+    pub fn name(&self) -> &str {
+        self.source.name()
+    }
+
+    /// Samples the current value of the metric.
+    pub async fn sample(&mut self) -> miette::Result<Percent> {
+        self.source.sample().await
+    }
+}
+
+// === impl SourceOptions ===
+
+impl SourceOptions {
+    /// Returns an error if these options can't be used on this system.
+    pub(super) fn ensure_supported(&self) -> miette::Result<()> {
+        if self.backend == MetricBackend::Sysinfo && !cfg!(feature = "sysinfo") {
+            return Err(miette::miette!(
+                help = "rebuild vupdated with `--features sysinfo`, or remove `metric-backend`",
+                "the config file selects the `sysinfo` metric backend, but vupdated was built without it"
+            ));
+        }
+        if self.scope == MetricScope::Container {
+            #[cfg(target_os = "linux")]
+            cgroup::cgroup()?;
+            #[cfg(not(target_os = "linux"))]
+            miette::bail!("`metric-scope = \"container\"` is only supported on Linux");
+        }
+        Ok(())
+    }
+}
+
+// === impl Sampler ===
+
+impl Sampler {
+    pub(super) fn new(remotes: Remotes, options: SourceOptions) -> Self {
+        Self {
+            metrics: Vec::new(),
+            remotes,
+            options,
+        }
+    }
+
+    /// Returns a [`MetricSource`] for a dial that displays `metric` every
+    /// `interval`.
+    ///
+    /// If `host` is set, the metric is sampled by `vupdated agent` on that
+    /// host, rather than locally.
+    pub(super) fn subscribe(
+        &mut self,
+        metric: &Metric,
+        host: Option<&str>,
+        interval: Duration,
+        backoff: &backoff::ExponentialBackoffBuilder,
+    ) -> Shared {
+        if let Some(host) = host {
+            let source = metric.describe();
+            return Shared {
+                name: source.name().to_owned(),
+                image: source.image(),
+                unit: source.unit().to_owned(),
+                // Whether the metric is supported is up to the remote host.
+                is_supported: true,
+                samples: self.remotes.subscribe(host, &metric.key()),
+            };
+        }
+
+        let shared = match self.metrics.iter_mut().find(|m| &m.metric == metric) {
+            Some(shared) => {
+                shared.interval = shared.interval.min(interval);
+                shared
+            }
+            None => {
+                let (samples, _) = watch::channel(None);
+                self.metrics.push(SharedMetric {
+                    metric: metric.clone(),
+                    interval,
+                    source: metric.source(self.options, backoff),
+                    samples,
+                });
+                self.metrics.last_mut().expect("we just pushed a metric")
+            }
+        };
+        Shared {
+            name: shared.source.name().to_owned(),
+            image: shared.source.image(),
+            unit: shared.source.unit().to_owned(),
+            is_supported: shared.source.is_supported(),
+            samples: shared.samples.subscribe(),
+        }
+    }
+
+    /// Spawns a sampling task for each subscribed metric.
+    pub(super) fn spawn(self, tasks: &mut JoinSet<miette::Result<()>>) {
+        for shared in self.metrics {
+            // Unsupported metrics can't be sampled, and the dials displaying
+            // them won't try to.
+            if shared.source.is_supported() {
+                tasks.spawn(shared.run());
+            }
+        }
+    }
+}
+
+impl SharedMetric {
+    async fn run(self) -> miette::Result<()> {
+        let Self {
+            metric,
+            interval,
+            mut source,
+            samples,
+        } = self;
+        tracing::debug!("sampling {metric:?} every {interval:?}");
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let sample = source.sample().await.map_err(|error| {
+                tracing::debug!(%error, "failed to sample {metric:?}");
+                Arc::new(error)
+            });
+            samples.send_replace(Some(sample));
+        }
+    }
+}
+
+impl MetricSource for Shared {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        self.image
+    }
+
+    fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    fn is_supported(&self) -> bool {
+        self.is_supported
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            // Wait for the first sample to be taken, and then always use the
+            // most recent one.
+            let sample = self
+                .samples
+                .wait_for(Option::is_some)
+                .await
+                .map_err(|_| miette::miette!("{} sampler stopped", self.name))?;
+            match sample.as_ref().expect("we just waited for a sample") {
+                Ok(value) => Ok(*value),
+                Err(error) if error.downcast_ref::<Unavailable>().is_some() => {
+                    Err(Unavailable(error.to_string()).into())
+                }
+                Err(error) => Err(miette::miette!("{error}")),
+            }
+        })
+    }
+}
+
+/// Runs a blocking `systemstat` call on Tokio's blocking thread pool.
+///
+/// Some `systemstat` calls can be slow (such as enumerating mounts on a system
+/// with network filesystems), and shouldn't stall the runtime that's also
+/// sending updates to the VU-Server and handling signals.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce(systemstat::System) -> miette::Result<T> + Send + 'static,
+) -> miette::Result<T> {
+    spawn_blocking(move || f(systemstat::System::new())).await
+}
+
+/// Runs a blocking call on Tokio's blocking thread pool.
+async fn spawn_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> miette::Result<T> + Send + 'static,
+) -> miette::Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .into_diagnostic()
+        .context("metric sampling task panicked")?
+}
+
+// === CPU load ===
+
+#[cfg(not(windows))]
+#[derive(Default)]
+struct CpuLoad {
+    /// The in-progress measurement started when the previous sample was taken.
+    measurement: Option<DelayedMeasurement<CPULoad>>,
+}
+
+#[cfg(not(windows))]
+impl CpuLoad {
+    /// How long to measure CPU load for when there's no previous measurement.
+    const FIRST_MEASUREMENT: Duration = Duration::from_secs(1);
+
+    fn start_measurement(
+        system: &systemstat::System,
+    ) -> miette::Result<DelayedMeasurement<CPULoad>> {
+        system
+            .cpu_load_aggregate()
+            .into_diagnostic()
+            .context("failed to start load aggregate measurement")
+    }
+}
+
+#[cfg(not(windows))]
+impl MetricSource for CpuLoad {
+    fn name(&self) -> &str {
+        "CPU Load"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("cpu_load.png");
+        Some(&IMG)
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            // CPU load is measured over the time between samples, so each
+            // sample completes the measurement started by the previous one.
+            let measurement = match self.measurement.take() {
+                Some(measurement) => measurement,
+                None => {
+                    let measurement = blocking(|system| Self::start_measurement(&system)).await?;
+                    tokio::time::sleep(Self::FIRST_MEASUREMENT).await;
+                    measurement
+                }
+            };
+            let (load, next) = blocking(move |system| {
+                let load = measurement
+                    .done()
+                    .into_diagnostic()
+                    .context("failed to read load aggregate")?;
+                Ok((load, Self::start_measurement(&system)?))
+            })
+            .await?;
+            self.measurement = Some(next);
+
+            let percent = (load.user + load.system + load.interrupt + load.nice) * 100.0;
+            tracing::debug!("CPU Load: {percent}%");
+            Ok(Percent::from_f64_clamped(f64::from(percent)))
+        })
+    }
+}
+
+// === memory usage ===
+
+#[cfg(not(windows))]
+#[derive(Default)]
+struct Memory;
+
+#[cfg(not(windows))]
+impl MetricSource for Memory {
+    fn name(&self) -> &str {
+        "Memory Usage"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("mem.png");
+        Some(&IMG)
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let systemstat::Memory { total, free, .. } = blocking(|system| {
+                system
+                    .memory()
+                    .into_diagnostic()
+                    .context("failed to read memory usage")
+            })
+            .await?;
+            let percent_free = free.0 / (total.0 / 100);
+            let percent_used = 100 - percent_free;
+            tracing::debug!("Memory: {percent_used}% used");
+            Ok(Percent::from_f64_clamped(percent_used as f64))
+        })
+    }
+}
+
+// === swap usage ===
+
+#[cfg(not(windows))]
+#[derive(Default)]
+struct Swap;
+
+#[cfg(not(windows))]
+impl MetricSource for Swap {
+    fn name(&self) -> &str {
+        "Swap Usage"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("swap.png");
+        Some(&IMG)
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let systemstat::Swap { total, free, .. } = blocking(|system| {
+                system
+                    .swap()
+                    .into_diagnostic()
+                    .context("failed to read swap usage")
+            })
+            .await?;
+            let percent_free = free.0 / (total.0 / 100);
+            let percent_used = 100 - percent_free;
+            tracing::debug!("Swap: {percent_used}% used");
+            Ok(Percent::from_f64_clamped(percent_used as f64))
+        })
+    }
+}
+
+// === CPU temperature ===
+
+#[cfg(not(windows))]
+#[derive(Default)]
+struct CpuTemp;
+
+#[cfg(not(windows))]
+impl MetricSource for CpuTemp {
+    fn name(&self) -> &str {
+        "CPU Temperature"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("cpu_temp.png");
+        Some(&IMG)
+    }
+
+    fn unit(&self) -> &str {
+        "°C"
+    }
+
+    fn is_supported(&self) -> bool {
+        // `systemstat` can only read CPU temperatures on Linux and some BSDs.
+        cfg!(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly"
+        ))
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let temp = blocking(|system| {
+                system
+                    .cpu_temp()
+                    .into_diagnostic()
+                    .context("failed to read CPU temp")
+            })
+            .await
+            .map_err(cpu_temp_unavailable)?;
+            tracing::debug!("CPU temp: {temp}°C");
+            Ok(Percent::from_f64_clamped(f64::from(temp)))
+        })
+    }
+}
+
+/// Many systems don't expose a CPU temperature sensor at all, or only expose
+/// it some of the time, so failing to read it is logged, but doesn't count
+/// towards the dial's error budget.
+fn cpu_temp_unavailable(error: miette::Report) -> miette::Report {
+    tracing::warn!(%error, "failed to read CPU temp");
+    Unavailable(error.to_string()).into()
+}
+
+// === battery ===
+
+#[derive(Default)]
+struct Battery;
+
+impl MetricSource for Battery {
+    fn name(&self) -> &str {
+        "Battery Remaining"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("battery.png");
+        Some(&IMG)
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let remaining = blocking(|system| {
+                let battery = system
+                    .battery_life()
+                    .map(|battery| battery.remaining_capacity * 100.0)
+                    .into_diagnostic();
+                // `systemstat` doesn't support reading battery status on
+                // macOS, so ask `pmset` instead.
+                #[cfg(target_os = "macos")]
+                let battery = battery.or_else(|_| super::macos::battery_percent());
+                battery.context("failed to read battery status")
+            })
+            .await?;
+            tracing::debug!("Battery: {remaining}% remaining");
+            Ok(Percent::from_f64_clamped(f64::from(remaining)))
+        })
+    }
+}
+
+// === disk usage ===
+
+#[cfg(not(windows))]
+#[derive(Default)]
+struct DiskUsage;
+
+#[cfg(not(windows))]
+impl MetricSource for DiskUsage {
+    fn name(&self) -> &str {
+        "Disk Usage"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("disk.png");
+        Some(&IMG)
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let filesystems = blocking(|system| {
+                system
+                    .mounts()
+                    .into_diagnostic()
+                    .context("failed to read mounts")
+            })
+            .await?;
+            let (total, free) = filesystems.iter().fold((0, 0), |(total, free), fs| {
+                let total = total + fs.total.as_u64();
+                let free = free + fs.free.as_u64();
+                tracing::trace!(
+                    "filesystem {} has {} bytes free, {} bytes total",
+                    fs.fs_mounted_on,
+                    fs.free,
+                    fs.total
+                );
+                (total, free)
+            });
+
+            let percent_free = free / (total / 100);
+            let percent_used = 100 - percent_free;
+            tracing::debug!("Disk: {percent_used}% used");
+            Ok(Percent::from_f64_clamped(percent_used as f64))
+        })
+    }
+}
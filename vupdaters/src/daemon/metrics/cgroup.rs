@@ -0,0 +1,266 @@
+//! Container metrics, read from cgroup v2.
+//!
+//! Inside a container, the host-wide CPU load and memory usage aren't very
+//! meaningful: the container may be limited to a fraction of the host's CPUs
+//! and memory, and other containers' usage is counted too. These sources
+//! instead read the usage and limits of the cgroup that `vupdated` is running
+//! in, so that dials show how much of the container's budget is in use.
+use super::{spawn_blocking, ImgFile, Metric, MetricSource, SampleFuture};
+use crate::daemon::config::MetricScope;
+use miette::{Context, IntoDiagnostic};
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+use systemstat::Platform;
+use vu_api::dial::Percent;
+
+/// Where the cgroup v2 hierarchy is mounted.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// A [`MetricSource`] that reads a metric from `vupdated`'s cgroup.
+struct Source {
+    /// The host-wide source for this metric, which provides its metadata.
+    native: Box<dyn MetricSource>,
+    cgroup: &'static Path,
+    kind: Kind,
+}
+
+enum Kind {
+    CpuLoad {
+        /// The cgroup's total CPU time when the previous sample was taken.
+        last: Option<(Duration, Instant)>,
+    },
+    Mem,
+}
+
+/// Returns a [`MetricSource`] that reads `metric` from `vupdated`'s cgroup if
+/// `scope` calls for it, or `native` otherwise.
+pub(super) fn source(
+    metric: &Metric,
+    scope: MetricScope,
+    native: Box<dyn MetricSource>,
+) -> Box<dyn MetricSource> {
+    let kind = match metric {
+        Metric::CpuLoad => Kind::CpuLoad { last: None },
+        Metric::Mem => Kind::Mem,
+        _ => return native,
+    };
+    let use_cgroup = match scope {
+        MetricScope::Host => false,
+        MetricScope::Container => true,
+        MetricScope::Auto => in_container(),
+    };
+    if !use_cgroup {
+        return native;
+    }
+    match cgroup() {
+        Ok(cgroup) => Box::new(Source {
+            native,
+            cgroup,
+            kind,
+        }),
+        // A missing cgroup in `container` scope is rejected by
+        // `SourceOptions::ensure_supported`, so this only happens in `auto`.
+        Err(error) => {
+            tracing::warn!(%error, "reading {metric:?} for the whole host");
+            native
+        }
+    }
+}
+
+/// Returns the path to `vupdated`'s cgroup, or an error if cgroup v2 isn't
+/// available.
+pub(super) fn cgroup() -> miette::Result<&'static Path> {
+    static CGROUP: OnceLock<Option<PathBuf>> = OnceLock::new();
+    CGROUP
+        .get_or_init(|| {
+            let path = find_cgroup();
+            match path {
+                Some(ref path) => tracing::debug!("found cgroup at {}", path.display()),
+                None => tracing::debug!("cgroup v2 is not available"),
+            }
+            path
+        })
+        .as_deref()
+        .ok_or_else(|| {
+            miette::miette!(
+                help = "container metrics require cgroup v2 to be mounted at {CGROUP_ROOT}",
+                "could not find vupdated's cgroup"
+            )
+        })
+}
+
+fn find_cgroup() -> Option<PathBuf> {
+    // In cgroup v2, `/proc/self/cgroup` has a single `0::<path>` line.
+    let cgroups = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    let path = cgroups.lines().find_map(|line| line.strip_prefix("0::"))?;
+    let root = Path::new(CGROUP_ROOT);
+    [root.join(path.trim_start_matches('/')), root.to_path_buf()]
+        .into_iter()
+        // If the container doesn't have its own cgroup namespace, the path is
+        // relative to the host's hierarchy, and the container's cgroup is
+        // usually mounted at the root instead.
+        .find(|path| path.join("cgroup.controllers").exists())
+}
+
+/// Returns `true` if `vupdated` appears to be running in a container.
+fn in_container() -> bool {
+    static IN_CONTAINER: OnceLock<bool> = OnceLock::new();
+    *IN_CONTAINER.get_or_init(|| {
+        // Docker, Podman, and Kubernetes each leave different traces, and
+        // systemd-nspawn and LXC set `container` in the environment.
+        let in_container = Path::new("/.dockerenv").exists()
+            || Path::new("/run/.containerenv").exists()
+            || std::env::var_os("container").is_some()
+            || std::env::var_os("KUBERNETES_SERVICE_HOST").is_some();
+        if in_container {
+            tracing::info!("running in a container, reading CPU load and memory usage from cgroup");
+        }
+        in_container
+    })
+}
+
+// === impl Source ===
+
+impl Source {
+    /// How long to measure CPU time for when there's no previous sample.
+    const FIRST_MEASUREMENT: Duration = Duration::from_secs(1);
+
+    async fn cpu_load(&mut self) -> miette::Result<f64> {
+        let cgroup = self.cgroup;
+        let Kind::CpuLoad { ref mut last } = self.kind else {
+            unreachable!("only called for CPU load sources")
+        };
+        let (prev_usage, prev_time) = match last.take() {
+            Some(last) => last,
+            None => {
+                let usage = spawn_blocking(move || cpu_usage(cgroup)).await?;
+                let now = Instant::now();
+                tokio::time::sleep(Self::FIRST_MEASUREMENT).await;
+                (usage, now)
+            }
+        };
+        let (usage, cpus) =
+            spawn_blocking(move || Ok((cpu_usage(cgroup)?, cpu_limit(cgroup)?))).await?;
+        let now = Instant::now();
+        *last = Some((usage, now));
+
+        let elapsed = now.duration_since(prev_time).as_secs_f64() * cpus;
+        let used = usage.saturating_sub(prev_usage).as_secs_f64();
+        Ok((used / elapsed * 100.0).min(100.0))
+    }
+
+    async fn mem(cgroup: &'static Path) -> miette::Result<f64> {
+        spawn_blocking(move || {
+            let current = read_u64(cgroup, "memory.current")?;
+            // Like `docker stats`, don't count page cache that the kernel can
+            // reclaim as being in use.
+            let inactive = read_stat(cgroup, "memory.stat", "inactive_file")?.unwrap_or(0);
+            let used = current.saturating_sub(inactive);
+            let limit = match read_limit(cgroup, "memory.max")? {
+                Some(limit) => limit,
+                // If the cgroup isn't limited, it can use all of the host's
+                // memory.
+                None => systemstat::System::new()
+                    .memory()
+                    .into_diagnostic()
+                    .context("failed to read total memory")?
+                    .total
+                    .as_u64(),
+            };
+            Ok((used as f64 / limit as f64 * 100.0).min(100.0))
+        })
+        .await
+    }
+}
+
+impl MetricSource for Source {
+    fn name(&self) -> &str {
+        self.native.name()
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        self.native.image()
+    }
+
+    fn unit(&self) -> &str {
+        self.native.unit()
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let percent = match self.kind {
+                Kind::CpuLoad { .. } => self.cpu_load().await?,
+                Kind::Mem => Self::mem(self.cgroup).await?,
+            };
+            tracing::debug!("{} (cgroup): {percent:.1}%", self.name());
+            Ok(Percent::from_f64_clamped(percent))
+        })
+    }
+}
+
+/// Returns the total CPU time used by the cgroup.
+fn cpu_usage(cgroup: &Path) -> miette::Result<Duration> {
+    let usec = read_stat(cgroup, "cpu.stat", "usage_usec")?
+        .ok_or_else(|| miette::miette!("cpu.stat has no usage_usec"))?;
+    Ok(Duration::from_micros(usec))
+}
+
+/// Returns the number of CPUs the cgroup may use.
+fn cpu_limit(cgroup: &Path) -> miette::Result<f64> {
+    // `cpu.max` is "<quota> <period>", or "max <period>" if unlimited.
+    let max = read(cgroup, "cpu.max")?;
+    if let Some((quota, period)) = max.trim().split_once(' ') {
+        if let (Ok(quota), Ok(period)) = (quota.parse::<f64>(), period.parse::<f64>()) {
+            return Ok(quota / period);
+        }
+    }
+    let cpus = std::thread::available_parallelism()
+        .into_diagnostic()
+        .context("failed to determine number of CPUs")?;
+    Ok(cpus.get() as f64)
+}
+
+fn read(cgroup: &Path, file: &str) -> miette::Result<String> {
+    let path = cgroup.join(file);
+    std::fs::read_to_string(&path)
+        .into_diagnostic()
+        .with_context(|| format!("failed to read {}", path.display()))
+}
+
+fn read_u64(cgroup: &Path, file: &str) -> miette::Result<u64> {
+    read(cgroup, file)?
+        .trim()
+        .parse()
+        .into_diagnostic()
+        .with_context(|| format!("invalid value in {file}"))
+}
+
+/// Reads a limit, which is either a number or `max`.
+fn read_limit(cgroup: &Path, file: &str) -> miette::Result<Option<u64>> {
+    match read(cgroup, file)?.trim() {
+        "max" => Ok(None),
+        limit => limit
+            .parse()
+            .map(Some)
+            .into_diagnostic()
+            .with_context(|| format!("invalid value in {file}")),
+    }
+}
+
+/// Reads a value from a flat-keyed file, such as `cpu.stat`.
+fn read_stat(cgroup: &Path, file: &str, key: &str) -> miette::Result<Option<u64>> {
+    read(cgroup, file)?
+        .lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(' '))
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .into_diagnostic()
+                .with_context(|| format!("invalid {key} in {file}"))
+        })
+        .transpose()
+}
@@ -0,0 +1,251 @@
+//! External metric plugins.
+//!
+//! A plugin is an executable that provides values for a dial. The daemon runs
+//! the plugin as a child process, and communicates with it by exchanging
+//! newline-delimited JSON messages over the plugin's stdin and stdout. Anything
+//! the plugin writes to stderr is passed through to the daemon's stderr.
+//!
+//! When the plugin starts, the daemon sends it a `hello` message with the
+//! protocol version, and the plugin responds with its own `hello`:
+//!
+//! ```text
+//! -> {"type":"hello","version":1}
+//! <- {"type":"hello","version":1,"name":"my-plugin"}
+//! ```
+//!
+//! Then, each time the dial is updated, the daemon requests a sample, and the
+//! plugin responds with either a value from 0 to 100, or an error:
+//!
+//! ```text
+//! -> {"type":"sample"}
+//! <- {"type":"sample","value":42}
+//! -> {"type":"sample"}
+//! <- {"type":"error","message":"sensor not found"}
+//! ```
+//!
+//! If the plugin exits, stops responding, or sends an invalid message, it is
+//! killed and restarted, waiting between restarts according to the configured
+//! retry backoff.
+use super::{MetricSource, SampleFuture};
+use crate::daemon::config::PluginConfig;
+use backoff::backoff::Backoff;
+use camino::Utf8Path;
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use std::{process::Stdio, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStdin, ChildStdout, Command},
+};
+use vu_api::dial::Percent;
+
+/// The version of the plugin protocol implemented by this daemon.
+const PROTOCOL_VERSION: u32 = 1;
+
+pub(super) struct Plugin {
+    config: PluginConfig,
+    name: String,
+    restarts: backoff::ExponentialBackoff,
+    restart_after: Option<Duration>,
+    process: Option<Process>,
+}
+
+/// A running plugin process.
+struct Process {
+    /// The child process is killed when this is dropped.
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+/// Messages sent from the daemon to a plugin.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Request {
+    Hello { version: u32 },
+    Sample,
+}
+
+/// Messages sent from a plugin to the daemon.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Response {
+    Hello {
+        version: u32,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    Sample {
+        value: Percent,
+    },
+    Error {
+        message: String,
+    },
+}
+
+// === impl Plugin ===
+
+impl Plugin {
+    pub(super) fn new(config: PluginConfig, backoff: &backoff::ExponentialBackoffBuilder) -> Self {
+        let name = config.name.clone().unwrap_or_else(|| {
+            let command = Utf8Path::new(&config.command);
+            command.file_stem().unwrap_or(command.as_str()).to_owned()
+        });
+        Self {
+            config,
+            name,
+            restarts: backoff.build(),
+            restart_after: None,
+            process: None,
+        }
+    }
+
+    async fn start(&mut self) -> miette::Result<&mut Process> {
+        if let Some(delay) = self.restart_after.take() {
+            tracing::info!(plugin = %self.name, "restarting plugin in {delay:?}...");
+            tokio::time::sleep(delay).await;
+        }
+
+        let Self {
+            ref config,
+            ref name,
+            ..
+        } = *self;
+        tracing::info!(plugin = %name, command = %config.command, "starting plugin...");
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .into_diagnostic()
+            .with_context(|| format!("failed to spawn plugin `{}`", config.command))?;
+        let stdin = child.stdin.take().expect("plugin stdin must be piped");
+        let stdout = child.stdout.take().expect("plugin stdout must be piped");
+        let mut process = Process {
+            _child: child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+        };
+
+        let hello = Request::Hello {
+            version: PROTOCOL_VERSION,
+        };
+        match process.request(&hello, config.timeout).await? {
+            Response::Hello {
+                version,
+                name: plugin_name,
+            } if version == PROTOCOL_VERSION => {
+                tracing::info!(plugin = %name, ?plugin_name, "plugin started");
+            }
+            Response::Hello { version, .. } => miette::bail!(
+                "plugin uses protocol version {version}, but only version \
+                {PROTOCOL_VERSION} is supported"
+            ),
+            response => miette::bail!("expected a hello message from plugin, got {response:?}"),
+        }
+
+        Ok(self.process.insert(process))
+    }
+
+    async fn try_sample(&mut self) -> miette::Result<Percent> {
+        let timeout = self.config.timeout;
+        let process = match self.process {
+            Some(ref mut process) => process,
+            None => self.start().await?,
+        };
+        match process.request(&Request::Sample, timeout).await? {
+            Response::Sample { value } => Ok(value),
+            response => miette::bail!("expected a sample from plugin, got {response:?}"),
+        }
+    }
+
+    /// Kills the plugin process, and schedules it to be restarted.
+    fn crashed(&mut self) {
+        self.process = None;
+        // If we've exhausted the retry budget, keep trying at the maximum
+        // interval; the dial manager will give up if sampling keeps failing.
+        self.restart_after = Some(
+            self.restarts
+                .next_backoff()
+                .unwrap_or(self.restarts.max_interval),
+        );
+    }
+}
+
+impl MetricSource for Plugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            match self.try_sample().await {
+                Ok(value) => {
+                    tracing::debug!(plugin = %self.name, "Plugin: {value}");
+                    self.restarts.reset();
+                    Ok(value)
+                }
+                // The plugin is still running, it just couldn't get a value
+                // this time. If it reported an error in response to the
+                // hello, though, it never started, and must be restarted
+                // (with a backoff) like any other failure to start.
+                Err(error)
+                    if self.process.is_some() && error.downcast_ref::<PluginError>().is_some() =>
+                {
+                    Err(error)
+                }
+                Err(error) => {
+                    self.crashed();
+                    Err(error.wrap_err(format!("plugin `{}` failed", self.name)))
+                }
+            }
+        })
+    }
+}
+
+/// An error reported by the plugin itself.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+#[error("plugin reported an error: {message}")]
+#[diagnostic(code(vupdated::PluginError))]
+struct PluginError {
+    message: String,
+}
+
+// === impl Process ===
+
+impl Process {
+    async fn request(&mut self, request: &Request, timeout: Duration) -> miette::Result<Response> {
+        let mut line = serde_json::to_string(request)
+            .into_diagnostic()
+            .context("failed to serialize plugin request")?;
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .await
+            .into_diagnostic()
+            .context("failed to write to plugin")?;
+        self.stdin
+            .flush()
+            .await
+            .into_diagnostic()
+            .context("failed to write to plugin")?;
+
+        let line = tokio::time::timeout(timeout, self.stdout.next_line())
+            .await
+            .map_err(|_| miette::miette!("plugin did not respond within {timeout:?}"))?
+            .into_diagnostic()
+            .context("failed to read from plugin")?
+            .ok_or_else(|| miette::miette!("plugin exited"))?;
+        tracing::trace!(?request, response = %line, "plugin responded");
+        match serde_json::from_str(&line)
+            .into_diagnostic()
+            .with_context(|| format!("invalid message from plugin: {line}"))?
+        {
+            Response::Error { message } => Err(PluginError { message }.into()),
+            response => Ok(response),
+        }
+    }
+}
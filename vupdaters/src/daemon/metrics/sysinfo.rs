@@ -0,0 +1,182 @@
+//! Metric sources backed by the `sysinfo` crate.
+//!
+//! These are used instead of the default sources when the config file sets
+//! `metric-backend = "sysinfo"`. `systemstat` is no longer actively
+//! maintained, and misreports some values on recent Linux kernels and on
+//! macOS, which `sysinfo` reads correctly. Metrics that `sysinfo` can't read,
+//! such as battery status, still use the default sources.
+use super::{spawn_blocking, ImgFile, Metric, MetricSource, SampleFuture};
+use ::sysinfo::{Components, Disks, System};
+use std::time::Duration;
+use vu_api::dial::Percent;
+
+/// Reads a metric's current value from `sysinfo`.
+type Read = fn(&mut Sysinfo) -> miette::Result<f64>;
+
+/// How to read each [`Metric`] that `sysinfo` supports.
+static READERS: &[(Metric, Read)] = &[
+    (Metric::CpuLoad, cpu_load),
+    (Metric::Mem, memory),
+    (Metric::DiskUsage, disk_usage),
+    (Metric::CpuTemp, cpu_temp),
+    (Metric::Swap, swap),
+];
+
+/// A [`MetricSource`] that reads a metric using `sysinfo`.
+struct Source {
+    /// The default source for this metric, which provides its metadata.
+    native: Box<dyn MetricSource>,
+    read: Read,
+    /// The `sysinfo` state, which is created the first time the metric is
+    /// sampled.
+    sysinfo: Option<Box<Sysinfo>>,
+}
+
+/// The `sysinfo` state that's refreshed when sampling a metric.
+///
+/// Each metric only refreshes the parts that it reads.
+#[derive(Default)]
+struct Sysinfo {
+    system: System,
+    disks: Disks,
+    components: Components,
+}
+
+/// Returns a [`MetricSource`] that reads `metric` using `sysinfo`, or `native`
+/// if `sysinfo` can't read that metric.
+pub(super) fn source(metric: &Metric, native: Box<dyn MetricSource>) -> Box<dyn MetricSource> {
+    match READERS.iter().find(|(m, _)| m == metric) {
+        Some(&(_, read)) => Box::new(Source {
+            native,
+            read,
+            sysinfo: None,
+        }),
+        None => native,
+    }
+}
+
+// === impl Source ===
+
+impl Source {
+    /// How long to wait between the first two refreshes.
+    ///
+    /// CPU load is measured over the time between two refreshes, so the first
+    /// refresh can't produce a value.
+    const FIRST_MEASUREMENT: Duration = Duration::from_secs(1);
+}
+
+impl MetricSource for Source {
+    fn name(&self) -> &str {
+        self.native.name()
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        self.native.image()
+    }
+
+    fn unit(&self) -> &str {
+        self.native.unit()
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let read = self.read;
+            let mut sysinfo = match self.sysinfo.take() {
+                Some(sysinfo) => sysinfo,
+                None => {
+                    let sysinfo = spawn_blocking(move || {
+                        let mut sysinfo = Box::<Sysinfo>::default();
+                        read(&mut sysinfo)?;
+                        Ok(sysinfo)
+                    })
+                    .await?;
+                    tokio::time::sleep(Self::FIRST_MEASUREMENT).await;
+                    sysinfo
+                }
+            };
+            let (sysinfo, value) = spawn_blocking(move || {
+                let value = read(&mut sysinfo);
+                Ok((sysinfo, value))
+            })
+            .await?;
+            self.sysinfo = Some(sysinfo);
+
+            let value = value?;
+            tracing::debug!("{}: {value:.1}{}", self.name(), self.unit());
+            Ok(Percent::from_f64_clamped(value))
+        })
+    }
+}
+
+fn cpu_load(sysinfo: &mut Sysinfo) -> miette::Result<f64> {
+    sysinfo.system.refresh_cpu_usage();
+    Ok(sysinfo.system.global_cpu_info().cpu_usage().into())
+}
+
+fn memory(sysinfo: &mut Sysinfo) -> miette::Result<f64> {
+    sysinfo.system.refresh_memory();
+    percent(
+        sysinfo.system.used_memory(),
+        sysinfo.system.total_memory(),
+        "memory",
+    )
+}
+
+fn swap(sysinfo: &mut Sysinfo) -> miette::Result<f64> {
+    sysinfo.system.refresh_memory();
+    percent(
+        sysinfo.system.used_swap(),
+        sysinfo.system.total_swap(),
+        "swap space",
+    )
+}
+
+fn disk_usage(sysinfo: &mut Sysinfo) -> miette::Result<f64> {
+    // Disks may have been mounted or unmounted since the last sample.
+    sysinfo.disks.refresh_list();
+    let disks = sysinfo.disks.list();
+    let (total, available) = disks.iter().fold((0, 0), |(total, available), disk| {
+        tracing::trace!(
+            "disk {} has {} bytes available, {} bytes total",
+            disk.mount_point().display(),
+            disk.available_space(),
+            disk.total_space()
+        );
+        (
+            total + disk.total_space(),
+            available + disk.available_space(),
+        )
+    });
+    percent(total.saturating_sub(available), total, "disks")
+}
+
+fn cpu_temp(sysinfo: &mut Sysinfo) -> miette::Result<f64> {
+    if sysinfo.components.list().is_empty() {
+        sysinfo.components.refresh_list();
+    } else {
+        sysinfo.components.refresh();
+    }
+    // Component labels vary between platforms and drivers, such as
+    // "coretemp Package id 0" or "k10temp Tctl" on Linux, and "PECI CPU" on
+    // macOS.
+    const CPU_LABELS: &[&str] = &["cpu", "package", "tctl", "tdie", "core"];
+    sysinfo
+        .components
+        .list()
+        .iter()
+        .filter(|component| {
+            let label = component.label().to_lowercase();
+            CPU_LABELS.iter().any(|cpu| label.contains(cpu))
+        })
+        .map(|component| f64::from(component.temperature()))
+        .reduce(f64::max)
+        .ok_or_else(|| miette::miette!("no CPU temperature sensors found"))
+}
+
+/// Returns `used` as a percentage of `total`.
+fn percent(used: u64, total: u64, what: &str) -> miette::Result<f64> {
+    if total == 0 {
+        miette::bail!("this system has no {what}");
+    }
+    Ok(used as f64 / total as f64 * 100.0)
+}
@@ -0,0 +1,373 @@
+//! Windows metric sources.
+//!
+//! `systemstat`'s Windows support is limited: it can't read CPU temperatures,
+//! and its CPU load measurements are unreliable. On Windows, the built-in
+//! metrics are instead read from performance counters using the Performance
+//! Data Helper (PDH) library, and memory usage from `GlobalMemoryStatusEx`.
+//! These sources replace the `systemstat` ones in
+//! [`Metric::source`](super::Metric::source), so dials are configured with the
+//! same [`Metric`](super::Metric)s on every platform.
+//!
+//! Since there's no network usage [`Metric`](super::Metric) on any platform,
+//! there's no source for the network interface counters here, either. Network
+//! throughput can be displayed using a [plugin](super::plugin).
+use super::{cpu_temp_unavailable, spawn_blocking, ImgFile, MetricSource, SampleFuture};
+use miette::{Context, IntoDiagnostic};
+use std::{sync::Arc, time::Duration};
+use vu_api::dial::Percent;
+use windows_sys::Win32::System::{
+    Performance::{
+        PdhAddEnglishCounterW, PdhCloseQuery, PdhCollectQueryData, PdhGetFormattedCounterArrayW,
+        PdhGetFormattedCounterValue, PdhOpenQueryW, PDH_CSTATUS_NEW_DATA, PDH_CSTATUS_VALID_DATA,
+        PDH_FMT_COUNTERVALUE, PDH_FMT_COUNTERVALUE_ITEM_W, PDH_FMT_DOUBLE, PDH_MORE_DATA,
+    },
+    SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX},
+};
+
+/// A PDH query that collects a single counter.
+struct Counter {
+    path: &'static str,
+    query: isize,
+    counter: isize,
+}
+
+/// A [`Counter`] that's opened the first time it's sampled.
+///
+/// Sources are also constructed just to read their metadata, so they shouldn't
+/// open queries until they're actually used.
+struct LazyCounter {
+    path: &'static str,
+    counter: Option<Arc<Counter>>,
+}
+
+// === CPU load ===
+
+pub(super) struct CpuLoad(LazyCounter);
+
+impl Default for CpuLoad {
+    fn default() -> Self {
+        Self(LazyCounter::new(r"\Processor(_Total)\% Processor Time"))
+    }
+}
+
+impl MetricSource for CpuLoad {
+    fn name(&self) -> &str {
+        "CPU Load"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("cpu_load.png");
+        Some(&IMG)
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let percent = self.0.sample(Counter::value).await?;
+            tracing::debug!("CPU Load: {percent}%");
+            Ok(Percent::from_f64_clamped(percent))
+        })
+    }
+}
+
+// === memory usage ===
+
+#[derive(Default)]
+pub(super) struct Memory;
+
+impl MetricSource for Memory {
+    fn name(&self) -> &str {
+        "Memory Usage"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("mem.png");
+        Some(&IMG)
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let mut status = MEMORYSTATUSEX {
+                dwLength: std::mem::size_of::<MEMORYSTATUSEX>() as u32,
+                dwMemoryLoad: 0,
+                ullTotalPhys: 0,
+                ullAvailPhys: 0,
+                ullTotalPageFile: 0,
+                ullAvailPageFile: 0,
+                ullTotalVirtual: 0,
+                ullAvailVirtual: 0,
+                ullAvailExtendedVirtual: 0,
+            };
+            if unsafe { GlobalMemoryStatusEx(&mut status) } == 0 {
+                return Err(std::io::Error::last_os_error())
+                    .into_diagnostic()
+                    .context("failed to read memory usage");
+            }
+            let percent_used = status.dwMemoryLoad;
+            tracing::debug!("Memory: {percent_used}% used");
+            Ok(Percent::from_f64_clamped(f64::from(percent_used)))
+        })
+    }
+}
+
+// === swap usage ===
+
+pub(super) struct Swap(LazyCounter);
+
+impl Default for Swap {
+    fn default() -> Self {
+        Self(LazyCounter::new(r"\Paging File(_Total)\% Usage"))
+    }
+}
+
+impl MetricSource for Swap {
+    fn name(&self) -> &str {
+        "Swap Usage"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("swap.png");
+        Some(&IMG)
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let percent_used = self.0.sample(Counter::value).await?;
+            tracing::debug!("Swap: {percent_used}% used");
+            Ok(Percent::from_f64_clamped(f64::from(percent_used)))
+        })
+    }
+}
+
+// === CPU temperature ===
+
+pub(super) struct CpuTemp(LazyCounter);
+
+impl Default for CpuTemp {
+    fn default() -> Self {
+        // One instance for each ACPI thermal zone.
+        Self(LazyCounter::new(
+            r"\Thermal Zone Information(*)\Temperature",
+        ))
+    }
+}
+
+impl MetricSource for CpuTemp {
+    fn name(&self) -> &str {
+        "CPU Temperature"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("cpu_temp.png");
+        Some(&IMG)
+    }
+
+    fn unit(&self) -> &str {
+        "°C"
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let zones = self
+                .0
+                .sample(Counter::values)
+                .await
+                .map_err(cpu_temp_unavailable)?;
+            // Thermal zones are reported in Kelvin, and the hottest zone is
+            // usually the CPU.
+            let kelvin = zones
+                .into_iter()
+                .reduce(f64::max)
+                .ok_or_else(|| miette::miette!("this system doesn't report any thermal zones"))
+                .map_err(cpu_temp_unavailable)?;
+            let temp = kelvin - 273.15;
+            tracing::debug!("CPU temp: {temp}°C");
+            Ok(Percent::from_f64_clamped(temp))
+        })
+    }
+}
+
+// === disk usage ===
+
+pub(super) struct DiskUsage(LazyCounter);
+
+impl Default for DiskUsage {
+    fn default() -> Self {
+        Self(LazyCounter::new(r"\LogicalDisk(_Total)\% Free Space"))
+    }
+}
+
+impl MetricSource for DiskUsage {
+    fn name(&self) -> &str {
+        "Disk Usage"
+    }
+
+    fn image(&self) -> Option<&'static ImgFile> {
+        static IMG: ImgFile = imgfile!("disk.png");
+        Some(&IMG)
+    }
+
+    fn sample(&mut self) -> SampleFuture<'_> {
+        Box::pin(async move {
+            let percent_free = self.0.sample(Counter::value).await?;
+            let percent_used = 100.0 - percent_free;
+            tracing::debug!("Disk: {percent_used}% used");
+            Ok(Percent::from_f64_clamped(f64::from(percent_used)))
+        })
+    }
+}
+
+// === impl LazyCounter ===
+
+impl LazyCounter {
+    /// How long to wait between opening a counter and its first sample.
+    ///
+    /// Rate counters, such as CPU time, are measured over the time between two
+    /// collections, so they can't be read until they've been collected twice.
+    const FIRST_MEASUREMENT: Duration = Duration::from_secs(1);
+
+    const fn new(path: &'static str) -> Self {
+        Self {
+            path,
+            counter: None,
+        }
+    }
+
+    /// Collects the counter and reads it using `read`, opening the counter
+    /// if it hasn't been opened yet.
+    async fn sample<T: Send + 'static>(
+        &mut self,
+        read: fn(&Counter) -> miette::Result<T>,
+    ) -> miette::Result<T> {
+        let counter = match self.counter {
+            Some(ref counter) => counter.clone(),
+            None => {
+                let path = self.path;
+                let counter = Arc::new(spawn_blocking(move || Counter::open(path)).await?);
+                tokio::time::sleep(Self::FIRST_MEASUREMENT).await;
+                self.counter = Some(counter.clone());
+                counter
+            }
+        };
+        spawn_blocking(move || {
+            counter.collect()?;
+            read(&counter)
+        })
+        .await
+    }
+}
+
+// === impl Counter ===
+
+impl Counter {
+    fn open(path: &'static str) -> miette::Result<Self> {
+        let mut query = 0;
+        check(unsafe { PdhOpenQueryW(std::ptr::null(), 0, &mut query) })
+            .context("failed to open performance counter query")?;
+        // Construct the counter before adding it to the query, so that the
+        // query is closed if that fails.
+        let mut counter = Self {
+            path,
+            query,
+            counter: 0,
+        };
+        let wide = path.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+        check(unsafe { PdhAddEnglishCounterW(query, wide.as_ptr(), 0, &mut counter.counter) })
+            .with_context(|| format!("failed to add performance counter {path}"))?;
+        counter.collect()?;
+        Ok(counter)
+    }
+
+    fn collect(&self) -> miette::Result<()> {
+        check(unsafe { PdhCollectQueryData(self.query) })
+            .with_context(|| format!("failed to collect performance counter {}", self.path))
+    }
+
+    /// Returns the counter's most recently collected value.
+    fn value(&self) -> miette::Result<f64> {
+        let mut value: PDH_FMT_COUNTERVALUE = unsafe { std::mem::zeroed() };
+        check(unsafe {
+            PdhGetFormattedCounterValue(
+                self.counter,
+                PDH_FMT_DOUBLE,
+                std::ptr::null_mut(),
+                &mut value,
+            )
+        })
+        .and_then(|()| check_value(value.CStatus))
+        .with_context(|| format!("failed to read performance counter {}", self.path))?;
+        Ok(unsafe { value.Anonymous.doubleValue })
+    }
+
+    /// Returns the most recently collected value of each instance of a
+    /// wildcard counter.
+    fn values(&self) -> miette::Result<Vec<f64>> {
+        type Item = PDH_FMT_COUNTERVALUE_ITEM_W;
+
+        let mut size = 0;
+        let mut count = 0;
+        let status = unsafe {
+            PdhGetFormattedCounterArrayW(
+                self.counter,
+                PDH_FMT_DOUBLE,
+                &mut size,
+                &mut count,
+                std::ptr::null_mut(),
+            )
+        };
+        if status != PDH_MORE_DATA {
+            // If there's no more data, there are no instances.
+            check(status)
+                .with_context(|| format!("failed to read performance counter {}", self.path))?;
+            return Ok(Vec::new());
+        }
+
+        // The buffer holds the items followed by the instance names that they
+        // point to, so allocate it in units of items to keep it aligned.
+        let len = (size as usize).div_ceil(std::mem::size_of::<Item>());
+        let mut items = Vec::<Item>::with_capacity(len);
+        size = (len * std::mem::size_of::<Item>()) as u32;
+        check(unsafe {
+            PdhGetFormattedCounterArrayW(
+                self.counter,
+                PDH_FMT_DOUBLE,
+                &mut size,
+                &mut count,
+                items.as_mut_ptr(),
+            )
+        })
+        .with_context(|| format!("failed to read performance counter {}", self.path))?;
+        // Safety: PDH has initialized `count` items at the start of the buffer.
+        let items = unsafe { std::slice::from_raw_parts(items.as_ptr(), count as usize) };
+        Ok(items
+            .iter()
+            .filter(|item| check_value(item.FmtValue.CStatus).is_ok())
+            .map(|item| unsafe { item.FmtValue.Anonymous.doubleValue })
+            .collect())
+    }
+}
+
+impl Drop for Counter {
+    fn drop(&mut self) {
+        // Closing the query also removes its counters.
+        unsafe {
+            PdhCloseQuery(self.query);
+        }
+    }
+}
+
+fn check(status: u32) -> miette::Result<()> {
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(miette::miette!("PDH error {status:#010x}"))
+    }
+}
+
+fn check_value(status: u32) -> miette::Result<()> {
+    match status {
+        PDH_CSTATUS_VALID_DATA | PDH_CSTATUS_NEW_DATA => Ok(()),
+        status => Err(miette::miette!(
+            "invalid counter data (status {status:#010x})"
+        )),
+    }
+}
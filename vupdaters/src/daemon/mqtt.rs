@@ -0,0 +1,537 @@
+//! MQTT publishing, with Home Assistant discovery.
+//!
+//! When an `[mqtt]` table is configured, the daemon connects to an MQTT broker
+//! and announces each managed dial to Home Assistant using [MQTT discovery].
+//! Each dial's current value and backlight color are published to its state
+//! topics. If commands are enabled, values and backlight colors published to a
+//! dial's command topics are applied to that dial.
+//!
+//! The same connection is used to receive metrics published by
+//! [agents](super::agent) on other hosts.
+//!
+//! [MQTT discovery]: https://www.home-assistant.io/integrations/mqtt/#mqtt-discovery
+use super::{config::MqttConfig, control::DaemonState};
+use miette::{Context, IntoDiagnostic};
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use vu_api::dial::{Backlight, Percent};
+
+/// How long to wait before reconnecting after the connection to the broker
+/// fails.
+pub(super) const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[tracing::instrument(
+    level = tracing::Level::INFO,
+    name = "mqtt",
+    fields(broker = %format_args!("{}:{}", config.host, config.port)),
+    skip_all,
+    err(Display),
+)]
+pub(super) async fn run(config: MqttConfig, state: DaemonState) -> miette::Result<()> {
+    let topics = Topics::new(&config);
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options
+        .set_keep_alive(Duration::from_secs(30))
+        .set_last_will(LastWill::new(
+            &topics.availability,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+    if let Some(ref username) = config.username {
+        options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+    }
+
+    let (client, eventloop) = AsyncClient::new(options, 64);
+    let (events_tx, mut events) = mpsc::channel(16);
+    tokio::spawn(poll_eventloop(eventloop, events_tx));
+
+    let mut publisher = Publisher {
+        client,
+        config,
+        topics,
+        state,
+        announced: BTreeSet::new(),
+        published: BTreeMap::new(),
+    };
+    let mut interval = tokio::time::interval(publisher.config.publish_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // Failing to publish or subscribe (e.g. because the client's request
+    // queue is full while the broker is unreachable) shouldn't stop MQTT
+    // altogether: log it, and try again on the next tick or reconnect.
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(Incoming::Connected) => {
+                    if let Err(error) = publisher.connected().await {
+                        tracing::warn!(%error, "failed to set up MQTT connection");
+                    }
+                }
+                Some(Incoming::Message { topic, payload }) => publisher.message(&topic, &payload).await,
+                None => miette::bail!("MQTT event loop terminated"),
+            },
+            _ = interval.tick() => {
+                if let Err(error) = publisher.publish_changes().await {
+                    tracing::warn!(%error, "failed to publish dial states over MQTT");
+                }
+            }
+        }
+    }
+}
+
+struct Publisher {
+    client: AsyncClient,
+    config: MqttConfig,
+    topics: Topics,
+    state: DaemonState,
+    /// The dials that have been announced via Home Assistant discovery, by
+    /// object ID.
+    announced: BTreeSet<String>,
+    /// The state most recently published for each dial, by object ID.
+    published: BTreeMap<String, DialState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DialState {
+    name: String,
+    value: Option<Percent>,
+    backlight: Option<Backlight>,
+}
+
+struct Topics {
+    prefix: String,
+    availability: String,
+    /// The prefix for topics published by agents on other hosts.
+    agents: String,
+}
+
+enum Incoming {
+    Connected,
+    Message { topic: String, payload: Vec<u8> },
+}
+
+/// A Home Assistant JSON schema light command.
+#[derive(Debug, Deserialize)]
+struct LightCommand {
+    state: LightState,
+    #[serde(default)]
+    brightness: Option<u8>,
+    #[serde(default)]
+    color: Option<Rgb>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+enum LightState {
+    On,
+    Off,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+struct Rgb {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+/// Polls the MQTT event loop, forwarding connections and incoming messages to
+/// the publisher.
+async fn poll_eventloop(mut eventloop: EventLoop, events: mpsc::Sender<Incoming>) {
+    loop {
+        let incoming = match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                tracing::info!("connected to MQTT broker");
+                Incoming::Connected
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => Incoming::Message {
+                topic: publish.topic,
+                payload: publish.payload.to_vec(),
+            },
+            Ok(event) => {
+                tracing::trace!(?event);
+                continue;
+            }
+            Err(error) => {
+                // The event loop reconnects the next time it's polled.
+                tracing::warn!(%error, "MQTT connection failed, reconnecting in {RECONNECT_DELAY:?}...");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        if events.send(incoming).await.is_err() {
+            return;
+        }
+    }
+}
+
+// === impl Publisher ===
+
+impl Publisher {
+    async fn connected(&mut self) -> miette::Result<()> {
+        // Retained messages may have been lost if the broker restarted, so
+        // announce and publish everything again.
+        self.announced.clear();
+        self.published.clear();
+
+        self.publish(&self.topics.availability, "online").await?;
+        for topic in [
+            format!("{}/+/metrics/+", self.topics.agents),
+            format!("{}/+/status", self.topics.agents),
+        ] {
+            self.client
+                .subscribe(&topic, QoS::AtMostOnce)
+                .await
+                .into_diagnostic()
+                .with_context(|| format!("failed to subscribe to {topic}"))?;
+        }
+        if self.config.commands {
+            for command in ["value", "backlight"] {
+                let topic = format!("{}/+/{command}/set", self.topics.prefix);
+                self.client
+                    .subscribe(&topic, QoS::AtLeastOnce)
+                    .await
+                    .into_diagnostic()
+                    .with_context(|| format!("failed to subscribe to {topic}"))?;
+            }
+        }
+        self.publish_changes().await
+    }
+
+    async fn publish_changes(&mut self) -> miette::Result<()> {
+        let dials = self
+            .state
+            .dial_statuses()
+            .into_iter()
+            .map(|(name, status)| {
+                let state = DialState {
+                    name,
+                    value: status.value,
+                    backlight: status.backlight,
+                };
+                (object_id(&state.name), state)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        // Remove dials that are no longer managed (e.g. after switching
+        // profiles) from Home Assistant.
+        let removed = self
+            .announced
+            .iter()
+            .filter(|id| !dials.contains_key(*id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for id in removed {
+            tracing::debug!(dial = %id, "removing dial from Home Assistant");
+            for (component, entity) in self.components() {
+                self.publish(&self.discovery_topic(component, &id, entity), "")
+                    .await?;
+            }
+            self.announced.remove(&id);
+            self.published.remove(&id);
+        }
+
+        for (id, dial) in dials {
+            if !self.announced.contains(&id) {
+                self.announce(&id, &dial.name).await?;
+                self.announced.insert(id.clone());
+            }
+
+            let prev = self.published.get(&id);
+            if prev.map(|prev| prev.value) != Some(dial.value) {
+                if let Some(value) = dial.value {
+                    let topic = self.topics.state(&id, "value");
+                    self.publish(&topic, u8::from(value).to_string()).await?;
+                }
+            }
+            if prev.map(|prev| prev.backlight) != Some(dial.backlight) {
+                if let Some(backlight) = dial.backlight {
+                    let topic = self.topics.state(&id, "backlight");
+                    self.publish(&topic, light_state(backlight).to_string())
+                        .await?;
+                }
+            }
+            self.published.insert(id, dial);
+        }
+
+        Ok(())
+    }
+
+    /// Announces a dial to Home Assistant.
+    async fn announce(&self, id: &str, name: &str) -> miette::Result<()> {
+        tracing::info!(dial = %name, "announcing dial to Home Assistant");
+        let device = json!({
+            "identifiers": [self.config.client_id],
+            "name": "VU-1 Dials",
+            "manufacturer": "Streacom",
+            "model": "VU1",
+        });
+        let common = |entity: &str, name: String| {
+            json!({
+                "name": name,
+                "unique_id": format!("{}_{id}_{entity}", self.config.client_id),
+                "state_topic": self.topics.state(id, entity),
+                "availability_topic": self.topics.availability,
+                "device": device,
+            })
+        };
+
+        let mut value = common("value", name.to_owned());
+        let mut backlight = common("backlight", format!("{name} Backlight"));
+        extend(
+            &mut value,
+            json!({
+                "unit_of_measurement": "%",
+                "icon": "mdi:gauge",
+            }),
+        );
+        if self.config.commands {
+            extend(
+                &mut value,
+                json!({
+                    "command_topic": self.topics.command(id, "value"),
+                    "min": 0,
+                    "max": 100,
+                    "step": 1,
+                    "mode": "slider",
+                }),
+            );
+            extend(
+                &mut backlight,
+                json!({
+                    "command_topic": self.topics.command(id, "backlight"),
+                    "schema": "json",
+                    "supported_color_modes": ["rgb"],
+                    "brightness": true,
+                }),
+            );
+        } else {
+            // Without commands, the backlight can't be a light, since lights
+            // must have a command topic.
+            extend(
+                &mut backlight,
+                json!({
+                    "icon": "mdi:palette",
+                    "value_template": "{{ value_json.state }}",
+                    "json_attributes_topic": self.topics.state(id, "backlight"),
+                }),
+            );
+        }
+
+        let [(value_component, _), (backlight_component, _)] = self.components();
+        self.publish(
+            &self.discovery_topic(value_component, id, "value"),
+            value.to_string(),
+        )
+        .await?;
+        self.publish(
+            &self.discovery_topic(backlight_component, id, "backlight"),
+            backlight.to_string(),
+        )
+        .await
+    }
+
+    async fn message(&self, topic: &str, payload: &[u8]) {
+        if let Some(topic) = topic
+            .strip_prefix(&self.topics.agents)
+            .and_then(|topic| topic.strip_prefix('/'))
+        {
+            self.state.remotes().handle(topic, payload);
+            return;
+        }
+
+        let Some((id, command)) = topic
+            .strip_prefix(&self.topics.prefix)
+            .and_then(|topic| topic.strip_prefix('/'))
+            .and_then(|topic| topic.strip_suffix("/set"))
+            .and_then(|topic| topic.split_once('/'))
+        else {
+            tracing::debug!(%topic, "ignoring message on unknown topic");
+            return;
+        };
+
+        let Some(name) = self.published.get(id).map(|dial| dial.name.as_str()) else {
+            tracing::warn!(%topic, "received command for unknown dial {id:?}");
+            return;
+        };
+        let payload = String::from_utf8_lossy(payload);
+        tracing::debug!(dial = %name, %command, %payload, "received MQTT command");
+        let result = match command {
+            "value" => self.set_value(name, &payload),
+            "backlight" => self.set_backlight(name, &payload).await,
+            _ => Err(miette::miette!("unknown command {command:?}")),
+        };
+        if let Err(error) = result {
+            tracing::warn!(dial = %name, %command, %payload, "MQTT command failed: {error:?}");
+        }
+    }
+
+    /// Sets a manual value for a dial. An empty payload clears the manual
+    /// value, so that the dial displays its metric again.
+    fn set_value(&self, name: &str, payload: &str) -> miette::Result<()> {
+        let payload = payload.trim();
+        let value = if payload.is_empty() {
+            None
+        } else {
+            // Home Assistant may send numbers with a fractional part, even
+            // when the step is 1.
+            let value = payload
+                .parse::<f64>()
+                .into_diagnostic()
+                .with_context(|| format!("invalid value {payload:?}"))?;
+            Some(Percent::try_from(value)?)
+        };
+        tracing::info!(dial = %name, ?value, "setting dial value from MQTT");
+        self.state.set_manual_value(name, value)
+    }
+
+    async fn set_backlight(&self, name: &str, payload: &str) -> miette::Result<()> {
+        let command: LightCommand = serde_json::from_str(payload)
+            .into_diagnostic()
+            .context("invalid light command")?;
+        let current = self
+            .published
+            .get(&object_id(name))
+            .and_then(|dial| dial.backlight);
+        let backlight = command.backlight(current)?;
+        let dial = self
+            .state
+            .dial(name)
+            .ok_or_else(|| miette::miette!("no dial named {name:?} is managed by vupdated"))?;
+        tracing::info!(dial = %name, ?backlight, "setting dial backlight from MQTT");
+        dial.set_backlight(backlight)
+            .await
+            .context("failed to set dial backlight")?;
+        self.state.record_backlight(name, backlight);
+        Ok(())
+    }
+
+    /// Returns the Home Assistant component used for each of a dial's
+    /// entities.
+    fn components(&self) -> [(&'static str, &'static str); 2] {
+        if self.config.commands {
+            [("number", "value"), ("light", "backlight")]
+        } else {
+            [("sensor", "value"), ("sensor", "backlight")]
+        }
+    }
+
+    fn discovery_topic(&self, component: &str, id: &str, entity: &str) -> String {
+        format!(
+            "{}/{component}/{}/{id}_{entity}/config",
+            self.config.discovery_prefix, self.config.client_id
+        )
+    }
+
+    async fn publish(&self, topic: &str, payload: impl Into<Vec<u8>>) -> miette::Result<()> {
+        self.client
+            .publish(topic, QoS::AtLeastOnce, true, payload)
+            .await
+            .into_diagnostic()
+            .with_context(|| format!("failed to publish to {topic}"))
+    }
+}
+
+// === impl Topics ===
+
+impl Topics {
+    fn new(config: &MqttConfig) -> Self {
+        let prefix = config.topic_prefix.trim_end_matches('/').to_owned();
+        let availability = format!("{prefix}/status");
+        let agents = format!("{prefix}/agents");
+        Self {
+            prefix,
+            availability,
+            agents,
+        }
+    }
+
+    fn state(&self, id: &str, entity: &str) -> String {
+        format!("{}/{id}/{entity}", self.prefix)
+    }
+
+    fn command(&self, id: &str, entity: &str) -> String {
+        format!("{}/{id}/{entity}/set", self.prefix)
+    }
+}
+
+// === impl LightCommand ===
+
+impl LightCommand {
+    /// Returns the backlight color to set for this command, given the dial's
+    /// current backlight color.
+    fn backlight(&self, current: Option<Backlight>) -> miette::Result<Backlight> {
+        if self.state == LightState::Off {
+            return Ok(Backlight::OFF);
+        }
+
+        // If the command doesn't change the color or brightness, keep the
+        // current ones, unless the backlight is currently off.
+        let (color, brightness) = match current.map(rgb_brightness) {
+            Some((color, brightness)) if brightness > 0 => (color, brightness),
+            _ => (
+                Rgb {
+                    r: 255,
+                    g: 255,
+                    b: 255,
+                },
+                255,
+            ),
+        };
+        let Rgb { r, g, b } = self.color.unwrap_or(color);
+        let brightness = self.brightness.unwrap_or(brightness);
+        Ok(Backlight::from_rgb8(r, g, b).scale(f32::from(brightness) / 255.0))
+    }
+}
+
+/// Returns a Home Assistant JSON schema light state for a backlight color.
+fn light_state(backlight: Backlight) -> serde_json::Value {
+    let (Rgb { r, g, b }, brightness) = rgb_brightness(backlight);
+    if brightness == 0 {
+        return json!({ "state": "OFF" });
+    }
+
+    json!({
+        "state": "ON",
+        "color_mode": "rgb",
+        "brightness": brightness,
+        "color": { "r": r, "g": g, "b": b },
+    })
+}
+
+/// Splits a backlight color into a full-brightness RGB color and a brightness,
+/// both in the range 0-255, as Home Assistant expects.
+fn rgb_brightness(backlight: Backlight) -> (Rgb, u8) {
+    let brightness = u8::from(backlight.brightness());
+    if brightness == 0 {
+        return (Rgb { r: 0, g: 0, b: 0 }, 0);
+    }
+
+    let [r, g, b] = backlight.with_brightness(Percent::MAX).to_rgb8();
+    let brightness = (u16::from(brightness) * 255 / 100) as u8;
+    (Rgb { r, g, b }, brightness)
+}
+
+/// Returns the MQTT object ID for the dial with the given name.
+fn object_id(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn extend(value: &mut serde_json::Value, fields: serde_json::Value) {
+    if let (Some(value), serde_json::Value::Object(fields)) = (value.as_object_mut(), fields) {
+        value.extend(fields);
+    }
+}
@@ -0,0 +1,284 @@
+//! Rendering dial values onto dial images.
+//!
+//! When a dial is configured with a [`ValueImageConfig`], its current value
+//! (and unit) is drawn in the middle of its background image, using a small
+//! built-in bitmap font, and the resulting PNG is uploaded to the dial.
+//!
+//! Uploading an image rewrites the dial's flash, so this is throttled: the
+//! image is only re-rendered once per [`ValueImageConfig::interval`], and only
+//! if the value has changed by at least [`ValueImageConfig::min_change`].
+use super::{config::ValueImageConfig, images::ImageCache, metrics::ImgFile};
+use miette::{Context, IntoDiagnostic};
+use std::time::Instant;
+use vu_api::{client::Dial, dial::Percent};
+
+/// The size of the VU1 dial's display, in pixels.
+const WIDTH: u32 = 200;
+const HEIGHT: u32 = 144;
+
+/// Glyphs are 5 pixels wide and 7 pixels tall, with a 1 pixel gap between
+/// them, before scaling.
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+/// Renders a dial's value onto its background image.
+pub(super) struct ValueImage {
+    config: ValueImageConfig,
+    background: Option<&'static ImgFile>,
+    unit: String,
+    filename: String,
+    images: ImageCache,
+    last: Option<(Instant, Percent)>,
+}
+
+/// An RGBA8 image.
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+// === impl ValueImage ===
+
+impl ValueImage {
+    pub(super) fn new(
+        config: ValueImageConfig,
+        background: Option<&'static ImgFile>,
+        unit: &str,
+        dial: &Dial,
+        images: ImageCache,
+    ) -> Self {
+        let unit = config.unit.clone().unwrap_or_else(|| unit.to_owned());
+        Self {
+            config,
+            background,
+            unit,
+            filename: format!("vupdated-value-{}.png", dial.id()),
+            images,
+            last: None,
+        }
+    }
+
+    /// Forgets the most recently uploaded image, so that the next call to
+    /// [`ValueImage::update`] uploads a new one regardless of throttling.
+    ///
+    /// This should be called whenever the dial's image may have been changed
+    /// by something else.
+    pub(super) fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Renders and uploads a new image for `value`, unless an image was
+    /// uploaded recently, or the value hasn't changed enough since then.
+    ///
+    /// This is best-effort: if the image can't be rendered or uploaded, the
+    /// dial will keep displaying its previous image, so errors are logged
+    /// rather than returned.
+    pub(super) async fn update(&mut self, dial: &Dial, value: Percent) {
+        if let Some((uploaded, last)) = self.last {
+            if uploaded.elapsed() < self.config.interval {
+                return;
+            }
+            if u8::from(value).abs_diff(u8::from(last)) < self.config.min_change {
+                return;
+            }
+        }
+
+        let text = format!("{}{}", u8::from(value), self.unit);
+        let png = match render(self.background.map(|img| img.image), &text) {
+            Ok(png) => png,
+            Err(error) => {
+                tracing::warn!(%error, "failed to render value image");
+                return;
+            }
+        };
+
+        // After a restart, the dial may already be displaying this value.
+        if self.images.is_current(dial, &self.filename, &png) {
+            tracing::debug!(%text, "dial already has value image");
+            self.last = Some((Instant::now(), value));
+            return;
+        }
+
+        tracing::debug!(%text, "uploading value image");
+        match dial.set_image(&self.filename, &png, true).await {
+            Ok(()) => {
+                self.images.record(dial, &self.filename, &png);
+                self.last = Some((Instant::now(), value));
+            }
+            Err(error) => tracing::warn!(%error, "failed to upload value image"),
+        }
+    }
+}
+
+/// Draws `text` in the middle of `background` (or a blank image, if there is
+/// no background), returning the result as a PNG.
+fn render(background: Option<&[u8]>, text: &str) -> miette::Result<Vec<u8>> {
+    let mut canvas = match background {
+        Some(png) => Canvas::decode(png).context("failed to decode background image")?,
+        None => Canvas::blank(WIDTH, HEIGHT),
+    };
+
+    // Use the largest scale at which the text fits in the middle 80% of the
+    // image.
+    let chars = text.chars().count() as u32;
+    let unscaled = (chars * GLYPH_ADVANCE).saturating_sub(1).max(1);
+    let scale = (canvas.width * 4 / 5 / unscaled).clamp(1, 5);
+    let text_width = unscaled * scale;
+    let text_height = GLYPH_HEIGHT * scale;
+    let x = canvas.width.saturating_sub(text_width) / 2;
+    let y = canvas.height.saturating_sub(text_height) / 2;
+
+    // Clear a box behind the text, so that it's legible regardless of what's
+    // in the background.
+    let pad = scale;
+    canvas.fill(
+        x.saturating_sub(pad),
+        y.saturating_sub(pad),
+        text_width + pad * 2,
+        text_height + pad * 2,
+        WHITE,
+    );
+
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = x + i as u32 * GLYPH_ADVANCE * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                    canvas.fill(
+                        glyph_x + col * scale,
+                        y + row as u32 * scale,
+                        scale,
+                        scale,
+                        BLACK,
+                    );
+                }
+            }
+        }
+    }
+
+    canvas.encode().context("failed to encode value image")
+}
+
+// === impl Canvas ===
+
+impl Canvas {
+    fn blank(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: WHITE.repeat((width * height) as usize),
+        }
+    }
+
+    fn decode(png: &[u8]) -> miette::Result<Self> {
+        let mut decoder = png::Decoder::new(png);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().into_diagnostic()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).into_diagnostic()?;
+        buf.truncate(info.buffer_size());
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => buf
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buf
+                .chunks_exact(2)
+                .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+                .collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+            png::ColorType::Indexed => {
+                miette::bail!("indexed color should have been expanded to RGB")
+            }
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    fn encode(&self) -> miette::Result<Vec<u8>> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().into_diagnostic()?;
+        writer.write_image_data(&self.pixels).into_diagnostic()?;
+        writer.finish().into_diagnostic()?;
+        Ok(png)
+    }
+
+    /// Fills a rectangle with `color`, clipping it to the canvas.
+    fn fill(&mut self, x: u32, y: u32, width: u32, height: u32, color: [u8; 4]) {
+        let x_end = (x + width).min(self.width);
+        let y_end = (y + height).min(self.height);
+        for y in y..y_end {
+            let row = (y * self.width) as usize * 4;
+            for x in x..x_end {
+                let i = row + x as usize * 4;
+                self.pixels[i..i + 4].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+/// Returns the bitmap for `c`, one byte per row, with the leftmost pixel in
+/// the most significant of the low 5 bits.
+///
+/// Lowercase letters are drawn as uppercase, and characters that aren't in
+/// the font are drawn as spaces.
+#[rustfmt::skip]
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        '%' => [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011],
+        '°' => [0b01100, 0b10010, 0b10010, 0b01100, 0b00000, 0b00000, 0b00000],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        _ => [0; GLYPH_HEIGHT as usize],
+    }
+}
@@ -0,0 +1,176 @@
+//! Batched dial update scheduling.
+//!
+//! Rather than each dial manager sending its own request to the VU-Server
+//! whenever its update interval fires, dial value and backlight updates are
+//! sent through a [`Scheduler`]. The scheduler collects the updates that are
+//! due at around the same time into a batch, coalescing multiple updates to the
+//! same dial, and then sends the batch with bounded concurrency, so that many
+//! dials sharing the same update interval don't all hit the VU-Server at once.
+use super::config::SchedulerConfig;
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinSet,
+};
+use vu_api::{
+    client::{Dial, Error},
+    dial::{Backlight, Percent},
+};
+
+/// A handle for sending dial updates through the scheduler.
+#[derive(Debug, Clone)]
+pub(super) struct Scheduler {
+    tx: mpsc::Sender<Update>,
+}
+
+/// The scheduler task, which sends batches of updates to the VU-Server.
+pub(super) struct Task {
+    config: SchedulerConfig,
+    rx: mpsc::Receiver<Update>,
+}
+
+#[derive(Debug)]
+struct Update {
+    dial: Dial,
+    kind: UpdateKind,
+    done: oneshot::Sender<Result<(), Error>>,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum UpdateKind {
+    Value(Percent),
+    Backlight(Backlight),
+}
+
+pub(super) fn new(config: SchedulerConfig) -> (Scheduler, Task) {
+    let (tx, rx) = mpsc::channel(64);
+    (Scheduler { tx }, Task { config, rx })
+}
+
+// === impl Scheduler ===
+
+impl Scheduler {
+    /// Sets the dial's value, as part of the next batch of updates.
+    pub(super) async fn set(&self, dial: &Dial, value: Percent) -> Result<(), Error> {
+        self.update(dial, UpdateKind::Value(value)).await
+    }
+
+    /// Sets the dial's backlight color, as part of the next batch of updates.
+    pub(super) async fn set_backlight(
+        &self,
+        dial: &Dial,
+        backlight: Backlight,
+    ) -> Result<(), Error> {
+        self.update(dial, UpdateKind::Backlight(backlight)).await
+    }
+
+    async fn update(&self, dial: &Dial, kind: UpdateKind) -> Result<(), Error> {
+        let (done, rx) = oneshot::channel();
+        let update = Update {
+            dial: dial.clone(),
+            kind,
+            done,
+        };
+        // If the scheduler task has stopped (e.g. because the dial managers
+        // are being restarted), send the update directly.
+        if self.tx.send(update).await.is_err() {
+            return kind.send(dial).await;
+        }
+        match rx.await {
+            Ok(result) => result,
+            Err(_) => kind.send(dial).await,
+        }
+    }
+}
+
+// === impl Task ===
+
+impl Task {
+    #[tracing::instrument(level = tracing::Level::INFO, name = "scheduler", skip_all, err(Display))]
+    pub(super) async fn run(mut self) -> miette::Result<()> {
+        let SchedulerConfig {
+            batch_window,
+            max_concurrency,
+            pacing,
+        } = self.config;
+        let max_concurrency = max_concurrency.max(1);
+        tracing::debug!(
+            ?batch_window,
+            max_concurrency,
+            ?pacing,
+            "starting scheduler"
+        );
+
+        let mut batch: Vec<Update> = Vec::new();
+        loop {
+            // Wait for the first update in the next batch...
+            let Some(update) = self.rx.recv().await else {
+                return Ok(());
+            };
+            push(&mut batch, update);
+
+            // ...and then collect any other updates that arrive within the
+            // batch window.
+            let window = tokio::time::sleep(batch_window);
+            tokio::pin!(window);
+            loop {
+                tokio::select! {
+                    biased;
+                    update = self.rx.recv() => match update {
+                        Some(update) => push(&mut batch, update),
+                        None => break,
+                    },
+                    _ = &mut window => break,
+                }
+            }
+
+            tracing::trace!(updates = batch.len(), "sending batch");
+            let mut requests = JoinSet::new();
+            for (i, Update { dial, kind, done }) in batch.drain(..).enumerate() {
+                if i > 0 {
+                    if let Some(pacing) = pacing {
+                        tokio::time::sleep(pacing).await;
+                    }
+                }
+                while requests.len() >= max_concurrency {
+                    requests.join_next().await;
+                }
+                requests.spawn(async move {
+                    // If the dial manager has stopped waiting for the update,
+                    // there's nobody to tell about the result.
+                    let _ = done.send(kind.send(&dial).await);
+                });
+            }
+            while requests.join_next().await.is_some() {}
+        }
+    }
+}
+
+/// Adds an update to the batch, replacing any update of the same kind to the
+/// same dial that's already in the batch.
+fn push(batch: &mut Vec<Update>, update: Update) {
+    let existing = batch.iter_mut().find(|queued| {
+        queued.dial.id() == update.dial.id()
+            && std::mem::discriminant(&queued.kind) == std::mem::discriminant(&update.kind)
+    });
+    match existing {
+        Some(queued) => {
+            tracing::trace!(dial = %update.dial, kind = ?update.kind, "coalescing update");
+            let superseded = std::mem::replace(queued, update);
+            // The superseded update won't be sent, but as far as its sender
+            // is concerned, the dial is being updated.
+            let _ = superseded.done.send(Ok(()));
+        }
+        None => batch.push(update),
+    }
+}
+
+// === impl UpdateKind ===
+
+impl UpdateKind {
+    async fn send(self, dial: &Dial) -> Result<(), Error> {
+        match self {
+            Self::Value(value) => dial.set(value).await,
+            Self::Backlight(backlight) => dial.set_backlight(backlight).await,
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Windows service support.
+//!
+//! When `vupdated` is run with `--service`, it registers with the Windows
+//! Service Control Manager (SCM), which then starts the daemon on a background
+//! thread. Stop and shutdown events from the SCM shut down the daemon, and
+//! parameter change events reload its config file.
+use super::{
+    install::{self, Install},
+    signal::{SignalAction, SignalListener},
+    Args,
+};
+use camino::Utf8Path;
+use miette::{Context, IntoDiagnostic};
+use std::{ffi::OsString, sync::Mutex, time::Duration};
+use tokio::sync::mpsc;
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+
+const SERVICE_NAME: &str = "vupdated";
+
+/// The SCM calls the service's main function with only the arguments passed to
+/// `StartService`, rather than the process's command-line arguments, so the
+/// parsed command-line arguments are stashed here for `service_main` to pick
+/// up.
+static ARGS: Mutex<Option<Args>> = Mutex::new(None);
+
+define_windows_service!(ffi_service_main, service_main);
+
+pub(super) fn run(mut args: Args) -> miette::Result<()> {
+    // Services don't have a console, so unless we were told to log somewhere
+    // else, log to a file.
+    args.output_args.default_log_file(|| {
+        let dirs = directories::BaseDirs::new()?;
+        let dir = Utf8Path::from_path(dirs.data_local_dir())?;
+        Some(dir.join("vupdate").join("vupdated.log"))
+    });
+    *ARGS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(args);
+
+    // This blocks until the service has stopped.
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+        .into_diagnostic()
+        .context(
+            "failed to connect to the Windows Service Control Manager \
+            (`--service` should only be used when vupdated is started as a service)",
+        )
+}
+
+/// Registers `vupdated` as a service with the Service Control Manager.
+///
+/// Services can't be given environment variables, so the API key and VU-Server
+/// address are passed on the service's command line.
+pub(super) fn install(install: &Install<'_>) -> miette::Result<()> {
+    let launch_arguments = ["--service", "--key", install.api_key()?]
+        .into_iter()
+        .map(OsString::from)
+        .chain([
+            OsString::from("--server"),
+            OsString::from(install.client_args.server().as_str()),
+        ])
+        .chain(install.args()?.into_iter().map(OsString::from))
+        .collect();
+    let info = ServiceInfo {
+        name: SERVICE_NAME.into(),
+        display_name: "vupdated".into(),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: install::current_exe()?.into(),
+        launch_arguments,
+        dependencies: Vec::new(),
+        // Run as LocalSystem.
+        account_name: None,
+        account_password: None,
+    };
+
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )
+    .into_diagnostic()
+    .context(
+        "failed to connect to the Windows Service Control Manager \
+        (installing a service requires an elevated command prompt)",
+    )?;
+    let access = ServiceAccess::CHANGE_CONFIG | ServiceAccess::START;
+    let service = match manager.open_service(SERVICE_NAME, access) {
+        Ok(_) if !install.force => {
+            return Err(miette::miette!(
+                help = "use `--force` to overwrite it",
+                "a `{SERVICE_NAME}` service is already installed"
+            ));
+        }
+        Ok(service) => {
+            service
+                .change_config(&info)
+                .into_diagnostic()
+                .context("failed to update the existing service")?;
+            service
+        }
+        Err(_) => manager
+            .create_service(&info, access)
+            .into_diagnostic()
+            .context("failed to create service")?,
+    };
+    service
+        .set_description("Updates VU-1 dials with system metrics")
+        .into_diagnostic()
+        .context("failed to set service description")?;
+    tracing::info!("installed service `{SERVICE_NAME}`");
+
+    if install.start {
+        service
+            .start::<&str>(&[])
+            .into_diagnostic()
+            .context("failed to start service")?;
+        tracing::info!("started service `{SERVICE_NAME}`");
+    } else {
+        tracing::info!("run `sc.exe start {SERVICE_NAME}` to start vupdated");
+    }
+
+    Ok(())
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    let Some(args) = ARGS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+    else {
+        return;
+    };
+
+    if let Err(error) = run_service(args) {
+        // Tracing may not have been initialized if the service failed early,
+        // in which case this goes nowhere, but there's nowhere else for it to
+        // go.
+        tracing::error!(error = ?error, "vupdated service failed");
+    }
+}
+
+fn run_service(args: Args) -> miette::Result<()> {
+    let (actions, signals) = mpsc::unbounded_channel();
+    let status = service_control_handler::register(SERVICE_NAME, move |control| {
+        let action = match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => SignalAction::Shutdown,
+            ServiceControl::ParamChange => SignalAction::Reload,
+            ServiceControl::Interrogate => return ServiceControlHandlerResult::NoError,
+            _ => return ServiceControlHandlerResult::NotImplemented,
+        };
+        // If the daemon has already shut down, there's nothing to tell.
+        let _ = actions.send(action);
+        ServiceControlHandlerResult::NoError
+    })
+    .into_diagnostic()
+    .context("failed to register service control handler")?;
+
+    set_status(
+        &status,
+        ServiceState::Running,
+        ServiceControlAccept::STOP
+            | ServiceControlAccept::SHUTDOWN
+            | ServiceControlAccept::PARAM_CHANGE,
+        ServiceExitCode::Win32(0),
+    )?;
+
+    let result = args.runtime().and_then(|rt| {
+        rt.block_on(args.run_with_signals(Some(SignalListener::from_service(signals))))
+    });
+
+    let exit_code = match result {
+        Ok(()) => ServiceExitCode::Win32(0),
+        Err(_) => ServiceExitCode::ServiceSpecific(1),
+    };
+    set_status(
+        &status,
+        ServiceState::Stopped,
+        ServiceControlAccept::empty(),
+        exit_code,
+    )?;
+
+    result
+}
+
+fn set_status(
+    status: &ServiceStatusHandle,
+    current_state: ServiceState,
+    controls_accepted: ServiceControlAccept,
+    exit_code: ServiceExitCode,
+) -> miette::Result<()> {
+    status
+        .set_service_status(ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state,
+            controls_accepted,
+            exit_code,
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        })
+        .into_diagnostic()
+        .with_context(|| format!("failed to set service status to {current_state:?}"))
+}
@@ -1,12 +1,22 @@
-// The `SignalAction::Reload` variant is currently only used on Linux systems.
+// The `SignalAction::UserDefined1` and `SignalAction::UserDefined2` variants
+// are currently only used on Unix systems, and `SignalAction::Reload` is only
+// used on Unix systems and by the Windows service.
 #[cfg_attr(windows, allow(dead_code))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(super) enum SignalAction {
-    // Basically the only two things we do when handling a signal:
     /// Reload the config file and restart dial managers.
     ///
-    /// This action is performed on receipt of a SIGHUP on Unix systems.
+    /// This action is performed on receipt of a SIGHUP on Unix systems, or a
+    /// `SERVICE_CONTROL_PARAMCHANGE` event when running as a Windows service.
     Reload,
+    /// Perform the action configured for SIGUSR1.
+    ///
+    /// This action is performed on receipt of a SIGUSR1 on Unix systems.
+    UserDefined1,
+    /// Perform the action configured for SIGUSR2.
+    ///
+    /// This action is performed on receipt of a SIGUSR2 on Unix systems.
+    UserDefined2,
     /// Shut down the daemon.
     Shutdown,
 }
@@ -23,6 +33,9 @@ mod signal_impl {
     pub(crate) struct SignalListener {
         // Reload config file on SIGHUP
         sighup: Signal,
+        // Perform the configured actions on SIGUSR1/SIGUSR2
+        sigusr1: Signal,
+        sigusr2: Signal,
         // Shutdown on SIGINT/SIGTERM/SIGQUIT
         sigint: Signal,
         sigterm: Signal,
@@ -34,6 +47,12 @@ mod signal_impl {
             let sighup = signal(SignalKind::hangup())
                 .into_diagnostic()
                 .context("failed to start listening for SIGHUP")?;
+            let sigusr1 = signal(SignalKind::user_defined1())
+                .into_diagnostic()
+                .context("failed to start listening for SIGUSR1")?;
+            let sigusr2 = signal(SignalKind::user_defined2())
+                .into_diagnostic()
+                .context("failed to start listening for SIGUSR2")?;
             let sigint = signal(SignalKind::interrupt())
                 .into_diagnostic()
                 .context("failed to start listening for SIGINT")?;
@@ -46,6 +65,8 @@ mod signal_impl {
 
             Ok(Self {
                 sighup,
+                sigusr1,
+                sigusr2,
                 sigint,
                 sigterm,
                 sigquit,
@@ -59,6 +80,14 @@ mod signal_impl {
                     tracing::info!("Received SIGHUP, reloading config");
                     SignalAction::Reload
                 }
+                _ = self.sigusr1.recv() => {
+                    tracing::info!("Received SIGUSR1");
+                    SignalAction::UserDefined1
+                }
+                _ = self.sigusr2.recv() => {
+                    tracing::info!("Received SIGUSR2");
+                    SignalAction::UserDefined2
+                }
                 _ = self.sigint.recv() => {
                     tracing::info!("Received SIGINT, shutting down");
                     SignalAction::Shutdown
@@ -80,12 +109,17 @@ mod signal_impl {
 mod signal_impl {
     use super::SignalAction;
     use miette::{Context, IntoDiagnostic};
-    use tokio::signal::windows::{ctrl_c, CtrlC};
+    use tokio::{
+        signal::windows::{ctrl_c, CtrlC},
+        sync::mpsc,
+    };
 
     #[derive(Debug)]
     pub(crate) struct SignalListener {
-        ctrl_c: CtrlC,
-        // TODO(eliza): what are the other Windows signals supposed to do?
+        ctrl_c: Option<CtrlC>,
+        // Control events from the Windows Service Control Manager, if running
+        // as a service.
+        service: Option<mpsc::UnboundedReceiver<SignalAction>>,
     }
 
     impl SignalListener {
@@ -94,14 +128,51 @@ mod signal_impl {
                 .into_diagnostic()
                 .context("failed to start listening for Ctrl-C")?;
 
-            Ok(Self { ctrl_c })
+            Ok(Self {
+                ctrl_c: Some(ctrl_c),
+                service: None,
+            })
+        }
+
+        /// Returns a `SignalListener` that receives actions from the Windows
+        /// Service Control Manager, rather than from Ctrl-C.
+        ///
+        /// Services don't have a console, so there's no Ctrl-C to listen for.
+        pub(crate) fn from_service(service: mpsc::UnboundedReceiver<SignalAction>) -> Self {
+            Self {
+                ctrl_c: None,
+                service: Some(service),
+            }
         }
 
         #[must_use]
         pub(crate) async fn next_signal(&mut self) -> SignalAction {
-            self.ctrl_c.recv().await;
-            tracing::info!("Received Ctrl-C, shutting down");
-            SignalAction::Shutdown
+            let Self { ctrl_c, service } = self;
+            let ctrl_c = async {
+                match ctrl_c {
+                    Some(ctrl_c) => ctrl_c.recv().await,
+                    None => futures::future::pending().await,
+                }
+            };
+            let service = async {
+                match service {
+                    Some(service) => service.recv().await,
+                    None => futures::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = ctrl_c => {
+                    tracing::info!("Received Ctrl-C, shutting down");
+                    SignalAction::Shutdown
+                }
+                action = service => {
+                    // If the service control handler was dropped, the Service
+                    // Control Manager is no longer talking to us, so shut down.
+                    let action = action.unwrap_or(SignalAction::Shutdown);
+                    tracing::info!("Received service control event: {action:?}");
+                    action
+                }
+            }
         }
     }
 }
@@ -1,8 +1,33 @@
-use crate::MultiError;
-use camino::Utf8PathBuf;
+use crate::{
+    cli::{completions, man},
+    daemon::control,
+    MultiError,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use futures::StreamExt;
 use miette::{Context, IntoDiagnostic};
-use std::fmt;
-use vu_api::{api::DialInfo, dial, Dial};
+use std::{fmt, future::Future, time::Duration};
+use vu_api::{api::DialInfo, dial, retry::RetryPolicy, Dial};
+
+mod batch;
+mod calibrate;
+mod color;
+mod config;
+mod demo;
+pub mod exit;
+mod firmware;
+mod gauge;
+mod get;
+mod hwtest;
+mod identify;
+mod power;
+mod preprocess;
+mod server;
+mod stream;
+mod table;
+#[cfg(feature = "tui")]
+mod tui;
+mod watch;
 
 /// A command-line tool for controlling Streacom VU-1 dials.
 ///
@@ -33,6 +58,15 @@ pub enum Command {
         /// Configures how the dials are displayed.
         #[clap(long, short = 'o', default_value_t = OutputMode::Text, value_enum)]
         output: OutputMode,
+
+        /// The columns to display with `--output table`.
+        #[clap(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_values_t = table::DEFAULT_COLUMNS.to_vec(),
+        )]
+        columns: Vec<table::Column>,
     },
 
     /// Get detailed status information about a dial.
@@ -46,25 +80,358 @@ pub enum Command {
         /// Configures how the dial's status is displayed.
         #[clap(long, short = 'o', default_value_t = OutputMode::Text, value_enum)]
         output: OutputMode,
+
+        /// The columns to display with `--output table`.
+        #[clap(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_values_t = table::DEFAULT_COLUMNS.to_vec(),
+        )]
+        columns: Vec<table::Column>,
+    },
+
+    /// Print a single field of a dial's status, with no other output.
+    ///
+    /// This is intended for use in shell scripts, where picking one value out
+    /// of `dialctl status` is clumsy. Values are printed without a `%` sign.
+    Get {
+        #[clap(flatten)]
+        dial: DialSelection,
+
+        /// The field to print.
+        #[clap(value_enum)]
+        field: get::Field,
     },
 
     /// Set a dial's value, image file, backlight, or easing config.
     ///
-    /// At least one of `--value`, `--image`, `--red`, `--green`, or `--blue`
-    /// must be provided.
+    /// At least one of `--value`, `--stdin`, `--image`, `--color`, `--red`,
+    /// `--green`, `--blue`, or one of the easing options must be provided.
+    /// Multiple dials can be set at once by repeating `--dial`, `--index`, or
+    /// `--name`, or by passing `--all`.
     Set {
         #[clap(flatten)]
-        dial: DialSelection,
+        dials: MultiDialSelection,
 
         #[clap(flatten)]
         values: SetValues,
     },
 
-    /// Forcibly reload a dial's hardware info.
+    /// Calibrate a dial's needle position.
+    ///
+    /// This sets the raw needle position that the dial displays as 100% (or,
+    /// with `--half`, as 50%), to correct needle drift. If no `--value` is
+    /// provided, the position is adjusted interactively.
+    Calibrate {
+        #[clap(flatten)]
+        dial: DialSelection,
+
+        /// Calibrate the needle's position at 100%. This is the default.
+        #[clap(long, conflicts_with = "half")]
+        max: bool,
+
+        /// Calibrate the needle's position at 50%.
+        #[clap(long)]
+        half: bool,
+
+        /// Set the calibrated position to this raw needle position, rather
+        /// than adjusting it interactively.
+        #[clap(long, short = 'v', value_name = "RAW")]
+        value: Option<u16>,
+
+        /// The raw needle position to start from in interactive mode.
+        ///
+        /// By default, this is the largest raw position when calibrating
+        /// 100%, and half of it when calibrating 50%.
+        #[clap(long, value_name = "RAW", conflicts_with = "value")]
+        start: Option<u16>,
+
+        /// How much to change the raw position by for each step in interactive
+        /// mode.
+        #[clap(long, default_value_t = 100, conflicts_with = "value")]
+        step: u16,
+    },
+
+    /// Check that a dial is working, end to end.
+    ///
+    /// This sweeps the dial's needle and cycles its backlight through red,
+    /// green, and blue, checking that the dial's status reflects each change,
+    /// and prints a report of which checks passed. The dial's value and
+    /// backlight are restored afterwards.
+    Test {
+        #[clap(flatten)]
+        dial: DialSelection,
+
+        /// How long to wait after each change, so that the dial's response
+        /// can be seen.
+        #[clap(
+            long,
+            default_value = "500ms",
+            value_parser = humantime_serde::re::humantime::parse_duration,
+        )]
+        delay: Duration,
+    },
+
+    /// Check that the VU-Server is reachable.
+    ///
+    /// This makes a single request to the VU-Server, and reports whether it
+    /// could be reached, what web server it's running on, how long it took
+    /// to respond, and how many dials it has provisioned.
+    Server {
+        /// Configures how the report is displayed.
+        #[clap(long, short = 'o', default_value_t = OutputMode::Text, value_enum)]
+        output: OutputMode,
+    },
+
+    /// Flash dials' backlights, to find out which physical dial is which.
+    ///
+    /// Each selected dial's backlight flashes in turn, while its UID, index,
+    /// and name are printed. Backlights are restored afterwards.
+    Identify {
+        #[clap(flatten)]
+        dials: MultiDialSelection,
+
+        /// How long to flash each dial for.
+        #[clap(
+            long,
+            default_value = "3s",
+            value_parser = humantime_serde::re::humantime::parse_duration,
+        )]
+        duration: Duration,
+    },
+
+    /// Turn dials off, setting their values to zero and backlights to black.
+    ///
+    /// Each dial's value and backlight are saved to a snapshot file first, so
+    /// that they can be restored with `dialctl on`.
+    Off {
+        #[clap(flatten)]
+        dials: MultiDialSelection,
+
+        /// The file to save the dials' state to.
+        #[clap(
+            long,
+            default_value_t = power::default_snapshot_path(),
+            value_hint = clap::ValueHint::FilePath,
+        )]
+        snapshot: Utf8PathBuf,
+    },
+
+    /// Turn dials that were turned off with `dialctl off` back on.
+    ///
+    /// Each dial's value and backlight are restored from the snapshot file
+    /// written by `dialctl off`. Selected dials that weren't turned off are
+    /// left as they are.
+    On {
+        #[clap(flatten)]
+        dials: MultiDialSelection,
+
+        /// The file to restore the dials' state from.
+        #[clap(
+            long,
+            default_value_t = power::default_snapshot_path(),
+            value_hint = clap::ValueHint::FilePath,
+        )]
+        snapshot: Utf8PathBuf,
+    },
+
+    /// Scan for newly connected dials.
+    ///
+    /// This asks the VU-Server to provision any dials that have been
+    /// connected since it started, and prints the UIDs of the new dials.
+    Provision {
+        /// How long to wait for new dials to be provisioned.
+        #[clap(
+            long,
+            default_value = "5s",
+            value_parser = humantime_serde::re::humantime::parse_duration,
+        )]
+        wait: Duration,
+    },
+
+    /// Continuously display the dials' values and backlights.
+    ///
+    /// The dials are polled at an interval, and redrawn in place until
+    /// interrupted with Ctrl-C.
+    Watch {
+        /// Only display the dial with this UID.
+        ///
+        /// This may be repeated to display multiple dials. If no dials are
+        /// selected, all dials are displayed.
+        #[clap(long = "dial", short = 'd', add = completions::dial_uids())]
+        dials: Vec<dial::Id>,
+
+        /// How often to poll the dials.
+        #[clap(
+            long,
+            short = 'n',
+            default_value = "1s",
+            value_parser = crate::cli::parse_nonzero_duration,
+        )]
+        interval: Duration,
+
+        /// Only use ASCII characters when drawing the dials.
+        #[clap(long)]
+        ascii: bool,
+    },
+
+    /// Display a system metric on a dial until interrupted.
+    ///
+    /// This samples the metric the same way `vupdated` does, but without a
+    /// config file, which is handy for trying out a dial, or for ad-hoc
+    /// monitoring. The dial's value is restored when stopped with Ctrl-C.
+    Gauge {
+        #[clap(flatten)]
+        dial: DialSelection,
+
+        /// The metric to display.
+        #[clap(long, short = 'm', value_enum)]
+        metric: crate::daemon::Metric,
+
+        /// How often to sample the metric and update the dial.
+        #[clap(
+            long,
+            default_value = "1s",
+            value_parser = crate::cli::parse_nonzero_duration,
+        )]
+        interval: Duration,
+    },
+
+    /// Run an animation on all dials until interrupted.
+    ///
+    /// The dials' values and backlights are restored when the animation is
+    /// stopped with Ctrl-C.
+    Demo {
+        /// The animation to run.
+        #[clap(long, short = 'a', value_enum, default_value_t)]
+        animation: demo::Animation,
+
+        /// How long each cycle of the animation takes.
+        #[clap(
+            long,
+            short = 'p',
+            default_value = "4s",
+            value_parser = crate::cli::parse_nonzero_duration,
+        )]
+        period: Duration,
+
+        /// How often to update the dials.
+        #[clap(
+            long,
+            short = 'n',
+            default_value = "100ms",
+            value_parser = crate::cli::parse_nonzero_duration,
+        )]
+        interval: Duration,
+    },
+
+    /// Open a full-screen dashboard for the dials.
+    ///
+    /// Each dial is displayed as a gauge in its backlight color. Use the
+    /// arrow keys to select a dial and change its value or backlight, and `q`
+    /// to quit.
+    #[cfg(feature = "tui")]
+    Tui {
+        /// How often to poll the dials.
+        #[clap(
+            long,
+            short = 'n',
+            default_value = "1s",
+            value_parser = crate::cli::parse_nonzero_duration,
+        )]
+        interval: Duration,
+    },
+
+    /// Manage the VU-Server.
+    ///
+    /// These commands use the VU-Server's admin API, which requires the
+    /// VU-Server's master key, rather than a regular API key.
+    Admin {
+        #[clap(subcommand)]
+        command: AdminCommand,
+    },
+
+    /// Run a list of operations from a batch file.
+    ///
+    /// A batch file lists operations that set dials' values, backlights,
+    /// background images, and names. The operations are run in order, using a
+    /// single connection to the VU-Server, and the result of each is printed.
+    Batch {
+        /// The batch file to run. Files ending in `.json` are read as JSON,
+        /// and all others as TOML.
+        #[clap(value_hint = clap::ValueHint::FilePath)]
+        file: Utf8PathBuf,
+    },
+
+    /// Manage declarative dial configuration files.
+    ///
+    /// A dial config file describes the desired names, values, backlights,
+    /// easing, and background images of a set of dials.
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Query dial firmware.
+    Firmware {
+        #[clap(subcommand)]
+        command: FirmwareCommand,
+    },
+
+    /// Manage dial background images.
+    Image {
+        #[clap(subcommand)]
+        command: ImageCommand,
+    },
+
+    /// Control a running `vupdated` daemon.
+    ///
+    /// These commands talk to `vupdated`'s control socket, rather than to the
+    /// VU-Server, so they do not require an API key.
+    Daemon {
+        /// Path to the daemon's control socket (or, on Windows, the name of
+        /// its control named pipe).
+        #[clap(
+            long,
+            default_value_t = control::default_socket_path(),
+            value_hint = clap::ValueHint::FilePath,
+        )]
+        socket: Utf8PathBuf,
+
+        #[clap(subcommand)]
+        command: DaemonCommand,
+    },
+
+    /// Print a shell completion script.
+    ///
+    /// The script completes dial UIDs and names by asking the VU-Server, using
+    /// the API key and server address in the `VU_DIALS_API_KEY` and
+    /// `VU_DIALS_SERVER_ADDR` environment variables.
+    Completions {
+        /// The shell to generate completions for.
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Print roff man pages for `dialctl`.
+    ///
+    /// By default, only the page for `dialctl` itself is printed to stdout.
+    /// With `--out-dir`, a page is written for every subcommand as well.
+    Man {
+        /// The directory to write man pages to.
+        #[clap(long, value_hint = clap::ValueHint::DirPath)]
+        out_dir: Option<Utf8PathBuf>,
+    },
+
+    /// Forcibly reload dials' hardware info.
+    ///
+    /// Multiple dials can be reloaded at once by repeating `--dial`,
+    /// `--index`, or `--name`, or by passing `--all`. Each dial's refreshed
+    /// status is printed as soon as it's reloaded.
     Reload {
-        /// The UID of the dial to reload.
-        #[clap(long = "dial", short = 'd')]
-        dial: dial::Id,
+        #[clap(flatten)]
+        dials: MultiDialSelection,
 
         /// Configures how the dial's status is displayed.
         #[clap(long, short = 'o', default_value_t = OutputMode::Text, value_enum)]
@@ -72,6 +439,164 @@ pub enum Command {
     },
 }
 
+#[derive(Debug, clap::Subcommand)]
+pub enum AdminCommand {
+    /// Manage the VU-Server's API keys.
+    Keys {
+        #[clap(subcommand)]
+        command: KeysCommand,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum KeysCommand {
+    /// List all API keys.
+    List {
+        /// Configures how the keys are displayed.
+        #[clap(long, short = 'o', default_value_t = OutputMode::Text, value_enum)]
+        output: OutputMode,
+    },
+
+    /// Create a new API key, and print it.
+    Create {
+        /// The new key's name.
+        name: String,
+
+        /// The UID of a dial that the new key may access.
+        ///
+        /// This may be repeated to allow access to multiple dials.
+        #[clap(long = "dial", short = 'd', add = completions::dial_uids())]
+        dials: Vec<dial::Id>,
+    },
+
+    /// Rename an API key, and change the dials it may access.
+    Update {
+        /// The API key to update.
+        key: String,
+
+        /// The key's new name.
+        #[clap(long)]
+        name: String,
+
+        /// The UID of a dial that the key may access.
+        ///
+        /// This may be repeated to allow access to multiple dials. The key's
+        /// existing dials are replaced, rather than added to.
+        #[clap(long = "dial", short = 'd', add = completions::dial_uids())]
+        dials: Vec<dial::Id>,
+    },
+
+    /// Delete an API key.
+    Delete {
+        /// The API key to delete.
+        key: String,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum ConfigCommand {
+    /// Change the dials to match a config file.
+    ///
+    /// Each dial's live status is compared with the config file, and only the
+    /// settings that differ are changed. Settings that the config file
+    /// doesn't include are left as they are.
+    Apply {
+        /// The config file to apply.
+        #[clap(value_hint = clap::ValueHint::FilePath)]
+        file: Utf8PathBuf,
+    },
+
+    /// Print the changes that `dialctl config apply` would make, without
+    /// making them.
+    Diff {
+        /// The config file to compare the dials with.
+        #[clap(value_hint = clap::ValueHint::FilePath)]
+        file: Utf8PathBuf,
+    },
+
+    /// Write the current state of all dials to a config file.
+    ///
+    /// The config file includes each dial's name, value, backlight, easing,
+    /// and background image file name, and can be restored with `dialctl
+    /// config apply`.
+    Dump {
+        /// The file to write the config to. If this isn't provided, the config
+        /// is written to stdout.
+        #[clap(long, value_hint = clap::ValueHint::FilePath)]
+        out: Option<Utf8PathBuf>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum FirmwareCommand {
+    /// List the firmware and hardware versions of all dials.
+    List {
+        /// Configures how the versions are displayed.
+        #[clap(long, short = 'o', default_value_t = OutputMode::Text, value_enum)]
+        output: OutputMode,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum ImageCommand {
+    /// Download a dial's current background image.
+    ///
+    /// This can be used to back up a dial's custom background image before
+    /// replacing it with `dialctl set --image`.
+    Get {
+        #[clap(flatten)]
+        dial: DialSelection,
+
+        /// The file to save the image to.
+        #[clap(long, value_hint = clap::ValueHint::FilePath)]
+        out: Utf8PathBuf,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum DaemonCommand {
+    /// Reload the daemon's config file.
+    Reload,
+
+    /// Pause dial updates.
+    Pause,
+
+    /// Resume dial updates.
+    Resume,
+
+    /// Switch the daemon to a different config profile.
+    Profile {
+        /// The name of the profile to switch to.
+        profile: String,
+    },
+
+    /// Show the daemon's status.
+    Status {
+        /// Configures how the daemon's status is displayed.
+        #[clap(long, short = 'o', default_value_t = OutputMode::Text, value_enum)]
+        output: OutputMode,
+    },
+
+    /// Display a manually-set value on a dial managed by the daemon.
+    ///
+    /// The dial displays this value instead of its configured metric until
+    /// the value is cleared with `--clear`, or the daemon's config is
+    /// reloaded.
+    Set {
+        /// The name or index of the dial.
+        dial: String,
+
+        /// The value to display on the dial.
+        #[clap(required_unless_present = "clear")]
+        value: Option<dial::Percent>,
+
+        /// Clear the manually-set value, so that the dial displays its
+        /// configured metric again.
+        #[clap(long, conflicts_with = "value")]
+        clear: bool,
+    },
+}
+
 #[derive(Debug, clap::Parser)]
 #[command(next_help_heading = "Setting Values")]
 #[group(id = "set", required = true, multiple = true)]
@@ -82,10 +607,74 @@ pub struct SetValues {
     #[clap(long, short = 'v')]
     value: Option<dial::Percent>,
 
+    /// Read values from stdin, one per line, and set the dial's needle to
+    /// each value as it's read.
+    ///
+    /// Decimal values are rounded, and values outside of 0-100 are clamped.
+    /// This runs until stdin is closed.
+    #[clap(long, conflicts_with = "value", help_heading = "Streaming Options")]
+    stdin: bool,
+
+    /// When reading values from `--stdin`, wait at least this long between
+    /// updates.
+    ///
+    /// If multiple values are read while waiting, only the most recent one
+    /// is sent.
+    #[clap(
+        long,
+        requires = "stdin",
+        value_parser = humantime_serde::re::humantime::parse_duration,
+        help_heading = "Streaming Options"
+    )]
+    min_interval: Option<Duration>,
+
     /// Set the dial's background image to the provided image file.
+    ///
+    /// PNG and JPEG images are supported. Images are converted to the dial's
+    /// 200x144 pixel, 8-bit RGBA format before they're uploaded, unless
+    /// `--raw` is set.
     #[clap(long, value_hint = clap::ValueHint::FilePath)]
     image: Option<Utf8PathBuf>,
 
+    /// How to fit the image to the dial's display, if its aspect ratio
+    /// differs from the display's.
+    ///
+    /// [default: contain]
+    #[clap(long, value_enum, requires = "image", help_heading = "Image Options")]
+    fit: Option<preprocess::Fit>,
+
+    /// Crop the image to a region before fitting it to the display.
+    ///
+    /// Regions are written as `WIDTHxHEIGHT+X+Y`, where `X` and `Y` are the
+    /// offset of the region's top left corner from the top left corner of the
+    /// image. The offset may be omitted.
+    #[clap(
+        long,
+        value_name = "WIDTHxHEIGHT+X+Y",
+        requires = "image",
+        conflicts_with = "raw",
+        help_heading = "Image Options"
+    )]
+    crop: Option<preprocess::Crop>,
+
+    /// Upload the image file as-is, without converting it to the dial's
+    /// format.
+    #[clap(
+        long,
+        requires = "image",
+        conflicts_with = "fit",
+        help_heading = "Image Options"
+    )]
+    raw: bool,
+
+    /// Set the dial's backlight to the provided color.
+    ///
+    /// Colors may be hex colors (`#ff8800`), CSS color names (`orange`), or HSV
+    /// colors (`hsv(30, 100, 80)`), where the hue is in degrees and the
+    /// saturation and value are percentages.
+    #[clap(long, short = 'c', conflicts_with_all = ["red", "green", "blue"])]
+    color: Option<color::Color>,
+
     /// Set the red value of the dial's backlight to the provided value.
     ///
     /// Values must be between 0 and 100.
@@ -103,6 +692,43 @@ pub struct SetValues {
     /// Values must be between 0 and 100.
     #[clap(long, short = 'b')]
     blue: Option<dial::Percent>,
+
+    /// How often the dial's needle moves towards a new value.
+    ///
+    /// If `--dial-easing-step` isn't provided, the dial's current step is
+    /// kept.
+    #[clap(
+        long,
+        value_parser = humantime_serde::re::humantime::parse_duration,
+        help_heading = "Easing Options"
+    )]
+    dial_easing_period: Option<Duration>,
+
+    /// How far the dial's needle moves towards a new value in each period.
+    ///
+    /// If `--dial-easing-period` isn't provided, the dial's current period is
+    /// kept.
+    #[clap(long, help_heading = "Easing Options")]
+    dial_easing_step: Option<dial::Percent>,
+
+    /// How often the dial's backlight changes towards a new color.
+    ///
+    /// If `--backlight-easing-step` isn't provided, the dial's current step
+    /// is kept.
+    #[clap(
+        long,
+        value_parser = humantime_serde::re::humantime::parse_duration,
+        help_heading = "Easing Options"
+    )]
+    backlight_easing_period: Option<Duration>,
+
+    /// How far the dial's backlight changes towards a new color in each
+    /// period.
+    ///
+    /// If `--backlight-easing-period` isn't provided, the dial's current
+    /// period is kept.
+    #[clap(long, help_heading = "Easing Options")]
+    backlight_easing_step: Option<dial::Percent>,
 }
 
 #[derive(Debug, clap::Parser)]
@@ -110,7 +736,7 @@ pub struct SetValues {
 #[group(id = "selection", required = true, multiple = false)]
 pub struct DialSelection {
     /// Select a dial by its UID.
-    #[clap(long = "dial", short = 'd')]
+    #[clap(long = "dial", short = 'd', add = completions::dial_uids())]
     uid: Option<dial::Id>,
 
     /// Select a dial by its numeric index.
@@ -118,15 +744,66 @@ pub struct DialSelection {
     index: Option<usize>,
 
     /// Select a dial by its user-assigned name.
-    #[clap(long, short = 'n')]
+    ///
+    /// The name may be a glob pattern, where `*` matches any number of
+    /// characters and `?` matches a single character, as long as it only
+    /// matches one dial.
+    #[clap(long, short = 'n', add = completions::dial_names())]
     name: Option<String>,
 }
 
+#[derive(Debug, clap::Parser)]
+#[command(next_help_heading = "Dial Selection")]
+#[group(id = "selection", required = true, multiple = true)]
+pub struct MultiDialSelection {
+    /// Select all dials.
+    #[clap(long, conflicts_with_all = ["uids", "indices", "names"])]
+    all: bool,
+
+    /// Select a dial by its UID.
+    ///
+    /// This may be repeated to select multiple dials.
+    #[clap(
+        long = "dial",
+        short = 'd',
+        value_name = "UID",
+        add = completions::dial_uids()
+    )]
+    uids: Vec<dial::Id>,
+
+    /// Select a dial by its numeric index.
+    ///
+    /// This may be repeated to select multiple dials.
+    #[clap(long = "index", short = 'i', value_name = "INDEX")]
+    indices: Vec<usize>,
+
+    /// Select dials by their user-assigned names.
+    ///
+    /// Names may be glob patterns, where `*` matches any number of characters
+    /// and `?` matches a single character. This may be repeated to select
+    /// multiple dials.
+    #[clap(
+        long = "name",
+        short = 'n',
+        value_name = "NAME",
+        add = completions::dial_names()
+    )]
+    names: Vec<String>,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
 pub enum OutputMode {
     Text,
     Json,
+    /// One compact JSON object per line, with no other output on stdout.
+    Jsonl,
     Ascii,
+    /// A compact table, with one line per dial.
+    Table,
+    /// A YAML document per dial.
+    Yaml,
+    /// A TOML `[[dials]]` table per dial.
+    Toml,
 }
 
 impl Args {
@@ -136,13 +813,33 @@ impl Args {
             client_args,
             output_args,
         } = self;
-        output_args.init_tracing()?;
+        match command {
+            // The dashboard draws over the terminal, so it displays logs in
+            // its own pane, rather than writing them to stderr.
+            #[cfg(feature = "tui")]
+            Some(Command::Tui { .. }) => output_args.init_tracing_to(tui::LogWriter, false)?,
+            _ => output_args.init_tracing()?,
+        }
+
+        // Daemon, completions, and man page commands don't talk to the
+        // VU-Server, so they shouldn't require an API key.
+        match command {
+            Some(Command::Daemon { socket, command }) => return command.run(&socket).await,
+            Some(Command::Completions { shell }) => {
+                return completions::print_registration(shell, "dialctl")
+            }
+            Some(Command::Man { out_dir }) => return man::generate::<Args>(out_dir.as_deref()),
+            _ => {}
+        }
+
         let client = client_args
             .into_client()
-            .context("failed to build client")?;
+            .context("failed to build client")?
+            .with_status_cache(STATUS_CACHE_MAX_AGE)
+            .with_retry_policy(RetryPolicy::default().max_elapsed_time(MAX_RETRY_TIME));
         match command {
             Some(command) => command.run(&client).await,
-            None => list_dials(&client, false, OutputMode::Text).await,
+            None => list_dials(&client, false, OutputMode::Text, table::DEFAULT_COLUMNS).await,
         }
     }
 }
@@ -150,11 +847,34 @@ impl Args {
 impl Command {
     pub async fn run(self, client: &vu_api::Client) -> miette::Result<()> {
         match self {
-            Command::List { details, output } => {
-                list_dials(client, details, output).await?;
+            Command::List {
+                details,
+                output,
+                columns,
+            } => {
+                list_dials(client, details, output, &columns).await?;
+            }
+
+            Command::Status {
+                dial,
+                output,
+                columns,
+            } => {
+                let status = match dial.select_dial(client).await? {
+                    (_, Some(status)) => status,
+                    (d, None) => d
+                        .status()
+                        .await
+                        .with_context(|| format!("failed to get status for dial {dial}"))?,
+                };
+                if output == OutputMode::Table {
+                    table::print_dials(&[table::Row::from(&status)], &columns);
+                } else {
+                    output.print_status(&status)?;
+                }
             }
 
-            Command::Status { dial, output } => {
+            Command::Get { dial, field } => {
                 let status = match dial.select_dial(client).await? {
                     (_, Some(status)) => status,
                     (d, None) => d
@@ -162,23 +882,203 @@ impl Command {
                         .await
                         .with_context(|| format!("failed to get status for dial {dial}"))?,
                 };
-                output.print_status(&status)?;
+                get::print(&status, field);
             }
 
-            Command::Set { dial, values } => values.run(client, &dial).await?,
-            Command::Reload { dial, output } => {
-                let status = client
-                    .dial(dial)
-                    .into_diagnostic()?
-                    .reload_hw_info()
-                    .await?;
-                output.print_status(&status)?;
+            Command::Set { dials, values } => values.run(client, &dials).await?,
+            Command::Calibrate {
+                dial,
+                half,
+                value,
+                start,
+                step,
+                ..
+            } => {
+                let (d, _) = dial.select_dial(client).await?;
+                let point = if half {
+                    dial::CalibrationPoint::Half
+                } else {
+                    dial::CalibrationPoint::Max
+                };
+                calibrate::run(&d, point, value, start, step)
+                    .await
+                    .with_context(|| format!("failed to calibrate dial {dial}"))?;
+            }
+            Command::Test { dial, delay } => {
+                let (d, _) = dial.select_dial(client).await?;
+                hwtest::run(&d, delay).await?;
             }
+            Command::Identify { dials, duration } => {
+                let dials = dials
+                    .select_dials(client)
+                    .await?
+                    .into_iter()
+                    .map(|(dial, _)| dial)
+                    .collect::<Vec<_>>();
+                identify::run(&dials, duration).await?;
+            }
+            Command::Off { dials, snapshot } => power::off(client, &dials, &snapshot).await?,
+            Command::On { dials, snapshot } => power::on(client, &dials, &snapshot).await?,
+            Command::Server { output } => server::run(client, output).await?,
+            Command::Provision { wait } => provision(client, wait).await?,
+            Command::Watch {
+                dials,
+                interval,
+                ascii,
+            } => watch::run(client, &dials, interval, ascii).await?,
+            Command::Gauge {
+                dial,
+                metric,
+                interval,
+            } => {
+                let (d, status) = dial.select_dial(client).await?;
+                let original = match status {
+                    Some(status) => status.value,
+                    None => {
+                        d.status()
+                            .await
+                            .with_context(|| format!("failed to get status for dial {dial}"))?
+                            .value
+                    }
+                };
+                gauge::run(&d, original, &metric, interval).await?;
+            }
+            Command::Demo {
+                animation,
+                period,
+                interval,
+            } => demo::run(client, animation, period, interval).await?,
+            #[cfg(feature = "tui")]
+            Command::Tui { interval } => tui::run(client, interval).await?,
+            Command::Admin { command } => command.run(client).await?,
+            Command::Batch { file } => batch::run(client, &file).await?,
+            Command::Config { command } => command.run(client).await?,
+            Command::Firmware { command } => command.run(client).await?,
+            Command::Image { command } => command.run(client).await?,
+            Command::Daemon { socket, command } => command.run(&socket).await?,
+            Command::Completions { shell } => completions::print_registration(shell, "dialctl")?,
+            Command::Man { out_dir } => man::generate::<Args>(out_dir.as_deref())?,
+            Command::Reload { dials, output } => reload(client, &dials, output).await?,
         };
         Ok(())
     }
 }
 
+impl AdminCommand {
+    pub async fn run(self, client: &vu_api::Client) -> miette::Result<()> {
+        match self {
+            AdminCommand::Keys { command } => command.run(client).await,
+        }
+    }
+}
+
+impl KeysCommand {
+    pub async fn run(self, client: &vu_api::Client) -> miette::Result<()> {
+        match self {
+            KeysCommand::List { output } => {
+                let keys = client
+                    .list_api_keys()
+                    .await
+                    .context("failed to list API keys")?;
+                output.print_api_keys(&keys)?;
+            }
+            KeysCommand::Create { name, dials } => {
+                let key = client
+                    .create_api_key(&name, &dials)
+                    .await
+                    .with_context(|| format!("failed to create API key {name:?}"))?;
+                tracing::info!(%name, dials = dials.len(), "Created API key");
+                println!("{key}");
+            }
+            KeysCommand::Update { key, name, dials } => {
+                client
+                    .update_api_key(&key, &name, &dials)
+                    .await
+                    .context("failed to update API key")?;
+                tracing::info!(%name, dials = dials.len(), "Updated API key");
+            }
+            KeysCommand::Delete { key } => {
+                client
+                    .delete_api_key(&key)
+                    .await
+                    .context("failed to delete API key")?;
+                tracing::info!("Deleted API key");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ConfigCommand {
+    pub async fn run(self, client: &vu_api::Client) -> miette::Result<()> {
+        match self {
+            ConfigCommand::Apply { file } => config::apply(client, &file).await,
+            ConfigCommand::Diff { file } => config::diff(client, &file).await,
+            ConfigCommand::Dump { out } => config::dump(client, out.as_deref()).await,
+        }
+    }
+}
+
+impl FirmwareCommand {
+    pub async fn run(self, client: &vu_api::Client) -> miette::Result<()> {
+        match self {
+            FirmwareCommand::List { output } => firmware::list(client, output).await,
+        }
+    }
+}
+
+impl ImageCommand {
+    pub async fn run(self, client: &vu_api::Client) -> miette::Result<()> {
+        match self {
+            ImageCommand::Get { dial, out } => {
+                let (d, _) = dial.select_dial(client).await?;
+                let image = d
+                    .get_image()
+                    .await
+                    .with_context(|| format!("failed to get image for dial {dial}"))?;
+                std::fs::write(&out, &image.bytes)
+                    .into_diagnostic()
+                    .with_context(|| format!("failed to write image to {out}"))?;
+                tracing::info!(
+                    %dial,
+                    %out,
+                    bytes = image.bytes.len(),
+                    content_type = image.content_type.as_deref(),
+                    "Saved dial image",
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DaemonCommand {
+    pub async fn run(self, socket: &Utf8Path) -> miette::Result<()> {
+        let (request, output) = match self {
+            DaemonCommand::Reload => (control::Request::Reload, None),
+            DaemonCommand::Pause => (control::Request::Pause, None),
+            DaemonCommand::Resume => (control::Request::Resume, None),
+            DaemonCommand::Profile { profile } => {
+                (control::Request::SwitchProfile { profile }, None)
+            }
+            DaemonCommand::Status { output } => (control::Request::Status, Some(output)),
+            DaemonCommand::Set { dial, value, .. } => {
+                (control::Request::SetValue { dial, value }, None)
+            }
+        };
+
+        match control::send(socket, &request).await? {
+            control::Response::Ok => Ok(()),
+            control::Response::Status(status) => output
+                .unwrap_or(OutputMode::Text)
+                .print_daemon_status(&status),
+            control::Response::Error { message } => {
+                Err(miette::miette!("{message}").context("vupdated returned an error"))
+            }
+        }
+    }
+}
+
 impl DialSelection {
     #[tracing::instrument(
         level = tracing::Level::DEBUG,
@@ -197,13 +1097,131 @@ impl DialSelection {
         }
 
         let dials = client.list_dials().await?;
+        if let Some(name) = self.name.as_deref() {
+            return select_by_name(dials, name);
+        }
+        let index = self
+            .index
+            .expect("selection must be validated to include either an index or name");
+        for (dial, _) in dials {
+            let status = dial
+                .status()
+                .await
+                .with_context(|| format!("failed to get status for dial {}", dial.id()))?;
+            if status.index == index {
+                tracing::debug!(
+                    dial.index = index,
+                    dial.name = %status.dial_name,
+                    dial.uid = %status.uid,
+                    "found dial by index",
+                );
+                return Ok((dial, Some(status)));
+            }
+            tracing::debug!(
+                dial.index = status.index,
+                dial.name = %status.dial_name,
+                dial.uid = %status.uid,
+                "dial does not match index {index}",
+            );
+        }
+
+        Err(exit::DialNotFound {
+            selection: self.to_string(),
+        }
+        .into())
+    }
+}
+
+/// Selects the one dial whose name matches the glob `name`.
+///
+/// Unlike [`MultiDialSelection`], this is an error if the glob matches more
+/// than one dial, since commands that take a single dial can't act on all of
+/// them.
+fn select_by_name(
+    dials: Vec<(Dial, DialInfo)>,
+    name: &str,
+) -> miette::Result<(Dial, Option<dial::Status>)> {
+    let mut matches = dials
+        .into_iter()
+        .filter(|(_, info)| {
+            let matched = glob_match(name, &info.dial_name);
+            tracing::debug!(
+                dial.name = %info.dial_name,
+                dial.uid = %info.uid,
+                matched,
+                "matching dial name against {name:?}",
+            );
+            matched
+        })
+        .collect::<Vec<_>>();
+    match matches.len() {
+        0 => Err(exit::DialNotFound {
+            selection: format!("name {name:?}"),
+        }
+        .into()),
+        1 => {
+            let (dial, _) = matches.remove(0);
+            Ok((dial, None))
+        }
+        _ => {
+            let names = matches
+                .iter()
+                .map(|(_, info)| format!("{:?} ({})", info.dial_name, info.uid))
+                .collect::<Vec<_>>();
+            Err(miette::miette!(
+                help = "use a more specific name, or select the dial with `--dial` or `--index`",
+                "name {name:?} matches {} dials: {}",
+                names.len(),
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+impl MultiDialSelection {
+    /// Returns the selected dials, along with their info, if it had to be
+    /// fetched to select them.
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all)]
+    async fn select_dials(
+        &self,
+        client: &vu_api::Client,
+    ) -> miette::Result<Vec<(Dial, Option<DialInfo>)>> {
+        let mut selected = Vec::<(Dial, Option<DialInfo>)>::new();
+        for uid in &self.uids {
+            if !selected.iter().any(|(dial, _)| dial.id() == uid) {
+                selected.push((client.dial(uid.clone()).into_diagnostic()?, None));
+            }
+        }
+        if !self.all && self.indices.is_empty() && self.names.is_empty() {
+            return Ok(selected);
+        }
+
+        let dials = client.list_dials().await?;
+        // Which of the names and indices have matched a dial, so that we can
+        // report any that don't match anything.
+        let mut names_matched = vec![false; self.names.len()];
+        let mut indices_matched = vec![false; self.indices.len()];
         for (dial, info) in dials {
-            match (self.index, self.name.as_deref()) {
-                (Some(index), None) => {
-                    let status = dial
-                        .status()
-                        .await
-                        .with_context(|| format!("failed to get status for dial {}", dial.id()))?;
+            let mut matches = self.all;
+            for (name, matched) in self.names.iter().zip(&mut names_matched) {
+                if glob_match(name, &info.dial_name) {
+                    tracing::debug!(
+                        dial.name = %info.dial_name,
+                        dial.uid = %info.uid,
+                        "found dial by name {name:?}",
+                    );
+                    *matched = true;
+                    matches = true;
+                }
+            }
+            if !self.indices.is_empty() {
+                // The dial's index isn't included in its info, so we have to
+                // get its status.
+                let status = dial
+                    .status()
+                    .await
+                    .with_context(|| format!("failed to get status for dial {}", dial.id()))?;
+                for (&index, matched) in self.indices.iter().zip(&mut indices_matched) {
                     if status.index == index {
                         tracing::debug!(
                             dial.index = index,
@@ -211,37 +1229,75 @@ impl DialSelection {
                             dial.uid = %status.uid,
                             "found dial by index",
                         );
-                        return Ok((dial, Some(status)));
-                    } else {
-                        tracing::debug!(
-                            dial.index = status.index,
-                            dial.name = %status.dial_name,
-                            dial.uid = %status.uid,
-                            "dial does not match index {index}",
-                        );
+                        *matched = true;
+                        matches = true;
                     }
                 }
-                (None, Some(name)) if info.dial_name == name => {
-                    tracing::debug!(
-                        dial.name = %info.dial_name,
-                        dial.uid = %info.uid,
-                        "found dial by name",
-                    );
-                    return Ok((dial, None));
-                }
-                (None, Some(name)) => {
-                    tracing::debug!(
-                        dial.name = %info.dial_name,
-                        dial.uid = %info.uid,
-                        "dial does not match name {name:?}",
-                    );
-                }
-                _ => unreachable!("selection must be validated to include either an index or name"),
-            };
+            }
+
+            if matches && !selected.iter().any(|(d, _)| d.id() == dial.id()) {
+                selected.push((dial, Some(info)));
+            }
+        }
+
+        let unmatched = self
+            .indices
+            .iter()
+            .zip(indices_matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(index, _)| format!("index {index}"))
+            .chain(
+                self.names
+                    .iter()
+                    .zip(names_matched)
+                    .filter(|(_, matched)| !matched)
+                    .map(|(name, _)| format!("name {name:?}")),
+            )
+            .collect::<Vec<_>>();
+        if !unmatched.is_empty() {
+            return Err(exit::DialNotFound {
+                selection: unmatched.join(", "),
+            }
+            .into());
         }
+        if selected.is_empty() {
+            return Err(miette::miette!("no dials are connected"));
+        }
+        Ok(selected)
+    }
+}
 
-        Err(miette::miette!("no dial found for {self}"))
+/// Returns `true` if `name` matches the glob `pattern`, where `*` matches any
+/// number of characters and `?` matches a single character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` in the pattern, and the position in the
+    // name that it was matched up to, so that we can backtrack and have it
+    // match one more character.
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
     }
+    pattern[p..].iter().all(|&c| c == '*')
 }
 
 impl fmt::Display for DialSelection {
@@ -256,18 +1312,59 @@ impl fmt::Display for DialSelection {
 }
 
 impl SetValues {
+    async fn run(
+        self,
+        client: &vu_api::Client,
+        selection: &MultiDialSelection,
+    ) -> miette::Result<()> {
+        let dials = selection.select_dials(client).await?;
+
+        // Convert the image once, rather than for every dial.
+        let image = match self.image {
+            Some(ref path) => {
+                let options = preprocess::Options {
+                    fit: self.fit,
+                    crop: self.crop,
+                    raw: self.raw,
+                };
+                let image = preprocess::prepare(path, options)
+                    .with_context(|| format!("failed to set image to {path}"))?;
+                Some(image)
+            }
+            None => None,
+        };
+
+        let total = dials.len();
+        let mut errors = Vec::new();
+        for (dial, info) in &dials {
+            if let Err(e) = self.set(dial, info.clone(), image.as_ref()).await {
+                errors.push(e.context(format!("failed to set dial {dial}")));
+            }
+        }
+        exit::per_dial(errors, total, "failed to set some dials")?;
+
+        if self.stdin {
+            let dials = dials.into_iter().map(|(dial, _)| dial).collect::<Vec<_>>();
+            stream::run(&dials, self.min_interval.unwrap_or_default()).await?;
+        }
+        Ok(())
+    }
+
     #[tracing::instrument(
         name = "set",
         level = tracing::Level::INFO,
         skip_all,
-        fields(dial = %selection),
+        fields(%dial),
     )]
-    async fn run(self, client: &vu_api::Client, selection: &DialSelection) -> miette::Result<()> {
-        let (dial, status) = selection.select_dial(client).await?;
-        tracing::debug!(%dial, "Found dial for selection");
+    async fn set(
+        &self,
+        dial: &Dial,
+        info: Option<DialInfo>,
+        image: Option<&preprocess::Prepared>,
+    ) -> miette::Result<()> {
         let mut errors = Vec::new();
         if let Some(value) = self.value {
-            tracing::info!(%dial, %value, "Setting value...");
+            tracing::info!(%value, "Setting value...");
             if let Err(e) = dial
                 .set(value)
                 .await
@@ -277,13 +1374,21 @@ impl SetValues {
             }
         }
 
-        if self.red.is_some() || self.green.is_some() || self.blue.is_some() {
-            let backlight = match status {
-                Some(status) => Ok(status.backlight),
+        if let Some(color) = self.color {
+            let backlight = color.backlight();
+            tracing::info!(?backlight, "Setting backlight...");
+            if let Err(e) = dial.set_backlight(backlight).await.with_context(|| {
+                format!("failed to set backlight for dial {dial} to {backlight:?}")
+            }) {
+                errors.push(e);
+            }
+        } else if self.red.is_some() || self.green.is_some() || self.blue.is_some() {
+            let backlight = match info {
+                Some(info) => Ok(info.backlight),
                 None => dial
                     .status()
                     .await
-                    .with_context(|| format!("failed to get status for dial {selection}"))
+                    .with_context(|| format!("failed to get status for dial {dial}"))
                     .map(|status| status.backlight),
             };
             match backlight {
@@ -304,7 +1409,7 @@ impl SetValues {
                     }
 
                     if let Err(e) = dial.set_backlight(backlight).await.with_context(|| {
-                        format!("failed to set backlight for dial {selection} to {backlight:?}")
+                        format!("failed to set backlight for dial {dial} to {backlight:?}")
                     }) {
                         errors.push(e);
                     }
@@ -313,13 +1418,86 @@ impl SetValues {
             }
         }
 
-        if let Some(image) = self.image {
-            tracing::warn!("Not setting image to {image}; not yet implemented.");
-            errors.push(miette::miette!("setting images is not yet implemented"));
+        if let Err(e) = self.set_easing(dial).await {
+            errors.push(e);
+        }
+
+        if let Some(preprocess::Prepared { filename, png }) = image {
+            tracing::info!(%filename, "Setting image...");
+            if let Err(e) = dial
+                .set_image(filename, png, true)
+                .await
+                .with_context(|| format!("failed to set image to {filename}"))
+            {
+                errors.push(e);
+            }
         }
 
         MultiError::from_vec(errors, "failed to set some dial configurations")
     }
+
+    async fn set_easing(&self, dial: &Dial) -> miette::Result<()> {
+        let Self {
+            dial_easing_period,
+            dial_easing_step,
+            backlight_easing_period,
+            backlight_easing_step,
+            ..
+        } = *self;
+        // The VU-Server sets an easing's period and step together, so if only
+        // one of them was provided, the other is left as it currently is.
+        let current = if dial_easing_period.is_some() != dial_easing_step.is_some()
+            || backlight_easing_period.is_some() != backlight_easing_step.is_some()
+        {
+            let easing = dial
+                .get_easing()
+                .await
+                .with_context(|| format!("failed to get easing for dial {dial}"))
+                .context("failed to set easing")?;
+            Some(easing)
+        } else {
+            None
+        };
+
+        let mut errors = Vec::new();
+        if dial_easing_period.is_some() || dial_easing_step.is_some() {
+            let period = dial_easing_period
+                .or(current.map(|easing| easing.dial_period))
+                .expect("the current easing is fetched if the period is missing");
+            let step = dial_easing_step
+                .or(current.map(|easing| easing.dial_step))
+                .expect("the current easing is fetched if the step is missing");
+            tracing::info!(?period, %step, "Setting dial easing...");
+            if let Err(e) = dial
+                .set_dial_easing(period, step)
+                .await
+                .with_context(|| format!("failed to set dial easing to {step} every {period:?}"))
+            {
+                errors.push(e);
+            }
+        }
+
+        if backlight_easing_period.is_some() || backlight_easing_step.is_some() {
+            let period = backlight_easing_period
+                .or(current.map(|easing| easing.backlight_period))
+                .expect("the current easing is fetched if the period is missing");
+            let step = backlight_easing_step
+                .or(current.map(|easing| easing.backlight_step))
+                .expect("the current easing is fetched if the step is missing");
+            tracing::info!(?period, %step, "Setting backlight easing...");
+            if let Err(e) = dial
+                .set_backlight_easing(period, step)
+                .await
+                .with_context(|| {
+                    format!("failed to set backlight easing to {step} every {period:?}")
+                })
+            {
+                errors.push(e);
+            }
+        }
+
+        MultiError::from_vec(errors, "failed to set easing")
+    }
 }
 
 struct TextTheme {
@@ -342,15 +1520,88 @@ const ASCII_THEME: TextTheme = TextTheme {
 
 impl OutputMode {
     pub fn print_dial(&self, info: &DialInfo) -> miette::Result<()> {
-        fn print_info(dial: &DialInfo, theme: &TextTheme, style: owo_colors::Style) {
+        fn print_info(
+            dial: &DialInfo,
+            theme: &TextTheme,
+            style: owo_colors::Style,
+            truecolor: bool,
+        ) {
             let TextTheme { branch, leaf, .. } = theme;
             println!("DIAL: {}", style.style(&dial.uid));
             println!("{branch}name: {}", style.style(&dial.dial_name));
             println!("{branch}value: {}", style.style(dial.value));
-            print_backlight(&dial.backlight, theme, style);
+            print_backlight(&dial.backlight, theme, style, truecolor);
             println!("{leaf}image: {}\n", style.style(&dial.image_file));
         }
 
+        let color = supports_color::on(supports_color::Stream::Stdout);
+        let has_color = color.map(|s| s.has_basic).unwrap_or(false);
+        let truecolor = color.map(|s| s.has_16m).unwrap_or(false);
+        let style = if has_color {
+            owo_colors::Style::new().bold()
+        } else {
+            owo_colors::Style::new()
+        };
+        match self {
+            // The swatch is drawn with block characters, so it's left out of
+            // ASCII output.
+            OutputMode::Ascii => print_info(info, &ASCII_THEME, style, false),
+            OutputMode::Text => print_info(info, &UNICODE_THEME, style, truecolor),
+            OutputMode::Json => {
+                let json = serde_json::to_string_pretty(info).into_diagnostic()?;
+                println!("{json}");
+            }
+            OutputMode::Table => table::print_dials(&[info.into()], table::DEFAULT_COLUMNS),
+            OutputMode::Jsonl => print_jsonl(info)?,
+            OutputMode::Yaml => print_yaml(info)?,
+            OutputMode::Toml => print_toml_dial(info)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn print_daemon_status(&self, status: &control::DaemonStatus) -> miette::Result<()> {
+        fn print_status(
+            status: &control::DaemonStatus,
+            theme: &TextTheme,
+            style: owo_colors::Style,
+        ) {
+            let TextTheme {
+                branch,
+                trunk,
+                leaf,
+            } = theme;
+            println!("VUPDATED:");
+            println!("{branch}paused: {}", style.style(status.paused));
+            println!("{branch}idle: {}", style.style(status.idle));
+            println!(
+                "{branch}profile: {}",
+                style.style(status.profile.as_deref().unwrap_or("<none>"))
+            );
+            println!("{leaf}DIALS:");
+            let mut dials = status.dials.iter().peekable();
+            while let Some((name, dial)) = dials.next() {
+                let (branch, trunk) = if dials.peek().is_some() {
+                    (branch, trunk)
+                } else {
+                    (leaf, &"   ")
+                };
+                println!("   {branch}{}", style.style(name));
+                println!("   {trunk} {branch}index: {}", style.style(dial.index));
+                println!("   {trunk} {branch}metric: {:?}", style.style(&dial.metric));
+                match dial.value {
+                    Some(value) => println!("   {trunk} {branch}value: {}", style.style(value)),
+                    None => println!("   {trunk} {branch}value: <unknown>"),
+                }
+                match dial.manual_value {
+                    Some(value) => {
+                        println!("   {trunk} {leaf}manual value: {}", style.style(value))
+                    }
+                    None => println!("   {trunk} {leaf}manual value: <none>"),
+                }
+            }
+        }
+
         let has_color = supports_color::on(supports_color::Stream::Stdout)
             .map(|s| s.has_basic)
             .unwrap_or(false);
@@ -360,19 +1611,102 @@ impl OutputMode {
             owo_colors::Style::new()
         };
         match self {
-            OutputMode::Ascii => print_info(info, &ASCII_THEME, style),
-            OutputMode::Text => print_info(info, &UNICODE_THEME, style),
+            OutputMode::Ascii => print_status(status, &ASCII_THEME, style),
+            OutputMode::Text => print_status(status, &UNICODE_THEME, style),
             OutputMode::Json => {
-                let json = serde_json::to_string_pretty(info).into_diagnostic()?;
+                let json = serde_json::to_string_pretty(status).into_diagnostic()?;
                 println!("{json}");
             }
+            OutputMode::Table => {
+                println!(
+                    "paused: {}  idle: {}  profile: {}\n",
+                    style.style(status.paused),
+                    style.style(status.idle),
+                    style.style(status.profile.as_deref().unwrap_or("<none>"))
+                );
+                let rows = status
+                    .dials
+                    .iter()
+                    .map(|(name, dial)| {
+                        vec![
+                            name.clone(),
+                            dial.index.to_string(),
+                            format!("{:?}", dial.metric),
+                            dial.value.map_or_else(|| "-".to_owned(), |v| v.to_string()),
+                            dial.manual_value
+                                .map_or_else(|| "-".to_owned(), |v| v.to_string()),
+                        ]
+                    })
+                    .collect();
+                table::print(vec!["NAME", "INDEX", "METRIC", "VALUE", "MANUAL"], rows);
+            }
+            OutputMode::Jsonl => print_jsonl(status)?,
+            OutputMode::Yaml => print_yaml(status)?,
+            OutputMode::Toml => {
+                let toml = toml::to_string_pretty(status).into_diagnostic()?;
+                print!("{toml}");
+            }
         }
 
         Ok(())
     }
 
+    pub fn print_api_keys(&self, keys: &[vu_api::api::KeyInfo]) -> miette::Result<()> {
+        match self {
+            OutputMode::Text | OutputMode::Ascii | OutputMode::Table => {
+                let rows = keys
+                    .iter()
+                    .map(|key| {
+                        let dials = key
+                            .dials
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>();
+                        vec![
+                            key.name.clone(),
+                            key.key.clone(),
+                            key.priority_level
+                                .map_or_else(|| "-".to_owned(), |level| level.to_string()),
+                            if dials.is_empty() {
+                                "-".to_owned()
+                            } else {
+                                dials.join(",")
+                            },
+                        ]
+                    })
+                    .collect();
+                table::print(vec!["NAME", "KEY", "PRIORITY", "DIALS"], rows);
+            }
+            OutputMode::Json => {
+                let json = serde_json::to_string_pretty(keys).into_diagnostic()?;
+                println!("{json}");
+            }
+            OutputMode::Jsonl => {
+                for key in keys {
+                    print_jsonl(key)?;
+                }
+            }
+            OutputMode::Yaml => print_yaml(&keys)?,
+            OutputMode::Toml => {
+                #[derive(serde::Serialize)]
+                struct Keys<'a> {
+                    keys: &'a [vu_api::api::KeyInfo],
+                }
+
+                let toml = toml::to_string_pretty(&Keys { keys }).into_diagnostic()?;
+                print!("{toml}");
+            }
+        }
+        Ok(())
+    }
+
     pub fn print_status(&self, status: &dial::Status) -> miette::Result<()> {
-        fn print_status(dial: &dial::Status, theme: &TextTheme, style: owo_colors::Style) {
+        fn print_status(
+            dial: &dial::Status,
+            theme: &TextTheme,
+            style: owo_colors::Style,
+            truecolor: bool,
+        ) {
             let TextTheme {
                 branch,
                 trunk,
@@ -419,7 +1753,7 @@ impl OutputMode {
                 "{trunk} {leaf}protocol version: {}",
                 style.style(&dial.protocol_version)
             );
-            print_backlight(&dial.backlight, theme, style);
+            print_backlight(&dial.backlight, theme, style, truecolor);
             println!("{branch}STATUS:");
             println!(
                 "{trunk} {branch}value_changed: {}",
@@ -433,45 +1767,99 @@ impl OutputMode {
                 "{trunk} {leaf}image_changed: {}",
                 style.style(dial.image_changed)
             );
+            let deadline = chrono::DateTime::<chrono::Local>::from(dial.update_deadline);
             println!(
                 "{leaf}update deadline: {}\n",
-                style.style(dial.update_deadline)
+                style.style(deadline.format("%Y-%m-%d %H:%M:%S%.3f"))
             );
         }
-        let has_color = supports_color::on(supports_color::Stream::Stdout)
-            .map(|s| s.has_basic)
-            .unwrap_or(false);
+        let color = supports_color::on(supports_color::Stream::Stdout);
+        let has_color = color.map(|s| s.has_basic).unwrap_or(false);
+        let truecolor = color.map(|s| s.has_16m).unwrap_or(false);
         let style = if has_color {
             owo_colors::Style::new().bold()
         } else {
             owo_colors::Style::new()
         };
         match self {
-            OutputMode::Ascii => print_status(status, &ASCII_THEME, style),
-            OutputMode::Text => print_status(status, &UNICODE_THEME, style),
+            OutputMode::Ascii => print_status(status, &ASCII_THEME, style, false),
+            OutputMode::Text => print_status(status, &UNICODE_THEME, style, truecolor),
             OutputMode::Json => {
                 let json = serde_json::to_string_pretty(status).into_diagnostic()?;
                 println!("{json}");
             }
+            OutputMode::Table => table::print_dials(&[status.into()], table::DEFAULT_COLUMNS),
+            OutputMode::Jsonl => print_jsonl(status)?,
+            OutputMode::Yaml => print_yaml(status)?,
+            OutputMode::Toml => print_toml_dial(status)?,
         }
 
         Ok(())
     }
 }
+/// The maximum number of dial statuses that are fetched at once.
+///
+/// Fetching statuses one at a time is slow with a lot of dials, especially
+/// when the VU-Server is running on something like a Raspberry Pi, but
+/// fetching all of them at once could swamp it.
+const MAX_CONCURRENT_STATUSES: usize = 4;
+
+/// How long a dial's status is cached for.
+///
+/// Selecting dials by index fetches every dial's status, and most commands
+/// then fetch the selected dials' statuses again. This is long enough for
+/// the command to reuse the statuses fetched while selecting dials, but short
+/// enough that long-running commands don't show stale statuses.
+const STATUS_CACHE_MAX_AGE: Duration = Duration::from_secs(1);
+
+/// How long to keep retrying requests that fail with transient errors.
+///
+/// Someone is usually waiting on `dialctl`, so this is much shorter than the
+/// default retry budget: long enough to ride out a VU-Server restart, but not
+/// to hang for minutes when it's down.
+const MAX_RETRY_TIME: Duration = Duration::from_secs(10);
+
 async fn list_dials(
     client: &vu_api::client::Client,
     details: bool,
     output: OutputMode,
+    columns: &[table::Column],
 ) -> miette::Result<()> {
     let dials = client.list_dials().await?;
+    let total = dials.len();
     let mut errors = Vec::new();
-    if details {
-        for (dial, info) in dials {
-            match dial
-                .status()
+    if output == OutputMode::Table {
+        // Tables are aligned, so all the dials have to be fetched before any
+        // are printed. The dial's index is only included in its status, so
+        // get the status if the index is displayed.
+        let fetched = if details || columns.contains(&table::Column::Index) {
+            futures::stream::iter(fetch_statuses(dials))
+                .buffered(MAX_CONCURRENT_STATUSES)
+                .map(|(_, info, status)| match status {
+                    Ok(status) => (info, Some(status)),
+                    Err(error) => {
+                        tracing::warn!(%error);
+                        errors.push(error);
+                        (info, None)
+                    }
+                })
+                .collect::<Vec<_>>()
                 .await
-                .with_context(|| format!("failed to get detailed status for {dial}"))
-            {
+        } else {
+            dials.into_iter().map(|(_, info)| (info, None)).collect()
+        };
+        let rows = fetched
+            .iter()
+            .map(|(info, status)| status.as_ref().map_or_else(|| info.into(), Into::into))
+            .collect::<Vec<table::Row<'_>>>();
+        table::print_dials(&rows, columns);
+    } else if details {
+        // Print each dial's status as soon as it's fetched, rather than in
+        // the order the dials were listed.
+        let mut statuses =
+            futures::stream::iter(fetch_statuses(dials)).buffer_unordered(MAX_CONCURRENT_STATUSES);
+        while let Some((dial, info, status)) = statuses.next().await {
+            match status {
                 Ok(status) => {
                     if let Err(e) = output.print_status(&status) {
                         errors
@@ -499,19 +1887,136 @@ async fn list_dials(
         }
     }
 
-    MultiError::from_vec(errors, "could not get info for all dials")
+    exit::per_dial(errors, total, "could not get info for all dials")
 }
 
+/// Returns a future that fetches the status of each of `dials`.
+fn fetch_statuses(
+    dials: Vec<(Dial, DialInfo)>,
+) -> impl Iterator<Item = impl Future<Output = (Dial, DialInfo, miette::Result<dial::Status>)>> {
+    dials.into_iter().map(|(dial, info)| async move {
+        let status = dial
+            .status()
+            .await
+            .with_context(|| format!("failed to get detailed status for {dial}"));
+        (dial, info, status)
+    })
+}
+
+/// Reloads the hardware info of the selected dials, printing each dial's
+/// status as it's reloaded.
+async fn reload(
+    client: &vu_api::Client,
+    selection: &MultiDialSelection,
+    output: OutputMode,
+) -> miette::Result<()> {
+    let dials = selection.select_dials(client).await?;
+    let total = dials.len();
+    let mut errors = Vec::new();
+    let mut reloaded = futures::stream::iter(dials.into_iter().map(|(dial, _)| async move {
+        let status = dial
+            .reload_hw_info()
+            .await
+            .with_context(|| format!("failed to reload dial {dial}"));
+        (dial, status)
+    }))
+    .buffer_unordered(MAX_CONCURRENT_STATUSES);
+    while let Some((dial, status)) = reloaded.next().await {
+        let result = status.and_then(|status| {
+            output
+                .print_status(&status)
+                .with_context(|| format!("failed to print status for {dial}"))
+        });
+        if let Err(error) = result {
+            tracing::warn!(%error);
+            errors.push(error);
+        }
+    }
+
+    exit::per_dial(errors, total, "failed to reload some dials")
+}
+
+async fn provision(client: &vu_api::Client, wait: Duration) -> miette::Result<()> {
+    let provisioning = client
+        .start_provisioning()
+        .await
+        .context("failed to provision dials")?;
+
+    // Provisioning happens in the background, so poll until new dials show
+    // up, or we give up waiting.
+    let deadline = tokio::time::Instant::now() + wait;
+    let new = loop {
+        let new = provisioning
+            .new_dials()
+            .await
+            .context("failed to list dials")?;
+        if !new.is_empty() || tokio::time::Instant::now() >= deadline {
+            break new;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    };
+
+    if new.is_empty() {
+        println!("no new dials found");
+    }
+    for (_, info) in new {
+        println!("{}\t{}", info.uid, info.dial_name);
+    }
+    Ok(())
+}
+
+/// Prints `value` as a single line of JSON.
+fn print_jsonl(value: &impl serde::Serialize) -> miette::Result<()> {
+    let json = serde_json::to_string(value).into_diagnostic()?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Prints `value` as a YAML document.
+///
+/// Each value starts a new document, so that the output of commands that
+/// print multiple dials is still valid YAML.
+fn print_yaml(value: &impl serde::Serialize) -> miette::Result<()> {
+    let yaml = serde_yaml::to_string(value).into_diagnostic()?;
+    print!("---\n{yaml}");
+    Ok(())
+}
+
+/// Prints `dial` as an entry in a TOML `[[dials]]` array.
+///
+/// TOML doesn't have top-level arrays, so this lets the output of commands
+/// that print multiple dials be concatenated into a valid TOML document.
+fn print_toml_dial(dial: &impl serde::Serialize) -> miette::Result<()> {
+    #[derive(serde::Serialize)]
+    struct Dials<'a, T> {
+        dials: [&'a T; 1],
+    }
+
+    let toml = toml::to_string_pretty(&Dials { dials: [dial] }).into_diagnostic()?;
+    println!("{toml}");
+    Ok(())
+}
+
+/// Prints a dial's backlight.
+///
+/// If `truecolor` is set, a swatch of the backlight's color is printed next to
+/// its red, green, and blue percentages.
 fn print_backlight(
-    dial::Backlight { red, green, blue }: &dial::Backlight,
+    backlight: &dial::Backlight,
     TextTheme {
         branch,
         trunk,
         leaf,
     }: &TextTheme,
     style: owo_colors::Style,
+    truecolor: bool,
 ) {
-    println!("{branch}BACKLIGHT:");
+    let dial::Backlight { red, green, blue } = backlight;
+    if truecolor {
+        println!("{branch}BACKLIGHT: {}", color::swatch(backlight));
+    } else {
+        println!("{branch}BACKLIGHT:");
+    }
     println!("{trunk} {branch}red: {}", style.style(red));
     println!("{trunk} {branch}green: {}", style.style(green));
     println!("{trunk} {leaf}blue: {}", style.style(blue));
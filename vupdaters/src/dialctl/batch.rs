@@ -0,0 +1,149 @@
+//! `dialctl batch`: running a list of operations from a file.
+//!
+//! Scripts that change several dials at once otherwise have to run `dialctl
+//! set` once per change, which builds a new client and connects to the
+//! VU-Server every time. A batch file lists the operations instead, and they
+//! are run in order with a single client. Unlike `dialctl config apply`,
+//! operations are always performed, even if the dial is already in the
+//! requested state, and the same dial may appear more than once.
+use super::preprocess;
+use crate::MultiError;
+use camino::{Utf8Path, Utf8PathBuf};
+use miette::{Context, IntoDiagnostic};
+use std::fmt;
+use vu_api::dial::{self, Backlight, Percent};
+
+/// A batch file.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Batch {
+    #[serde(default)]
+    ops: Vec<Op>,
+}
+
+/// A single operation in a batch file.
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case", deny_unknown_fields)]
+enum Op {
+    #[serde(rename = "set-value")]
+    Value { dial: dial::Id, value: Percent },
+    #[serde(rename = "set-backlight")]
+    Backlight {
+        dial: dial::Id,
+        backlight: Backlight,
+    },
+    /// The image path is relative to the batch file.
+    #[serde(rename = "set-image")]
+    Image { dial: dial::Id, image: Utf8PathBuf },
+    #[serde(rename = "set-name")]
+    Name { dial: dial::Id, name: String },
+}
+
+/// Runs each operation in the batch file at `path`, in order, printing the
+/// result of each.
+///
+/// If an operation fails, the remaining operations are still run.
+pub(super) async fn run(client: &vu_api::Client, path: &Utf8Path) -> miette::Result<()> {
+    let batch = Batch::load(path)?;
+    let total = batch.ops.len();
+    let mut errors = Vec::new();
+    for (i, op) in batch.ops.iter().enumerate() {
+        let n = i + 1;
+        match op.run(client).await {
+            Ok(()) => println!("[{n}/{total}] {op}: ok"),
+            Err(error) => {
+                println!("[{n}/{total}] {op}: failed");
+                errors.push(error.context(format!("operation {n} ({op}) failed")));
+            }
+        }
+    }
+
+    let failed = errors.len();
+    println!("\n{} of {total} operations succeeded", total - failed);
+    MultiError::from_vec(errors, "some batch operations failed")
+}
+
+// === impl Batch ===
+
+impl Batch {
+    /// Reads a batch file, resolving image paths relative to the file.
+    ///
+    /// Files ending in `.json` are parsed as JSON, and all others as TOML.
+    fn load(path: &Utf8Path) -> miette::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .into_diagnostic()
+            .with_context(|| format!("failed to read {path}"))?;
+        let mut batch: Self = if path.extension() == Some("json") {
+            serde_json::from_str(&text).into_diagnostic()
+        } else {
+            toml::from_str(&text).into_diagnostic()
+        }
+        .with_context(|| format!("failed to parse {path}"))?;
+
+        let dir = path.parent().unwrap_or(Utf8Path::new(""));
+        for op in &mut batch.ops {
+            if let Op::Image { image, .. } = op {
+                if image.is_relative() {
+                    *image = dir.join(&*image);
+                }
+            }
+        }
+        Ok(batch)
+    }
+}
+
+// === impl Op ===
+
+impl Op {
+    fn dial(&self) -> &dial::Id {
+        match self {
+            Op::Value { dial, .. }
+            | Op::Backlight { dial, .. }
+            | Op::Image { dial, .. }
+            | Op::Name { dial, .. } => dial,
+        }
+    }
+
+    async fn run(&self, client: &vu_api::Client) -> miette::Result<()> {
+        let dial = client.dial(self.dial().clone()).into_diagnostic()?;
+        match self {
+            Op::Value { value, .. } => dial
+                .set(*value)
+                .await
+                .with_context(|| format!("failed to set value to {value}")),
+            Op::Backlight { backlight, .. } => dial
+                .set_backlight(*backlight)
+                .await
+                .with_context(|| format!("failed to set backlight to {backlight:?}")),
+            Op::Image { image, .. } => {
+                let preprocess::Prepared { filename, png } =
+                    preprocess::prepare(image, preprocess::Options::default())
+                        .with_context(|| format!("failed to set image to {image}"))?;
+                dial.set_image(&filename, &png, true)
+                    .await
+                    .with_context(|| format!("failed to set image to {filename}"))
+            }
+            Op::Name { name, .. } => dial
+                .set_name(name)
+                .await
+                .with_context(|| format!("failed to set name to {name:?}")),
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Value { dial, value } => write!(f, "set value of dial {dial} to {value}"),
+            Op::Backlight { dial, backlight } => {
+                let Backlight { red, green, blue } = backlight;
+                write!(
+                    f,
+                    "set backlight of dial {dial} to red {red}, green {green}, blue {blue}"
+                )
+            }
+            Op::Image { dial, image } => write!(f, "set image of dial {dial} to {image}"),
+            Op::Name { dial, name } => write!(f, "set name of dial {dial} to {name:?}"),
+        }
+    }
+}
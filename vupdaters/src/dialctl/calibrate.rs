@@ -0,0 +1,98 @@
+//! `dialctl calibrate`: correcting needle drift.
+//!
+//! VU1 dials' needles can drift over time, so that 100% no longer lines up
+//! with the end of the scale. Calibration sets the raw motor position that the
+//! dial uses for 100% (or 50%). In interactive mode, the raw position is
+//! stepped up and down until the user confirms that the needle is in the
+//! right place.
+use miette::{Context, IntoDiagnostic};
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use vu_api::{dial::CalibrationPoint, Dial};
+
+/// The largest raw needle position.
+const RAW_MAX: u16 = u16::MAX;
+
+/// How much `++` and `--` change the raw position by, relative to `+` and
+/// `-`.
+const BIG_STEP_FACTOR: u16 = 10;
+
+/// Calibrates `point` on `dial`, either to `value`, or interactively if no
+/// value is given.
+pub(super) async fn run(
+    dial: &Dial,
+    point: CalibrationPoint,
+    value: Option<u16>,
+    start: Option<u16>,
+    step: u16,
+) -> miette::Result<()> {
+    if let Some(value) = value {
+        return calibrate(dial, point, value).await;
+    }
+
+    let mut raw = start.unwrap_or(match point {
+        CalibrationPoint::Max => RAW_MAX,
+        CalibrationPoint::Half => RAW_MAX / 2,
+    });
+    let (name, target) = match point {
+        CalibrationPoint::Max => ("100%", "at the end of the scale"),
+        CalibrationPoint::Half => ("50%", "in the middle of the scale"),
+    };
+    println!("Adjust the raw position until the needle is {target}.");
+    println!(
+        "Enter `+` or `-` to step by {step}, `++` or `--` to step by {}, or a raw \
+        position from 0 to {RAW_MAX}.",
+        step.saturating_mul(BIG_STEP_FACTOR)
+    );
+    println!("Press Enter when the needle is in place, or enter `q` to stop.");
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut applied = None;
+    loop {
+        if applied != Some(raw) {
+            calibrate(dial, point, raw).await?;
+            applied = Some(raw);
+        }
+        print!("raw position {raw}> ");
+        std::io::stdout()
+            .flush()
+            .into_diagnostic()
+            .context("failed to flush stdout")?;
+
+        let Some(line) = lines
+            .next_line()
+            .await
+            .into_diagnostic()
+            .context("failed to read from stdin")?
+        else {
+            // Stdin was closed, so there's no one left to confirm anything.
+            println!();
+            miette::bail!("calibration was not confirmed");
+        };
+        raw = match line.trim() {
+            "" | "y" => break,
+            "q" => miette::bail!("calibration stopped at raw position {raw}"),
+            "+" => raw.saturating_add(step),
+            "-" => raw.saturating_sub(step),
+            "++" => raw.saturating_add(step.saturating_mul(BIG_STEP_FACTOR)),
+            "--" => raw.saturating_sub(step.saturating_mul(BIG_STEP_FACTOR)),
+            input => match input.parse() {
+                Ok(value) => value,
+                Err(_) => {
+                    println!("invalid raw position {input:?}");
+                    raw
+                }
+            },
+        };
+    }
+
+    println!("Calibrated {name} to raw position {raw}.");
+    Ok(())
+}
+
+async fn calibrate(dial: &Dial, point: CalibrationPoint, raw: u16) -> miette::Result<()> {
+    tracing::debug!(?point, raw, "Calibrating...");
+    dial.calibrate(point, raw)
+        .await
+        .with_context(|| format!("failed to calibrate {point:?} to raw position {raw}"))
+}
@@ -0,0 +1,114 @@
+//! Parsing backlight colors.
+//!
+//! The VU-Server sets backlights using red, green, and blue percentages, but
+//! that's not how most people think about colors. `dialctl set --color`
+//! accepts hex colors, CSS color names, and HSV colors instead, and converts
+//! them to percentages.
+use std::str::FromStr;
+use vu_api::dial::Backlight;
+
+/// A backlight color.
+///
+/// Colors may be written as:
+///
+/// - hex RGB colors, such as `#ff8800` or `#f80`,
+/// - CSS color names, such as `orange`,
+/// - HSV colors, such as `hsv(30, 100, 80)`, where the hue is in degrees, and
+///   the saturation and value are percentages.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Color(Backlight);
+
+const HELP: &str =
+    "colors are written as hex colors like `#ff8800`, CSS color names like `orange`, \
+    or HSV colors like `hsv(30, 100, 80)`";
+
+/// CSS color names, and their 8-bit RGB values.
+const NAMED: &[(&str, [u8; 3])] = &[
+    ("black", [0, 0, 0]),
+    ("white", [255, 255, 255]),
+    ("gray", [128, 128, 128]),
+    ("grey", [128, 128, 128]),
+    ("silver", [192, 192, 192]),
+    ("red", [255, 0, 0]),
+    ("maroon", [128, 0, 0]),
+    ("orange", [255, 165, 0]),
+    ("gold", [255, 215, 0]),
+    ("yellow", [255, 255, 0]),
+    ("olive", [128, 128, 0]),
+    ("lime", [0, 255, 0]),
+    ("green", [0, 128, 0]),
+    ("teal", [0, 128, 128]),
+    ("cyan", [0, 255, 255]),
+    ("aqua", [0, 255, 255]),
+    ("blue", [0, 0, 255]),
+    ("navy", [0, 0, 128]),
+    ("indigo", [75, 0, 130]),
+    ("purple", [128, 0, 128]),
+    ("violet", [238, 130, 238]),
+    ("magenta", [255, 0, 255]),
+    ("fuchsia", [255, 0, 255]),
+    ("pink", [255, 192, 203]),
+];
+
+/// Returns a block of text in `backlight`'s color, for terminals that support
+/// truecolor.
+pub(super) fn swatch(backlight: &Backlight) -> String {
+    let [red, green, blue] = backlight.to_rgb8();
+    let style = owo_colors::Style::new().truecolor(red, green, blue);
+    style.style("██").to_string()
+}
+
+// === impl Color ===
+
+impl Color {
+    pub(super) fn backlight(self) -> Backlight {
+        self.0
+    }
+}
+
+impl FromStr for Color {
+    type Err = miette::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let color = s.trim().to_ascii_lowercase();
+
+        if color.starts_with('#') {
+            return Backlight::from_hex(&color)
+                .map(Self)
+                .map_err(|_| miette::miette!(help = HELP, "invalid hex color {s:?}"));
+        }
+
+        if let Some(args) = color
+            .strip_prefix("hsv(")
+            .and_then(|rest| rest.strip_suffix(')'))
+        {
+            let parse = |n: &str, what: &str, max: f32| {
+                let n = n.trim();
+                match n.trim_end_matches(['%', '°']).trim().parse::<f32>() {
+                    Ok(value) if (0.0..=max).contains(&value) => Ok(value),
+                    _ => Err(miette::miette!(
+                        help = format!("the {what} must be a number from 0 to {max}"),
+                        "invalid HSV {what} {n:?}"
+                    )),
+                }
+            };
+            let [hue, saturation, value] = args.split(',').collect::<Vec<_>>()[..] else {
+                return Err(miette::miette!(
+                    help = HELP,
+                    "HSV color {s:?} must have a hue, saturation, and value"
+                ));
+            };
+            return Ok(Self(Backlight::from_hsv(
+                parse(hue, "hue", 360.0)?,
+                parse(saturation, "saturation", 100.0)? / 100.0,
+                parse(value, "value", 100.0)? / 100.0,
+            )));
+        }
+
+        NAMED
+            .iter()
+            .find(|(name, _)| *name == color)
+            .map(|&(_, [red, green, blue])| Self(Backlight::from_rgb8(red, green, blue)))
+            .ok_or_else(|| miette::miette!(help = HELP, "unknown color {s:?}"))
+    }
+}
@@ -0,0 +1,467 @@
+//! `dialctl config`: declarative dial state.
+//!
+//! Rather than running `dialctl set` once per dial, the desired state of a
+//! whole desk of dials can be written down in a TOML file. `dialctl config
+//! apply` then compares each dial's live status with the file, and changes
+//! only the settings that don't match. Settings that the file leaves out are
+//! left as they are, and `dialctl config diff` prints what would be changed
+//! without changing it. `dialctl config dump` goes the other way, writing the
+//! dials' current state to a file that can be applied later, or on another
+//! machine.
+use super::{exit, preprocess};
+use crate::MultiError;
+use camino::{Utf8Path, Utf8PathBuf};
+use miette::{Context, IntoDiagnostic};
+use std::{collections::HashSet, fmt, time::Duration};
+use vu_api::{
+    dial::{self, Backlight, Percent, Status},
+    Dial,
+};
+
+/// The desired state of a set of dials.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(super) struct DeskState {
+    #[serde(default)]
+    dials: Vec<DialState>,
+}
+
+/// The desired state of a single dial.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct DialState {
+    /// The dial's UID.
+    uid: dial::Id,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<Percent>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backlight: Option<Backlight>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    easing: Option<EasingState>,
+
+    /// The path to the dial's background image, relative to the config file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image: Option<Utf8PathBuf>,
+}
+
+/// The desired easing configuration of a dial.
+///
+/// The VU-Server sets an easing's period and step together, so if only one
+/// of them is provided, the other is left as it currently is.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct EasingState {
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    dial_period: Option<Duration>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dial_step: Option<Percent>,
+
+    #[serde(
+        with = "humantime_serde",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    backlight_period: Option<Duration>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backlight_step: Option<Percent>,
+}
+
+/// A change to a single setting of a dial.
+#[derive(Debug)]
+enum Change {
+    Name { from: String, to: String },
+    DialEasing { from: Easing, to: Easing },
+    BacklightEasing { from: Easing, to: Easing },
+    Value { from: Percent, to: Percent },
+    Backlight { from: Backlight, to: Backlight },
+    Image { from: String, to: Utf8PathBuf },
+}
+
+/// An easing's period and step.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Easing {
+    period: Duration,
+    step: Percent,
+}
+
+/// The changes needed to bring a dial to its desired state.
+struct Plan {
+    dial: Dial,
+    name: String,
+    changes: Vec<Change>,
+}
+
+/// Reconciles the dials with the state described in the config file at
+/// `path`, printing each change that's made.
+pub(super) async fn apply(client: &vu_api::Client, path: &Utf8Path) -> miette::Result<()> {
+    reconcile(client, path, false).await
+}
+
+/// Prints the changes that [`apply`] would make for the config file at
+/// `path`, without making them.
+pub(super) async fn diff(client: &vu_api::Client, path: &Utf8Path) -> miette::Result<()> {
+    reconcile(client, path, true).await
+}
+
+/// Compares the dials with the config file at `path`, and prints the changes
+/// needed for each dial, making them unless this is a `dry_run`.
+async fn reconcile(client: &vu_api::Client, path: &Utf8Path, dry_run: bool) -> miette::Result<()> {
+    let desired = DeskState::load(path)?;
+    let total = desired.dials.len();
+    let (plans, mut errors) = plan(client, &desired).await;
+
+    let mut changed = 0;
+    let mut applied = 0;
+    for Plan {
+        dial,
+        name,
+        changes,
+    } in plans
+    {
+        let uid = dial.id();
+        if changes.is_empty() {
+            println!("dial {uid} ({name}): up to date");
+            continue;
+        }
+
+        println!("dial {uid} ({name}):");
+        changed += 1;
+        let mut dial_errors = Vec::new();
+        for change in changes {
+            if dry_run {
+                applied += 1;
+                println!("  {change}");
+                continue;
+            }
+            match change.apply(&dial).await {
+                Ok(()) => {
+                    applied += 1;
+                    println!("  {change}");
+                }
+                Err(error) => {
+                    println!("  {change} (failed)");
+                    dial_errors.push(error);
+                }
+            }
+        }
+        if let Err(error) = MultiError::from_vec(dial_errors, "failed to apply some changes") {
+            errors.push(error.context(format!("failed to apply config to dial {uid}")));
+        }
+    }
+
+    if applied > 0 {
+        let verb = if dry_run { "would apply" } else { "applied" };
+        println!("\n{verb} {applied} changes to {changed} dials");
+    } else if errors.is_empty() {
+        println!("\nall dials are up to date");
+    }
+    let message = if dry_run {
+        "failed to compare config for some dials"
+    } else {
+        "failed to apply config to some dials"
+    };
+    exit::per_dial(errors, total, message)
+}
+
+/// Writes the current state of all dials to `out`, or to stdout.
+pub(super) async fn dump(client: &vu_api::Client, out: Option<&Utf8Path>) -> miette::Result<()> {
+    let dials = client.list_dials().await.context("failed to list dials")?;
+    let total = dials.len();
+    let mut state = DeskState::default();
+    let mut errors = Vec::new();
+    for (dial, _) in dials {
+        match dial.status().await {
+            Ok(status) => state.dials.push(DialState::from(status)),
+            Err(error) => {
+                errors.push(
+                    miette::Report::from(error)
+                        .context(format!("failed to get status for dial {}", dial.id())),
+                );
+            }
+        }
+    }
+
+    let text = toml::to_string_pretty(&state)
+        .into_diagnostic()
+        .context("failed to serialize dial config")?;
+    match out {
+        Some(path) => {
+            std::fs::write(path, text)
+                .into_diagnostic()
+                .with_context(|| format!("failed to write {path}"))?;
+            tracing::info!(%path, dials = state.dials.len(), "Wrote dial config");
+        }
+        None => print!("{text}"),
+    }
+    exit::per_dial(errors, total, "failed to dump config for some dials")
+}
+
+/// Compares each dial in `desired` with its live status.
+///
+/// Returns the changes needed for each dial whose status could be fetched,
+/// and an error for each dial whose status couldn't be.
+async fn plan(client: &vu_api::Client, desired: &DeskState) -> (Vec<Plan>, Vec<miette::Report>) {
+    let mut plans = Vec::with_capacity(desired.dials.len());
+    let mut errors = Vec::new();
+    for state in &desired.dials {
+        let uid = &state.uid;
+        let result = async {
+            let dial = client.dial(uid.clone()).into_diagnostic()?;
+            let status = dial
+                .status()
+                .await
+                .with_context(|| format!("failed to get status for dial {uid}"))?;
+            Ok::<_, miette::Report>(Plan {
+                changes: state.changes(&status),
+                name: status.dial_name,
+                dial,
+            })
+        }
+        .await;
+        match result {
+            Ok(plan) => plans.push(plan),
+            Err(error) => errors.push(error),
+        }
+    }
+    (plans, errors)
+}
+
+// === impl DeskState ===
+
+impl DeskState {
+    /// Reads a config file, resolving image paths relative to the file.
+    fn load(path: &Utf8Path) -> miette::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .into_diagnostic()
+            .with_context(|| format!("failed to read {path}"))?;
+        let mut state: Self = toml::from_str(&text)
+            .into_diagnostic()
+            .with_context(|| format!("failed to parse {path}"))?;
+
+        let mut uids = HashSet::new();
+        for dial in &state.dials {
+            if !uids.insert(&dial.uid) {
+                return Err(miette::miette!(
+                    help = "each dial may only be listed once",
+                    "dial {} is listed more than once in {path}",
+                    dial.uid
+                ));
+            }
+        }
+
+        let dir = path.parent().unwrap_or(Utf8Path::new(""));
+        for image in state
+            .dials
+            .iter_mut()
+            .filter_map(|dial| dial.image.as_mut())
+        {
+            if image.is_relative() {
+                *image = dir.join(&*image);
+            }
+        }
+        Ok(state)
+    }
+}
+
+// === impl DialState ===
+
+impl DialState {
+    /// Returns the changes needed to bring a dial with `status` to this
+    /// state.
+    fn changes(&self, status: &Status) -> Vec<Change> {
+        let mut changes = Vec::new();
+        if let Some(ref name) = self.name {
+            if name != &status.dial_name {
+                changes.push(Change::Name {
+                    from: status.dial_name.clone(),
+                    to: name.clone(),
+                });
+            }
+        }
+
+        if let Some(ref easing) = self.easing {
+            let current = status.easing;
+            let from = Easing {
+                period: current.dial_period,
+                step: current.dial_step,
+            };
+            let to = Easing {
+                period: easing.dial_period.unwrap_or(from.period),
+                step: easing.dial_step.unwrap_or(from.step),
+            };
+            if from != to {
+                changes.push(Change::DialEasing { from, to });
+            }
+
+            let from = Easing {
+                period: current.backlight_period,
+                step: current.backlight_step,
+            };
+            let to = Easing {
+                period: easing.backlight_period.unwrap_or(from.period),
+                step: easing.backlight_step.unwrap_or(from.step),
+            };
+            if from != to {
+                changes.push(Change::BacklightEasing { from, to });
+            }
+        }
+
+        if let Some(value) = self.value {
+            if value != status.value {
+                changes.push(Change::Value {
+                    from: status.value,
+                    to: value,
+                });
+            }
+        }
+
+        if let Some(backlight) = self.backlight {
+            if backlight != status.backlight {
+                changes.push(Change::Backlight {
+                    from: status.backlight,
+                    to: backlight,
+                });
+            }
+        }
+
+        if let Some(ref image) = self.image {
+            if !same_image(image, &status.image_file) {
+                changes.push(Change::Image {
+                    from: status.image_file.clone(),
+                    to: image.clone(),
+                });
+            }
+        }
+
+        changes
+    }
+}
+
+impl From<Status> for DialState {
+    fn from(status: Status) -> Self {
+        let Status {
+            uid,
+            dial_name,
+            value,
+            backlight,
+            easing,
+            image_file,
+            ..
+        } = status;
+        Self {
+            uid,
+            name: Some(dial_name),
+            value: Some(value),
+            backlight: Some(backlight),
+            easing: Some(EasingState {
+                dial_period: Some(easing.dial_period),
+                dial_step: Some(easing.dial_step),
+                backlight_period: Some(easing.backlight_period),
+                backlight_step: Some(easing.backlight_step),
+            }),
+            // The VU-Server only knows the image's file name, so this is only
+            // a path to the image if the image file is placed next to the
+            // config file. Applying the config to the same dial won't
+            // re-upload the image either way, since the names will match.
+            image: Some(Utf8PathBuf::from(image_file)),
+        }
+    }
+}
+
+/// Returns `true` if the image at `path` looks like the dial's current
+/// `image_file`.
+///
+/// The VU-Server only reports the name of the file a dial's image was
+/// uploaded from, so images are compared by name, rather than by contents.
+/// Uploaded images may have been converted to PNGs, so the extension is
+/// ignored.
+fn same_image(path: &Utf8Path, image_file: &str) -> bool {
+    path.file_stem().is_some() && path.file_stem() == Utf8Path::new(image_file).file_stem()
+}
+
+// === impl Change ===
+
+impl Change {
+    async fn apply(&self, dial: &Dial) -> miette::Result<()> {
+        match self {
+            Change::Name { to, .. } => dial
+                .set_name(to)
+                .await
+                .with_context(|| format!("failed to set name to {to:?}")),
+            Change::DialEasing { to, .. } => dial
+                .set_dial_easing(to.period, to.step)
+                .await
+                .with_context(|| format!("failed to set dial easing to {to}")),
+            Change::BacklightEasing { to, .. } => dial
+                .set_backlight_easing(to.period, to.step)
+                .await
+                .with_context(|| format!("failed to set backlight easing to {to}")),
+            Change::Value { to, .. } => dial
+                .set(*to)
+                .await
+                .with_context(|| format!("failed to set value to {to}")),
+            Change::Backlight { to, .. } => dial
+                .set_backlight(*to)
+                .await
+                .with_context(|| format!("failed to set backlight to {to:?}")),
+            Change::Image { to, .. } => {
+                let preprocess::Prepared { filename, png } =
+                    preprocess::prepare(to, preprocess::Options::default())
+                        .with_context(|| format!("failed to set image to {to}"))?;
+                dial.set_image(&filename, &png, true)
+                    .await
+                    .with_context(|| format!("failed to set image to {filename}"))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Name { from, to } => write!(f, "name: {from:?} -> {to:?}"),
+            Change::DialEasing { from, to } => write!(f, "dial easing: {from} -> {to}"),
+            Change::BacklightEasing { from, to } => {
+                write!(f, "backlight easing: {from} -> {to}")
+            }
+            Change::Value { from, to } => write!(f, "value: {from} -> {to}"),
+            Change::Backlight { from, to } => write!(
+                f,
+                "backlight: {} -> {}",
+                DisplayBacklight(from),
+                DisplayBacklight(to)
+            ),
+            Change::Image { from, to } => write!(f, "image: {from} -> {to}"),
+        }
+    }
+}
+
+// === impl Easing ===
+
+impl fmt::Display for Easing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { period, step } = self;
+        write!(f, "{step} every {period:?}")
+    }
+}
+
+struct DisplayBacklight<'a>(&'a Backlight);
+
+impl fmt::Display for DisplayBacklight<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Backlight { red, green, blue } = self.0;
+        write!(f, "red {red}, green {green}, blue {blue}")
+    }
+}
@@ -0,0 +1,217 @@
+//! `dialctl demo`: animating the dials.
+//!
+//! This drives every connected dial's needle and backlight through an
+//! animation until interrupted, and then puts the dials back the way they
+//! were. Besides showing off the hardware, leaving it running is a decent
+//! soak test for the VU-Server, so it reports how many updates failed when it
+//! exits.
+use crate::MultiError;
+use miette::Context;
+use std::{
+    f32::consts::TAU,
+    hash::{BuildHasher, Hasher},
+    time::{Duration, Instant},
+};
+use vu_api::{
+    api::DialInfo,
+    dial::{Backlight, Percent},
+    Dial,
+};
+
+/// An animation to display on the dials.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Animation {
+    /// Sweep each needle back and forth, with each dial slightly behind the
+    /// one before it, while the backlights cycle through the rainbow.
+    #[default]
+    Sine,
+    /// Light up one dial at a time, moving along the row of dials.
+    Chase,
+    /// Move each needle and backlight hue randomly.
+    RandomWalk,
+}
+
+/// The state of a dial in the animation.
+struct DemoDial {
+    dial: Dial,
+    /// The dial's value and backlight before the demo started, to restore
+    /// afterwards.
+    original: DialInfo,
+    /// The last value and backlight sent to the dial, so that unchanged
+    /// settings aren't sent again.
+    value: Option<Percent>,
+    backlight: Option<Backlight>,
+    /// The dial's position in a random walk.
+    walk: (f32, f32),
+}
+
+/// How many updates were sent during the demo, and how many failed.
+#[derive(Default)]
+struct Stats {
+    updates: usize,
+    errors: usize,
+}
+
+/// Runs `animation` on all dials until interrupted.
+///
+/// `period` is how long one cycle of the animation takes, and `interval` is
+/// how often the dials are updated.
+pub(super) async fn run(
+    client: &vu_api::Client,
+    animation: Animation,
+    period: Duration,
+    interval: Duration,
+) -> miette::Result<()> {
+    let mut dials = client
+        .list_dials()
+        .await
+        .context("failed to list dials")?
+        .into_iter()
+        .map(|(dial, original)| DemoDial {
+            dial,
+            original,
+            value: None,
+            backlight: None,
+            walk: (50.0, 0.0),
+        })
+        .collect::<Vec<_>>();
+    if dials.is_empty() {
+        miette::bail!("no dials connected");
+    }
+    tracing::info!(
+        dials = dials.len(),
+        ?animation,
+        "Running demo, press Ctrl-C to stop..."
+    );
+
+    let mut rng = Rng::new();
+    let mut stats = Stats::default();
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let start = Instant::now();
+    let count = dials.len() as f32;
+    let result = loop {
+        tokio::select! {
+            _ = ticks.tick() => {},
+            result = tokio::signal::ctrl_c() => break result,
+        }
+
+        // How far through the current cycle the animation is, from 0 to 1.
+        let phase = (start.elapsed().as_secs_f32() / period.as_secs_f32()).fract();
+        for (i, dial) in dials.iter_mut().enumerate() {
+            let offset = i as f32 / count;
+            let (value, hue) = match animation {
+                Animation::Sine => {
+                    let value = 50.0 - 50.0 * ((phase - offset) * TAU).cos();
+                    (value, (phase + offset) * 360.0)
+                }
+                Animation::Chase => {
+                    let lit = (phase * count) as usize == i;
+                    let value = if lit { 100.0 } else { 0.0 };
+                    (value, offset * 360.0)
+                }
+                Animation::RandomWalk => {
+                    let (value, hue) = &mut dial.walk;
+                    *value = (*value + rng.between(-10.0, 10.0)).clamp(0.0, 100.0);
+                    *hue = (*hue + rng.between(-15.0, 15.0)).rem_euclid(360.0);
+                    dial.walk
+                }
+            };
+            let backlight = if animation == Animation::Chase && value == 0.0 {
+                Backlight::OFF
+            } else {
+                Backlight::from_hsv(hue, 1.0, 1.0)
+            };
+            let value = Percent::from_f64_clamped(f64::from(value));
+            dial.update(value, backlight, &mut stats).await;
+        }
+    };
+
+    let Stats { updates, errors } = stats;
+    tracing::info!(updates, errors, "Demo stopped, restoring dials...");
+    let mut restore_errors = Vec::new();
+    for dial in &dials {
+        if let Err(error) = dial.restore().await {
+            restore_errors.push(error);
+        }
+    }
+    MultiError::from_vec(restore_errors, "failed to restore some dials")?;
+    if errors > 0 {
+        println!("{errors} of {updates} updates failed");
+    } else {
+        println!("{updates} updates sent");
+    }
+
+    result.map_err(|error| miette::miette!("failed to listen for Ctrl-C: {error}"))
+}
+
+// === impl DemoDial ===
+
+impl DemoDial {
+    async fn update(&mut self, value: Percent, backlight: Backlight, stats: &mut Stats) {
+        let uid = self.dial.id();
+        if self.value != Some(value) {
+            stats.updates += 1;
+            match self.dial.set(value).await {
+                Ok(_) => self.value = Some(value),
+                Err(error) => {
+                    stats.errors += 1;
+                    tracing::warn!(%error, %uid, %value, "failed to set value");
+                }
+            }
+        }
+
+        if self.backlight != Some(backlight) {
+            stats.updates += 1;
+            match self.dial.set_backlight(backlight).await {
+                Ok(_) => self.backlight = Some(backlight),
+                Err(error) => {
+                    stats.errors += 1;
+                    tracing::warn!(%error, %uid, ?backlight, "failed to set backlight");
+                }
+            }
+        }
+    }
+
+    async fn restore(&self) -> miette::Result<()> {
+        let DialInfo {
+            value, backlight, ..
+        } = self.original;
+        let uid = self.dial.id();
+        self.dial
+            .set(value)
+            .await
+            .with_context(|| format!("failed to restore value of dial {uid} to {value}"))?;
+        self.dial
+            .set_backlight(backlight)
+            .await
+            .with_context(|| format!("failed to restore backlight of dial {uid} to {backlight:?}"))
+    }
+}
+
+/// A tiny xorshift random number generator, so that the random walk doesn't
+/// need a dependency on `rand`.
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        // `RandomState` is randomly seeded by the standard library.
+        let seed = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        Self(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a random number between `min` and `max`.
+    fn between(&mut self, min: f32, max: f32) -> f32 {
+        let unit = (self.next() >> 40) as f32 / (1u64 << 24) as f32;
+        min + unit * (max - min)
+    }
+}
@@ -0,0 +1,94 @@
+//! `dialctl`'s exit codes.
+//!
+//! Scripts that run `dialctl` often need to know *why* it failed: a dial that
+//! isn't plugged in should be handled differently from a VU-Server that isn't
+//! running. So, rather than exiting with 1 for every error, `dialctl`
+//! classifies errors by walking their chain of causes.
+use crate::MultiError;
+
+/// Any error that doesn't fall into one of the classes below.
+pub const FAILURE: u8 = 1;
+
+/// The command line was invalid.
+///
+/// This is the exit code that `clap` uses, so it's reserved here.
+pub const USAGE: u8 = 2;
+
+/// A selected dial couldn't be found.
+pub const DIAL_NOT_FOUND: u8 = 3;
+
+/// The VU-Server couldn't be reached.
+pub const SERVER_UNREACHABLE: u8 = 4;
+
+/// The VU-Server rejected the API key.
+pub const UNAUTHORIZED: u8 = 5;
+
+/// A command that operates on multiple dials succeeded for some dials, but
+/// failed for others.
+pub const PARTIAL_FAILURE: u8 = 6;
+
+/// No dial matched a dial selection.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+#[error("no dial found for {selection}")]
+pub(super) struct DialNotFound {
+    pub(super) selection: String,
+}
+
+/// A command failed for some, but not all, of the dials it operated on.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+#[error("{msg} ({failed} of {total} dials failed)", failed = .errors.len())]
+pub(super) struct PartialFailure {
+    msg: &'static str,
+    total: usize,
+    #[related]
+    errors: Vec<miette::Report>,
+}
+
+/// Returns the exit code for `error`.
+pub fn code(error: &miette::Report) -> u8 {
+    classify(error).unwrap_or(FAILURE)
+}
+
+/// Returns an error if any of the `total` dials a command operated on failed.
+///
+/// If only some of the dials failed, this is a [`PartialFailure`].
+pub(super) fn per_dial(
+    errors: Vec<miette::Report>,
+    total: usize,
+    msg: &'static str,
+) -> miette::Result<()> {
+    if errors.is_empty() || errors.len() >= total {
+        return MultiError::from_vec(errors, msg);
+    }
+    Err(PartialFailure { msg, total, errors }.into())
+}
+
+fn classify(error: &miette::Report) -> Option<u8> {
+    for cause in error.chain() {
+        if cause.is::<PartialFailure>() {
+            return Some(PARTIAL_FAILURE);
+        }
+        if cause.is::<DialNotFound>() {
+            return Some(DIAL_NOT_FOUND);
+        }
+        if let Some(error) = cause.downcast_ref::<vu_api::client::Error>() {
+            if error.is_unreachable() {
+                return Some(SERVER_UNREACHABLE);
+            }
+            if error.is_unauthorized() {
+                return Some(UNAUTHORIZED);
+            }
+            if error.is_not_found() {
+                return Some(DIAL_NOT_FOUND);
+            }
+        }
+        // If every dial failed for the same reason, use that reason's exit
+        // code.
+        if let Some(MultiError { errors, .. }) = cause.downcast_ref::<MultiError>() {
+            let mut codes = errors.iter().map(classify);
+            let first = codes.next().flatten()?;
+            return codes.all(|code| code == Some(first)).then_some(first);
+        }
+    }
+    None
+}
@@ -0,0 +1,118 @@
+//! `dialctl firmware`: dial firmware versions.
+//!
+//! Each dial's firmware and hardware versions are only included in its
+//! detailed status, so comparing them across a desk of dials means running
+//! `dialctl status` once per dial. This collects them into one list.
+//!
+//! Flashing firmware isn't supported: the VU-Server has no firmware update
+//! endpoint. The `vu-protocol` crate's `hub` module can talk to the hub
+//! directly, and could send the commands that make the hub or a dial jump to
+//! its bootloader, but the protocol for transferring a firmware image to the
+//! bootloader isn't documented, so there's nothing to send once it's there.
+use super::{exit, table, OutputMode};
+use miette::{Context, IntoDiagnostic};
+use vu_api::dial::{self, Status};
+
+/// The firmware and hardware versions of a dial.
+#[derive(Debug, serde::Serialize)]
+struct Versions {
+    uid: dial::Id,
+    dial_name: String,
+    index: usize,
+    fw_version: String,
+    fw_hash: String,
+    hw_version: String,
+    protocol_version: String,
+}
+
+/// Prints the firmware versions of all dials.
+pub(super) async fn list(client: &vu_api::Client, output: OutputMode) -> miette::Result<()> {
+    let dials = client.list_dials().await.context("failed to list dials")?;
+    let total = dials.len();
+    let mut versions = Vec::with_capacity(total);
+    let mut errors = Vec::new();
+    for (dial, _) in dials {
+        match dial.status().await {
+            Ok(status) => versions.push(Versions::from(status)),
+            Err(error) => errors.push(
+                miette::Report::from(error)
+                    .context(format!("failed to get status for dial {dial}")),
+            ),
+        }
+    }
+    versions.sort_by_key(|versions| versions.index);
+
+    match output {
+        OutputMode::Text | OutputMode::Ascii | OutputMode::Table => {
+            let rows = versions
+                .iter()
+                .map(|v| {
+                    vec![
+                        v.uid.to_string(),
+                        v.dial_name.clone(),
+                        v.index.to_string(),
+                        v.fw_version.clone(),
+                        v.hw_version.clone(),
+                        v.protocol_version.clone(),
+                        v.fw_hash.clone(),
+                    ]
+                })
+                .collect();
+            table::print(
+                vec![
+                    "UID", "NAME", "INDEX", "FIRMWARE", "HARDWARE", "PROTOCOL", "HASH",
+                ],
+                rows,
+            );
+        }
+        OutputMode::Json => {
+            let json = serde_json::to_string_pretty(&versions).into_diagnostic()?;
+            println!("{json}");
+        }
+        OutputMode::Jsonl => {
+            for v in &versions {
+                super::print_jsonl(v)?;
+            }
+        }
+        OutputMode::Yaml => {
+            for v in &versions {
+                super::print_yaml(v)?;
+            }
+        }
+        OutputMode::Toml => {
+            for v in &versions {
+                super::print_toml_dial(v)?;
+            }
+        }
+    }
+
+    exit::per_dial(
+        errors,
+        total,
+        "could not get firmware versions for all dials",
+    )
+}
+
+impl From<Status> for Versions {
+    fn from(status: Status) -> Self {
+        let Status {
+            uid,
+            dial_name,
+            index,
+            fw_version,
+            fw_hash,
+            hw_version,
+            protocol_version,
+            ..
+        } = status;
+        Self {
+            uid,
+            dial_name,
+            index,
+            fw_version,
+            fw_hash,
+            hw_version,
+            protocol_version,
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! `dialctl gauge`: displaying a metric without the daemon.
+//!
+//! Setting up `vupdated` means writing a config file and running a service,
+//! which is a lot of work just to see a dial do something useful. This runs a
+//! single dial's update loop in the foreground instead, sampling a metric
+//! with the same sources the daemon uses, until interrupted. The dial's
+//! original value is restored afterwards.
+use crate::daemon::{LocalMetric, Metric};
+use miette::Context;
+use std::time::Duration;
+use vu_api::Dial;
+
+/// Displays `metric` on `dial`, sampling it every `interval`, until
+/// interrupted.
+pub(super) async fn run(
+    dial: &Dial,
+    original: vu_api::dial::Percent,
+    metric: &Metric,
+    interval: Duration,
+) -> miette::Result<()> {
+    let mut source = LocalMetric::new(metric)?;
+    tracing::info!(
+        %dial,
+        metric = source.name(),
+        ?interval,
+        "Displaying metric, press Ctrl-C to stop..."
+    );
+
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut last = None;
+    let result = loop {
+        tokio::select! {
+            _ = ticks.tick() => {},
+            result = tokio::signal::ctrl_c() => break result,
+        }
+
+        let value = match source.sample().await {
+            Ok(value) => value,
+            Err(error) => {
+                tracing::warn!(%error, "Failed to sample metric");
+                continue;
+            }
+        };
+        tracing::debug!(%value, "Sampled metric");
+        if last == Some(value) {
+            continue;
+        }
+        match dial.set(value).await {
+            Ok(()) => last = Some(value),
+            Err(error) => tracing::warn!(%error, %value, "Failed to set value"),
+        }
+    };
+
+    tracing::info!(%dial, value = %original, "Stopped, restoring dial...");
+    dial.set(original)
+        .await
+        .with_context(|| format!("failed to restore value of dial {dial} to {original}"))?;
+    result.map_err(|error| miette::miette!("failed to listen for Ctrl-C: {error}"))
+}
@@ -0,0 +1,37 @@
+//! `dialctl get`: printing a single field of a dial's status.
+//!
+//! Scripts that only need one number, like a dial's value, otherwise have to
+//! pick it out of the tree output, or pipe the JSON output through `jq`. This
+//! prints just the field's raw value, with no labels, styling, or `%` signs.
+use vu_api::dial::{self, Backlight};
+
+/// A field of a dial's status that `dialctl get` can print.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Field {
+    /// The dial's value, as a number between 0 and 100.
+    Value,
+    /// The dial's name.
+    Name,
+    /// The dial's backlight, as space-separated red, green, and blue values
+    /// between 0 and 100.
+    Backlight,
+    /// The dial's background image file.
+    Image,
+    /// The dial's index.
+    Index,
+}
+
+/// Prints `field` of `status` on its own line.
+pub(super) fn print(status: &dial::Status, field: Field) {
+    let value = match field {
+        Field::Value => u8::from(status.value).to_string(),
+        Field::Name => status.dial_name.clone(),
+        Field::Backlight => {
+            let Backlight { red, green, blue } = status.backlight;
+            format!("{} {} {}", u8::from(red), u8::from(green), u8::from(blue))
+        }
+        Field::Image => status.image_file.clone(),
+        Field::Index => status.index.to_string(),
+    };
+    println!("{value}");
+}
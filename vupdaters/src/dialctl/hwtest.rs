@@ -0,0 +1,166 @@
+//! `dialctl test`: checking a dial end to end.
+//!
+//! When a dial misbehaves, it can be hard to tell whether the problem is the
+//! dial itself, the USB hub it's connected to, or the VU-Server. This runs
+//! the dial through a series of checks, confirming that each setting sent to
+//! the VU-Server is reflected in the dial's status, and prints a report of
+//! which checks passed and how long they took. The dial's value and backlight
+//! are restored afterwards.
+use miette::Context;
+use owo_colors::OwoColorize;
+use std::time::{Duration, Instant};
+use vu_api::{
+    dial::{Backlight, Percent, Status},
+    Dial,
+};
+
+/// The needle positions checked by the sweep, in order.
+const SWEEP: [u8; 6] = [0, 25, 50, 75, 100, 0];
+
+/// The result of a single check.
+struct Check {
+    name: String,
+    elapsed: Duration,
+    result: miette::Result<()>,
+}
+
+/// Runs all checks on `dial`, waiting `delay` after each setting is changed,
+/// so that the dial's response can be seen.
+pub(super) async fn run(dial: &Dial, delay: Duration) -> miette::Result<()> {
+    let color = supports_color::on(supports_color::Stream::Stdout).is_some();
+    let mut checks = Vec::new();
+
+    let start = Instant::now();
+    let original = dial.status().await.context("failed to get status");
+    let original = match original {
+        Ok(status) => {
+            let Status {
+                dial_name,
+                fw_version,
+                hw_version,
+                protocol_version,
+                ..
+            } = &status;
+            println!(
+                "testing dial {} ({dial_name}): firmware {fw_version}, hardware \
+                {hw_version}, protocol {protocol_version}\n",
+                dial.id()
+            );
+            checks.push(Check::new("get status", start, Ok(())));
+            status
+        }
+        Err(error) => {
+            // Without the dial's status, none of the other checks can be
+            // verified, and there's nothing to restore.
+            checks.push(Check::new("get status", start, Err(error)));
+            return report(&checks, color);
+        }
+    };
+
+    for value in SWEEP {
+        let value = Percent::new(value).expect("sweep values are valid percents");
+        let start = Instant::now();
+        let result = set_value(dial, value).await;
+        checks.push(Check::new(format!("set needle to {value}"), start, result));
+        tokio::time::sleep(delay).await;
+    }
+
+    for (name, (red, green, blue)) in [
+        ("red", (100, 0, 0)),
+        ("green", (0, 100, 0)),
+        ("blue", (0, 0, 100)),
+    ] {
+        let backlight = Backlight::new(red, green, blue).expect("backlight values are valid");
+        let start = Instant::now();
+        let result = set_backlight(dial, backlight).await;
+        checks.push(Check::new(
+            format!("set backlight to {name}"),
+            start,
+            result,
+        ));
+        tokio::time::sleep(delay).await;
+    }
+
+    let start = Instant::now();
+    let result = async {
+        set_value(dial, original.value).await?;
+        set_backlight(dial, original.backlight).await
+    }
+    .await;
+    checks.push(Check::new("restore dial", start, result));
+
+    report(&checks, color)
+}
+
+async fn set_value(dial: &Dial, value: Percent) -> miette::Result<()> {
+    dial.set(value)
+        .await
+        .with_context(|| format!("failed to set value to {value}"))?;
+    let status = dial.status().await.context("failed to get status")?;
+    if status.value != value {
+        miette::bail!("dial reports value {}, expected {value}", status.value);
+    }
+    Ok(())
+}
+
+async fn set_backlight(dial: &Dial, backlight: Backlight) -> miette::Result<()> {
+    dial.set_backlight(backlight)
+        .await
+        .with_context(|| format!("failed to set backlight to {backlight:?}"))?;
+    let status = dial.status().await.context("failed to get status")?;
+    if status.backlight != backlight {
+        miette::bail!(
+            "dial reports backlight {:?}, expected {backlight:?}",
+            status.backlight
+        );
+    }
+    Ok(())
+}
+
+/// Prints the result of each check, returning an error if any of them failed.
+fn report(checks: &[Check], color: bool) -> miette::Result<()> {
+    let width = checks
+        .iter()
+        .map(|check| check.name.len())
+        .max()
+        .unwrap_or(0);
+    let mut failed = 0;
+    for Check {
+        name,
+        elapsed,
+        result,
+    } in checks
+    {
+        let status = match (result, color) {
+            (Ok(()), true) => "PASS".green().to_string(),
+            (Ok(()), false) => "PASS".to_owned(),
+            (Err(_), true) => "FAIL".red().to_string(),
+            (Err(_), false) => "FAIL".to_owned(),
+        };
+        println!("{status}  {name:<width$}  {:>7.1?}", elapsed);
+        if let Err(error) = result {
+            failed += 1;
+            let causes = error.chain().map(ToString::to_string).collect::<Vec<_>>();
+            println!("      {}", causes.join(": "));
+        }
+    }
+    println!();
+
+    if failed > 0 {
+        miette::bail!("{failed} of {} checks failed", checks.len());
+    }
+    println!("all {} checks passed", checks.len());
+    Ok(())
+}
+
+// === impl Check ===
+
+impl Check {
+    fn new(name: impl Into<String>, start: Instant, result: miette::Result<()>) -> Self {
+        Self {
+            name: name.into(),
+            elapsed: start.elapsed(),
+            result,
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! `dialctl identify`: finding out which dial is which.
+//!
+//! With several dials on a hub, there's no way to tell from a UID or index
+//! which physical dial it refers to. This flashes each selected dial's
+//! backlight in turn, printing the dial's UID, index, and name while it
+//! flashes, and then restores the backlight.
+use miette::Context;
+use std::time::Duration;
+use vu_api::{
+    dial::{Backlight, Percent, Status},
+    Dial,
+};
+
+/// How long the backlight stays on or off while flashing.
+const BLINK: Duration = Duration::from_millis(250);
+
+/// Flashes each of `dials` for `duration`, one after the other.
+///
+/// If interrupted with Ctrl-C, the dial that's currently flashing is restored,
+/// and the remaining dials are skipped.
+pub(super) async fn run(dials: &[Dial], duration: Duration) -> miette::Result<()> {
+    for dial in dials {
+        let status = dial
+            .status()
+            .await
+            .with_context(|| format!("failed to get status for dial {dial}"))?;
+        println!(
+            "flashing dial {dial} (index {}, {:?})...",
+            status.index, status.dial_name
+        );
+        let interrupted = tokio::select! {
+            result = flash(dial, duration) => {
+                result?;
+                false
+            }
+            _ = tokio::signal::ctrl_c() => true,
+        };
+        restore(dial, &status).await?;
+        if interrupted {
+            miette::bail!("interrupted");
+        }
+    }
+    Ok(())
+}
+
+async fn flash(dial: &Dial, duration: Duration) -> miette::Result<()> {
+    // With the default backlight easing, the backlight takes about a second to
+    // fade in, which is too slow to blink. Make it change immediately while
+    // flashing, and restore the easing afterwards.
+    let step = Percent::MAX;
+    dial.set_backlight_easing(Duration::from_millis(50), step)
+        .await
+        .context("failed to set backlight easing")?;
+
+    let on = Backlight::WHITE;
+    let off = Backlight::OFF;
+    let blinks = (duration.as_millis() / BLINK.as_millis()).max(1);
+    for i in 0..blinks {
+        let backlight = if i % 2 == 0 { on } else { off };
+        dial.set_backlight(backlight)
+            .await
+            .context("failed to set backlight")?;
+        tokio::time::sleep(BLINK).await;
+    }
+    Ok(())
+}
+
+async fn restore(dial: &Dial, status: &Status) -> miette::Result<()> {
+    let Status {
+        backlight, easing, ..
+    } = status;
+    dial.set_backlight(*backlight)
+        .await
+        .with_context(|| format!("failed to restore backlight of dial {dial} to {backlight:?}"))?;
+    dial.set_backlight_easing(easing.backlight_period, easing.backlight_step)
+        .await
+        .with_context(|| format!("failed to restore backlight easing of dial {dial}"))
+}
@@ -0,0 +1,171 @@
+//! `dialctl off` and `dialctl on`: turning dials off and back on.
+//!
+//! Turning a desk of dials off for the night means setting each dial's value
+//! to zero and its backlight to black, which is a lot of `dialctl set`
+//! commands, and then remembering what they were set to in the morning. `off`
+//! does both at once, saving each dial's value and backlight to a snapshot
+//! file first, and `on` restores them from it.
+use super::{exit, MultiDialSelection};
+use camino::{Utf8Path, Utf8PathBuf};
+use miette::{Context, IntoDiagnostic};
+use std::collections::BTreeMap;
+use vu_api::{
+    dial::{Backlight, Percent},
+    Dial,
+};
+
+/// The state of each dial before it was turned off, by dial UID.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Snapshot {
+    #[serde(default)]
+    dials: BTreeMap<String, Saved>,
+}
+
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+struct Saved {
+    value: Percent,
+    backlight: Backlight,
+}
+
+/// Returns the default path of the snapshot file.
+pub(super) fn default_snapshot_path() -> Utf8PathBuf {
+    directories::BaseDirs::new()
+        .and_then(|dirs| {
+            let path = Utf8Path::from_path(dirs.data_local_dir())?.join("vupdate/dialctl-off.json");
+            Some(path)
+        })
+        .unwrap_or_else(|| {
+            ["$HOME", ".local", "share", "vupdate", "dialctl-off.json"]
+                .iter()
+                .collect()
+        })
+}
+
+/// Saves the value and backlight of each selected dial to the snapshot file at
+/// `path`, and then sets them to zero and black.
+///
+/// Dials that are already in the snapshot keep their saved state, so turning
+/// a dial off twice doesn't lose the state it should be restored to.
+pub(super) async fn off(
+    client: &vu_api::Client,
+    selection: &MultiDialSelection,
+    path: &Utf8Path,
+) -> miette::Result<()> {
+    let dials = selection.select_dials(client).await?;
+    let total = dials.len();
+    let mut snapshot = Snapshot::load(path)?;
+    let mut errors = Vec::new();
+    let mut selected = Vec::with_capacity(total);
+    for (dial, info) in dials {
+        let uid = dial.id().to_string();
+        if snapshot.dials.contains_key(&uid) {
+            tracing::debug!(%dial, "Dial is already in the snapshot, keeping its saved state");
+            selected.push(dial);
+            continue;
+        }
+        let saved = match info {
+            Some(info) => Ok(Saved {
+                value: info.value,
+                backlight: info.backlight,
+            }),
+            None => dial
+                .status()
+                .await
+                .with_context(|| format!("failed to get status for dial {dial}"))
+                .map(|status| Saved {
+                    value: status.value,
+                    backlight: status.backlight,
+                }),
+        };
+        match saved {
+            Ok(saved) => {
+                snapshot.dials.insert(uid, saved);
+                selected.push(dial);
+            }
+            // Don't turn off dials whose state couldn't be saved, since they
+            // couldn't be turned back on.
+            Err(error) => errors.push(error.context(format!("failed to turn off dial {dial}"))),
+        }
+    }
+    snapshot.save(path)?;
+
+    let zero = Percent::MIN;
+    for dial in selected {
+        tracing::info!(%dial, "Turning off...");
+        if let Err(error) = set(&dial, zero, Backlight::OFF).await {
+            errors.push(error.context(format!("failed to turn off dial {dial}")));
+        }
+    }
+    exit::per_dial(errors, total, "failed to turn off some dials")
+}
+
+/// Restores the value and backlight of each selected dial from the snapshot
+/// file at `path`, and removes them from the snapshot.
+///
+/// Selected dials that aren't in the snapshot are skipped.
+pub(super) async fn on(
+    client: &vu_api::Client,
+    selection: &MultiDialSelection,
+    path: &Utf8Path,
+) -> miette::Result<()> {
+    let dials = selection.select_dials(client).await?;
+    let mut snapshot = Snapshot::load(path)?;
+    let mut errors = Vec::new();
+    let mut total = 0;
+    for (dial, _) in dials {
+        let uid = dial.id().to_string();
+        let Some(&Saved { value, backlight }) = snapshot.dials.get(&uid) else {
+            tracing::info!(%dial, "Dial wasn't turned off, skipping it");
+            continue;
+        };
+        total += 1;
+        tracing::info!(%dial, %value, ?backlight, "Turning on...");
+        match set(&dial, value, backlight).await {
+            Ok(()) => {
+                snapshot.dials.remove(&uid);
+            }
+            Err(error) => errors.push(error.context(format!("failed to turn on dial {dial}"))),
+        }
+    }
+    snapshot.save(path)?;
+    exit::per_dial(errors, total, "failed to turn on some dials")
+}
+
+async fn set(dial: &Dial, value: Percent, backlight: Backlight) -> miette::Result<()> {
+    dial.set(value)
+        .await
+        .with_context(|| format!("failed to set value to {value}"))?;
+    dial.set_backlight(backlight)
+        .await
+        .with_context(|| format!("failed to set backlight to {backlight:?}"))
+}
+
+// === impl Snapshot ===
+
+impl Snapshot {
+    /// Loads the snapshot file at `path`, or an empty snapshot if it doesn't
+    /// exist.
+    fn load(path: &Utf8Path) -> miette::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .into_diagnostic()
+                .with_context(|| format!("failed to parse snapshot file {path}")),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error)
+                .into_diagnostic()
+                .with_context(|| format!("failed to read snapshot file {path}")),
+        }
+    }
+
+    fn save(&self, path: &Utf8Path) -> miette::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .into_diagnostic()
+                .with_context(|| format!("failed to create {dir}"))?;
+        }
+        let json = serde_json::to_string_pretty(self).into_diagnostic()?;
+        std::fs::write(path, json)
+            .into_diagnostic()
+            .with_context(|| format!("failed to write snapshot file {path}"))
+    }
+}
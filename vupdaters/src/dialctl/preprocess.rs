@@ -0,0 +1,231 @@
+//! Preparing background images for upload.
+//!
+//! The VU1 dial's display is 200×144 pixels, and the VU-Server expects images
+//! to be 8-bit RGBA PNGs of that size; anything else is displayed distorted,
+//! or not at all. `dialctl set --image` therefore converts arbitrary input
+//! images (JPEGs, large or paletted PNGs, and so on) to that format before
+//! uploading them, optionally cropping them first.
+use camino::Utf8Path;
+use image::{imageops::FilterType, DynamicImage, GenericImageView, ImageFormat, RgbaImage};
+use miette::{Context, IntoDiagnostic};
+use std::{fmt, str::FromStr};
+
+/// The size of the VU1 dial's display, in pixels.
+const WIDTH: u32 = 200;
+const HEIGHT: u32 = 144;
+
+const WHITE: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+
+/// How to fit an image to the dial's display, if its aspect ratio differs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Fit {
+    /// Scale the image to fit inside the display, filling the rest of the
+    /// display with white.
+    #[default]
+    Contain,
+    /// Scale the image to cover the whole display, cutting off its edges.
+    Cover,
+    /// Stretch the image to the size of the display, ignoring its aspect
+    /// ratio.
+    Stretch,
+}
+
+/// A region of an image to crop to, in pixels.
+///
+/// Crop regions are written as `WIDTHxHEIGHT+X+Y`, like X11 geometry
+/// specifications and ImageMagick's `-crop`, where `X` and `Y` are the offset
+/// of the region's top left corner. The offset may be omitted, in which case
+/// the region starts at the top left corner of the image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Crop {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// The options for preparing an image.
+#[derive(Copy, Clone, Debug, Default)]
+pub(super) struct Options {
+    pub(super) fit: Option<Fit>,
+    pub(super) crop: Option<Crop>,
+    /// If set, upload the image as-is, without converting it.
+    pub(super) raw: bool,
+}
+
+/// An image that's ready to upload to a dial.
+pub(super) struct Prepared {
+    pub(super) filename: String,
+    pub(super) png: Vec<u8>,
+}
+
+/// Reads the image at `path` and converts it to the format the dial expects.
+pub(super) fn prepare(path: &Utf8Path, options: Options) -> miette::Result<Prepared> {
+    let data = std::fs::read(path)
+        .into_diagnostic()
+        .with_context(|| format!("failed to read {path}"))?;
+    if options.raw {
+        let filename = path.file_name().unwrap_or(path.as_str()).to_owned();
+        tracing::debug!(%filename, "uploading image without converting it");
+        return Ok(Prepared {
+            filename,
+            png: data,
+        });
+    }
+
+    // The VU-Server only accepts PNGs, so converted images are always renamed
+    // to `.png`.
+    let filename = format!("{}.png", path.file_stem().unwrap_or("image"));
+    if options.fit.is_none() && options.crop.is_none() && is_dial_png(&data) {
+        tracing::debug!(%filename, "image is already in the dial's format");
+        return Ok(Prepared {
+            filename,
+            png: data,
+        });
+    }
+
+    let format = image::guess_format(&data)
+        .ok()
+        .or_else(|| ImageFormat::from_path(path).ok())
+        .ok_or_else(|| {
+            miette::miette!(
+                help = "images must be PNG or JPEG files",
+                "could not determine the format of {path}"
+            )
+        })?;
+    let mut img = image::load_from_memory_with_format(&data, format)
+        .into_diagnostic()
+        .with_context(|| format!("failed to decode {path}"))?;
+    tracing::debug!(
+        ?format,
+        width = img.width(),
+        height = img.height(),
+        "decoded image"
+    );
+
+    if let Some(crop) = options.crop {
+        img = crop.apply(&img)?;
+    }
+    let img = options.fit.unwrap_or_default().apply(&img);
+
+    let mut png = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+        .into_diagnostic()
+        .context("failed to encode image as PNG")?;
+    Ok(Prepared { filename, png })
+}
+
+/// Returns `true` if `data` is already an 8-bit RGBA PNG of the dial's size.
+fn is_dial_png(data: &[u8]) -> bool {
+    let Ok(reader) = png::Decoder::new(data).read_info() else {
+        return false;
+    };
+    let info = reader.info();
+    info.width == WIDTH
+        && info.height == HEIGHT
+        && info.bit_depth == png::BitDepth::Eight
+        && info.color_type == png::ColorType::Rgba
+}
+
+// === impl Fit ===
+
+impl Fit {
+    fn apply(self, img: &DynamicImage) -> RgbaImage {
+        if img.dimensions() == (WIDTH, HEIGHT) {
+            return img.to_rgba8();
+        }
+        match self {
+            Fit::Contain => {
+                let scaled = img.resize(WIDTH, HEIGHT, FilterType::Lanczos3).to_rgba8();
+                let mut canvas = RgbaImage::from_pixel(WIDTH, HEIGHT, WHITE);
+                let x = (WIDTH - scaled.width()) / 2;
+                let y = (HEIGHT - scaled.height()) / 2;
+                image::imageops::overlay(&mut canvas, &scaled, x.into(), y.into());
+                canvas
+            }
+            Fit::Cover => img
+                .resize_to_fill(WIDTH, HEIGHT, FilterType::Lanczos3)
+                .to_rgba8(),
+            Fit::Stretch => img
+                .resize_exact(WIDTH, HEIGHT, FilterType::Lanczos3)
+                .to_rgba8(),
+        }
+    }
+}
+
+// === impl Crop ===
+
+impl Crop {
+    fn apply(&self, img: &DynamicImage) -> miette::Result<DynamicImage> {
+        let (width, height) = img.dimensions();
+        let fits = self
+            .x
+            .checked_add(self.width)
+            .is_some_and(|right| right <= width)
+            && self
+                .y
+                .checked_add(self.height)
+                .is_some_and(|bottom| bottom <= height);
+        if !fits {
+            return Err(miette::miette!(
+                help = format!("the image is {width}x{height} pixels"),
+                "crop region {self} is outside the image"
+            ));
+        }
+        Ok(img.crop_imm(self.x, self.y, self.width, self.height))
+    }
+}
+
+impl FromStr for Crop {
+    type Err = miette::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const HELP: &str = "crop regions are written as WIDTHxHEIGHT+X+Y, such as 400x288+10+20";
+        let parse = |n: &str, what: &str| {
+            n.parse::<u32>()
+                .map_err(|_| miette::miette!(help = HELP, "invalid crop {what} {n:?}"))
+        };
+
+        let (size, offset) = match s.split_once('+') {
+            Some((size, offset)) => (size, Some(offset)),
+            None => (s, None),
+        };
+        let (width, height) = size
+            .split_once(['x', 'X'])
+            .ok_or_else(|| miette::miette!(help = HELP, "invalid crop region {s:?}"))?;
+        let (x, y) = match offset {
+            Some(offset) => {
+                let (x, y) = offset.split_once('+').ok_or_else(|| {
+                    miette::miette!(help = HELP, "invalid crop offset {offset:?}")
+                })?;
+                (parse(x, "X offset")?, parse(y, "Y offset")?)
+            }
+            None => (0, 0),
+        };
+        let crop = Self {
+            x,
+            y,
+            width: parse(width, "width")?,
+            height: parse(height, "height")?,
+        };
+        if crop.width == 0 || crop.height == 0 {
+            return Err(miette::miette!(
+                help = HELP,
+                "crop region {s:?} must not be empty"
+            ));
+        }
+        Ok(crop)
+    }
+}
+
+impl fmt::Display for Crop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            x,
+            y,
+            width,
+            height,
+        } = self;
+        write!(f, "{width}x{height}+{x}+{y}")
+    }
+}
@@ -0,0 +1,148 @@
+//! `dialctl server`: checking the connection to the VU-Server.
+//!
+//! Most problems with `dialctl` turn out to be a VU-Server that isn't
+//! running, a wrong server URL, or a wrong API key. This makes one request to
+//! the VU-Server and reports what happened, so that those problems are
+//! obvious.
+use super::{OutputMode, TextTheme, ASCII_THEME, UNICODE_THEME};
+use miette::IntoDiagnostic;
+use std::time::Duration;
+
+/// The result of checking the VU-Server.
+#[derive(Debug, serde::Serialize)]
+struct Report {
+    url: String,
+    reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    server: Option<String>,
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    latency: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dials: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The VU-Server check failed.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+#[error("failed to check VU-Server at {url}")]
+struct CheckFailed {
+    url: String,
+    #[help]
+    help: &'static str,
+    #[source]
+    error: vu_api::client::Error,
+}
+
+/// Checks the VU-Server, and prints a report.
+///
+/// If the check failed, the report is printed before the error is returned,
+/// so that the exit code still reflects what went wrong.
+pub(super) async fn run(client: &vu_api::Client, output: OutputMode) -> miette::Result<()> {
+    let url = client.base_url().to_string();
+    let (report, result) = match client.server_info().await {
+        Ok(info) => {
+            let report = Report {
+                url,
+                reachable: true,
+                server: info.server,
+                // Nobody needs nanosecond precision here, and it makes the
+                // serialized latency harder to read.
+                latency: info
+                    .latency
+                    .map(|latency| Duration::from_micros(latency.as_micros() as u64)),
+                dials: Some(info.dials),
+                error: None,
+            };
+            (report, Ok(()))
+        }
+        Err(error) => {
+            let report = Report {
+                // If the VU-Server responded at all, it's reachable, even if
+                // it rejected the request.
+                reachable: !error.is_unreachable(),
+                url,
+                server: None,
+                latency: None,
+                dials: None,
+                error: Some(error.to_string()),
+            };
+            let help = if error.is_unreachable() {
+                "check that the VU-Server is running, and that `--server` (or \
+                $VU_DIALS_SERVER_ADDR) is its URL"
+            } else if error.is_unauthorized() {
+                "check that `--key` (or $VU_DIALS_API_KEY) is a valid API key"
+            } else {
+                "check that `--server` (or $VU_DIALS_SERVER_ADDR) is the URL of a \
+                VU-Server, rather than another web server"
+            };
+            let result = Err(CheckFailed {
+                url: report.url.clone(),
+                help,
+                error,
+            }
+            .into());
+            (report, result)
+        }
+    };
+    report.print(output)?;
+    result
+}
+
+// === impl Report ===
+
+impl Report {
+    fn print(&self, output: OutputMode) -> miette::Result<()> {
+        match output {
+            OutputMode::Text | OutputMode::Table => self.print_text(&UNICODE_THEME),
+            OutputMode::Ascii => self.print_text(&ASCII_THEME),
+            OutputMode::Json => {
+                let json = serde_json::to_string_pretty(self).into_diagnostic()?;
+                println!("{json}");
+            }
+            OutputMode::Jsonl => super::print_jsonl(self)?,
+            OutputMode::Yaml => super::print_yaml(self)?,
+            OutputMode::Toml => {
+                let toml = toml::to_string_pretty(self).into_diagnostic()?;
+                print!("{toml}");
+            }
+        }
+        Ok(())
+    }
+
+    fn print_text(&self, TextTheme { branch, leaf, .. }: &TextTheme) {
+        let has_color = supports_color::on(supports_color::Stream::Stdout)
+            .map(|s| s.has_basic)
+            .unwrap_or(false);
+        let style = if has_color {
+            owo_colors::Style::new().bold()
+        } else {
+            owo_colors::Style::new()
+        };
+        let Self {
+            url,
+            reachable,
+            server,
+            latency,
+            dials,
+            error,
+        } = self;
+        println!("VU-SERVER {}", style.style(url));
+        let reachable = if *reachable { "yes" } else { "no" };
+        println!("{branch}reachable: {}", style.style(reachable));
+        if let Some(error) = error {
+            println!("{leaf}error: {}", style.style(error));
+            return;
+        }
+        println!(
+            "{branch}server: {}",
+            style.style(server.as_deref().unwrap_or("<unknown>"))
+        );
+        if let Some(latency) = latency {
+            println!("{branch}latency: {}", style.style(format!("{latency:.1?}")));
+        }
+        if let Some(dials) = dials {
+            println!("{leaf}dials: {}", style.style(dials));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! `dialctl set --stdin`: streaming values from a pipeline.
+//!
+//! Any program that can print numbers can drive a dial this way, without
+//! having to know anything about the VU-Server's API. Values are read one per
+//! line, and each one is sent to the selected dials as soon as it arrives. If
+//! `--min-interval` is set and values arrive faster than that, only the most
+//! recent value is sent when the interval elapses, so a fast producer can't
+//! flood the VU-Server.
+use miette::{Context, IntoDiagnostic};
+use std::time::Duration;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    time::Instant,
+};
+use vu_api::{dial::Percent, Dial};
+
+/// Reads values from stdin until it's closed, setting `dials` to each value.
+pub(super) async fn run(dials: &[Dial], min_interval: Duration) -> miette::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut pending = None;
+    let mut next_update = Instant::now();
+    let mut updates = 0;
+    let mut errors = 0;
+    let mut line_number = 0;
+    tracing::debug!(
+        dials = dials.len(),
+        ?min_interval,
+        "Reading values from stdin..."
+    );
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line
+                    .into_diagnostic()
+                    .context("failed to read from stdin")?
+                else {
+                    break;
+                };
+                line_number += 1;
+                match parse(&line) {
+                    Ok(Some(value)) => pending = Some(value),
+                    Ok(None) => {}
+                    Err(error) => tracing::warn!(line = line_number, %error, "Skipping invalid value"),
+                }
+            }
+            _ = tokio::time::sleep_until(next_update), if pending.is_some() => {
+                let value = pending.take().expect("only polled if a value is pending");
+                update(dials, value, &mut updates, &mut errors).await;
+                next_update = Instant::now() + min_interval;
+            }
+        }
+    }
+
+    // Don't drop the last value, even if stdin closed before the interval
+    // elapsed.
+    if let Some(value) = pending {
+        tokio::time::sleep_until(next_update).await;
+        update(dials, value, &mut updates, &mut errors).await;
+    }
+
+    tracing::debug!(updates, errors, "Stdin closed");
+    if errors > 0 {
+        miette::bail!("{errors} of {updates} updates failed");
+    }
+    Ok(())
+}
+
+async fn update(dials: &[Dial], value: Percent, updates: &mut usize, errors: &mut usize) {
+    for dial in dials {
+        *updates += 1;
+        tracing::debug!(%dial, %value, "Setting value...");
+        // A pipeline shouldn't stop just because the VU-Server briefly
+        // hiccuped, so failed updates are only logged.
+        if let Err(error) = dial.set(value).await {
+            *errors += 1;
+            tracing::warn!(%dial, %value, %error, "Failed to set value");
+        }
+    }
+}
+
+/// Parses a line of input as a value.
+///
+/// Decimal values are rounded to the nearest percent, and values outside of
+/// 0-100 are clamped, since metrics often overshoot slightly. Blank lines are
+/// ignored.
+fn parse(line: &str) -> miette::Result<Option<Percent>> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let value = line
+        .trim_end_matches('%')
+        .parse::<f64>()
+        .map_err(|error| miette::miette!("invalid value {line:?}: {error}"))?;
+    if !value.is_finite() {
+        miette::bail!("invalid value {line:?}: values must be finite");
+    }
+    Ok(Some(Percent::from_f64_clamped(value)))
+}
@@ -0,0 +1,164 @@
+//! Table output (`-o table`).
+//!
+//! The tree output is easy to read for one or two dials, but with a lot of
+//! dials connected, it's hard to scan. Tables print one dial per line, with
+//! each column aligned.
+use vu_api::{
+    api::DialInfo,
+    dial::{self, Backlight, Percent},
+};
+
+/// A column of the dial table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Column {
+    /// The dial's UID.
+    Uid,
+    /// The dial's name.
+    Name,
+    /// The dial's index.
+    Index,
+    /// The dial's value.
+    Value,
+    /// The dial's backlight, as red, green, and blue percentages.
+    Backlight,
+    /// The dial's background image file.
+    Image,
+}
+
+/// The columns that are displayed if `--columns` isn't provided.
+pub(super) const DEFAULT_COLUMNS: &[Column] = &[
+    Column::Uid,
+    Column::Name,
+    Column::Index,
+    Column::Value,
+    Column::Backlight,
+    Column::Image,
+];
+
+/// A dial to display in the table.
+///
+/// A dial's index is only included in its detailed status, so it's missing
+/// for dials that were only listed.
+pub(super) struct Row<'a> {
+    uid: &'a dial::Id,
+    name: &'a str,
+    index: Option<usize>,
+    value: Percent,
+    backlight: Backlight,
+    image: &'a str,
+}
+
+/// Prints a table of dials, with the given columns.
+pub(super) fn print_dials(rows: &[Row<'_>], columns: &[Column]) {
+    let header = columns.iter().map(|column| column.header()).collect();
+    let cells = rows
+        .iter()
+        .map(|row| columns.iter().map(|&column| row.cell(column)).collect())
+        .collect();
+    print(header, cells);
+}
+
+/// Prints a table with the given header, aligning each column to its widest
+/// cell.
+pub(super) fn print(header: Vec<&str>, rows: Vec<Vec<String>>) {
+    let mut widths = header
+        .iter()
+        .map(|title| title.chars().count())
+        .collect::<Vec<_>>();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let has_color = supports_color::on(supports_color::Stream::Stdout)
+        .map(|s| s.has_basic)
+        .unwrap_or(false);
+    let style = if has_color {
+        owo_colors::Style::new().bold()
+    } else {
+        owo_colors::Style::new()
+    };
+    let header = header.iter().map(ToString::to_string).collect::<Vec<_>>();
+    print_line(&header, &widths, style);
+    for row in &rows {
+        print_line(row, &widths, owo_colors::Style::new());
+    }
+}
+
+fn print_line(cells: &[String], widths: &[usize], style: owo_colors::Style) {
+    let mut line = String::new();
+    for (i, (cell, width)) in cells.iter().zip(widths).enumerate() {
+        if i > 0 {
+            line.push_str("  ");
+        }
+        // Don't pad the last column, so lines don't have trailing spaces.
+        if i + 1 == cells.len() {
+            line.push_str(cell);
+        } else {
+            line.push_str(&format!("{cell:<width$}"));
+        }
+    }
+    println!("{}", style.style(line));
+}
+
+// === impl Column ===
+
+impl Column {
+    fn header(self) -> &'static str {
+        match self {
+            Column::Uid => "UID",
+            Column::Name => "NAME",
+            Column::Index => "INDEX",
+            Column::Value => "VALUE",
+            Column::Backlight => "BACKLIGHT",
+            Column::Image => "IMAGE",
+        }
+    }
+}
+
+// === impl Row ===
+
+impl Row<'_> {
+    fn cell(&self, column: Column) -> String {
+        match column {
+            Column::Uid => self.uid.to_string(),
+            Column::Name => self.name.to_owned(),
+            Column::Index => self
+                .index
+                .map_or_else(|| "-".to_owned(), |index| index.to_string()),
+            Column::Value => self.value.to_string(),
+            Column::Backlight => {
+                let Backlight { red, green, blue } = self.backlight;
+                format!("{red} {green} {blue}")
+            }
+            Column::Image => self.image.to_owned(),
+        }
+    }
+}
+
+impl<'a> From<&'a DialInfo> for Row<'a> {
+    fn from(info: &'a DialInfo) -> Self {
+        Self {
+            uid: &info.uid,
+            name: &info.dial_name,
+            index: None,
+            value: info.value,
+            backlight: info.backlight,
+            image: &info.image_file,
+        }
+    }
+}
+
+impl<'a> From<&'a dial::Status> for Row<'a> {
+    fn from(status: &'a dial::Status) -> Self {
+        Self {
+            uid: &status.uid,
+            name: &status.dial_name,
+            index: Some(status.index),
+            value: status.value,
+            backlight: status.backlight,
+            image: &status.image_file,
+        }
+    }
+}
@@ -0,0 +1,418 @@
+//! `dialctl tui`: a full-screen dashboard for the dials.
+//!
+//! Each dial is drawn as a gauge, in its backlight color, mirroring the
+//! physical dials. The selected dial's value and backlight can be nudged with
+//! the keyboard, and logs are displayed in a pane at the bottom of the screen,
+//! since writing them to stderr would draw over the dashboard.
+use crossterm::{
+    event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use miette::{Context, IntoDiagnostic};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, Gauge, Paragraph},
+    Frame,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, Stdout},
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use vu_api::{api::DialInfo, dial};
+
+/// How much each key press changes a value or backlight channel by.
+const STEP: u8 = 5;
+
+/// The maximum number of lines kept in the log pane.
+const MAX_LOG_LINES: usize = 200;
+
+const LOG_PANE_HEIGHT: u16 = 8;
+const DIAL_HEIGHT: u16 = 4;
+
+/// Log lines written by [`LogWriter`], to be displayed in the log pane.
+static LOG: Mutex<Log> = Mutex::new(Log {
+    lines: VecDeque::new(),
+    partial: String::new(),
+});
+
+/// A [`MakeWriter`](tracing_subscriber::fmt::MakeWriter) that writes logs to
+/// the dashboard's log pane.
+#[derive(Copy, Clone, Debug, Default)]
+pub(super) struct LogWriter;
+
+struct Log {
+    lines: VecDeque<String>,
+    /// The last line written, if it hasn't been terminated yet.
+    partial: String,
+}
+
+struct App {
+    client: vu_api::Client,
+    dials: Vec<DialInfo>,
+    selected: usize,
+    control: Control,
+}
+
+/// Which of the selected dial's settings the arrow keys change.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Control {
+    Value,
+    Red,
+    Green,
+    Blue,
+}
+
+/// Restores the terminal when the dashboard exits.
+struct TerminalGuard;
+
+type Terminal = ratatui::Terminal<CrosstermBackend<Stdout>>;
+
+pub(super) async fn run(client: &vu_api::Client, interval: Duration) -> miette::Result<()> {
+    let mut app = App {
+        client: client.clone(),
+        dials: Vec::new(),
+        selected: 0,
+        control: Control::Value,
+    };
+
+    let guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))
+        .into_diagnostic()
+        .context("failed to initialize terminal")?;
+    let mut events = spawn_event_reader();
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        terminal
+            .draw(|frame| app.draw(frame))
+            .into_diagnostic()
+            .context("failed to draw dashboard")?;
+
+        tokio::select! {
+            _ = ticks.tick() => app.refresh().await,
+            event = events.recv() => match event {
+                Some(Ok(Event::Key(key))) if key.kind != KeyEventKind::Release => {
+                    if !app.handle_key(key).await {
+                        break;
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(error)) => {
+                    return Err(error)
+                        .into_diagnostic()
+                        .context("failed to read terminal events");
+                }
+                None => break,
+            },
+        }
+    }
+
+    drop(guard);
+    Ok(())
+}
+
+/// Reads terminal events on a blocking thread, until the returned receiver is
+/// dropped.
+fn spawn_event_reader() -> mpsc::Receiver<io::Result<Event>> {
+    let (tx, rx) = mpsc::channel(16);
+    std::thread::spawn(move || loop {
+        let event = match crossterm::event::poll(Duration::from_millis(100)) {
+            Ok(true) => crossterm::event::read(),
+            Ok(false) if tx.is_closed() => return,
+            Ok(false) => continue,
+            Err(error) => Err(error),
+        };
+        if tx.blocking_send(event).is_err() {
+            return;
+        }
+    });
+    rx
+}
+
+// === impl App ===
+
+impl App {
+    async fn refresh(&mut self) {
+        match self.client.list_dials().await {
+            Ok(dials) => {
+                self.dials = dials.into_iter().map(|(_, info)| info).collect();
+                self.selected = self.selected.min(self.dials.len().saturating_sub(1));
+            }
+            Err(error) => tracing::warn!(%error, "failed to list dials"),
+        }
+    }
+
+    /// Handles a key press, returning `false` if the dashboard should exit.
+    async fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.dials.len().saturating_sub(1));
+            }
+            KeyCode::Tab => self.control = self.control.next(),
+            KeyCode::Char('v') => self.control = Control::Value,
+            KeyCode::Char('r') => self.control = Control::Red,
+            KeyCode::Char('g') => self.control = Control::Green,
+            KeyCode::Char('b') => self.control = Control::Blue,
+            KeyCode::Left | KeyCode::Char('h') | KeyCode::Char('-') => {
+                self.nudge(|value| value.saturating_sub(STEP)).await
+            }
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char('+') => {
+                self.nudge(|value| value.saturating_add(STEP).min(100))
+                    .await
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Changes the selected control of the selected dial.
+    async fn nudge(&mut self, f: impl FnOnce(u8) -> u8) {
+        let control = self.control;
+        let Some(info) = self.dials.get_mut(self.selected) else {
+            return;
+        };
+        let dial = match self.client.dial(info.uid.clone()) {
+            Ok(dial) => dial,
+            Err(error) => {
+                tracing::warn!(%error, uid = %info.uid, "invalid dial UID");
+                return;
+            }
+        };
+
+        let percent = match control {
+            Control::Value => &mut info.value,
+            Control::Red => &mut info.backlight.red,
+            Control::Green => &mut info.backlight.green,
+            Control::Blue => &mut info.backlight.blue,
+        };
+        let value = dial::Percent::new(f(u8::from(*percent))).expect("values are clamped to 100");
+        if value == *percent {
+            return;
+        }
+        // Update the dashboard immediately, rather than waiting for the next
+        // refresh.
+        *percent = value;
+
+        let result = match control {
+            Control::Value => {
+                tracing::info!(dial = %info.dial_name, %value, "Setting value...");
+                dial.set(value).await
+            }
+            _ => {
+                let backlight = info.backlight;
+                tracing::info!(dial = %info.dial_name, ?backlight, "Setting backlight...");
+                dial.set_backlight(backlight).await
+            }
+        };
+        if let Err(error) = result {
+            tracing::warn!(%error, dial = %info.dial_name, "failed to set {control:?}");
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame<'_>) {
+        let [dials_area, log_area, help_area] = Layout::vertical([
+            Constraint::Min(DIAL_HEIGHT),
+            Constraint::Length(LOG_PANE_HEIGHT),
+            Constraint::Length(1),
+        ])
+        .areas(frame.size());
+
+        if self.dials.is_empty() {
+            let text = Paragraph::new("no dials connected")
+                .block(Block::default().borders(Borders::ALL).title(" Dials "));
+            frame.render_widget(text, dials_area);
+        } else {
+            // Only as many dials as fit on the screen are drawn, so keep the
+            // selected dial visible by scrolling past the ones above it.
+            let visible = usize::from((dials_area.height / DIAL_HEIGHT).max(1));
+            let first = self.selected.saturating_sub(visible - 1);
+            let constraints = vec![Constraint::Length(DIAL_HEIGHT); visible]
+                .into_iter()
+                .chain(Some(Constraint::Min(0)));
+            let areas = Layout::vertical(constraints).split(dials_area);
+            for ((i, info), area) in self.dials.iter().enumerate().skip(first).zip(areas.iter()) {
+                self.draw_dial(frame, info, i == self.selected, *area);
+            }
+        }
+
+        self.draw_log(frame, log_area);
+
+        let help = Line::from(vec![
+            Span::styled(" ↑/↓", Style::new().bold()),
+            Span::raw(" select dial  "),
+            Span::styled("Tab/v/r/g/b", Style::new().bold()),
+            Span::raw(" select setting  "),
+            Span::styled("←/→", Style::new().bold()),
+            Span::raw(format!(" change by {STEP}  ")),
+            Span::styled("q", Style::new().bold()),
+            Span::raw(" quit"),
+        ]);
+        frame.render_widget(Paragraph::new(help), help_area);
+    }
+
+    fn draw_dial(&self, frame: &mut Frame<'_>, info: &DialInfo, selected: bool, area: Rect) {
+        let DialInfo {
+            uid,
+            dial_name,
+            value,
+            backlight,
+            ..
+        } = info;
+        let border = if selected {
+            Style::new().add_modifier(Modifier::BOLD).fg(Color::Yellow)
+        } else {
+            Style::new()
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(border)
+            .title(format!(" {dial_name} "))
+            .title_bottom(Line::from(format!(" {uid} ")).right_aligned());
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let [gauge_area, backlight_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Length(1)]).areas(inner);
+        let gauge = Gauge::default()
+            .gauge_style(Style::new().fg(backlight_color(backlight)))
+            .percent(u16::from(u8::from(*value)))
+            .label(self.highlight(selected, Control::Value, value.to_string()));
+        frame.render_widget(gauge, gauge_area);
+
+        let backlight = Line::from(vec![
+            Span::raw("backlight: "),
+            self.highlight(selected, Control::Red, format!("R {}", backlight.red)),
+            Span::raw(" "),
+            self.highlight(selected, Control::Green, format!("G {}", backlight.green)),
+            Span::raw(" "),
+            self.highlight(selected, Control::Blue, format!("B {}", backlight.blue)),
+        ]);
+        frame.render_widget(Paragraph::new(backlight), backlight_area);
+    }
+
+    /// Highlights `text` if it's the control that the arrow keys change.
+    fn highlight(&self, selected: bool, control: Control, text: String) -> Span<'static> {
+        if selected && self.control == control {
+            Span::styled(text, Style::new().add_modifier(Modifier::REVERSED))
+        } else {
+            Span::raw(text)
+        }
+    }
+
+    fn draw_log(&self, frame: &mut Frame<'_>, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title(" Log ");
+        let height = usize::from(block.inner(area).height);
+        let log = LOG.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let lines = log
+            .lines
+            .iter()
+            .skip(log.lines.len().saturating_sub(height))
+            .map(|line| Line::raw(line.clone()))
+            .collect::<Vec<_>>();
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+/// Returns the color of a dial's backlight, or gray if it's turned off.
+fn backlight_color(backlight: &dial::Backlight) -> Color {
+    let scale = |percent: dial::Percent| (u16::from(u8::from(percent)) * 255 / 100) as u8;
+    match (
+        scale(backlight.red),
+        scale(backlight.green),
+        scale(backlight.blue),
+    ) {
+        (0, 0, 0) => Color::Gray,
+        (r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+// === impl Control ===
+
+impl Control {
+    fn next(self) -> Self {
+        match self {
+            Control::Value => Control::Red,
+            Control::Red => Control::Green,
+            Control::Green => Control::Blue,
+            Control::Blue => Control::Value,
+        }
+    }
+}
+
+// === impl TerminalGuard ===
+
+impl TerminalGuard {
+    fn enter() -> miette::Result<Self> {
+        terminal::enable_raw_mode()
+            .into_diagnostic()
+            .context("failed to enable raw mode")?;
+        // Construct the guard first, so that raw mode is disabled if entering
+        // the alternate screen fails.
+        let guard = Self;
+        io::stdout()
+            .execute(EnterAlternateScreen)
+            .into_diagnostic()
+            .context("failed to enter alternate screen")?;
+
+        // Also restore the terminal if we panic, so that the panic message is
+        // readable.
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            Self::restore();
+            hook(info);
+        }));
+        Ok(guard)
+    }
+
+    fn restore() {
+        let _ = io::stdout().execute(LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        Self::restore();
+    }
+}
+
+// === impl LogWriter ===
+
+impl io::Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut log = LOG.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Log { lines, partial } = &mut *log;
+        partial.push_str(&String::from_utf8_lossy(buf));
+        while let Some(end) = partial.find('\n') {
+            lines.push_back(partial[..end].to_owned());
+            partial.drain(..=end);
+        }
+        while lines.len() > MAX_LOG_LINES {
+            lines.pop_front();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'w> tracing_subscriber::fmt::MakeWriter<'w> for LogWriter {
+    type Writer = Self;
+
+    fn make_writer(&'w self) -> Self::Writer {
+        *self
+    }
+}
@@ -0,0 +1,159 @@
+//! `dialctl watch`: a live view of the dials' state.
+//!
+//! This polls the VU-Server at an interval, and redraws each dial's name,
+//! value, and backlight in place, rather than clearing the whole screen like
+//! `watch dialctl list` would.
+use std::{
+    fmt::Write as _,
+    io::{IsTerminal, Write as _},
+    time::Duration,
+};
+use vu_api::{api::DialInfo, dial};
+
+/// The width of each dial's value bar, in characters.
+const BAR_WIDTH: usize = 20;
+
+/// Polls the dials every `interval` until interrupted, redrawing them each
+/// time.
+pub(super) async fn run(
+    client: &vu_api::Client,
+    dials: &[dial::Id],
+    interval: Duration,
+    ascii: bool,
+) -> miette::Result<()> {
+    let stdout = std::io::stdout();
+    let term = Terminal {
+        in_place: stdout.is_terminal(),
+        color: supports_color::on(supports_color::Stream::Stdout),
+        ascii,
+    };
+
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut last_lines = 0;
+    if term.in_place {
+        print!("{HIDE_CURSOR}");
+    }
+    let result = loop {
+        tokio::select! {
+            _ = ticks.tick() => {},
+            result = tokio::signal::ctrl_c() => break result,
+        }
+
+        // If polling fails, keep watching: the VU-Server may be restarting.
+        let frame = match client.list_dials().await {
+            Ok(infos) => term.render(infos.into_iter().map(|(_, info)| info), dials),
+            Err(error) => format!("failed to list dials: {error}\n"),
+        };
+        let header = format!(
+            "Every {}: dialctl watch{:>width$}\n\n",
+            humantime_serde::re::humantime::format_duration(interval),
+            chrono::Local::now().format("%H:%M:%S"),
+            width = 12,
+        );
+
+        let mut out = stdout.lock();
+        if term.in_place && last_lines > 0 {
+            // Move back to the start of the previous frame, and clear it.
+            let _ = write!(out, "\x1b[{last_lines}A\x1b[J");
+        }
+        let _ = write!(out, "{header}{frame}");
+        let _ = out.flush();
+        last_lines = header.lines().count() + frame.lines().count();
+    };
+    if term.in_place {
+        print!("{SHOW_CURSOR}");
+    }
+
+    result.map_err(|error| miette::miette!("failed to listen for Ctrl-C: {error}"))
+}
+
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+
+struct Terminal {
+    /// If `true`, redraw each frame over the previous one.
+    in_place: bool,
+    color: Option<supports_color::ColorLevel>,
+    ascii: bool,
+}
+
+// === impl Terminal ===
+
+impl Terminal {
+    fn render(&self, infos: impl Iterator<Item = DialInfo>, dials: &[dial::Id]) -> String {
+        let mut infos = infos.collect::<Vec<_>>();
+        let mut missing = Vec::new();
+        if !dials.is_empty() {
+            infos.retain(|info| dials.contains(&info.uid));
+            missing.extend(
+                dials
+                    .iter()
+                    .filter(|uid| !infos.iter().any(|info| &info.uid == *uid)),
+            );
+        }
+
+        let name_width = infos
+            .iter()
+            .map(|info| info.dial_name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let bold = if self.color.is_some() {
+            owo_colors::Style::new().bold()
+        } else {
+            owo_colors::Style::new()
+        };
+
+        let mut frame = String::new();
+        if infos.is_empty() && missing.is_empty() {
+            frame.push_str("no dials connected\n");
+        }
+        for info in &infos {
+            let DialInfo {
+                uid,
+                dial_name,
+                value,
+                backlight,
+                ..
+            } = info;
+            let _ = writeln!(
+                frame,
+                "{name:<name_width$}  {value:>3}% {bar}  {swatch}{red:>3} {green:>3} {blue:>3}  {uid}",
+                name = bold.style(dial_name),
+                value = u8::from(*value),
+                bar = self.bar(*value),
+                swatch = self.swatch(backlight),
+                red = u8::from(backlight.red),
+                green = u8::from(backlight.green),
+                blue = u8::from(backlight.blue),
+            );
+        }
+        for uid in missing {
+            let _ = writeln!(frame, "{uid}: not connected");
+        }
+        frame
+    }
+
+    fn bar(&self, value: dial::Percent) -> String {
+        let (full, empty) = if self.ascii {
+            ('#', '-')
+        } else {
+            ('█', '░')
+        };
+        let filled = usize::from(u8::from(value)) * BAR_WIDTH / 100;
+        (0..BAR_WIDTH)
+            .map(|i| if i < filled { full } else { empty })
+            .collect()
+    }
+
+    /// Returns a block in the backlight's color, if the terminal can display
+    /// it.
+    fn swatch(&self, backlight: &dial::Backlight) -> String {
+        match self.color {
+            Some(color) if color.has_16m && !self.ascii => {
+                format!("{} ", super::color::swatch(backlight))
+            }
+            _ => String::new(),
+        }
+    }
+}
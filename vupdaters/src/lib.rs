@@ -2,34 +2,79 @@
 pub mod cli;
 pub mod daemon;
 pub mod dialctl;
+#[cfg(feature = "server")]
+pub mod server;
+
+use std::{
+    borrow::Cow,
+    time::{Duration, Instant},
+};
 
 #[derive(miette::Diagnostic, Debug, thiserror::Error)]
 #[error("{}", .msg)]
 #[diagnostic()]
 pub(crate) struct MultiError {
-    msg: &'static str,
+    msg: Cow<'static, str>,
     #[related]
     errors: Vec<miette::Report>,
+    /// When each error in `errors` occurred.
+    times: Vec<Instant>,
     max_errors: Option<usize>,
+    /// If set, only errors that occurred within this long of the most recent
+    /// error count towards `max_errors`.
+    window: Option<Duration>,
 }
 
 impl MultiError {
-    pub(crate) fn with_max_errors(msg: &'static str, max_errors: usize) -> Self {
+    /// Returns a `MultiError` that fails after `max_errors` consecutive
+    /// errors.
+    pub(crate) fn with_max_errors(msg: impl Into<Cow<'static, str>>, max_errors: usize) -> Self {
         Self {
-            msg,
+            msg: msg.into(),
             errors: Vec::with_capacity(max_errors),
+            times: Vec::with_capacity(max_errors),
             max_errors: Some(max_errors),
+            window: None,
+        }
+    }
+
+    /// Returns a `MultiError` that fails after `max_errors` errors within
+    /// `window`, regardless of whether any operations succeeded in between.
+    pub(crate) fn with_max_errors_in(
+        msg: impl Into<Cow<'static, str>>,
+        max_errors: usize,
+        window: Duration,
+    ) -> Self {
+        Self {
+            window: Some(window),
+            ..Self::with_max_errors(msg, max_errors)
         }
     }
 
     pub(crate) fn push_error(&mut self, error: impl Into<miette::Report>) -> Result<(), Self> {
+        let now = Instant::now();
+        if let Some(window) = self.window {
+            // Forget about errors that have aged out of the window.
+            let expired = self
+                .times
+                .iter()
+                .take_while(|&&time| now.duration_since(time) > window)
+                .count();
+            self.errors.drain(..expired);
+            self.times.drain(..expired);
+        }
+
         self.errors.push(error.into());
+        self.times.push(now);
         if let Some(max_errors) = self.max_errors {
             if self.errors.len() >= max_errors {
+                self.times.clear();
                 return Err(Self {
-                    msg: self.msg,
+                    msg: self.msg.clone(),
                     errors: std::mem::take(&mut self.errors),
+                    times: Vec::new(),
                     max_errors: None,
+                    window: None,
                 });
             }
         }
@@ -37,8 +82,16 @@ impl MultiError {
         Ok(())
     }
 
-    pub(crate) fn clear(&mut self) {
-        self.errors.clear();
+    /// Records that an operation succeeded.
+    ///
+    /// If only consecutive errors are counted, this resets the error count.
+    /// Otherwise, this does nothing, as errors age out of the window on their
+    /// own.
+    pub(crate) fn success(&mut self) {
+        if self.window.is_none() {
+            self.errors.clear();
+            self.times.clear();
+        }
     }
 
     pub(crate) fn from_vec(errors: Vec<miette::Report>, msg: &'static str) -> miette::Result<()> {
@@ -51,9 +104,11 @@ impl MultiError {
         }
 
         Err(MultiError {
-            msg,
+            msg: msg.into(),
             errors,
+            times: Vec::new(),
             max_errors: None,
+            window: None,
         }
         .into())
     }
@@ -0,0 +1,317 @@
+//! `vu-server`, a replacement for the VU-Server that drives the dials' hub
+//! directly.
+//!
+//! This serves the same `/api/v0/dial/*` HTTP API as the VU-Server, so
+//! `dialctl`, `vupdated`, and anything else written against the VU-Server can
+//! use it unchanged, but talks to the hub over its serial port itself, rather
+//! than needing the Python VU-Server to be installed.
+//!
+//! New dials are provisioned when the server starts. The admin API isn't
+//! implemented yet; API keys other than the master key are read from
+//! `keys.json` in the data directory.
+use self::{
+    dials::{DialError, Dials},
+    keys::Keys,
+};
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use camino::Utf8PathBuf;
+use miette::{Context, IntoDiagnostic};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use vu_api::{
+    api,
+    dial::{self, Backlight, Percent},
+};
+use vu_protocol::hub::AsyncHub;
+
+mod dials;
+mod display;
+mod keys;
+
+/// The message the VU-Server responds with when a request's API key is
+/// missing or invalid.
+const UNAUTHORIZED: &str = "Unauthorized: invalid API key";
+
+#[derive(Debug, clap::Parser)]
+#[command(
+    name = "vu-server",
+    author,
+    version,
+    about = "A VU-Server replacement that drives VU1 dials directly"
+)]
+pub struct Args {
+    /// The address to listen for HTTP requests on.
+    #[clap(
+        long,
+        short = 'l',
+        env = "VU_SERVER_LISTEN",
+        default_value = "127.0.0.1:5340"
+    )]
+    listen: SocketAddr,
+
+    /// The serial port that the VU1 hub is connected to, such as
+    /// `/dev/ttyUSB0` or `COM3`.
+    ///
+    /// If this isn't set, each USB serial port whose vendor and product IDs
+    /// match the hub's is probed, and the first one that a hub answers on is
+    /// used.
+    #[clap(long, env = "VU_SERVER_SERIAL_PORT", value_hint = clap::ValueHint::FilePath)]
+    serial_port: Option<String>,
+
+    /// The master API key, which may access every dial.
+    #[clap(long, env = "VU_SERVER_MASTER_KEY")]
+    master_key: String,
+
+    /// The directory to store API keys, dial names and settings, and dial
+    /// images in.
+    #[clap(
+        long,
+        env = "VU_SERVER_DATA_DIR",
+        default_value_t = default_data_dir(),
+        value_hint = clap::ValueHint::DirPath,
+    )]
+    data_dir: Utf8PathBuf,
+
+    /// Reset every dial's address on startup, and provision them all again.
+    ///
+    /// This recovers dials whose provisioning was left in a bad state, such
+    /// as dials with conflicting addresses. Dials may get different indices
+    /// than they had before.
+    #[clap(long)]
+    reprovision: bool,
+
+    #[clap(flatten)]
+    output_args: crate::cli::OutputArgs,
+}
+
+struct Server {
+    keys: Keys,
+    dials: Dials,
+}
+
+type Shared = State<Arc<Server>>;
+
+impl Args {
+    pub async fn run(self) -> miette::Result<()> {
+        let Self {
+            listen,
+            serial_port,
+            master_key,
+            data_dir,
+            reprovision,
+            output_args,
+        } = self;
+        output_args.init_tracing()?;
+
+        let hub = match serial_port {
+            Some(ref path) => AsyncHub::open(path),
+            None => AsyncHub::find().await,
+        }
+        .into_diagnostic()
+        .context("failed to open the VU1 hub's serial port")?;
+        let keys = Keys::load(&data_dir, master_key)?;
+        let dials = Dials::discover(hub, &data_dir, reprovision).await?;
+        let server = Arc::new(Server { keys, dials });
+
+        let app = Router::new()
+            .route("/api/v0/dial/list", get(list_dials))
+            .route("/api/v0/dial/:uid/*endpoint", get(dial_get).post(dial_post))
+            .with_state(server);
+        let listener = tokio::net::TcpListener::bind(listen)
+            .await
+            .into_diagnostic()
+            .with_context(|| format!("failed to listen on {listen}"))?;
+        tracing::info!(%listen, "listening for VU-Server API requests");
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async {
+                let _ = tokio::signal::ctrl_c().await;
+                tracing::info!("shutting down");
+            })
+            .await
+            .into_diagnostic()
+    }
+}
+
+fn default_data_dir() -> Utf8PathBuf {
+    directories::BaseDirs::new()
+        .and_then(|dirs| {
+            Some(camino::Utf8Path::from_path(dirs.data_local_dir())?.join("vu-server"))
+        })
+        .unwrap_or_else(|| ["$HOME", ".local", "share", "vu-server"].iter().collect())
+}
+
+async fn list_dials(
+    State(server): Shared,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let Some(key) = server.keys.get(query.get("key")) else {
+        return unauthorized();
+    };
+    ok(server.dials.list(|uid| key.may_access(uid)))
+}
+
+async fn dial_get(
+    State(server): Shared,
+    Path((uid, endpoint)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let uid = match authorize(&server, &uid, &query) {
+        Ok(uid) => uid,
+        Err(message) => return fail(message),
+    };
+    let dials = &server.dials;
+    let result = match endpoint.trim_start_matches('/') {
+        "status" | "reload" => return respond(dials.status(&uid)),
+        "easing/get" => return respond(dials.status(&uid).map(|status| status.easing)),
+        "image/get" => {
+            return match dials.image(&uid) {
+                Ok((content_type, image)) => {
+                    ([(header::CONTENT_TYPE, content_type)], image).into_response()
+                }
+                Err(error) => fail(error),
+            }
+        }
+        "set" => match param::<Percent>(&query, "value") {
+            Ok(value) => dials.set_value(&uid, value).await,
+            Err(message) => return fail(message),
+        },
+        "backlight" => match backlight(&query) {
+            Ok(backlight) => dials.set_backlight(&uid, backlight).await,
+            Err(message) => return fail(message),
+        },
+        "name" => match query.get("name") {
+            Some(name) => dials.set_name(&uid, name),
+            None => return fail("missing 'name' parameter"),
+        },
+        endpoint @ ("easing/dial" | "easing/backlight") => match easing(&query) {
+            Ok(easing) => {
+                dials
+                    .set_easing(&uid, endpoint == "easing/backlight", easing)
+                    .await
+            }
+            Err(message) => return fail(message),
+        },
+        endpoint @ ("calibrate/max" | "calibrate/half") => match param::<u16>(&query, "value") {
+            Ok(value) => {
+                dials
+                    .calibrate(&uid, endpoint == "calibrate/half", value)
+                    .await
+            }
+            Err(message) => return fail(message),
+        },
+        _ => return (StatusCode::NOT_FOUND, "Not Found").into_response(),
+    };
+    respond(result)
+}
+
+async fn dial_post(
+    State(server): Shared,
+    Path((uid, endpoint)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
+    mut multipart: Multipart,
+) -> Response {
+    let uid = match authorize(&server, &uid, &query) {
+        Ok(uid) => uid,
+        Err(message) => return fail(message),
+    };
+    if endpoint.trim_start_matches('/') != "image/set" {
+        return (StatusCode::NOT_FOUND, "Not Found").into_response();
+    }
+
+    let mut image = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some("imgfile") {
+            let filename = field.file_name().map(ToOwned::to_owned);
+            match field.bytes().await {
+                Ok(bytes) => image = Some((filename, bytes)),
+                Err(error) => return fail(error),
+            }
+            break;
+        }
+    }
+    let Some((filename, bytes)) = image else {
+        return fail("missing 'imgfile' upload");
+    };
+    let filename = query
+        .get("imgfile")
+        .cloned()
+        .or(filename)
+        .unwrap_or_else(|| format!("img_{uid}"));
+    respond(server.dials.set_image(&uid, filename, &bytes).await)
+}
+
+/// Checks the request's API key, and returns the dial UID it's for if the
+/// key may access that dial.
+///
+/// Like the other request helpers, this returns the message to [`fail`] with
+/// if the request is rejected.
+fn authorize(
+    server: &Server,
+    uid: &str,
+    query: &HashMap<String, String>,
+) -> Result<dial::Id, String> {
+    let key = server
+        .keys
+        .get(query.get("key"))
+        .ok_or_else(|| UNAUTHORIZED.to_owned())?;
+    let uid = dial::Id::new_unchecked(uid);
+    // Don't tell keys about dials they can't access.
+    if !key.may_access(&uid) {
+        return Err(DialError::NotFound.to_string());
+    }
+    Ok(uid)
+}
+
+fn param<T: std::str::FromStr>(query: &HashMap<String, String>, name: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    let value = query
+        .get(name)
+        .ok_or_else(|| format!("missing '{name}' parameter"))?;
+    value
+        .parse()
+        .map_err(|error| format!("invalid '{name}' parameter: {error}"))
+}
+
+fn backlight(query: &HashMap<String, String>) -> Result<Backlight, String> {
+    Ok(Backlight {
+        red: param(query, "red")?,
+        green: param(query, "green")?,
+        blue: param(query, "blue")?,
+    })
+}
+
+fn easing(query: &HashMap<String, String>) -> Result<dial::EasingConfig, String> {
+    let period = Duration::from_millis(param(query, "period")?);
+    dial::EasingConfig::new(period, param(query, "step")?).map_err(|error| error.to_string())
+}
+
+fn respond<T: serde::Serialize>(result: Result<T, DialError>) -> Response {
+    match result {
+        Ok(data) => ok(data),
+        Err(error) => {
+            if let DialError::Hub(ref error) = error {
+                tracing::warn!(%error, "hub command failed");
+            }
+            fail(error)
+        }
+    }
+}
+
+fn ok<T: serde::Serialize>(data: T) -> Response {
+    Json(api::Response::ok(data)).into_response()
+}
+
+fn fail(message: impl std::fmt::Display) -> Response {
+    Json(api::Response::fail(message.to_string())).into_response()
+}
+
+fn unauthorized() -> Response {
+    fail(UNAUTHORIZED)
+}
@@ -0,0 +1,393 @@
+//! The dials attached to the hub, and the settings the server remembers for
+//! them.
+//!
+//! The hub doesn't store dials' names or images, so the server keeps them,
+//! along with each dial's easing config, in `dials.json` in its data
+//! directory, and keeps uploaded images in its `images` directory.
+use super::display;
+use camino::{Utf8Path, Utf8PathBuf};
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+use vu_api::{
+    api::DialInfo,
+    dial::{self, Backlight, Easing, EasingConfig, Percent},
+};
+use vu_protocol::hub::{self, AsyncHub, DialMap};
+
+pub(super) struct Dials {
+    hub: AsyncHub,
+    state_path: Utf8PathBuf,
+    images_dir: Utf8PathBuf,
+    dials: Mutex<Vec<Dial>>,
+    /// Saved settings for dials that weren't attached to the hub at startup,
+    /// which are kept so that they're still there when the dial is plugged
+    /// back in.
+    unplugged: HashMap<dial::Id, Saved>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(super) enum DialError {
+    #[error("Dial not found")]
+    NotFound,
+    #[error("Image not found")]
+    NoImage,
+    #[error("hub error: {0}")]
+    Hub(#[from] hub::Error),
+    #[error("invalid image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("failed to store image: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Clone, Debug)]
+struct Dial {
+    index: u8,
+    uid: dial::Id,
+    saved: Saved,
+    value: Percent,
+    backlight: Backlight,
+}
+
+/// The settings that the server remembers for each dial.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Saved {
+    name: String,
+    image_file: String,
+    easing: Easing,
+}
+
+// === impl Dials ===
+
+impl Dials {
+    /// Provisions any new dials attached to `hub`, finds them all, and
+    /// restores their saved settings from `data_dir`.
+    ///
+    /// If `reprovision` is set, every dial's address is reset first, and
+    /// they're all provisioned again.
+    pub(super) async fn discover(
+        hub: AsyncHub,
+        data_dir: &Utf8Path,
+        reprovision: bool,
+    ) -> miette::Result<Self> {
+        let state_path = data_dir.join("dials.json");
+        let mut saved = load_state(&state_path);
+
+        let map_path = data_dir.join("dial-map");
+        let mut dial_map = DialMap::load(map_path.as_std_path())
+            .into_diagnostic()
+            .with_context(|| format!("failed to read dial map from {map_path}"))?;
+        let provisioned = if reprovision {
+            tracing::info!("resetting and reprovisioning all dials");
+            hub.reset_and_provision(&mut dial_map).await
+        } else {
+            hub.provision(&mut dial_map).await
+        }
+        .into_diagnostic()
+        .context("failed to provision dials")?;
+        for address in provisioned {
+            tracing::info!(address, "provisioned new dial");
+        }
+        if let Err(error) = save_dial_map(&map_path, &dial_map) {
+            tracing::warn!(%error, "failed to save dial map");
+        }
+
+        let devices = hub
+            .devices()
+            .await
+            .into_diagnostic()
+            .context("failed to list the dials attached to the hub")?;
+        let mut dials = Vec::new();
+        for device in devices {
+            let index = device.address;
+            let Some(uid) = device.state.uid() else {
+                continue;
+            };
+            let uid = uid
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<String>();
+            // Like UIDs from the VU-Server, UIDs from the hub are accepted
+            // as-is.
+            let uid = dial::Id::new_unchecked(uid);
+            let saved = saved
+                .remove(&uid)
+                .unwrap_or_else(|| Saved::new(index, &uid));
+            let Easing {
+                backlight_step,
+                backlight_period,
+                dial_step,
+                dial_period,
+            } = saved.easing;
+            for (backlight, period, step) in [
+                (false, dial_period, dial_step),
+                (true, backlight_period, backlight_step),
+            ] {
+                let config = EasingConfig { period, step };
+                set_easing(&hub, index, backlight, config)
+                    .await
+                    .into_diagnostic()
+                    .with_context(|| format!("failed to restore the easing of dial {index}"))?;
+            }
+            tracing::info!(index, %uid, name = %saved.name, "found dial");
+            dials.push(Dial {
+                index,
+                uid,
+                saved,
+                value: Percent::MIN,
+                backlight: Backlight::OFF,
+            });
+        }
+
+        Ok(Self {
+            hub,
+            state_path,
+            images_dir: data_dir.join("images"),
+            dials: Mutex::new(dials),
+            unplugged: saved,
+        })
+    }
+
+    /// Lists the dials whose UIDs match `filter`.
+    pub(super) fn list(&self, filter: impl Fn(&dial::Id) -> bool) -> Vec<DialInfo> {
+        self.lock()
+            .iter()
+            .filter(|dial| filter(&dial.uid))
+            .map(|dial| DialInfo {
+                uid: dial.uid.clone(),
+                dial_name: dial.saved.name.clone(),
+                value: dial.value,
+                backlight: dial.backlight,
+                image_file: dial.saved.image_file.clone(),
+            })
+            .collect()
+    }
+
+    pub(super) fn status(&self, uid: &dial::Id) -> Result<dial::Status, DialError> {
+        self.with_dial(uid, |dial| {
+            let Backlight { red, green, blue } = dial.backlight;
+            dial::Status {
+                index: usize::from(dial.index),
+                uid: dial.uid.clone(),
+                dial_name: dial.saved.name.clone(),
+                value: dial.value,
+                rgbw: [red, green, blue, Percent::MIN],
+                easing: dial.saved.easing,
+                fw_hash: String::new(),
+                fw_version: String::new(),
+                hw_version: String::new(),
+                protocol_version: "V1".to_owned(),
+                backlight: dial.backlight,
+                image_file: dial.saved.image_file.clone(),
+                // Changes are sent to the hub as soon as they're made.
+                update_deadline: SystemTime::now(),
+                value_changed: false,
+                backlight_changed: false,
+                image_changed: false,
+            }
+        })
+    }
+
+    pub(super) async fn set_value(&self, uid: &dial::Id, value: Percent) -> Result<(), DialError> {
+        let index = self.index(uid)?;
+        self.hub.set_percent(index, u8::from(value)).await?;
+        self.with_dial(uid, |dial| dial.value = value)
+    }
+
+    pub(super) async fn set_backlight(
+        &self,
+        uid: &dial::Id,
+        backlight: Backlight,
+    ) -> Result<(), DialError> {
+        let index = self.index(uid)?;
+        let Backlight { red, green, blue } = backlight;
+        self.hub
+            .set_backlight(index, red.into(), green.into(), blue.into())
+            .await?;
+        self.with_dial(uid, |dial| dial.backlight = backlight)
+    }
+
+    pub(super) fn set_name(&self, uid: &dial::Id, name: &str) -> Result<(), DialError> {
+        self.with_dial(uid, |dial| dial.saved.name = name.to_owned())?;
+        self.save();
+        Ok(())
+    }
+
+    pub(super) async fn set_easing(
+        &self,
+        uid: &dial::Id,
+        backlight: bool,
+        config: EasingConfig,
+    ) -> Result<(), DialError> {
+        let index = self.index(uid)?;
+        set_easing(&self.hub, index, backlight, config).await?;
+        self.with_dial(uid, |dial| {
+            let easing = &mut dial.saved.easing;
+            if backlight {
+                easing.backlight_period = config.period;
+                easing.backlight_step = config.step;
+            } else {
+                easing.dial_period = config.period;
+                easing.dial_step = config.step;
+            }
+        })?;
+        self.save();
+        Ok(())
+    }
+
+    pub(super) async fn calibrate(
+        &self,
+        uid: &dial::Id,
+        half: bool,
+        value: u16,
+    ) -> Result<(), DialError> {
+        let index = self.index(uid)?;
+        self.hub.calibrate(index, half, value).await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_image(
+        &self,
+        uid: &dial::Id,
+        filename: String,
+        image: &[u8],
+    ) -> Result<(), DialError> {
+        let index = self.index(uid)?;
+        let pixels = display::to_pixels(image)?;
+        self.hub.show_image(index, &pixels).await?;
+        std::fs::create_dir_all(&self.images_dir)?;
+        std::fs::write(self.image_path(uid), image)?;
+        self.with_dial(uid, |dial| dial.saved.image_file = filename)?;
+        self.save();
+        Ok(())
+    }
+
+    /// Returns the content type and contents of the dial's last uploaded
+    /// image.
+    pub(super) fn image(&self, uid: &dial::Id) -> Result<(&'static str, Vec<u8>), DialError> {
+        self.index(uid)?;
+        match std::fs::read(self.image_path(uid)) {
+            Ok(image) => Ok((display::content_type(&image), image)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Err(DialError::NoImage),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn image_path(&self, uid: &dial::Id) -> Utf8PathBuf {
+        self.images_dir.join(uid.to_string())
+    }
+
+    fn index(&self, uid: &dial::Id) -> Result<u8, DialError> {
+        self.with_dial(uid, |dial| dial.index)
+    }
+
+    fn with_dial<T>(&self, uid: &dial::Id, f: impl FnOnce(&mut Dial) -> T) -> Result<T, DialError> {
+        self.lock()
+            .iter_mut()
+            .find(|dial| dial.uid == *uid)
+            .map(f)
+            .ok_or(DialError::NotFound)
+    }
+
+    /// Writes every dial's saved settings to the state file.
+    ///
+    /// This is best-effort: if the state file can't be written, the settings
+    /// still apply until the server restarts.
+    fn save(&self) {
+        let mut state = self.unplugged.clone();
+        state.extend(
+            self.lock()
+                .iter()
+                .map(|dial| (dial.uid.clone(), dial.saved.clone())),
+        );
+        if let Err(error) = save_state(&self.state_path, &state) {
+            tracing::warn!(%error, "failed to save dial settings");
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Dial>> {
+        self.dials
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// === impl Saved ===
+
+impl Saved {
+    fn new(index: u8, uid: &dial::Id) -> Self {
+        let step = Percent::saturating_new(5);
+        Self {
+            name: format!("Dial {index}"),
+            image_file: format!("img_{uid}"),
+            easing: Easing {
+                backlight_step: step,
+                backlight_period: Duration::from_millis(100),
+                dial_step: step,
+                dial_period: Duration::from_millis(50),
+            },
+        }
+    }
+}
+
+async fn set_easing(
+    hub: &AsyncHub,
+    index: u8,
+    backlight: bool,
+    EasingConfig { period, step }: EasingConfig,
+) -> Result<(), hub::Error> {
+    let period = u32::try_from(period.as_millis()).unwrap_or(u32::MAX);
+    if backlight {
+        hub.set_backlight_easing(index, period, step.into()).await
+    } else {
+        hub.set_dial_easing(index, period, step.into()).await
+    }
+}
+
+fn load_state(path: &Utf8Path) -> HashMap<dial::Id, Saved> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|error| {
+            tracing::warn!(%error, "failed to parse dial settings from {path}, ignoring them");
+            HashMap::new()
+        }),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+        Err(error) => {
+            tracing::warn!(%error, "failed to read dial settings from {path}, ignoring them");
+            HashMap::new()
+        }
+    }
+}
+
+fn save_dial_map(path: &Utf8Path, map: &DialMap) -> miette::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .into_diagnostic()
+            .with_context(|| format!("failed to create {parent}"))?;
+    }
+    map.save(path.as_std_path())
+        .into_diagnostic()
+        .with_context(|| format!("failed to write {path}"))
+}
+
+fn save_state(path: &Utf8Path, state: &HashMap<dial::Id, Saved>) -> miette::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .into_diagnostic()
+            .with_context(|| format!("failed to create {parent}"))?;
+    }
+    let json = serde_json::to_string_pretty(state).into_diagnostic()?;
+    // Write to a temporary file and rename it into place, so that a crash
+    // mid-write doesn't leave a truncated state file behind.
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json)
+        .into_diagnostic()
+        .with_context(|| format!("failed to write {tmp}"))?;
+    std::fs::rename(&tmp, path)
+        .into_diagnostic()
+        .with_context(|| format!("failed to rename {tmp} to {path}"))
+}
@@ -0,0 +1,40 @@
+//! Converting images for the dials' e-paper displays.
+use image::imageops::FilterType;
+
+/// The width of a dial's display, in pixels.
+const WIDTH: u32 = 200;
+
+/// The height of a dial's display, in pixels.
+const HEIGHT: u32 = 144;
+
+/// Converts an image file to the 1-bit pixel data that the hub sends to a
+/// dial's display.
+///
+/// The image is scaled to the display's size and thresholded to black and
+/// white. Pixels are packed eight to a byte, in columns from left to right,
+/// and from top to bottom within each column, with the topmost pixel in the
+/// most significant bit. A set bit is a black pixel.
+pub(super) fn to_pixels(image: &[u8]) -> Result<Vec<u8>, image::ImageError> {
+    let image = image::load_from_memory(image)?
+        .resize_exact(WIDTH, HEIGHT, FilterType::Triangle)
+        .into_luma8();
+    let mut pixels = Vec::with_capacity((WIDTH * HEIGHT / 8) as usize);
+    for x in 0..WIDTH {
+        for y in (0..HEIGHT).step_by(8) {
+            let byte = (0..8).fold(0u8, |byte, bit| {
+                let black = image.get_pixel(x, y + bit).0[0] < 128;
+                byte | (u8::from(black) << (7 - bit))
+            });
+            pixels.push(byte);
+        }
+    }
+    Ok(pixels)
+}
+
+/// Returns the MIME type of an image file, based on its contents.
+pub(super) fn content_type(image: &[u8]) -> &'static str {
+    match image::guess_format(image) {
+        Ok(format) => format.to_mime_type(),
+        Err(_) => "application/octet-stream",
+    }
+}
@@ -0,0 +1,65 @@
+//! API key authorization.
+use camino::Utf8Path;
+use miette::{Context, IntoDiagnostic};
+use vu_api::{api::KeyInfo, dial};
+
+/// The API keys that the server accepts.
+pub(super) struct Keys {
+    master: String,
+    keys: Vec<KeyInfo>,
+}
+
+/// A key that a request was authorized with.
+pub(super) enum Key<'a> {
+    Master,
+    Limited(&'a KeyInfo),
+}
+
+// === impl Keys ===
+
+impl Keys {
+    /// Loads the keys listed in `keys.json` in `data_dir`, in the same format
+    /// as the VU-Server admin API's key list.
+    ///
+    /// If there's no `keys.json`, only the master key is accepted.
+    pub(super) fn load(data_dir: &Utf8Path, master: String) -> miette::Result<Self> {
+        let path = data_dir.join("keys.json");
+        let keys = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .into_diagnostic()
+                .with_context(|| format!("failed to parse API keys from {path}"))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => {
+                return Err(error)
+                    .into_diagnostic()
+                    .with_context(|| format!("failed to read API keys from {path}"))
+            }
+        };
+        tracing::debug!(keys = keys.len(), "loaded API keys");
+        Ok(Self { master, keys })
+    }
+
+    /// Returns the key matching a request's `key` parameter, if it's valid.
+    pub(super) fn get(&self, key: Option<&String>) -> Option<Key<'_>> {
+        let key = key?;
+        if *key == self.master {
+            return Some(Key::Master);
+        }
+        self.keys
+            .iter()
+            .find(|info| info.key == *key)
+            .map(Key::Limited)
+    }
+}
+
+// === impl Key ===
+
+impl Key<'_> {
+    /// Returns `true` if this key may access the dial with the UID `uid`.
+    pub(super) fn may_access(&self, uid: &dial::Id) -> bool {
+        match self {
+            Self::Master => true,
+            Self::Limited(info) => info.dials.contains(uid),
+        }
+    }
+}